@@ -0,0 +1,1073 @@
+//! Heuristic time complexity of Rust functions.
+//!
+//! Every loop (`for`, `while`, `loop` and closures passed to iterator
+//! adapters such as `map` or `filter`) multiplies the cost of its body by
+//! `n`, unless it runs over a literal or constant bound. A linear search
+//! (`contains` on a `Vec` or slice) or a copy of one inside a loop adds
+//! another factor, sorting adds `n log n`. Recursion is linear, `log n` when
+//! the input is halved, and exponential when a function calls itself more
+//! than once on the same branch without memoization. Types are not
+//! resolved: a binding counts as a `Vec` only when its declaration says so.
+//!
+//! Each estimate carries the evidence that produced it, so callers can point
+//! at the loop or call responsible for a quadratic class.
+
+use super::complexity::ParseFailure;
+use super::{display_path, source_files};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{BinOp, Block, Expr, FnArg, Ident, ImplItemFn, ItemFn, ItemImpl, ItemTrait, Pat};
+use syn::{Signature, TraitItemFn, Type};
+
+/// Methods that start iterating over their receiver.
+const ITERATOR_SOURCES: &[&str] = &[
+    "iter",
+    "iter_mut",
+    "into_iter",
+    "chars",
+    "bytes",
+    "char_indices",
+    "lines",
+    "keys",
+    "values",
+    "values_mut",
+    "drain",
+    "windows",
+    "chunks",
+    "split_whitespace",
+];
+
+/// Lazy adapters that keep iterating over the same elements.
+const ITERATOR_ADAPTERS: &[&str] = &[
+    "enumerate",
+    "rev",
+    "skip",
+    "take",
+    "zip",
+    "chain",
+    "cloned",
+    "copied",
+    "map",
+    "filter",
+    "filter_map",
+    "flat_map",
+    "peekable",
+    "step_by",
+    "skip_while",
+    "take_while",
+    "inspect",
+];
+
+/// Methods whose closure argument runs once per element.
+const ITERATOR_CALLBACKS: &[&str] = &[
+    "map",
+    "filter",
+    "filter_map",
+    "flat_map",
+    "for_each",
+    "any",
+    "all",
+    "find",
+    "find_map",
+    "position",
+    "fold",
+    "skip_while",
+    "take_while",
+    "inspect",
+    "max_by_key",
+    "min_by_key",
+];
+
+const SORT_METHODS: &[&str] = &[
+    "sort",
+    "sort_by",
+    "sort_by_key",
+    "sort_unstable",
+    "sort_unstable_by",
+    "sort_unstable_by_key",
+];
+
+const ALLOCATING_METHODS: &[&str] = &["to_vec", "to_string", "to_owned", "clone", "collect"];
+const ALLOCATING_CALLS: &[&str] = &["new", "with_capacity", "from"];
+const ALLOCATING_TYPES: &[&str] = &["Vec", "VecDeque", "String", "HashMap", "HashSet", "Box"];
+const ALLOCATING_MACROS: &[&str] = &["vec", "format"];
+
+/// Collections where `contains` is a linear scan.
+const LINEAR_TYPES: &[&str] = &["Vec", "VecDeque", "LinkedList"];
+/// Parameter types that suggest a recursive function memoizes its results.
+const MEMO_TYPES: &[&str] = &["HashMap", "BTreeMap"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Evidence {
+    /// "nested_loop", "nested_loop_same_collection", "linear_search_in_loop",
+    /// "sort", "allocation_in_loop", "recursion" or "exponential_recursion"
+    pub kind: &'static str,
+    pub line: usize,
+    pub end_line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionEstimate {
+    /// `name`, or `Type::name` for methods
+    pub function: String,
+    pub line: usize,
+    pub end_line: usize,
+    /// Estimated time complexity, e.g. "O(n)" or "O(n^2)"
+    pub class: String,
+    pub evidence: Vec<Evidence>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionBigO {
+    pub file: String,
+    #[serde(flatten)]
+    pub estimate: FunctionEstimate,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub total_files: usize,
+    pub total_functions: usize,
+    /// Number of functions in each class
+    pub by_class: BTreeMap<String, usize>,
+    /// Functions estimated worse than O(n)
+    pub above_linear: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BigOReport {
+    pub summary: Summary,
+    pub functions: Vec<FunctionBigO>,
+    /// Files that could not be parsed
+    pub errors: Vec<ParseFailure>,
+}
+
+/// A complexity class: `O(2^n)` when exponential, else `O(n^power log n)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct Cost {
+    exponential: bool,
+    power: u32,
+    log: bool,
+}
+
+impl Cost {
+    fn polynomial(power: u32) -> Self {
+        Self {
+            power,
+            ..Self::default()
+        }
+    }
+
+    fn class(self) -> String {
+        if self.exponential {
+            return "O(2^n)".to_string();
+        }
+        match (self.power, self.log) {
+            (0, false) => "O(1)".to_string(),
+            (0, true) => "O(log n)".to_string(),
+            (1, false) => "O(n)".to_string(),
+            (1, true) => "O(n log n)".to_string(),
+            (power, false) => format!("O(n^{})", power),
+            (power, true) => format!("O(n^{} log n)", power),
+        }
+    }
+}
+
+/// Estimated complexity of every function in a Rust source file.
+pub fn analyze_source(source: &str) -> Result<Vec<FunctionEstimate>, syn::Error> {
+    let file = syn::parse_file(source)?;
+    let mut collector = FunctionCollector::default();
+    collector.visit_file(&file);
+    Ok(collector.functions)
+}
+
+/// Estimate every Rust function under `target`, reporting paths relative
+/// to `root`.
+pub fn analyze_path(root: &Path, target: &Path) -> BigOReport {
+    let mut total_files = 0;
+    let mut functions = Vec::new();
+    let mut errors = Vec::new();
+
+    for path in source_files(target, &["rs"]) {
+        let file = display_path(root, &path);
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| analyze_source(&source).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(estimates) => {
+                total_files += 1;
+                functions.extend(estimates.into_iter().map(|estimate| FunctionBigO {
+                    file: file.clone(),
+                    estimate,
+                }));
+            }
+            Err(error) => errors.push(ParseFailure { file, error }),
+        }
+    }
+
+    let mut by_class = BTreeMap::new();
+    for function in &functions {
+        *by_class.entry(function.estimate.class.clone()).or_insert(0) += 1;
+    }
+    let above_linear = functions
+        .iter()
+        .filter(|f| !matches!(f.estimate.class.as_str(), "O(1)" | "O(log n)" | "O(n)"))
+        .count();
+
+    BigOReport {
+        summary: Summary {
+            total_files,
+            total_functions: functions.len(),
+            by_class,
+            above_linear,
+        },
+        functions,
+        errors,
+    }
+}
+
+/// Finds function items and estimates each body.
+#[derive(Default)]
+struct FunctionCollector {
+    /// Type or trait name of the enclosing `impl`/`trait` block
+    owner: Option<String>,
+    functions: Vec<FunctionEstimate>,
+}
+
+impl FunctionCollector {
+    fn estimate(&mut self, sig: &Signature, block: &Block) {
+        let ident = &sig.ident;
+        let function = match &self.owner {
+            Some(owner) => format!("{}::{}", owner, ident),
+            None => ident.to_string(),
+        };
+        let mut body = BodyEstimate::new(sig);
+        body.visit_block(block);
+        let (cost, evidence) = body.finish();
+        self.functions.push(FunctionEstimate {
+            function,
+            line: ident.span().start().line,
+            end_line: block.span().end().line,
+            class: cost.class(),
+            evidence,
+        });
+    }
+
+    fn with_owner(&mut self, owner: Option<String>, visit: impl FnOnce(&mut Self)) {
+        let saved = std::mem::replace(&mut self.owner, owner);
+        visit(self);
+        self.owner = saved;
+    }
+}
+
+impl<'ast> Visit<'ast> for FunctionCollector {
+    fn visit_item_fn(&mut self, item: &'ast ItemFn) {
+        self.estimate(&item.sig, &item.block);
+        // Functions nested in the body are reported on their own
+        self.with_owner(None, |this| visit::visit_item_fn(this, item));
+    }
+
+    fn visit_item_impl(&mut self, item: &'ast ItemImpl) {
+        let owner = match &*item.self_ty {
+            Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+            _ => None,
+        };
+        self.with_owner(owner, |this| visit::visit_item_impl(this, item));
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast ImplItemFn) {
+        self.estimate(&item.sig, &item.block);
+        self.with_owner(None, |this| visit::visit_impl_item_fn(this, item));
+    }
+
+    fn visit_item_trait(&mut self, item: &'ast ItemTrait) {
+        let owner = Some(item.ident.to_string());
+        self.with_owner(owner, |this| visit::visit_item_trait(this, item));
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast TraitItemFn) {
+        if let Some(block) = &item.default {
+            self.estimate(&item.sig, block);
+        }
+        self.with_owner(None, |this| visit::visit_trait_item_fn(this, item));
+    }
+}
+
+/// What a loop iterates over.
+#[derive(Debug, Clone, PartialEq)]
+enum Bound {
+    /// A literal, array or `CONSTANT`: does not grow with the input
+    Constant,
+    /// A named collection or length, e.g. `items` or `self.nodes`
+    Named(String),
+    Unknown,
+}
+
+struct Loop {
+    bound: Bound,
+    line: usize,
+    /// Binding for each element when the loop walks a collection, e.g. `child`
+    element: Option<String>,
+}
+
+/// Estimates a single function body.
+struct BodyEstimate {
+    name: String,
+    /// Bindings known to be a `Vec` or slice
+    linear: HashSet<String>,
+    /// Enclosing loops, outermost first
+    loops: Vec<Loop>,
+    cost: Cost,
+    evidence: Vec<Evidence>,
+    /// Line of each recursive call and whether it sits in a loop
+    recursive_calls: Vec<(usize, bool)>,
+    /// Whether the recursion descends once per element of a loop, like a tree walk
+    walks_children: bool,
+    /// Most recursive calls on any one path through the branches so far
+    path_calls: usize,
+    /// Whether some recursive call receives a halved input
+    splits_input: bool,
+    memoized: bool,
+}
+
+impl BodyEstimate {
+    fn new(sig: &Signature) -> Self {
+        let mut body = Self {
+            name: sig.ident.to_string(),
+            linear: HashSet::new(),
+            loops: Vec::new(),
+            cost: Cost::default(),
+            evidence: Vec::new(),
+            recursive_calls: Vec::new(),
+            walks_children: false,
+            path_calls: 0,
+            splits_input: false,
+            memoized: false,
+        };
+        for input in &sig.inputs {
+            let FnArg::Typed(arg) = input else { continue };
+            let Some(ident) = pat_ident(&arg.pat) else {
+                continue;
+            };
+            if is_linear_type(&arg.ty) {
+                body.linear.insert(ident.to_string());
+            }
+            if is_memo_name(ident)
+                || type_name(&arg.ty).is_some_and(|t| MEMO_TYPES.contains(&t.as_str()))
+            {
+                body.memoized = true;
+            }
+        }
+        body
+    }
+
+    /// Loops whose bound grows with the input.
+    fn depth(&self) -> u32 {
+        self.loops
+            .iter()
+            .filter(|l| l.bound != Bound::Constant)
+            .count() as u32
+    }
+
+    fn charge(&mut self, cost: Cost) {
+        self.cost = self.cost.max(cost);
+    }
+
+    fn record(&mut self, kind: &'static str, span: proc_macro2::Span, message: String) {
+        self.evidence.push(Evidence {
+            kind,
+            line: span.start().line,
+            end_line: span.end().line,
+            message,
+        });
+    }
+
+    fn with_loop(
+        &mut self,
+        bound: Bound,
+        element: Option<String>,
+        span: proc_macro2::Span,
+        visit: impl FnOnce(&mut Self),
+    ) {
+        if bound != Bound::Constant {
+            let outer = self.loops.iter().rev().find(|l| l.bound != Bound::Constant);
+            let same = match &bound {
+                Bound::Named(name) => self.loops.iter().find(|l| l.bound == bound).map(|l| {
+                    format!(
+                        "loop over `{}` nested in a loop over the same collection (line {})",
+                        name, l.line
+                    )
+                }),
+                _ => None,
+            };
+            let nested = outer.map(|l| format!("loop nested in the loop at line {}", l.line));
+            match (same, nested) {
+                (Some(message), _) => self.record("nested_loop_same_collection", span, message),
+                (None, Some(message)) => self.record("nested_loop", span, message),
+                (None, None) => {}
+            }
+        }
+
+        let element = element.filter(|_| matches!(bound, Bound::Named(_)));
+        self.loops.push(Loop {
+            bound,
+            line: span.start().line,
+            element,
+        });
+        self.charge(Cost::polynomial(self.depth()));
+        visit(self);
+        self.loops.pop();
+    }
+
+    fn allocation_in_loop(&mut self, what: &str, span: proc_macro2::Span) {
+        if let Some(l) = self.loops.iter().rev().find(|l| l.bound != Bound::Constant) {
+            let message = format!(
+                "`{}` allocates on every iteration of the loop at line {}",
+                what, l.line
+            );
+            self.record("allocation_in_loop", span, message);
+        }
+    }
+
+    fn is_linear(&self, expr: &Expr) -> bool {
+        name_of(expr).is_some_and(|name| self.linear.contains(&name))
+    }
+
+    fn recursive_call(&mut self, args: &syn::punctuated::Punctuated<Expr, syn::Token![,]>) {
+        let line = args.span().start().line;
+        // `for child in &node.children { walk(child) }` visits each element
+        // once, so across the whole walk the loop and the recursion are linear
+        let per_element = self.depth() == 1
+            && self
+                .loops
+                .iter()
+                .rfind(|l| l.bound != Bound::Constant)
+                .and_then(|l| l.element.as_deref())
+                .is_some_and(|element| {
+                    args.iter()
+                        .any(|arg| root_ident(arg).is_some_and(|ident| ident == element))
+                });
+        self.walks_children |= per_element;
+        self.recursive_calls
+            .push((line, self.depth() > 0 && !per_element));
+        self.path_calls += 1;
+        self.splits_input |= args.iter().any(halves);
+    }
+
+    /// Fold recursion into the cost of the body.
+    fn finish(mut self) -> (Cost, Vec<Evidence>) {
+        let calls = self.path_calls;
+        let Some(&(line, _)) = self.recursive_calls.first() else {
+            return (self.cost, self.evidence);
+        };
+        let in_loop = self.recursive_calls.iter().any(|&(_, in_loop)| in_loop);
+        let body = self.cost;
+
+        let (cost, kind, message) = if self.splits_input {
+            // Master theorem with a halved input: a·T(n/2) + O(n^k)
+            let cost = match (calls, body.power) {
+                (1, 0) => Cost { log: true, ..body },
+                (1, _) => body,
+                (_, 0) => Cost::polynomial(1),
+                (_, 1) => Cost { log: true, ..body },
+                _ => body,
+            };
+            (cost, "recursion", "recursion on a halved input".to_string())
+        } else if (calls > 1 || in_loop) && !self.memoized {
+            let message = if in_loop {
+                format!(
+                    "`{}` calls itself inside a loop without memoization",
+                    self.name
+                )
+            } else {
+                format!(
+                    "`{}` calls itself {} times per call without memoization",
+                    self.name, calls
+                )
+            };
+            let cost = Cost {
+                exponential: true,
+                ..body
+            };
+            (cost, "exponential_recursion", message)
+        } else {
+            // A walk over the children already charged its loop as linear
+            let cost = Cost {
+                power: if self.walks_children {
+                    body.power
+                } else {
+                    body.power + 1
+                },
+                ..body
+            };
+            let message = if self.walks_children {
+                format!("`{}` visits each child once", self.name)
+            } else if self.memoized {
+                "memoized recursion visits each input once".to_string()
+            } else {
+                "recursion depth grows with the input".to_string()
+            };
+            (cost, "recursion", message)
+        };
+
+        self.evidence.push(Evidence {
+            kind,
+            line,
+            end_line: line,
+            message,
+        });
+        (self.cost.max(cost), self.evidence)
+    }
+}
+
+impl<'ast> Visit<'ast> for BodyEstimate {
+    fn visit_expr_for_loop(&mut self, expr: &'ast syn::ExprForLoop) {
+        self.visit_expr(&expr.expr);
+        let bound = collection_bound(&expr.expr);
+        let element = element_binding(&expr.pat, &expr.expr);
+        self.with_loop(bound, element, expr.span(), |this| {
+            this.visit_block(&expr.body)
+        });
+    }
+
+    fn visit_expr_while(&mut self, expr: &'ast syn::ExprWhile) {
+        // The condition is evaluated on every iteration
+        self.with_loop(Bound::Unknown, None, expr.span(), |this| {
+            this.visit_expr(&expr.cond);
+            this.visit_block(&expr.body);
+        });
+    }
+
+    fn visit_expr_loop(&mut self, expr: &'ast syn::ExprLoop) {
+        self.with_loop(Bound::Unknown, None, expr.span(), |this| {
+            this.visit_block(&expr.body)
+        });
+    }
+
+    fn visit_expr_if(&mut self, expr: &'ast syn::ExprIf) {
+        // Only one branch runs, so recursive calls count per branch
+        self.visit_expr(&expr.cond);
+        let before = self.path_calls;
+        self.visit_block(&expr.then_branch);
+        let then_calls = std::mem::replace(&mut self.path_calls, before);
+        if let Some((_, else_branch)) = &expr.else_branch {
+            self.visit_expr(else_branch);
+        }
+        self.path_calls = self.path_calls.max(then_calls);
+    }
+
+    fn visit_expr_match(&mut self, expr: &'ast syn::ExprMatch) {
+        self.visit_expr(&expr.expr);
+        let before = self.path_calls;
+        let mut most = before;
+        for arm in &expr.arms {
+            self.path_calls = before;
+            self.visit_arm(arm);
+            most = most.max(self.path_calls);
+        }
+        self.path_calls = most;
+    }
+
+    fn visit_expr_method_call(&mut self, expr: &'ast syn::ExprMethodCall) {
+        let method = expr.method.to_string();
+        let span = expr.span();
+        let depth = self.depth();
+
+        if method == "contains" && self.is_linear(&expr.receiver) {
+            self.charge(Cost::polynomial(depth + 1));
+            if depth > 0 {
+                let message = format!(
+                    "`{}.contains` scans a Vec inside a loop; consider a HashSet",
+                    name_of(&expr.receiver).unwrap_or_default()
+                );
+                self.record("linear_search_in_loop", span, message);
+            }
+        } else if SORT_METHODS.contains(&method.as_str()) {
+            self.charge(Cost {
+                power: depth + 1,
+                log: true,
+                ..Cost::default()
+            });
+            let message = if depth > 0 {
+                format!("`{}` inside a loop costs O(n log n) per iteration", method)
+            } else {
+                format!("`{}` costs O(n log n)", method)
+            };
+            self.record("sort", span, message);
+        } else if ALLOCATING_METHODS.contains(&method.as_str()) && depth > 0 {
+            // Copying a whole collection is linear in its length
+            if matches!(method.as_str(), "to_vec" | "clone") && self.is_linear(&expr.receiver) {
+                self.charge(Cost::polynomial(depth + 1));
+            }
+            self.allocation_in_loop(&format!(".{}()", method), span);
+        } else if matches!(method.as_str(), "contains_key" | "entry") {
+            self.memoized = true;
+        }
+
+        let on_self = matches!(&*expr.receiver, Expr::Path(p) if p.path.is_ident("self"));
+        if on_self && expr.method == self.name {
+            self.recursive_call(&expr.args);
+        }
+
+        self.visit_expr(&expr.receiver);
+        let callback_bound = ITERATOR_CALLBACKS
+            .contains(&method.as_str())
+            .then(|| iterator_bound(&expr.receiver))
+            .flatten();
+        for arg in &expr.args {
+            match (arg, &callback_bound) {
+                (Expr::Closure(closure), Some(bound)) => {
+                    let element = closure
+                        .inputs
+                        .first()
+                        .and_then(|pat| element_binding(pat, &expr.receiver));
+                    self.with_loop(bound.clone(), element, closure.span(), |this| {
+                        this.visit_expr(&closure.body)
+                    });
+                }
+                _ => self.visit_expr(arg),
+            }
+        }
+    }
+
+    fn visit_expr_call(&mut self, expr: &'ast syn::ExprCall) {
+        if let Expr::Path(path) = &*expr.func {
+            let segments: Vec<String> = path
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            match segments.as_slice() {
+                [name] if *name == self.name => self.recursive_call(&expr.args),
+                [owner, name] if owner == "Self" && *name == self.name => {
+                    self.recursive_call(&expr.args)
+                }
+                [.., ty, call]
+                    if ALLOCATING_TYPES.contains(&ty.as_str())
+                        && ALLOCATING_CALLS.contains(&call.as_str()) =>
+                {
+                    self.allocation_in_loop(&format!("{}::{}", ty, call), expr.span());
+                }
+                _ => {}
+            }
+        }
+        visit::visit_expr_call(self, expr);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        if let Some(name) = mac.path.segments.last().map(|s| s.ident.to_string()) {
+            if ALLOCATING_MACROS.contains(&name.as_str()) {
+                self.allocation_in_loop(&format!("{}!", name), mac.span());
+            }
+        }
+    }
+
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        let (ident, ty) = match &local.pat {
+            Pat::Type(typed) => (pat_ident(&typed.pat), Some(&*typed.ty)),
+            pat => (pat_ident(pat), None),
+        };
+        if let Some(ident) = ident {
+            let init = local.init.as_ref().map(|init| &*init.expr);
+            if ty.is_some_and(is_linear_type) || init.is_some_and(is_linear_init) {
+                self.linear.insert(ident.to_string());
+            }
+        }
+        visit::visit_local(self, local);
+    }
+
+    fn visit_ident(&mut self, ident: &'ast Ident) {
+        if is_memo_name(ident) {
+            self.memoized = true;
+        }
+    }
+
+    fn visit_item(&mut self, _item: &'ast syn::Item) {
+        // Nested items are estimated separately by the collector
+    }
+}
+
+fn pat_ident(pat: &Pat) -> Option<&Ident> {
+    match pat {
+        Pat::Ident(pat) => Some(&pat.ident),
+        _ => None,
+    }
+}
+
+fn is_memo_name(ident: &Ident) -> bool {
+    let name = ident.to_string().to_lowercase();
+    name.contains("memo") || name.contains("cache")
+}
+
+/// Last path segment of `ty`, looking through references.
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Reference(reference) => type_name(&reference.elem),
+        Type::Paren(paren) => type_name(&paren.elem),
+        Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
+
+fn is_linear_type(ty: &Type) -> bool {
+    match ty {
+        Type::Slice(_) => true,
+        Type::Reference(reference) => is_linear_type(&reference.elem),
+        Type::Paren(paren) => is_linear_type(&paren.elem),
+        _ => type_name(ty).is_some_and(|name| LINEAR_TYPES.contains(&name.as_str())),
+    }
+}
+
+/// Whether `expr` builds a `Vec`.
+fn is_linear_init(expr: &Expr) -> bool {
+    match expr {
+        Expr::Macro(mac) => mac.mac.path.is_ident("vec"),
+        Expr::Call(call) => match &*call.func {
+            Expr::Path(path) => {
+                let mut segments = path.path.segments.iter().rev().map(|s| &s.ident);
+                let call = segments.next();
+                let ty = segments.next();
+                ty.is_some_and(|ty| LINEAR_TYPES.iter().any(|t| ty == t))
+                    && call.is_some_and(|c| ALLOCATING_CALLS.iter().any(|a| c == a))
+            }
+            _ => false,
+        },
+        Expr::MethodCall(call) if call.method == "to_vec" => true,
+        Expr::MethodCall(call) if call.method == "collect" => {
+            call.turbofish.as_ref().is_some_and(|turbofish| {
+                turbofish
+                    .args
+                    .iter()
+                    .any(|arg| matches!(arg, syn::GenericArgument::Type(ty) if is_linear_type(ty)))
+            })
+        }
+        _ => false,
+    }
+}
+
+/// Dotted name of a binding or field, e.g. `items` or `self.nodes`.
+fn name_of(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Path(path) => path.path.get_ident().map(ToString::to_string),
+        Expr::Field(field) => {
+            let member = match &field.member {
+                syn::Member::Named(ident) => ident.to_string(),
+                syn::Member::Unnamed(index) => index.index.to_string(),
+            };
+            name_of(&field.base).map(|base| format!("{}.{}", base, member))
+        }
+        Expr::Reference(reference) => name_of(&reference.expr),
+        Expr::Paren(paren) => name_of(&paren.expr),
+        _ => None,
+    }
+}
+
+/// What a `for` loop over `expr` iterates over.
+fn collection_bound(expr: &Expr) -> Bound {
+    match expr {
+        Expr::Paren(paren) => collection_bound(&paren.expr),
+        Expr::Reference(reference) => collection_bound(&reference.expr),
+        Expr::Lit(_) | Expr::Array(_) => Bound::Constant,
+        Expr::Range(range) => range
+            .end
+            .as_deref()
+            .map_or(Bound::Unknown, collection_bound),
+        // `n - 1`, `items.len() / 2`
+        Expr::Binary(binary) => collection_bound(&binary.left),
+        Expr::MethodCall(call) => {
+            let method = call.method.to_string();
+            if method == "len"
+                || ITERATOR_SOURCES.contains(&method.as_str())
+                || ITERATOR_ADAPTERS.contains(&method.as_str())
+            {
+                collection_bound(&call.receiver)
+            } else {
+                Bound::Unknown
+            }
+        }
+        _ => match name_of(expr) {
+            Some(name) if name.chars().all(|c| c.is_uppercase() || c == '_') => Bound::Constant,
+            Some(name) => Bound::Named(name),
+            None => Bound::Unknown,
+        },
+    }
+}
+
+/// Binding for each element when `pat` iterates a collection rather than a range.
+fn element_binding(pat: &Pat, iterated: &Expr) -> Option<String> {
+    fn over_range(expr: &Expr) -> bool {
+        match expr {
+            Expr::Range(_) => true,
+            Expr::Paren(paren) => over_range(&paren.expr),
+            Expr::Reference(reference) => over_range(&reference.expr),
+            Expr::MethodCall(call) => over_range(&call.receiver),
+            _ => false,
+        }
+    }
+
+    let pat = match pat {
+        Pat::Reference(reference) => &*reference.pat,
+        pat => pat,
+    };
+    (!over_range(iterated))
+        .then(|| pat_ident(pat))
+        .flatten()
+        .map(ToString::to_string)
+}
+
+/// Binding an argument is derived from, e.g. `child` in `&child.left`.
+fn root_ident(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Path(path) => path.path.get_ident().map(ToString::to_string),
+        Expr::Field(field) => root_ident(&field.base),
+        Expr::MethodCall(call) => root_ident(&call.receiver),
+        Expr::Reference(reference) => root_ident(&reference.expr),
+        Expr::Paren(paren) => root_ident(&paren.expr),
+        _ => None,
+    }
+}
+
+/// Bound of the iterator chain ending in `expr`, if it is one.
+fn iterator_bound(expr: &Expr) -> Option<Bound> {
+    match expr {
+        Expr::Paren(paren) => iterator_bound(&paren.expr),
+        Expr::Range(_) => Some(collection_bound(expr)),
+        Expr::MethodCall(call) => {
+            let method = call.method.to_string();
+            if ITERATOR_SOURCES.contains(&method.as_str()) {
+                Some(collection_bound(&call.receiver))
+            } else if ITERATOR_ADAPTERS.contains(&method.as_str()) {
+                iterator_bound(&call.receiver)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether `expr` looks like half of the input: `n / 2`, `&v[..mid]`.
+fn halves(expr: &Expr) -> bool {
+    struct Finder(bool);
+
+    impl<'ast> Visit<'ast> for Finder {
+        fn visit_bin_op(&mut self, op: &'ast BinOp) {
+            self.0 |= matches!(op, BinOp::Div(_) | BinOp::Shr(_));
+        }
+
+        fn visit_ident(&mut self, ident: &'ast Ident) {
+            self.0 |= matches!(ident.to_string().as_str(), "mid" | "middle" | "half");
+        }
+    }
+
+    let mut finder = Finder(false);
+    finder.visit_expr(expr);
+    finder.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimate<'a>(functions: &'a [FunctionEstimate], name: &str) -> &'a FunctionEstimate {
+        functions.iter().find(|f| f.function == name).unwrap()
+    }
+
+    fn kinds(function: &FunctionEstimate) -> Vec<&'static str> {
+        function.evidence.iter().map(|e| e.kind).collect()
+    }
+
+    #[test]
+    fn test_loops() {
+        let source = r#"
+fn constant(items: &[u32]) -> u32 {
+    let mut total = 0;
+    for i in 0..4 {
+        total += items[i];
+    }
+    total
+}
+
+fn linear(items: &[u32]) -> u32 {
+    items.iter().map(|x| x * 2).sum()
+}
+
+fn has_duplicate(items: &[u32]) -> bool {
+    for i in 0..items.len() {
+        for j in i + 1..items.len() {
+            if items[i] == items[j] {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn pairs(xs: &[u32], ys: &[u32]) -> usize {
+    let mut count = 0;
+    for x in xs {
+        for y in ys.iter() {
+            if x == y { count += 1; }
+        }
+    }
+    count
+}
+"#;
+        let functions = analyze_source(source).unwrap();
+        assert_eq!(estimate(&functions, "constant").class, "O(1)");
+        assert_eq!(estimate(&functions, "linear").class, "O(n)");
+
+        let duplicate = estimate(&functions, "has_duplicate");
+        assert_eq!(duplicate.class, "O(n^2)");
+        assert_eq!(kinds(duplicate), vec!["nested_loop_same_collection"]);
+        assert_eq!(duplicate.evidence[0].line, 16);
+        assert_eq!(duplicate.evidence[0].end_line, 20);
+
+        let pairs = estimate(&functions, "pairs");
+        assert_eq!(pairs.class, "O(n^2)");
+        assert_eq!(kinds(pairs), vec!["nested_loop"]);
+    }
+
+    #[test]
+    fn test_linear_search_sort_and_allocation() {
+        let source = r#"
+fn common(a: &[u32], b: Vec<u32>) -> Vec<u32> {
+    a.iter().filter(|x| b.contains(x)).copied().collect()
+}
+
+fn sorted(mut items: Vec<u32>) -> Vec<u32> {
+    items.sort_unstable();
+    items
+}
+
+fn labels(ids: &[u32]) -> usize {
+    let mut total = 0;
+    for id in ids {
+        let label = format!("id-{}", id);
+        total += label.len();
+    }
+    total
+}
+"#;
+        let functions = analyze_source(source).unwrap();
+        let common = estimate(&functions, "common");
+        assert_eq!(common.class, "O(n^2)");
+        assert_eq!(kinds(common), vec!["linear_search_in_loop"]);
+        assert!(common.evidence[0].message.contains("HashSet"));
+
+        assert_eq!(estimate(&functions, "sorted").class, "O(n log n)");
+
+        let labels = estimate(&functions, "labels");
+        assert_eq!(labels.class, "O(n)");
+        assert_eq!(kinds(labels), vec!["allocation_in_loop"]);
+        assert_eq!(labels.evidence[0].line, 14);
+    }
+
+    #[test]
+    fn test_recursion() {
+        let source = r#"
+fn fib(n: u64) -> u64 {
+    if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+}
+
+fn fib_memo(n: u64, memo: &mut HashMap<u64, u64>) -> u64 {
+    if let Some(&v) = memo.get(&n) { return v; }
+    let v = if n < 2 { n } else { fib_memo(n - 1, memo) + fib_memo(n - 2, memo) };
+    memo.insert(n, v);
+    v
+}
+
+fn search(items: &[u32], target: u32) -> bool {
+    if items.is_empty() { return false; }
+    let mid = items.len() / 2;
+    if items[mid] == target { true }
+    else if items[mid] < target { search(&items[mid + 1..], target) }
+    else { search(&items[..mid], target) }
+}
+
+fn depth(n: u32) -> u32 {
+    if n == 0 { 0 } else { 1 + depth(n - 1) }
+}
+"#;
+        let functions = analyze_source(source).unwrap();
+        let fib = estimate(&functions, "fib");
+        assert_eq!(fib.class, "O(2^n)");
+        assert_eq!(kinds(fib), vec!["exponential_recursion"]);
+        assert_eq!(fib.evidence[0].line, 3);
+
+        assert_eq!(estimate(&functions, "fib_memo").class, "O(n)");
+        assert_eq!(estimate(&functions, "depth").class, "O(n)");
+        // Two call sites in exclusive branches, each on half the input
+        assert_eq!(estimate(&functions, "search").class, "O(log n)");
+    }
+
+    #[test]
+    fn test_tree_walk_is_linear() {
+        let source = r#"
+fn count(node: &Node) -> usize {
+    let mut total = 1;
+    for child in &node.children {
+        total += count(child);
+    }
+    total
+}
+
+fn depth(node: &Node) -> usize {
+    node.children.iter().map(|c| depth(c)).max().unwrap_or(0) + 1
+}
+
+fn subsets(n: usize) -> usize {
+    let mut total = 1;
+    for i in 0..n {
+        total += subsets(i);
+    }
+    total
+}
+"#;
+        let functions = analyze_source(source).unwrap();
+        let count = estimate(&functions, "count");
+        assert_eq!(count.class, "O(n)");
+        assert_eq!(kinds(count), vec!["recursion"]);
+        assert_eq!(count.evidence[0].line, 5);
+
+        assert_eq!(estimate(&functions, "depth").class, "O(n)");
+        // A loop over a range is not a walk over children
+        let subsets = estimate(&functions, "subsets");
+        assert_eq!(subsets.class, "O(2^n)");
+        assert_eq!(kinds(subsets), vec!["exponential_recursion"]);
+    }
+
+    #[test]
+    fn test_analyze_path_summary() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(
+            src.join("lib.rs"),
+            "struct Grid;\nimpl Grid {\n    fn scan(&self, cells: &[u8]) -> usize {\n        cells.iter().filter(|a| cells.iter().any(|b| a == &b)).count()\n    }\n}\nfn id(x: u8) -> u8 { x }\n",
+        )
+        .unwrap();
+        std::fs::write(src.join("broken.rs"), "fn (").unwrap();
+
+        let report = analyze_path(dir.path(), &src);
+        assert_eq!(report.summary.total_files, 1);
+        assert_eq!(report.summary.total_functions, 2);
+        assert_eq!(report.summary.above_linear, 1);
+        assert_eq!(report.summary.by_class["O(n^2)"], 1);
+        assert_eq!(report.summary.by_class["O(1)"], 1);
+        assert_eq!(report.errors[0].file, "src/broken.rs");
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["functions"][0]["file"], "src/lib.rs");
+        assert_eq!(json["functions"][0]["function"], "Grid::scan");
+        assert_eq!(
+            json["functions"][0]["evidence"][0]["kind"],
+            "nested_loop_same_collection"
+        );
+    }
+}
@@ -0,0 +1,569 @@
+//! Cyclomatic and cognitive complexity of Rust functions.
+//!
+//! Cyclomatic complexity follows McCabe: one plus every branch point (`if`,
+//! `while`, `for`, each extra `match` arm and guard, `&&`, `||`). Cognitive
+//! complexity follows the SonarSource definition: control flow breaks are
+//! charged one plus the current nesting level, `else` branches, sequences of
+//! mixed logical operators, labelled jumps and direct recursion cost one
+//! each. Closures count towards the function that defines them; nested `fn`
+//! items are reported separately.
+
+use super::{display_path, source_files};
+use serde::Serialize;
+use std::path::Path;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{BinOp, Block, Expr, ImplItemFn, ItemFn, ItemImpl, ItemTrait, TraitItemFn};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Thresholds {
+    pub cyclomatic: u32,
+    pub cognitive: u32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            cyclomatic: 20,
+            cognitive: 15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionComplexity {
+    /// `name`, or `Type::name` for methods
+    pub name: String,
+    pub line: usize,
+    pub end_line: usize,
+    pub cyclomatic: u32,
+    pub cognitive: u32,
+    /// Deepest nesting of control flow and closures
+    pub nesting: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileComplexity {
+    pub file: String,
+    pub functions: Vec<FunctionComplexity>,
+}
+
+/// A function over one of the thresholds.
+#[derive(Debug, Clone, Serialize)]
+pub struct Violation {
+    pub file: String,
+    pub function: String,
+    pub line: usize,
+    /// "cyclomatic" or "cognitive"
+    pub metric: &'static str,
+    pub value: u32,
+    pub threshold: u32,
+    pub severity: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseFailure {
+    pub file: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub total_files: usize,
+    pub total_functions: usize,
+    pub max_complexity: u32,
+    pub average_complexity: f64,
+    pub max_cognitive: u32,
+    pub average_cognitive: f64,
+    pub violations: usize,
+    pub thresholds: Thresholds,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComplexityReport {
+    pub summary: Summary,
+    pub files: Vec<FileComplexity>,
+    pub violations: Vec<Violation>,
+    /// Files that could not be parsed
+    pub errors: Vec<ParseFailure>,
+}
+
+/// Complexity of every function in a Rust source file.
+pub fn analyze_source(source: &str) -> Result<Vec<FunctionComplexity>, syn::Error> {
+    let file = syn::parse_file(source)?;
+    let mut collector = FunctionCollector::default();
+    collector.visit_file(&file);
+    Ok(collector.functions)
+}
+
+/// Analyze every Rust file under `target`, reporting paths relative to `root`.
+pub fn analyze_path(root: &Path, target: &Path, thresholds: Thresholds) -> ComplexityReport {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+
+    for path in source_files(target, &["rs"]) {
+        let file = display_path(root, &path);
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| analyze_source(&source).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(functions) => files.push(FileComplexity { file, functions }),
+            Err(error) => errors.push(ParseFailure { file, error }),
+        }
+    }
+
+    build_report(files, errors, thresholds)
+}
+
+/// Summarize measured files and flag functions over `thresholds`.
+pub fn build_report(
+    files: Vec<FileComplexity>,
+    errors: Vec<ParseFailure>,
+    thresholds: Thresholds,
+) -> ComplexityReport {
+    let functions: Vec<&FunctionComplexity> = files.iter().flat_map(|f| &f.functions).collect();
+    let total = functions.len();
+    let average = |metric: fn(&FunctionComplexity) -> u32| {
+        if total == 0 {
+            0.0
+        } else {
+            let sum: u32 = functions.iter().map(|f| metric(f)).sum();
+            (sum as f64 / total as f64 * 100.0).round() / 100.0
+        }
+    };
+
+    let mut violations = Vec::new();
+    for file in &files {
+        for function in &file.functions {
+            let checks = [
+                ("cyclomatic", function.cyclomatic, thresholds.cyclomatic),
+                ("cognitive", function.cognitive, thresholds.cognitive),
+            ];
+            for (metric, value, threshold) in checks {
+                if value > threshold {
+                    violations.push(Violation {
+                        file: file.file.clone(),
+                        function: function.name.clone(),
+                        line: function.line,
+                        metric,
+                        value,
+                        threshold,
+                        severity: if value > threshold * 3 / 2 {
+                            "high"
+                        } else {
+                            "medium"
+                        },
+                        message: format!(
+                            "{} complexity of {} exceeds threshold {}",
+                            metric, value, threshold
+                        ),
+                    });
+                }
+            }
+        }
+    }
+
+    ComplexityReport {
+        summary: Summary {
+            total_files: files.len(),
+            total_functions: total,
+            max_complexity: functions.iter().map(|f| f.cyclomatic).max().unwrap_or(0),
+            average_complexity: average(|f| f.cyclomatic),
+            max_cognitive: functions.iter().map(|f| f.cognitive).max().unwrap_or(0),
+            average_cognitive: average(|f| f.cognitive),
+            violations: violations.len(),
+            thresholds,
+        },
+        files,
+        violations,
+        errors,
+    }
+}
+
+/// Finds function items and measures each body.
+#[derive(Default)]
+struct FunctionCollector {
+    /// Type or trait name of the enclosing `impl`/`trait` block
+    owner: Option<String>,
+    functions: Vec<FunctionComplexity>,
+}
+
+impl FunctionCollector {
+    fn measure(&mut self, ident: &syn::Ident, block: &Block) {
+        let name = match &self.owner {
+            Some(owner) => format!("{}::{}", owner, ident),
+            None => ident.to_string(),
+        };
+        let mut body = BodyComplexity::new(ident.to_string());
+        body.visit_block(block);
+        self.functions.push(FunctionComplexity {
+            name,
+            line: ident.span().start().line,
+            end_line: block.span().end().line,
+            cyclomatic: body.cyclomatic,
+            cognitive: body.cognitive,
+            nesting: body.max_nesting,
+        });
+    }
+
+    fn with_owner(&mut self, owner: Option<String>, visit: impl FnOnce(&mut Self)) {
+        let saved = std::mem::replace(&mut self.owner, owner);
+        visit(self);
+        self.owner = saved;
+    }
+}
+
+impl<'ast> Visit<'ast> for FunctionCollector {
+    fn visit_item_fn(&mut self, item: &'ast ItemFn) {
+        self.measure(&item.sig.ident, &item.block);
+        // Functions nested in the body are reported on their own
+        self.with_owner(None, |this| visit::visit_item_fn(this, item));
+    }
+
+    fn visit_item_impl(&mut self, item: &'ast ItemImpl) {
+        let owner = match &*item.self_ty {
+            syn::Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+            _ => None,
+        };
+        self.with_owner(owner, |this| visit::visit_item_impl(this, item));
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast ImplItemFn) {
+        self.measure(&item.sig.ident, &item.block);
+        self.with_owner(None, |this| visit::visit_impl_item_fn(this, item));
+    }
+
+    fn visit_item_trait(&mut self, item: &'ast ItemTrait) {
+        let owner = Some(item.ident.to_string());
+        self.with_owner(owner, |this| visit::visit_item_trait(this, item));
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast TraitItemFn) {
+        if let Some(block) = &item.default {
+            self.measure(&item.sig.ident, block);
+        }
+        self.with_owner(None, |this| visit::visit_trait_item_fn(this, item));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LogicalOp {
+    And,
+    Or,
+}
+
+/// Scores a single function body.
+struct BodyComplexity {
+    name: String,
+    cyclomatic: u32,
+    cognitive: u32,
+    nesting: u32,
+    max_nesting: u32,
+    /// Operator of the enclosing `&&`/`||` chain, if any
+    logical: Option<LogicalOp>,
+}
+
+impl BodyComplexity {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            cyclomatic: 1,
+            cognitive: 0,
+            nesting: 0,
+            max_nesting: 0,
+            logical: None,
+        }
+    }
+
+    /// Charge a structure that breaks linear flow and increases nesting.
+    fn structure(&mut self) {
+        self.cognitive += 1 + self.nesting;
+    }
+
+    fn nested(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.nesting += 1;
+        self.max_nesting = self.max_nesting.max(self.nesting);
+        visit(self);
+        self.nesting -= 1;
+    }
+
+    fn visit_if(&mut self, expr: &syn::ExprIf, else_if: bool) {
+        self.cyclomatic += 1;
+        if else_if {
+            self.cognitive += 1;
+        } else {
+            self.structure();
+        }
+        self.visit_expr(&expr.cond);
+        // An `else if` chain sits at the nesting level of its first `if`
+        if else_if {
+            self.visit_block(&expr.then_branch);
+        } else {
+            self.nested(|this| this.visit_block(&expr.then_branch));
+        }
+
+        if let Some((_, else_branch)) = &expr.else_branch {
+            match &**else_branch {
+                Expr::If(nested_if) => self.visit_if(nested_if, true),
+                other => {
+                    self.cognitive += 1;
+                    self.nested(|this| this.visit_expr(other));
+                }
+            }
+        }
+    }
+
+    fn is_recursive_call(&self, func: &Expr) -> bool {
+        match func {
+            Expr::Path(path) => path.path.is_ident(&self.name),
+            _ => false,
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for BodyComplexity {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match expr {
+            Expr::Binary(_) => visit::visit_expr(self, expr),
+            Expr::Paren(paren) => self.visit_expr(&paren.expr),
+            _ => {
+                // Any other expression starts a fresh operator sequence
+                let saved = self.logical.take();
+                visit::visit_expr(self, expr);
+                self.logical = saved;
+            }
+        }
+    }
+
+    fn visit_expr_binary(&mut self, expr: &'ast syn::ExprBinary) {
+        let op = match expr.op {
+            BinOp::And(_) => Some(LogicalOp::And),
+            BinOp::Or(_) => Some(LogicalOp::Or),
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.cyclomatic += 1;
+            if self.logical != Some(op) {
+                self.cognitive += 1;
+            }
+        }
+
+        let saved = std::mem::replace(&mut self.logical, op);
+        self.visit_expr(&expr.left);
+        self.visit_expr(&expr.right);
+        self.logical = saved;
+    }
+
+    fn visit_expr_if(&mut self, expr: &'ast syn::ExprIf) {
+        self.visit_if(expr, false);
+    }
+
+    fn visit_expr_match(&mut self, expr: &'ast syn::ExprMatch) {
+        let guards = expr.arms.iter().filter(|arm| arm.guard.is_some()).count();
+        self.cyclomatic += (expr.arms.len().saturating_sub(1) + guards) as u32;
+        self.structure();
+        self.visit_expr(&expr.expr);
+        self.nested(|this| {
+            for arm in &expr.arms {
+                this.visit_arm(arm);
+            }
+        });
+    }
+
+    fn visit_expr_while(&mut self, expr: &'ast syn::ExprWhile) {
+        self.cyclomatic += 1;
+        self.structure();
+        self.visit_expr(&expr.cond);
+        self.nested(|this| this.visit_block(&expr.body));
+    }
+
+    fn visit_expr_for_loop(&mut self, expr: &'ast syn::ExprForLoop) {
+        self.cyclomatic += 1;
+        self.structure();
+        self.visit_expr(&expr.expr);
+        self.nested(|this| this.visit_block(&expr.body));
+    }
+
+    fn visit_expr_loop(&mut self, expr: &'ast syn::ExprLoop) {
+        // `loop` only exits through a `break`, whose condition is counted
+        self.structure();
+        self.nested(|this| this.visit_block(&expr.body));
+    }
+
+    fn visit_expr_closure(&mut self, expr: &'ast syn::ExprClosure) {
+        self.nested(|this| this.visit_expr(&expr.body));
+    }
+
+    fn visit_expr_break(&mut self, expr: &'ast syn::ExprBreak) {
+        if expr.label.is_some() {
+            self.cognitive += 1;
+        }
+        visit::visit_expr_break(self, expr);
+    }
+
+    fn visit_expr_continue(&mut self, expr: &'ast syn::ExprContinue) {
+        if expr.label.is_some() {
+            self.cognitive += 1;
+        }
+    }
+
+    fn visit_expr_call(&mut self, expr: &'ast syn::ExprCall) {
+        if self.is_recursive_call(&expr.func) {
+            self.cognitive += 1;
+        }
+        visit::visit_expr_call(self, expr);
+    }
+
+    fn visit_expr_method_call(&mut self, expr: &'ast syn::ExprMethodCall) {
+        let on_self = matches!(&*expr.receiver, Expr::Path(p) if p.path.is_ident("self"));
+        if on_self && expr.method == self.name {
+            self.cognitive += 1;
+        }
+        visit::visit_expr_method_call(self, expr);
+    }
+
+    fn visit_item(&mut self, _item: &'ast syn::Item) {
+        // Nested items are measured separately by the collector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function<'a>(functions: &'a [FunctionComplexity], name: &str) -> &'a FunctionComplexity {
+        functions.iter().find(|f| f.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_straight_line_function() {
+        let functions = analyze_source("fn simple() -> u32 {\n    1 + 2\n}\n").unwrap();
+        assert_eq!(functions.len(), 1);
+        let simple = &functions[0];
+        assert_eq!(simple.name, "simple");
+        assert_eq!((simple.line, simple.end_line), (1, 3));
+        assert_eq!(
+            (simple.cyclomatic, simple.cognitive, simple.nesting),
+            (1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_branches_and_nesting() {
+        let source = r#"
+fn classify(items: &[i32], limit: i32) -> i32 {
+    let mut total = 0;
+    for item in items {                  // cyc +1, cog +1
+        if *item > limit && *item < 100 { // cyc +2, cog +2 (nesting 1) +1 (&&)
+            total += 1;
+        } else if *item == 0 {            // cyc +1, cog +1
+            continue;
+        } else {                          // cog +1
+            total -= 1;
+        }
+    }
+    match total {                         // cyc +2, cog +1
+        0 => 0,
+        n if n > 10 => 10,                // guard cyc +1
+        n => n,
+    }
+}
+"#;
+        let functions = analyze_source(source).unwrap();
+        let classify = function(&functions, "classify");
+        assert_eq!(classify.cyclomatic, 8);
+        assert_eq!(classify.cognitive, 7);
+        assert_eq!(classify.nesting, 2);
+    }
+
+    #[test]
+    fn test_logical_sequences_and_recursion() {
+        let source = r#"
+fn check(a: bool, b: bool, c: bool, n: u32) -> bool {
+    let mixed = a && b || c;   // cyc +2, cog +2
+    let same = a && b && c;    // cyc +2, cog +1
+    if n == 0 { return mixed; } // cyc +1, cog +1
+    check(a, b, same, n - 1)   // cog +1 recursion
+}
+"#;
+        let functions = analyze_source(source).unwrap();
+        let check = function(&functions, "check");
+        assert_eq!(check.cyclomatic, 6);
+        assert_eq!(check.cognitive, 5);
+    }
+
+    #[test]
+    fn test_methods_and_nested_functions() {
+        let source = r#"
+struct Parser;
+
+impl Parser {
+    fn parse(&self, input: &str) -> usize {
+        fn helper(x: usize) -> usize {
+            if x > 1 { x } else { 0 }
+        }
+        input.chars().filter(|c| if c.is_alphabetic() { true } else { false }).count() + helper(1)
+    }
+}
+
+trait Visitor {
+    fn visit(&self) -> bool {
+        true
+    }
+    fn required(&self);
+}
+"#;
+        let functions = analyze_source(source).unwrap();
+        let names: Vec<&str> = functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["Parser::parse", "helper", "Visitor::visit"]);
+
+        // The closure's `if` belongs to `parse`, nested one level deeper
+        let parse = function(&functions, "Parser::parse");
+        assert_eq!(parse.cyclomatic, 2);
+        assert_eq!(parse.cognitive, 3);
+        assert_eq!(parse.nesting, 2);
+
+        let helper = function(&functions, "helper");
+        assert_eq!(helper.line, 6);
+        assert_eq!(helper.cyclomatic, 2);
+    }
+
+    #[test]
+    fn test_report_violations() {
+        let files = vec![FileComplexity {
+            file: "src/lib.rs".to_string(),
+            functions: vec![
+                FunctionComplexity {
+                    name: "tangled".to_string(),
+                    line: 10,
+                    end_line: 90,
+                    cyclomatic: 31,
+                    cognitive: 12,
+                    nesting: 4,
+                },
+                FunctionComplexity {
+                    name: "tidy".to_string(),
+                    line: 95,
+                    end_line: 99,
+                    cyclomatic: 1,
+                    cognitive: 0,
+                    nesting: 0,
+                },
+            ],
+        }];
+
+        let report = build_report(files, Vec::new(), Thresholds::default());
+        assert_eq!(report.summary.total_functions, 2);
+        assert_eq!(report.summary.max_complexity, 31);
+        assert_eq!(report.summary.average_complexity, 16.0);
+        assert_eq!(report.violations.len(), 1);
+
+        let violation = &report.violations[0];
+        assert_eq!(violation.function, "tangled");
+        assert_eq!(violation.line, 10);
+        assert_eq!(violation.metric, "cyclomatic");
+        assert_eq!(violation.value, 31);
+        assert_eq!(violation.severity, "high");
+    }
+}
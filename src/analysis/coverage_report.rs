@@ -0,0 +1,526 @@
+//! LCOV and Cobertura coverage reports as per-file, per-function data.
+
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// An inclusive range of line numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionCoverage {
+    pub name: String,
+    pub line: usize,
+    pub hits: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FileCoverage {
+    pub file: String,
+    pub lines_hit: usize,
+    pub lines_total: usize,
+    pub percent: f64,
+    pub uncovered: Vec<LineRange>,
+    pub functions_hit: usize,
+    pub functions_total: usize,
+    pub functions: Vec<FunctionCoverage>,
+    /// Hit count per instrumented line
+    #[serde(skip)]
+    pub line_hits: BTreeMap<usize, u64>,
+}
+
+impl FileCoverage {
+    fn new(file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Recompute the derived fields from `line_hits` and `functions`.
+    fn finish(&mut self) {
+        self.lines_total = self.line_hits.len();
+        self.lines_hit = self.line_hits.values().filter(|&&hits| hits > 0).count();
+        self.percent = percent(self.lines_hit, self.lines_total);
+        self.uncovered = ranges(
+            self.line_hits
+                .iter()
+                .filter(|(_, &hits)| hits == 0)
+                .map(|(&line, _)| line),
+        );
+        self.functions.sort_by_key(|f| f.line);
+        self.functions_total = self.functions.len();
+        self.functions_hit = self.functions.iter().filter(|f| f.hits > 0).count();
+    }
+
+    /// Whether `line` is instrumented and was never executed.
+    pub fn is_uncovered(&self, line: usize) -> bool {
+        self.line_hits.get(&line) == Some(&0)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CoverageReport {
+    pub percent: f64,
+    pub lines_hit: usize,
+    pub lines_total: usize,
+    pub functions_hit: usize,
+    pub functions_total: usize,
+    pub files: Vec<FileCoverage>,
+}
+
+impl CoverageReport {
+    fn from_files(files: BTreeMap<String, FileCoverage>) -> Self {
+        let mut files: Vec<FileCoverage> = files.into_values().collect();
+        for file in &mut files {
+            file.finish();
+        }
+        let lines_hit = files.iter().map(|f| f.lines_hit).sum();
+        let lines_total = files.iter().map(|f| f.lines_total).sum();
+        Self {
+            percent: percent(lines_hit, lines_total),
+            lines_hit,
+            lines_total,
+            functions_hit: files.iter().map(|f| f.functions_hit).sum(),
+            functions_total: files.iter().map(|f| f.functions_total).sum(),
+            files,
+        }
+    }
+
+    pub fn file(&self, path: &str) -> Option<&FileCoverage> {
+        self.files.iter().find(|f| f.file == path)
+    }
+
+    /// Rewrite file paths relative to `root` where they lie inside it.
+    pub fn relativize(&mut self, root: &std::path::Path) {
+        for file in &mut self.files {
+            if let Ok(relative) = std::path::Path::new(&file.file).strip_prefix(root) {
+                file.file = relative.to_string_lossy().to_string();
+            }
+        }
+    }
+}
+
+pub fn percent(hit: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 100.0;
+    }
+    (hit as f64 / total as f64 * 10000.0).round() / 100.0
+}
+
+/// Collapse sorted line numbers into inclusive ranges.
+pub fn ranges(lines: impl IntoIterator<Item = usize>) -> Vec<LineRange> {
+    let mut out: Vec<LineRange> = Vec::new();
+    for line in lines {
+        match out.last_mut() {
+            Some(range) if range.end + 1 == line => range.end = line,
+            _ => out.push(LineRange {
+                start: line,
+                end: line,
+            }),
+        }
+    }
+    out
+}
+
+/// Parse an LCOV tracefile (`SF`, `FN`, `FNDA`, `DA` records).
+pub fn parse_lcov(text: &str) -> CoverageReport {
+    let mut files: BTreeMap<String, FileCoverage> = BTreeMap::new();
+    let mut current: Option<FileCoverage> = None;
+    let mut function_lines: BTreeMap<String, usize> = BTreeMap::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        let (tag, value) = line.split_once(':').unwrap_or((line, ""));
+        match tag {
+            "SF" => {
+                let file = files
+                    .remove(value)
+                    .unwrap_or_else(|| FileCoverage::new(value));
+                current = Some(file);
+                function_lines.clear();
+            }
+            "FN" => {
+                // FN:<line>,<name> or, since lcov 2.0, FN:<line>,<end line>,<name>
+                let mut parts = value.splitn(3, ',');
+                let start = parts.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+                let rest: Vec<&str> = parts.collect();
+                if let Some(name) = rest.last() {
+                    function_lines.insert(name.to_string(), start);
+                }
+            }
+            "FNDA" => {
+                if let (Some(file), Some((hits, name))) = (current.as_mut(), value.split_once(','))
+                {
+                    let hits = hits.parse().unwrap_or(0);
+                    let line = function_lines.get(name).copied().unwrap_or(0);
+                    match file.functions.iter_mut().find(|f| f.name == name) {
+                        Some(function) => function.hits += hits,
+                        None => file.functions.push(FunctionCoverage {
+                            name: name.to_string(),
+                            line,
+                            hits,
+                        }),
+                    }
+                }
+            }
+            "DA" => {
+                let mut parts = value.split(',');
+                let number = parts.next().and_then(|n| n.parse().ok());
+                let hits = parts.next().and_then(|n| n.parse::<u64>().ok());
+                if let (Some(file), Some(number), Some(hits)) = (current.as_mut(), number, hits) {
+                    *file.line_hits.entry(number).or_insert(0) += hits;
+                }
+            }
+            "end_of_record" => {
+                if let Some(mut file) = current.take() {
+                    // Functions declared without an FNDA record were never hit
+                    for (name, &line) in &function_lines {
+                        if !file.functions.iter().any(|f| &f.name == name) {
+                            file.functions.push(FunctionCoverage {
+                                name: name.clone(),
+                                line,
+                                hits: 0,
+                            });
+                        }
+                    }
+                    files.insert(file.file.clone(), file);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    CoverageReport::from_files(files)
+}
+
+/// Lines added or modified per file in a `git diff --unified=0` patch.
+pub fn changed_lines(diff: &str) -> BTreeMap<String, Vec<usize>> {
+    let mut changed: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    let mut current: Option<String> = None;
+
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            // `+++ /dev/null` marks a deleted file
+            current = path.strip_prefix("b/").map(str::to_string);
+        } else if let (Some(hunk), Some(file)) = (line.strip_prefix("@@ "), &current) {
+            // @@ -<old>[,<count>] +<start>[,<count>] @@
+            let Some(new) = hunk.split_whitespace().find_map(|p| p.strip_prefix('+')) else {
+                continue;
+            };
+            let (start, count) = match new.split_once(',') {
+                Some((start, count)) => (start.parse().unwrap_or(0), count.parse().unwrap_or(0)),
+                None => (new.parse().unwrap_or(0), 1),
+            };
+            changed
+                .entry(file.clone())
+                .or_default()
+                .extend(start..start + count);
+        }
+    }
+    changed
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDiffCoverage {
+    pub file: String,
+    /// Changed lines that are instrumented
+    pub lines_changed: usize,
+    pub lines_covered: usize,
+    pub percent: f64,
+    pub uncovered: Vec<LineRange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffCoverage {
+    pub percent: f64,
+    pub lines_changed: usize,
+    pub lines_covered: usize,
+    pub files: Vec<FileDiffCoverage>,
+}
+
+/// Intersect changed lines with coverage data.
+///
+/// Changed lines that carry no instrumentation (comments, blank lines,
+/// declarations) are ignored, as are files the report does not cover.
+pub fn diff_coverage(
+    report: &CoverageReport,
+    changed: &BTreeMap<String, Vec<usize>>,
+) -> DiffCoverage {
+    let mut files = Vec::new();
+    for (path, lines) in changed {
+        let Some(coverage) = report.file(path) else {
+            continue;
+        };
+        let instrumented: Vec<usize> = lines
+            .iter()
+            .copied()
+            .filter(|line| coverage.line_hits.contains_key(line))
+            .collect();
+        if instrumented.is_empty() {
+            continue;
+        }
+        let uncovered: Vec<usize> = instrumented
+            .iter()
+            .copied()
+            .filter(|&line| coverage.is_uncovered(line))
+            .collect();
+        let covered = instrumented.len() - uncovered.len();
+        files.push(FileDiffCoverage {
+            file: path.clone(),
+            lines_changed: instrumented.len(),
+            lines_covered: covered,
+            percent: percent(covered, instrumented.len()),
+            uncovered: ranges(uncovered),
+        });
+    }
+
+    let lines_changed = files.iter().map(|f| f.lines_changed).sum();
+    let lines_covered = files.iter().map(|f| f.lines_covered).sum();
+    DiffCoverage {
+        percent: percent(lines_covered, lines_changed),
+        lines_changed,
+        lines_covered,
+        files,
+    }
+}
+
+fn tag_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"<(/?)([A-Za-z_][\w.-]*)([^>]*?)(/?)>").unwrap())
+}
+
+fn attribute_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r#"([\w.-]+)\s*=\s*"([^"]*)""#).unwrap())
+}
+
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    attribute_pattern()
+        .captures_iter(attrs)
+        .find(|caps| &caps[1] == name)
+        .map(|caps| unescape(&caps[2]))
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Parse a Cobertura XML report.
+///
+/// Line hits come from each class's `<lines>`; function coverage from its
+/// `<methods>`, using the first line of each method.
+pub fn parse_cobertura(xml: &str) -> CoverageReport {
+    let mut files: BTreeMap<String, FileCoverage> = BTreeMap::new();
+    let mut class_file: Option<String> = None;
+    let mut method: Option<FunctionCoverage> = None;
+
+    for caps in tag_pattern().captures_iter(xml) {
+        let closing = !caps[1].is_empty();
+        let name = &caps[2];
+        let attrs = &caps[3];
+        let self_closing = !caps[4].is_empty();
+
+        match (name, closing) {
+            ("class", false) => {
+                class_file = attribute(attrs, "filename");
+                if let Some(file) = &class_file {
+                    files
+                        .entry(file.clone())
+                        .or_insert_with(|| FileCoverage::new(file));
+                }
+                if self_closing {
+                    class_file = None;
+                }
+            }
+            ("class", true) => class_file = None,
+            ("method", false) => {
+                method = Some(FunctionCoverage {
+                    name: attribute(attrs, "name").unwrap_or_default(),
+                    line: 0,
+                    hits: 0,
+                });
+            }
+            ("method", true) => {
+                if let (Some(function), Some(file)) = (method.take(), &class_file) {
+                    if let Some(coverage) = files.get_mut(file) {
+                        coverage.functions.push(function);
+                    }
+                }
+            }
+            ("line", false) => {
+                let number = attribute(attrs, "number").and_then(|n| n.parse().ok());
+                let hits = attribute(attrs, "hits")
+                    .and_then(|h| h.parse::<u64>().ok())
+                    .unwrap_or(0);
+                let Some(number) = number else {
+                    continue;
+                };
+                if let Some(function) = method.as_mut() {
+                    // Method lines repeat the class lines; only note the entry
+                    if function.line == 0 {
+                        function.line = number;
+                        function.hits = hits;
+                    }
+                } else if let Some(coverage) = class_file.as_ref().and_then(|f| files.get_mut(f)) {
+                    *coverage.line_hits.entry(number).or_insert(0) += hits;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    CoverageReport::from_files(files)
+}
+
+/// Parse either format, detected from the content.
+pub fn parse(text: &str) -> CoverageReport {
+    if text.trim_start().starts_with('<') {
+        parse_cobertura(text)
+    } else {
+        parse_lcov(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LCOV: &str = "\
+TN:
+SF:/work/src/lib.rs
+FN:3,add
+FN:10,16,unused
+FNDA:4,add
+FNDA:0,unused
+DA:3,4
+DA:4,4
+DA:10,0
+DA:11,0
+DA:12,0
+DA:15,1
+end_of_record
+SF:/work/src/main.rs
+DA:1,1
+end_of_record
+";
+
+    #[test]
+    fn test_parse_lcov() {
+        let report = parse_lcov(LCOV);
+        assert_eq!(report.files.len(), 2);
+        assert_eq!((report.lines_hit, report.lines_total), (4, 7));
+        assert_eq!(report.percent, 57.14);
+
+        let lib = report.file("/work/src/lib.rs").unwrap();
+        assert_eq!((lib.lines_hit, lib.lines_total), (3, 6));
+        assert_eq!(lib.uncovered, vec![LineRange { start: 10, end: 12 }]);
+        assert_eq!(
+            lib.functions,
+            vec![
+                FunctionCoverage {
+                    name: "add".to_string(),
+                    line: 3,
+                    hits: 4
+                },
+                FunctionCoverage {
+                    name: "unused".to_string(),
+                    line: 10,
+                    hits: 0
+                },
+            ]
+        );
+        assert_eq!((lib.functions_hit, lib.functions_total), (1, 2));
+        assert!(lib.is_uncovered(11));
+        assert!(!lib.is_uncovered(3));
+        assert!(!lib.is_uncovered(5));
+
+        let mut report = report;
+        report.relativize(std::path::Path::new("/work"));
+        assert!(report.file("src/main.rs").is_some());
+    }
+
+    #[test]
+    fn test_parse_cobertura() {
+        let xml = r#"<?xml version="1.0" ?>
+<coverage line-rate="0.6" version="1.9">
+  <packages><package name="pcode"><classes>
+    <class name="lib" filename="src/lib.rs" line-rate="0.6">
+      <methods>
+        <method name="add" signature="" line-rate="1">
+          <lines><line number="3" hits="2"/></lines>
+        </method>
+        <method name="sub" signature="" line-rate="0">
+          <lines><line number="8" hits="0"/></lines>
+        </method>
+      </methods>
+      <lines>
+        <line number="3" hits="2"/>
+        <line number="4" hits="2"/>
+        <line number="8" hits="0"/>
+        <line number="9" hits="0"/>
+        <line number="12" hits="1" branch="true" condition-coverage="50% (1/2)"/>
+      </lines>
+    </class>
+  </classes></package></packages>
+</coverage>"#;
+
+        let report = parse_cobertura(xml);
+        let lib = report.file("src/lib.rs").unwrap();
+        assert_eq!((lib.lines_hit, lib.lines_total), (3, 5));
+        assert_eq!(lib.uncovered, vec![LineRange { start: 8, end: 9 }]);
+        assert_eq!(lib.functions.len(), 2);
+        assert_eq!(lib.functions[1].name, "sub");
+        assert_eq!(lib.functions[1].line, 8);
+        assert_eq!(lib.functions[1].hits, 0);
+        assert_eq!(report.percent, 60.0);
+
+        // Content sniffing picks the right parser
+        assert_eq!(parse(xml).lines_total, 5);
+        assert_eq!(parse(LCOV).lines_total, 7);
+    }
+
+    #[test]
+    fn test_diff_coverage() {
+        let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -2,0 +3,2 @@ fn add()
++    let x = 1;
++    x
+@@ -9 +10,3 @@
++a
++b
++c
+@@ -20,2 +22,0 @@
+diff --git a/gone.rs b/gone.rs
+--- a/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+";
+        let changed = changed_lines(diff);
+        assert_eq!(changed["src/lib.rs"], vec![3, 4, 10, 11, 12]);
+        assert!(!changed.contains_key("gone.rs"));
+
+        let mut report = parse_lcov(LCOV);
+        report.relativize(std::path::Path::new("/work"));
+        let diff = diff_coverage(&report, &changed);
+        // Lines 3 and 4 are covered, 10-12 are not
+        assert_eq!((diff.lines_changed, diff.lines_covered), (5, 2));
+        assert_eq!(diff.percent, 40.0);
+        assert_eq!(
+            diff.files[0].uncovered,
+            vec![LineRange { start: 10, end: 12 }]
+        );
+    }
+}
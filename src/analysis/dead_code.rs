@@ -0,0 +1,259 @@
+//! Likely-unused Rust functions.
+//!
+//! A private function whose name never appears in code outside its own
+//! definition, anywhere in the workspace, is reported. Public items, trait
+//! implementations, tests, `main` and functions exported through attributes
+//! are assumed reachable. The check is name based, so a function sharing its
+//! name with something that is used is never reported.
+
+use super::lexer::{segments, SegmentKind, Syntax};
+use super::{display_path, source_files};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use syn::visit::{self, Visit};
+use syn::{Attribute, ImplItemFn, ItemFn, ItemImpl, ItemMod, Visibility};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadFunction {
+    pub file: String,
+    pub name: String,
+    pub line: usize,
+    /// "function" or "method"
+    pub kind: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub total_functions: usize,
+    pub dead_functions: usize,
+    pub dead_percentage: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadCodeReport {
+    pub summary: Summary,
+    pub dead_code: Vec<DeadFunction>,
+}
+
+struct Candidate {
+    name: String,
+    line: usize,
+    kind: &'static str,
+    /// Whether the function may be unused without being dead
+    exempt: bool,
+}
+
+/// Report likely-dead functions under `target`, resolving references
+/// against every Rust file under `root`.
+pub fn analyze_path(root: &Path, target: &Path) -> DeadCodeReport {
+    let mut references: HashMap<String, usize> = HashMap::new();
+    for path in source_files(root, &["rs"]) {
+        if let Ok(source) = std::fs::read_to_string(&path) {
+            count_identifiers(&source, &mut references);
+        }
+    }
+
+    let mut total = 0;
+    let mut dead_code = Vec::new();
+    let mut definitions: Vec<(String, Vec<Candidate>)> = Vec::new();
+    for path in source_files(target, &["rs"]) {
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        if let Ok(file) = syn::parse_file(&source) {
+            let mut collector = Collector::default();
+            collector.visit_file(&file);
+            total += collector.candidates.len();
+            definitions.push((display_path(root, &path), collector.candidates));
+        }
+    }
+
+    // A name defined N times is referenced only if it occurs more than N times
+    let mut defined: HashMap<&str, usize> = HashMap::new();
+    for candidate in definitions.iter().flat_map(|(_, c)| c) {
+        *defined.entry(&candidate.name).or_insert(0) += 1;
+    }
+
+    for (file, candidates) in &definitions {
+        for candidate in candidates {
+            let uses = references.get(&candidate.name).copied().unwrap_or(0);
+            if !candidate.exempt && uses <= defined[candidate.name.as_str()] {
+                dead_code.push(DeadFunction {
+                    file: file.clone(),
+                    name: candidate.name.clone(),
+                    line: candidate.line,
+                    kind: candidate.kind,
+                });
+            }
+        }
+    }
+
+    let dead = dead_code.len();
+    DeadCodeReport {
+        summary: Summary {
+            total_functions: total,
+            dead_functions: dead,
+            dead_percentage: if total == 0 {
+                0.0
+            } else {
+                (dead as f64 / total as f64 * 1000.0).round() / 10.0
+            },
+        },
+        dead_code,
+    }
+}
+
+/// Count identifier occurrences in code, ignoring comments and strings.
+///
+/// A string literal holding nothing but a path, as in
+/// `#[serde(default = "default_port")]`, counts as a use of its last segment.
+fn count_identifiers(source: &str, counts: &mut HashMap<String, usize>) {
+    for segment in segments(source, Syntax::Rust) {
+        let text = &source[segment.start..segment.end];
+        match segment.kind {
+            SegmentKind::Code => count_code_identifiers(text, counts),
+            SegmentKind::String => {
+                let inner = text.trim_matches('"');
+                let is_path = inner.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                    && inner
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '_' || c == ':');
+                if let Some(name) = inner.rsplit("::").next().filter(|_| is_path) {
+                    *counts.entry(name.to_string()).or_insert(0) += 1;
+                }
+            }
+            SegmentKind::Comment => {}
+        }
+    }
+}
+
+fn count_code_identifiers(code: &str, counts: &mut HashMap<String, usize>) {
+    let mut rest = code;
+    while let Some(start) = rest.find(|c: char| c.is_alphabetic() || c == '_') {
+        let tail = &rest[start..];
+        let len = tail
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(tail.len());
+        // Skip identifiers glued to a preceding digit, e.g. `1_000u32`
+        let glued = rest[..start]
+            .chars()
+            .last()
+            .is_some_and(|c| c.is_ascii_digit());
+        if !glued {
+            *counts.entry(tail[..len].to_string()).or_insert(0) += 1;
+        }
+        rest = &tail[len..];
+    }
+}
+
+/// Tests, benches and symbols exported to other linkers.
+fn is_entry_point(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        let path = attr.path();
+        ENTRY_ATTRIBUTES.iter().any(|name| path.is_ident(name))
+            || path.segments.last().is_some_and(|s| s.ident == "test")
+    })
+}
+
+fn is_cfg_test(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg")
+            && attr
+                .parse_args::<syn::Ident>()
+                .is_ok_and(|ident| ident == "test")
+    })
+}
+
+const ENTRY_ATTRIBUTES: &[&str] = &["no_mangle", "export_name", "bench", "ctor"];
+
+#[derive(Default)]
+struct Collector {
+    in_trait_impl: bool,
+    candidates: Vec<Candidate>,
+}
+
+impl<'ast> Visit<'ast> for Collector {
+    fn visit_item_mod(&mut self, item: &'ast ItemMod) {
+        // Test modules only hold test code
+        if !is_cfg_test(&item.attrs) {
+            visit::visit_item_mod(self, item);
+        }
+    }
+
+    fn visit_item_fn(&mut self, item: &'ast ItemFn) {
+        let name = item.sig.ident.to_string();
+        self.candidates.push(Candidate {
+            exempt: name == "main"
+                || !matches!(item.vis, Visibility::Inherited)
+                || is_entry_point(&item.attrs),
+            line: item.sig.ident.span().start().line,
+            name,
+            kind: "function",
+        });
+        visit::visit_item_fn(self, item);
+    }
+
+    fn visit_item_impl(&mut self, item: &'ast ItemImpl) {
+        let saved = std::mem::replace(&mut self.in_trait_impl, item.trait_.is_some());
+        visit::visit_item_impl(self, item);
+        self.in_trait_impl = saved;
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast ImplItemFn) {
+        self.candidates.push(Candidate {
+            name: item.sig.ident.to_string(),
+            line: item.sig.ident.span().start().line,
+            kind: "method",
+            exempt: self.in_trait_impl
+                || !matches!(item.vis, Visibility::Inherited)
+                || is_entry_point(&item.attrs),
+        });
+        visit::visit_impl_item_fn(self, item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_finds_unreferenced_private_functions() {
+        let dir = TempDir::new().unwrap();
+        let source = r#"
+fn main() { used(); }
+#[derive(Deserialize)]
+struct Config { #[serde(default = "defaults::port")] port: u16 }
+fn port() -> u16 { 80 }
+fn used() {}
+fn orphan() { let _ = "orphan is only mentioned in this string"; }
+pub fn exported() {}
+struct S;
+impl S { fn helper(&self) {} fn called(&self) {} fn run(&self) { self.called() } }
+impl Clone for S { fn clone(&self) -> Self { S } }
+// orphan is only mentioned in this comment
+#[cfg(test)]
+mod tests { fn fixture() {} }
+"#;
+        std::fs::write(dir.path().join("main.rs"), source).unwrap();
+
+        let report = analyze_path(dir.path(), dir.path());
+        let dead: Vec<(&str, &str)> = report
+            .dead_code
+            .iter()
+            .map(|d| (d.name.as_str(), d.kind))
+            .collect();
+        assert_eq!(
+            dead,
+            vec![
+                ("orphan", "function"),
+                ("helper", "method"),
+                ("run", "method")
+            ]
+        );
+        assert_eq!(report.dead_code[0].line, 7);
+        assert_eq!(report.summary.total_functions, 9);
+        assert_eq!(report.summary.dead_functions, 3);
+    }
+}
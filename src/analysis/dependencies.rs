@@ -0,0 +1,1222 @@
+//! Dependency graphs from package manifests and lockfiles.
+//!
+//! Cargo (`Cargo.toml`, `Cargo.lock`), npm (`package.json`,
+//! `package-lock.json`) and Python (`requirements*.txt`, `pyproject.toml`,
+//! `poetry.lock`) projects are read. Manifests give the direct dependencies
+//! and lockfiles the resolved versions and the edges between packages, so
+//! without a lockfile only direct dependencies are known.
+//!
+//! A direct dependency is likely unused when its import name never appears
+//! in code (Rust, Python) or in an import specifier (JS/TS) under its
+//! manifest's directory. Crates pulled in only for their features and
+//! packages used from configuration files look unused too, so the list is a
+//! set of leads rather than a verdict.
+
+use super::complexity::ParseFailure;
+use super::lexer::{segments, SegmentKind, Syntax};
+use super::{display_path, source_files};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+const CARGO: &str = "cargo";
+const NPM: &str = "npm";
+const PYTHON: &str = "python";
+
+const CARGO_TABLES: &[(&str, &str)] = &[
+    ("dependencies", "normal"),
+    ("dev-dependencies", "dev"),
+    ("build-dependencies", "build"),
+];
+const NPM_FIELDS: &[(&str, &str)] = &[
+    ("dependencies", "normal"),
+    ("devDependencies", "dev"),
+    ("optionalDependencies", "optional"),
+    ("peerDependencies", "peer"),
+];
+const JS_EXTENSIONS: &[&str] = &["js", "jsx", "mjs", "cjs", "ts", "tsx", "mts", "cts"];
+
+/// Distributions whose import name differs from the project name.
+const PYTHON_IMPORTS: &[(&str, &str)] = &[
+    ("attrs", "attr"),
+    ("beautifulsoup4", "bs4"),
+    ("opencv-python", "cv2"),
+    ("pillow", "pil"),
+    ("protobuf", "google"),
+    ("python-dateutil", "dateutil"),
+    ("pyyaml", "yaml"),
+    ("scikit-learn", "sklearn"),
+];
+
+/// A resolved package from a lockfile.
+#[derive(Debug, Clone, Serialize)]
+pub struct Package {
+    /// `ecosystem:name@version`
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub ecosystem: &'static str,
+    /// Ids of the packages this one depends on
+    pub dependencies: Vec<String>,
+}
+
+impl Package {
+    fn new(ecosystem: &'static str, name: &str, version: &str) -> Self {
+        Self {
+            id: package_id(ecosystem, name, version),
+            name: name.to_string(),
+            version: version.to_string(),
+            ecosystem,
+            dependencies: Vec::new(),
+        }
+    }
+}
+
+fn package_id(ecosystem: &str, name: &str, version: &str) -> String {
+    format!("{}:{}@{}", ecosystem, name, version)
+}
+
+/// A dependency declared in a manifest.
+#[derive(Debug, Clone, Serialize)]
+pub struct Dependency {
+    pub name: String,
+    pub requirement: String,
+    /// "normal", "dev", "build", "optional" or "peer"
+    pub kind: &'static str,
+    pub ecosystem: &'static str,
+    pub manifest: String,
+    /// Id of the locked package, when a lockfile resolves it
+    pub resolved: Option<String>,
+    /// Name the dependency is imported as
+    #[serde(skip)]
+    import_name: String,
+}
+
+/// A manifest and the project it declares.
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    pub path: String,
+    pub ecosystem: &'static str,
+    /// Package name, if the manifest declares one
+    pub name: Option<String>,
+    pub lockfile: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DependencyGraph {
+    pub manifests: Vec<Manifest>,
+    pub direct: Vec<Dependency>,
+    /// Locked packages by id
+    pub packages: BTreeMap<String, Package>,
+    /// Manifests and lockfiles that could not be parsed
+    pub errors: Vec<ParseFailure>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Duplicate {
+    pub ecosystem: &'static str,
+    pub name: String,
+    pub versions: Vec<String>,
+}
+
+/// A direct dependency and everything it pulls in.
+#[derive(Debug, Clone, Serialize)]
+pub struct Subtree {
+    pub ecosystem: &'static str,
+    pub name: String,
+    pub version: String,
+    /// Distinct packages reachable from it, itself excluded
+    pub transitive: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Unused {
+    pub ecosystem: &'static str,
+    pub name: String,
+    pub kind: &'static str,
+    pub manifest: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub manifests: usize,
+    pub lockfiles: usize,
+    pub packages: usize,
+    pub direct_dependencies: usize,
+    pub duplicates: usize,
+    pub unused: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyReport {
+    pub summary: Summary,
+    pub duplicates: Vec<Duplicate>,
+    pub heaviest: Vec<Subtree>,
+    pub unused: Vec<Unused>,
+    pub errors: Vec<ParseFailure>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Node {
+    pub id: String,
+    pub name: String,
+    pub version: Option<String>,
+    pub ecosystem: &'static str,
+    /// Whether this node is a manifest's own project
+    pub root: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub kind: &'static str,
+}
+
+/// Nodes and edges for export, with each manifest as a root node.
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphExport {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// A manifest's direct dependencies: name, requirement, kind and, for
+/// renamed Cargo dependencies, the name they are imported as.
+type Declared = Vec<(String, String, &'static str, Option<String>)>;
+
+/// Read every manifest under `target` and the lockfiles next to (or, for
+/// Cargo workspaces, above) them, reporting paths relative to `root`.
+pub fn load(root: &Path, target: &Path) -> DependencyGraph {
+    let mut graph = DependencyGraph::default();
+    let mut lockfiles: HashMap<PathBuf, Vec<Package>> = HashMap::new();
+
+    for path in source_files(target, &["toml", "json", "txt"]) {
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let ecosystem = match file_name {
+            "Cargo.toml" => CARGO,
+            "package.json" => NPM,
+            "pyproject.toml" => PYTHON,
+            name if name.starts_with("requirements") && name.ends_with(".txt") => PYTHON,
+            _ => continue,
+        };
+        let manifest = display_path(root, &path);
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| match file_name {
+                "Cargo.toml" => parse_cargo_manifest(&text),
+                "package.json" => parse_package_json(&text),
+                "pyproject.toml" => parse_pyproject(&text),
+                _ => Ok((None, parse_requirements(&text, file_name))),
+            });
+        let (name, declared) = match parsed {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                graph.errors.push(ParseFailure {
+                    file: manifest,
+                    error,
+                });
+                continue;
+            }
+        };
+
+        let lock_path = find_lockfile(&path, root, ecosystem);
+        let locked: &[Package] = match &lock_path {
+            Some(lock) => {
+                if !lockfiles.contains_key(lock) {
+                    let packages = match read_lockfile(lock, ecosystem) {
+                        Ok(packages) => packages,
+                        Err(error) => {
+                            graph.errors.push(ParseFailure {
+                                file: display_path(root, lock),
+                                error,
+                            });
+                            Vec::new()
+                        }
+                    };
+                    lockfiles.insert(lock.clone(), packages);
+                }
+                &lockfiles[lock]
+            }
+            None => &[],
+        };
+
+        for (dep_name, requirement, kind, alias) in declared {
+            let import_name = alias.unwrap_or_else(|| dep_name.clone());
+            graph.direct.push(Dependency {
+                resolved: resolve(locked, ecosystem, name.as_deref(), &dep_name),
+                import_name,
+                name: dep_name,
+                requirement,
+                kind,
+                ecosystem,
+                manifest: manifest.clone(),
+            });
+        }
+        graph.manifests.push(Manifest {
+            path: manifest,
+            ecosystem,
+            name,
+            lockfile: lock_path.map(|lock| display_path(root, &lock)),
+        });
+    }
+
+    for package in lockfiles.into_values().flatten() {
+        graph.packages.entry(package.id.clone()).or_insert(package);
+    }
+    graph
+}
+
+fn find_lockfile(manifest: &Path, root: &Path, ecosystem: &str) -> Option<PathBuf> {
+    let dir = manifest.parent()?;
+    let names: &[&str] = match ecosystem {
+        CARGO => &["Cargo.lock"],
+        NPM => &["package-lock.json", "npm-shrinkwrap.json"],
+        _ => &["poetry.lock"],
+    };
+    // Cargo workspace members share the lockfile at the workspace root
+    let search: Vec<&Path> = if ecosystem == CARGO {
+        dir.ancestors()
+            .take_while(|d| d.starts_with(root) || *d == dir)
+            .collect()
+    } else {
+        vec![dir]
+    };
+    search
+        .into_iter()
+        .flat_map(|d| names.iter().map(move |name| d.join(name)))
+        .find(|path| path.is_file())
+}
+
+fn read_lockfile(path: &Path, ecosystem: &str) -> Result<Vec<Package>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    match ecosystem {
+        CARGO => parse_cargo_lock(&text),
+        NPM => parse_package_lock(&text),
+        _ => parse_poetry_lock(&text),
+    }
+}
+
+/// Id of the locked package a manifest's dependency on `name` resolved to.
+fn resolve(
+    locked: &[Package],
+    ecosystem: &str,
+    project: Option<&str>,
+    name: &str,
+) -> Option<String> {
+    let key = normalize(ecosystem, name);
+    let candidates: Vec<&Package> = locked
+        .iter()
+        .filter(|p| normalize(ecosystem, &p.name) == key)
+        .collect();
+    if candidates.len() > 1 {
+        // The project's own lock entry says which version it uses
+        let own = locked.iter().find(|p| Some(p.name.as_str()) == project);
+        if let Some(chosen) =
+            own.and_then(|own| candidates.iter().find(|c| own.dependencies.contains(&c.id)))
+        {
+            return Some(chosen.id.clone());
+        }
+    }
+    candidates.first().map(|p| p.id.clone())
+}
+
+/// Python names compare case-insensitively with `-`, `_` and `.` equal.
+fn normalize(ecosystem: &str, name: &str) -> String {
+    if ecosystem == PYTHON {
+        name.to_lowercase().replace(['_', '.'], "-")
+    } else {
+        name.to_string()
+    }
+}
+
+fn parse_toml(text: &str) -> Result<toml::Table, String> {
+    text.parse::<toml::Table>()
+        .map_err(|e| e.message().to_string())
+}
+
+/// `[package] name` of the manifest in `dir`, if it is a Cargo package.
+pub fn cargo_package_name(dir: &Path) -> Option<String> {
+    let text = std::fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    package_name(&parse_toml(&text).ok()?)
+}
+
+fn package_name(manifest: &toml::Table) -> Option<String> {
+    manifest
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+        .map(str::to_string)
+}
+
+fn parse_cargo_manifest(text: &str) -> Result<(Option<String>, Declared), String> {
+    let manifest = parse_toml(text)?;
+    let name = package_name(&manifest);
+
+    let mut tables: Vec<&toml::Table> = vec![&manifest];
+    let targets = manifest.get("target").and_then(|t| t.as_table());
+    tables.extend(
+        targets
+            .into_iter()
+            .flat_map(|t| t.values())
+            .filter_map(|t| t.as_table()),
+    );
+
+    let mut declared = Declared::new();
+    for table in tables {
+        for (section, kind) in CARGO_TABLES {
+            let Some(entries) = table.get(*section).and_then(|d| d.as_table()) else {
+                continue;
+            };
+            for (key, spec) in entries {
+                let (package, requirement, optional) = match spec {
+                    toml::Value::String(version) => (key.clone(), version.clone(), false),
+                    toml::Value::Table(spec) => {
+                        let field = |name: &str| spec.get(name).and_then(|v| v.as_str());
+                        let requirement = match (field("version"), field("path"), field("git")) {
+                            (Some(version), _, _) => version.to_string(),
+                            (None, Some(path), _) => format!("path:{}", path),
+                            (None, None, Some(git)) => format!("git:{}", git),
+                            _ if spec.get("workspace").is_some() => "workspace".to_string(),
+                            _ => "*".to_string(),
+                        };
+                        let optional = spec.get("optional").and_then(|o| o.as_bool());
+                        (
+                            field("package").unwrap_or(key).to_string(),
+                            requirement,
+                            optional == Some(true),
+                        )
+                    }
+                    _ => continue,
+                };
+                let kind = if optional && *kind == "normal" {
+                    "optional"
+                } else {
+                    kind
+                };
+                let alias = (package != *key).then(|| key.clone());
+                declared.push((package, requirement, kind, alias));
+            }
+        }
+    }
+    Ok((name, declared))
+}
+
+fn parse_cargo_lock(text: &str) -> Result<Vec<Package>, String> {
+    let lock = parse_toml(text)?;
+    let entries = lock
+        .get("package")
+        .and_then(|p| p.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or(&[]);
+
+    let field = |entry: &toml::Value, name: &str| {
+        entry
+            .get(name)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let mut packages: Vec<Package> = entries
+        .iter()
+        .map(|entry| Package::new(CARGO, &field(entry, "name"), &field(entry, "version")))
+        .collect();
+
+    // Entries name a dependency alone when only one version is locked,
+    // else as "name version" or "name version (source)"
+    for (package, entry) in packages.clone().iter().zip(entries) {
+        let deps = entry.get("dependencies").and_then(|d| d.as_array());
+        let ids: Vec<String> = deps
+            .into_iter()
+            .flatten()
+            .filter_map(|dep| {
+                let mut parts = dep.as_str()?.split_whitespace();
+                let name = parts.next()?;
+                match parts.next() {
+                    Some(version) => Some(package_id(CARGO, name, version)),
+                    None => packages
+                        .iter()
+                        .find(|p| p.name == name)
+                        .map(|p| p.id.clone()),
+                }
+            })
+            .collect();
+        if let Some(target) = packages.iter_mut().find(|p| p.id == package.id) {
+            target.dependencies = ids;
+        }
+    }
+    Ok(packages)
+}
+
+fn parse_json(text: &str) -> Result<Value, String> {
+    serde_json::from_str(text).map_err(|e| e.to_string())
+}
+
+fn parse_package_json(text: &str) -> Result<(Option<String>, Declared), String> {
+    let manifest = parse_json(text)?;
+    let name = manifest["name"].as_str().map(str::to_string);
+    let mut declared = Declared::new();
+    for (field, kind) in NPM_FIELDS {
+        let Some(entries) = manifest[*field].as_object() else {
+            continue;
+        };
+        for (dep, requirement) in entries {
+            let requirement = requirement.as_str().unwrap_or("*").to_string();
+            declared.push((dep.clone(), requirement, *kind, None));
+        }
+    }
+    Ok((name, declared))
+}
+
+/// `package-lock.json` version 2 or 3, whose `packages` map is keyed by
+/// install path such as `node_modules/a/node_modules/b`.
+fn parse_package_lock(text: &str) -> Result<Vec<Package>, String> {
+    let lock = parse_json(text)?;
+    let Some(installed) = lock["packages"].as_object() else {
+        return Err("lockfileVersion 1 is not supported; run npm install to upgrade".to_string());
+    };
+
+    let mut by_path: BTreeMap<&str, String> = BTreeMap::new();
+    let mut packages = Vec::new();
+    for (path, entry) in installed {
+        let Some(index) = path.rfind("node_modules/") else {
+            continue; // the project itself, or a workspace link
+        };
+        let name = &path[index + "node_modules/".len()..];
+        let version = entry["version"].as_str().unwrap_or_default();
+        let package = Package::new(NPM, name, version);
+        by_path.insert(path.as_str(), package.id.clone());
+        packages.push((path.as_str(), entry, package));
+    }
+
+    // Node resolves a dependency from the nearest enclosing node_modules
+    let lookup = |from: &str, dep: &str| {
+        let mut scope = from;
+        loop {
+            let candidate = if scope.is_empty() {
+                format!("node_modules/{}", dep)
+            } else {
+                format!("{}/node_modules/{}", scope, dep)
+            };
+            if let Some(id) = by_path.get(candidate.as_str()) {
+                return Some(id.clone());
+            }
+            if scope.is_empty() {
+                return None;
+            }
+            scope = scope.rfind("/node_modules/").map_or("", |i| &scope[..i]);
+        }
+    };
+
+    Ok(packages
+        .into_iter()
+        .map(|(path, entry, mut package)| {
+            for field in ["dependencies", "optionalDependencies", "peerDependencies"] {
+                let names = entry[field].as_object().into_iter().flat_map(|o| o.keys());
+                package
+                    .dependencies
+                    .extend(names.filter_map(|dep| lookup(path, dep)));
+            }
+            package
+        })
+        .collect())
+}
+
+/// Name and version requirement of a PEP 508 requirement string.
+fn parse_requirement(spec: &str) -> Option<(String, String)> {
+    let spec = spec.split(';').next()?.trim();
+    let end = spec
+        .find(|c: char| !(c.is_alphanumeric() || "._-".contains(c)))
+        .unwrap_or(spec.len());
+    let name = &spec[..end];
+    if name.is_empty() {
+        return None;
+    }
+    let mut rest = spec[end..].trim();
+    // Extras, as in `requests[socks]>=2`
+    if rest.starts_with('[') {
+        rest = rest.find(']').map_or("", |i| rest[i + 1..].trim());
+    }
+    let requirement = rest.trim_matches(|c| c == '(' || c == ')').trim();
+    Some((
+        name.to_string(),
+        if requirement.is_empty() {
+            "*"
+        } else {
+            requirement
+        }
+        .to_string(),
+    ))
+}
+
+fn parse_requirements(text: &str, file_name: &str) -> Declared {
+    let kind = if file_name.contains("dev") || file_name.contains("test") {
+        "dev"
+    } else {
+        "normal"
+    };
+    text.lines()
+        .map(|line| line.split(" #").next().unwrap_or_default().trim())
+        // Options (`-r`, `-e`), URLs and local paths name no project
+        .filter(|line| {
+            !(line.is_empty() || line.starts_with(['#', '-', '.', '/']) || line.contains("://"))
+        })
+        .filter_map(parse_requirement)
+        .map(|(name, requirement)| (name, requirement, kind, None))
+        .collect()
+}
+
+fn parse_pyproject(text: &str) -> Result<(Option<String>, Declared), String> {
+    let project = parse_toml(text)?;
+    let mut declared = Declared::new();
+    let mut requirements = |values: Option<&toml::Value>, kind: &'static str| {
+        let specs = values.and_then(|v| v.as_array()).into_iter().flatten();
+        for (name, requirement) in specs.filter_map(|s| parse_requirement(s.as_str()?)) {
+            declared.push((name, requirement, kind, None));
+        }
+    };
+
+    let pep621 = project.get("project");
+    requirements(pep621.and_then(|p| p.get("dependencies")), "normal");
+    let extras = pep621.and_then(|p| p.get("optional-dependencies"));
+    for group in extras
+        .and_then(|e| e.as_table())
+        .into_iter()
+        .flat_map(|t| t.values())
+    {
+        requirements(Some(group), "optional");
+    }
+    let groups = project.get("dependency-groups").and_then(|g| g.as_table());
+    for group in groups.into_iter().flat_map(|t| t.values()) {
+        requirements(Some(group), "dev");
+    }
+
+    let poetry = project.get("tool").and_then(|t| t.get("poetry"));
+    let mut poetry_tables: Vec<(&toml::Value, &'static str)> = Vec::new();
+    if let Some(poetry) = poetry {
+        if let Some(deps) = poetry.get("dependencies") {
+            poetry_tables.push((deps, "normal"));
+        }
+        if let Some(deps) = poetry.get("dev-dependencies") {
+            poetry_tables.push((deps, "dev"));
+        }
+        let groups = poetry.get("group").and_then(|g| g.as_table());
+        for group in groups.into_iter().flat_map(|t| t.values()) {
+            if let Some(deps) = group.get("dependencies") {
+                poetry_tables.push((deps, "dev"));
+            }
+        }
+    }
+    for (table, kind) in poetry_tables {
+        for (name, spec) in table.as_table().into_iter().flatten() {
+            if name == "python" {
+                continue;
+            }
+            let requirement = match spec {
+                toml::Value::String(version) => version.clone(),
+                other => other
+                    .get("version")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("*")
+                    .to_string(),
+            };
+            declared.push((name.clone(), requirement, kind, None));
+        }
+    }
+
+    let name = pep621
+        .or(poetry)
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+        .map(str::to_string);
+    Ok((name, declared))
+}
+
+fn parse_poetry_lock(text: &str) -> Result<Vec<Package>, String> {
+    let lock = parse_toml(text)?;
+    let entries = lock
+        .get("package")
+        .and_then(|p| p.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or(&[]);
+    let mut packages: Vec<Package> = entries
+        .iter()
+        .map(|entry| {
+            let field = |name: &str| entry.get(name).and_then(|v| v.as_str()).unwrap_or_default();
+            Package::new(PYTHON, field("name"), field("version"))
+        })
+        .collect();
+
+    let ids: HashMap<String, String> = packages
+        .iter()
+        .map(|p| (normalize(PYTHON, &p.name), p.id.clone()))
+        .collect();
+    for (package, entry) in packages.iter_mut().zip(entries) {
+        let deps = entry.get("dependencies").and_then(|d| d.as_table());
+        package.dependencies = deps
+            .into_iter()
+            .flat_map(|d| d.keys())
+            .filter_map(|name| ids.get(&normalize(PYTHON, name)).cloned())
+            .collect();
+    }
+    Ok(packages)
+}
+
+impl DependencyGraph {
+    /// Packages reachable from `id`, itself excluded.
+    fn reachable(&self, id: &str) -> usize {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            let Some(package) = self.packages.get(current) else {
+                continue;
+            };
+            for dep in &package.dependencies {
+                if dep != id && seen.insert(dep) {
+                    stack.push(dep);
+                }
+            }
+        }
+        seen.len()
+    }
+
+    fn duplicates(&self) -> Vec<Duplicate> {
+        let mut versions: BTreeMap<(&'static str, &str), BTreeSet<&str>> = BTreeMap::new();
+        for package in self.packages.values() {
+            versions
+                .entry((package.ecosystem, &package.name))
+                .or_default()
+                .insert(&package.version);
+        }
+        versions
+            .into_iter()
+            .filter(|(_, versions)| versions.len() > 1)
+            .map(|((ecosystem, name), versions)| Duplicate {
+                ecosystem,
+                name: name.to_string(),
+                versions: versions.into_iter().map(str::to_string).collect(),
+            })
+            .collect()
+    }
+
+    /// The `top` direct dependencies with the largest transitive closure.
+    fn heaviest(&self, top: usize) -> Vec<Subtree> {
+        let direct: BTreeSet<&str> = self
+            .direct
+            .iter()
+            .filter_map(|d| d.resolved.as_deref())
+            .collect();
+        let mut subtrees: Vec<Subtree> = direct
+            .into_iter()
+            .filter_map(|id| self.packages.get(id))
+            .map(|package| Subtree {
+                ecosystem: package.ecosystem,
+                name: package.name.clone(),
+                version: package.version.clone(),
+                transitive: self.reachable(&package.id),
+            })
+            .collect();
+        subtrees.sort_by(|a, b| b.transitive.cmp(&a.transitive).then(a.name.cmp(&b.name)));
+        subtrees.truncate(top);
+        subtrees
+    }
+
+    /// Direct dependencies never referenced from sources under their
+    /// manifest's directory. Development-only npm and Python dependencies
+    /// are mostly tools run from scripts, so they are not checked.
+    fn unused(&self, root: &Path) -> Vec<Unused> {
+        let mut references: HashMap<(&str, PathBuf), Option<HashSet<String>>> = HashMap::new();
+        let mut unused = Vec::new();
+        for dep in &self.direct {
+            let checked = match dep.ecosystem {
+                CARGO => true,
+                _ => matches!(dep.kind, "normal" | "optional"),
+            };
+            if !checked || dep.name.starts_with("@types/") {
+                continue;
+            }
+            let dir = root
+                .join(&dep.manifest)
+                .parent()
+                .map_or_else(|| root.to_path_buf(), Path::to_path_buf);
+            let names = references
+                .entry((dep.ecosystem, dir.clone()))
+                .or_insert_with(|| referenced_names(dep.ecosystem, &dir));
+            // A project without sources tells nothing about its dependencies
+            let Some(names) = names else { continue };
+            if !names.contains(&import_name(dep)) {
+                unused.push(Unused {
+                    ecosystem: dep.ecosystem,
+                    name: dep.name.clone(),
+                    kind: dep.kind,
+                    manifest: dep.manifest.clone(),
+                });
+            }
+        }
+        unused
+    }
+
+    pub fn report(&self, root: &Path, top: usize) -> DependencyReport {
+        let duplicates = self.duplicates();
+        let unused = self.unused(root);
+        DependencyReport {
+            summary: Summary {
+                manifests: self.manifests.len(),
+                lockfiles: self
+                    .manifests
+                    .iter()
+                    .filter_map(|m| m.lockfile.as_deref())
+                    .collect::<HashSet<_>>()
+                    .len(),
+                packages: self.packages.len(),
+                direct_dependencies: self.direct.len(),
+                duplicates: duplicates.len(),
+                unused: unused.len(),
+            },
+            duplicates,
+            heaviest: self.heaviest(top),
+            unused,
+            errors: self.errors.clone(),
+        }
+    }
+
+    pub fn export(&self) -> GraphExport {
+        let mut nodes: Vec<Node> = self
+            .manifests
+            .iter()
+            .map(|manifest| Node {
+                id: manifest.path.clone(),
+                name: manifest
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| manifest.path.clone()),
+                version: None,
+                ecosystem: manifest.ecosystem,
+                root: true,
+            })
+            .collect();
+        nodes.extend(self.packages.values().map(|package| Node {
+            id: package.id.clone(),
+            name: package.name.clone(),
+            version: Some(package.version.clone()),
+            ecosystem: package.ecosystem,
+            root: false,
+        }));
+
+        let mut edges = Vec::new();
+        let mut unresolved = BTreeSet::new();
+        for dep in &self.direct {
+            let to = match &dep.resolved {
+                Some(id) => id.clone(),
+                None => {
+                    let id = package_id(dep.ecosystem, &dep.name, &dep.requirement);
+                    if unresolved.insert(id.clone()) {
+                        nodes.push(Node {
+                            id: id.clone(),
+                            name: dep.name.clone(),
+                            version: None,
+                            ecosystem: dep.ecosystem,
+                            root: false,
+                        });
+                    }
+                    id
+                }
+            };
+            edges.push(Edge {
+                from: dep.manifest.clone(),
+                to,
+                kind: dep.kind,
+            });
+        }
+        for package in self.packages.values() {
+            edges.extend(package.dependencies.iter().map(|dep| Edge {
+                from: package.id.clone(),
+                to: dep.clone(),
+                kind: "normal",
+            }));
+        }
+        GraphExport { nodes, edges }
+    }
+
+    /// Graphviz source for [`Self::export`]; manifests are boxes and
+    /// development dependencies dashed.
+    pub fn to_dot(&self) -> String {
+        let graph = self.export();
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut out = String::from("digraph dependencies {\n    rankdir=LR;\n");
+        for node in &graph.nodes {
+            let label = match &node.version {
+                Some(version) => format!("{} {}", node.name, version),
+                None => node.name.clone(),
+            };
+            let shape = if node.root { ", shape=box" } else { "" };
+            out.push_str(&format!(
+                "    {} [label={}{}];\n",
+                quote(&node.id),
+                quote(&label),
+                shape
+            ));
+        }
+        for edge in &graph.edges {
+            let style = if edge.kind == "normal" {
+                ""
+            } else {
+                " [style=dashed]"
+            };
+            out.push_str(&format!(
+                "    {} -> {}{};\n",
+                quote(&edge.from),
+                quote(&edge.to),
+                style
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// How `dep` appears in the sources of its ecosystem.
+fn import_name(dep: &Dependency) -> String {
+    match dep.ecosystem {
+        CARGO => dep.import_name.replace('-', "_"),
+        PYTHON => {
+            let name = normalize(PYTHON, &dep.import_name);
+            PYTHON_IMPORTS
+                .iter()
+                .find(|(project, _)| *project == name)
+                .map_or_else(|| name.replace('-', "_"), |(_, import)| import.to_string())
+        }
+        _ => dep.import_name.clone(),
+    }
+}
+
+/// Names referenced by the sources under `dir`: identifiers for Rust and
+/// Python (lowercased), imported package names for JS/TS. `None` when there
+/// are no sources.
+fn referenced_names(ecosystem: &str, dir: &Path) -> Option<HashSet<String>> {
+    let (extensions, syntax): (&[&str], Syntax) = match ecosystem {
+        CARGO => (&["rs"], Syntax::Rust),
+        PYTHON => (&["py", "pyi"], Syntax::Python),
+        _ => (JS_EXTENSIONS, Syntax::JavaScript),
+    };
+    let files = source_files(dir, extensions);
+    if files.is_empty() {
+        return None;
+    }
+
+    let mut names = HashSet::new();
+    for path in files {
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        for segment in segments(&source, syntax) {
+            let text = &source[segment.start..segment.end];
+            match (segment.kind, syntax) {
+                (SegmentKind::Code, Syntax::Rust) => names.extend(
+                    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .map(str::to_string),
+                ),
+                (SegmentKind::Code, Syntax::Python) => names.extend(
+                    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .map(str::to_lowercase),
+                ),
+                (SegmentKind::String, Syntax::JavaScript) => {
+                    let specifier = text.trim_matches(['"', '\'', '`']);
+                    if let Some(package) = package_of(specifier) {
+                        names.insert(package);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Some(names)
+}
+
+/// Package named by a JS import specifier: `lodash/fp` → `lodash`,
+/// `@scope/pkg/sub` → `@scope/pkg`. Relative paths and builtins name none.
+fn package_of(specifier: &str) -> Option<String> {
+    if specifier.is_empty()
+        || specifier.starts_with(['.', '/'])
+        || specifier.starts_with("node:")
+        || specifier.contains(char::is_whitespace)
+    {
+        return None;
+    }
+    let parts = if specifier.starts_with('@') { 2 } else { 1 };
+    let package: Vec<&str> = specifier.splitn(parts + 1, '/').take(parts).collect();
+    (package.len() == parts).then(|| package.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, file: &str, content: &str) {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    const CARGO_TOML: &str = r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+serde = { version = "1", features = ["derive"] }
+regex = "1"
+unused-crate = "0.3"
+json = { package = "serde_json", version = "1" }
+
+[dev-dependencies]
+tempfile = "3"
+
+[target.'cfg(windows)'.dependencies]
+winapi = { version = "0.3", optional = true }
+"#;
+
+    const CARGO_LOCK: &str = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["serde", "regex", "unused-crate", "serde_json", "tempfile", "winapi"]
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+
+[[package]]
+name = "serde_json"
+version = "1.0.120"
+dependencies = ["serde", "itoa"]
+
+[[package]]
+name = "itoa"
+version = "1.0.11"
+
+[[package]]
+name = "regex"
+version = "1.11.0"
+dependencies = ["memchr 2.7.4"]
+
+[[package]]
+name = "memchr"
+version = "2.7.4"
+
+[[package]]
+name = "unused-crate"
+version = "0.3.0"
+dependencies = ["memchr 2.5.0"]
+
+[[package]]
+name = "memchr"
+version = "2.5.0"
+
+[[package]]
+name = "tempfile"
+version = "3.14.0"
+
+[[package]]
+name = "winapi"
+version = "0.3.9"
+"#;
+
+    #[test]
+    fn test_cargo_graph_report() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(root, "Cargo.toml", CARGO_TOML);
+        write(root, "Cargo.lock", CARGO_LOCK);
+        write(
+            root,
+            "src/main.rs",
+            "use serde::Serialize;\n// regex is mentioned only here\nfn main() { let _ = json::Value::Null; }\n#[cfg(windows)]\nuse winapi as _;\n",
+        );
+        write(
+            root,
+            "tests/it.rs",
+            "#[test]\nfn t() { tempfile::tempdir().unwrap(); }\n",
+        );
+
+        let graph = load(root, root);
+        assert!(graph.errors.is_empty(), "{:?}", graph.errors);
+        assert_eq!(graph.manifests[0].name.as_deref(), Some("app"));
+        assert_eq!(graph.manifests[0].lockfile.as_deref(), Some("Cargo.lock"));
+        assert_eq!(graph.direct.len(), 6);
+
+        let json = graph
+            .direct
+            .iter()
+            .find(|d| d.name == "serde_json")
+            .unwrap();
+        assert_eq!(json.resolved.as_deref(), Some("cargo:serde_json@1.0.120"));
+        let winapi = graph.direct.iter().find(|d| d.name == "winapi").unwrap();
+        assert_eq!(winapi.kind, "optional");
+
+        let report = graph.report(root, 2);
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(report.duplicates[0].name, "memchr");
+        assert_eq!(report.duplicates[0].versions, vec!["2.5.0", "2.7.4"]);
+
+        assert_eq!(report.heaviest.len(), 2);
+        assert_eq!(report.heaviest[0].name, "serde_json");
+        assert_eq!(report.heaviest[0].transitive, 2);
+
+        // Comments are not references; a renamed crate is found by its alias
+        let unused: Vec<&str> = report.unused.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(unused, vec!["regex", "unused-crate"]);
+        assert_eq!(report.summary.unused, 2);
+        assert_eq!(report.summary.lockfiles, 1);
+    }
+
+    #[test]
+    fn test_npm_lock_nested_resolution() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "web/package.json",
+            r#"{"name": "web", "dependencies": {"react": "^18.2.0", "left-pad": "^1.3.0", "@scope/ui": "2.0.0"},
+                "devDependencies": {"typescript": "^5.0.0"}}"#,
+        );
+        write(
+            root,
+            "web/package-lock.json",
+            r#"{"lockfileVersion": 3, "packages": {
+                "": {"name": "web"},
+                "node_modules/react": {"version": "18.2.0", "dependencies": {"loose-envify": "^1.1.0"}},
+                "node_modules/loose-envify": {"version": "1.4.0", "dependencies": {"js-tokens": "^4.0.0"}},
+                "node_modules/js-tokens": {"version": "4.0.0"},
+                "node_modules/@scope/ui": {"version": "2.0.0", "dependencies": {"js-tokens": "^3.0.0"}},
+                "node_modules/@scope/ui/node_modules/js-tokens": {"version": "3.0.2"},
+                "node_modules/left-pad": {"version": "1.3.0"},
+                "node_modules/typescript": {"version": "5.4.0"}
+            }}"#,
+        );
+        write(
+            root,
+            "web/src/app.tsx",
+            "import React from 'react';\nimport { Button } from \"@scope/ui/button\";\nimport './app.css';\n",
+        );
+
+        let graph = load(root, root);
+        assert!(graph.errors.is_empty(), "{:?}", graph.errors);
+        assert_eq!(
+            graph.packages["npm:@scope/ui@2.0.0"].dependencies,
+            vec!["npm:js-tokens@3.0.2"]
+        );
+
+        let report = graph.report(root, 10);
+        assert_eq!(report.duplicates[0].name, "js-tokens");
+        assert_eq!(report.heaviest[0].name, "react");
+        assert_eq!(report.heaviest[0].transitive, 2);
+        // Dev dependencies are tools, not imports
+        let unused: Vec<&str> = report.unused.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(unused, vec!["left-pad"]);
+        assert_eq!(report.unused[0].manifest, "web/package.json");
+    }
+
+    #[test]
+    fn test_python_manifests() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "pyproject.toml",
+            r#"
+[project]
+name = "tool"
+dependencies = ["requests[socks]>=2.31", "PyYAML==6.0; python_version > '3.8'"]
+
+[project.optional-dependencies]
+fast = ["orjson"]
+
+[tool.poetry.group.test.dependencies]
+pytest = "^8"
+"#,
+        );
+        write(
+            root,
+            "requirements.txt",
+            "# pinned\nclick==8.1.7  # cli\n-r base.txt\nhttps://example.com/x.whl\n",
+        );
+        write(
+            root,
+            "poetry.lock",
+            "[[package]]\nname = \"requests\"\nversion = \"2.31.0\"\n\n[package.dependencies]\nurllib3 = \">=1.21\"\n\n[[package]]\nname = \"urllib3\"\nversion = \"2.2.1\"\n",
+        );
+        write(
+            root,
+            "tool/main.py",
+            "import yaml\nimport Requests as r  # case differs\n",
+        );
+
+        let graph = load(root, root);
+        let names: Vec<(&str, &str, &str)> = graph
+            .direct
+            .iter()
+            .map(|d| (d.name.as_str(), d.requirement.as_str(), d.kind))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("requests", ">=2.31", "normal"),
+                ("PyYAML", "==6.0", "normal"),
+                ("orjson", "*", "optional"),
+                ("pytest", "^8", "dev"),
+                ("click", "==8.1.7", "normal"),
+            ]
+        );
+        let requests = &graph.direct[0];
+        assert_eq!(requests.resolved.as_deref(), Some("python:requests@2.31.0"));
+
+        let report = graph.report(root, 10);
+        assert_eq!(report.heaviest[0].transitive, 1);
+        let unused: Vec<&str> = report.unused.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(unused, vec!["orjson", "click"]);
+    }
+
+    #[test]
+    fn test_export_and_dot() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "Cargo.toml",
+            "[package]\nname = \"app\"\n\n[dependencies]\nanyhow = \"1\"\n\n[dev-dependencies]\nproptest = \"1\"\n",
+        );
+        write(root, "broken/Cargo.toml", "[package\n");
+
+        let graph = load(root, root);
+        assert_eq!(graph.errors.len(), 1);
+        assert_eq!(graph.errors[0].file, "broken/Cargo.toml");
+
+        let export = graph.export();
+        assert_eq!(export.nodes.len(), 3);
+        assert!(export.nodes[0].root);
+        assert_eq!(export.edges[1].to, "cargo:proptest@1");
+        assert_eq!(export.edges[1].kind, "dev");
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph dependencies {"));
+        assert!(dot.contains("\"Cargo.toml\" [label=\"app\", shape=box];"));
+        assert!(dot.contains("\"Cargo.toml\" -> \"cargo:anyhow@1\";"));
+        assert!(dot.contains("\"Cargo.toml\" -> \"cargo:proptest@1\" [style=dashed];"));
+    }
+
+    #[test]
+    fn test_package_of() {
+        assert_eq!(package_of("lodash/fp").as_deref(), Some("lodash"));
+        assert_eq!(package_of("@scope/pkg/sub").as_deref(), Some("@scope/pkg"));
+        assert_eq!(package_of("@scope"), None);
+        assert_eq!(package_of("./local"), None);
+        assert_eq!(package_of("node:fs"), None);
+        assert_eq!(package_of("hello world"), None);
+    }
+}
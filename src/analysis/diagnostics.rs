@@ -0,0 +1,164 @@
+//! Compiler diagnostics from cargo's `--message-format=json` stream.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// A replacement rustc or clippy proposes for a byte range of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Suggestion {
+    pub file: String,
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub line: usize,
+    pub replacement: String,
+    /// "MachineApplicable", "MaybeIncorrect", "HasPlaceholders" or "Unspecified"
+    pub applicability: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    /// "error", "warning", "note" or "help"
+    pub level: String,
+    /// Lint or error code, e.g. `clippy::needless_return` or `E0308`
+    pub code: Option<String>,
+    pub message: String,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<Suggestion>,
+    #[serde(skip)]
+    pub rendered: Option<String>,
+}
+
+/// Collect the diagnostics in a cargo JSON message stream.
+///
+/// Messages without a primary span (such as "aborting due to..." summaries)
+/// are dropped, and a diagnostic reported for several targets is kept once.
+pub fn parse_cargo_messages(stream: &str) -> Vec<Diagnostic> {
+    let mut seen = HashSet::new();
+    let mut diagnostics = Vec::new();
+
+    for line in stream.lines() {
+        let Ok(message) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        if message["reason"] != "compiler-message" {
+            continue;
+        }
+        let Some(diagnostic) = parse_diagnostic(&message["message"]) else {
+            continue;
+        };
+        let key = (
+            diagnostic.file.clone(),
+            diagnostic.line,
+            diagnostic.column,
+            diagnostic.message.clone(),
+        );
+        if seen.insert(key) {
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics
+}
+
+fn parse_diagnostic(message: &Value) -> Option<Diagnostic> {
+    let spans = message["spans"].as_array()?;
+    let primary = spans.iter().find(|s| s["is_primary"] == true)?;
+
+    let mut suggestions = Vec::new();
+    collect_suggestions(message, &mut suggestions);
+
+    let number = |span: &Value, key: &str| span[key].as_u64().unwrap_or(0) as usize;
+    Some(Diagnostic {
+        level: message["level"].as_str().unwrap_or("warning").to_string(),
+        code: message["code"]["code"].as_str().map(str::to_string),
+        message: message["message"].as_str().unwrap_or_default().to_string(),
+        file: primary["file_name"].as_str()?.to_string(),
+        line: number(primary, "line_start"),
+        column: number(primary, "column_start"),
+        end_line: number(primary, "line_end"),
+        end_column: number(primary, "column_end"),
+        suggestions,
+        rendered: message["rendered"].as_str().map(str::to_string),
+    })
+}
+
+/// Suggestions live on the spans of the diagnostic and its child notes.
+fn collect_suggestions(message: &Value, out: &mut Vec<Suggestion>) {
+    for span in message["spans"].as_array().into_iter().flatten() {
+        let Some(replacement) = span["suggested_replacement"].as_str() else {
+            continue;
+        };
+        out.push(Suggestion {
+            file: span["file_name"].as_str().unwrap_or_default().to_string(),
+            byte_start: span["byte_start"].as_u64().unwrap_or(0) as usize,
+            byte_end: span["byte_end"].as_u64().unwrap_or(0) as usize,
+            line: span["line_start"].as_u64().unwrap_or(0) as usize,
+            replacement: replacement.to_string(),
+            applicability: span["suggestion_applicability"]
+                .as_str()
+                .unwrap_or("Unspecified")
+                .to_string(),
+        });
+    }
+    for child in message["children"].as_array().into_iter().flatten() {
+        collect_suggestions(child, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clippy_messages() {
+        let message = serde_json::json!({
+            "reason": "compiler-message",
+            "message": {
+                "message": "unneeded `return` statement",
+                "code": { "code": "clippy::needless_return" },
+                "level": "warning",
+                "spans": [{
+                    "file_name": "src/lib.rs", "is_primary": true,
+                    "line_start": 2, "line_end": 2, "column_start": 5, "column_end": 14,
+                    "byte_start": 20, "byte_end": 29,
+                    "suggested_replacement": null, "suggestion_applicability": null
+                }],
+                "children": [{
+                    "message": "remove `return`",
+                    "level": "help",
+                    "spans": [{
+                        "file_name": "src/lib.rs", "is_primary": true,
+                        "line_start": 2, "line_end": 2, "column_start": 5, "column_end": 14,
+                        "byte_start": 20, "byte_end": 29,
+                        "suggested_replacement": "x",
+                        "suggestion_applicability": "MachineApplicable"
+                    }],
+                    "children": []
+                }],
+                "rendered": "warning: unneeded `return` statement"
+            }
+        });
+        let summary = serde_json::json!({
+            "reason": "compiler-message",
+            "message": { "message": "1 warning emitted", "level": "warning", "spans": [], "children": [] }
+        });
+        let stream = format!(
+            "{}\n{}\n{}\n{{\"reason\":\"build-finished\",\"success\":true}}\n",
+            message, message, summary
+        );
+
+        let diagnostics = parse_cargo_messages(&stream);
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.code.as_deref(), Some("clippy::needless_return"));
+        assert_eq!((diagnostic.line, diagnostic.column), (2, 5));
+        assert_eq!(diagnostic.suggestions.len(), 1);
+        assert_eq!(diagnostic.suggestions[0].replacement, "x");
+        assert_eq!(diagnostic.suggestions[0].applicability, "MachineApplicable");
+    }
+}
@@ -0,0 +1,412 @@
+//! Splits source text into code, comment and string literal segments.
+//!
+//! This is deliberately shallow: it knows each language's comment and
+//! string syntax and nothing else, which is enough to find comments or to
+//! avoid touching text inside literals.
+
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Rust,
+    Python,
+    JavaScript,
+    Go,
+    Shell,
+}
+
+impl Syntax {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?;
+        Some(match ext {
+            "rs" => Self::Rust,
+            "py" | "pyi" => Self::Python,
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" => Self::JavaScript,
+            "go" => Self::Go,
+            "sh" | "bash" | "zsh" => Self::Shell,
+            _ => return None,
+        })
+    }
+
+    /// File extensions recognized by [`Self::from_path`].
+    pub const EXTENSIONS: &'static [&'static str] = &[
+        "rs", "py", "pyi", "js", "jsx", "mjs", "cjs", "ts", "tsx", "mts", "cts", "go", "sh",
+        "bash", "zsh",
+    ];
+
+    fn hash_comments(self) -> bool {
+        matches!(self, Self::Python | Self::Shell)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    Code,
+    Comment,
+    String,
+}
+
+/// A run of source text of one kind; `start..end` are byte offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub kind: SegmentKind,
+    pub start: usize,
+    pub end: usize,
+    /// 1-based line of `start`
+    pub line: usize,
+}
+
+/// Segment `source`, covering every byte exactly once.
+pub fn segments(source: &str, syntax: Syntax) -> Vec<Segment> {
+    Lexer {
+        bytes: source.as_bytes(),
+        syntax,
+        pos: 0,
+        line: 1,
+        out: Vec::new(),
+    }
+    .run()
+}
+
+struct Lexer<'a> {
+    bytes: &'a [u8],
+    syntax: Syntax,
+    pos: usize,
+    line: usize,
+    out: Vec<Segment>,
+}
+
+impl Lexer<'_> {
+    fn run(mut self) -> Vec<Segment> {
+        let mut code_start = 0;
+        let mut code_line = 1;
+
+        while self.pos < self.bytes.len() {
+            let start = self.pos;
+            let line = self.line;
+            let kind = if self.at_comment() {
+                self.skip_comment();
+                SegmentKind::Comment
+            } else if self.at_string() {
+                self.skip_string();
+                SegmentKind::String
+            } else {
+                self.advance(1);
+                continue;
+            };
+
+            if code_start < start {
+                self.push(SegmentKind::Code, code_start, start, code_line);
+            }
+            self.push(kind, start, self.pos, line);
+            code_start = self.pos;
+            code_line = self.line;
+        }
+
+        if code_start < self.bytes.len() {
+            self.push(SegmentKind::Code, code_start, self.bytes.len(), code_line);
+        }
+        self.out
+    }
+
+    fn push(&mut self, kind: SegmentKind, start: usize, end: usize, line: usize) {
+        self.out.push(Segment {
+            kind,
+            start,
+            end,
+            line,
+        });
+    }
+
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, text: &[u8]) -> bool {
+        self.bytes[self.pos..].starts_with(text)
+    }
+
+    fn advance(&mut self, count: usize) {
+        let end = (self.pos + count).min(self.bytes.len());
+        self.line += self.bytes[self.pos..end]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count();
+        self.pos = end;
+    }
+
+    fn previous(&self) -> Option<u8> {
+        self.pos.checked_sub(1).map(|i| self.bytes[i])
+    }
+
+    fn at_comment(&self) -> bool {
+        if self.syntax.hash_comments() {
+            // In shell `#` only starts a comment at the start of a word
+            self.peek(0) == Some(b'#')
+                && (self.syntax == Syntax::Python
+                    || self
+                        .previous()
+                        .is_none_or(|b| b.is_ascii_whitespace() || b == b';'))
+        } else {
+            self.starts_with(b"//") || self.starts_with(b"/*")
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if !self.starts_with(b"/*") {
+            while self.peek(0).is_some_and(|b| b != b'\n') {
+                self.advance(1);
+            }
+            return;
+        }
+
+        // Rust block comments nest; the other languages' do not
+        let nests = self.syntax == Syntax::Rust;
+        let mut depth = 0;
+        while self.pos < self.bytes.len() {
+            if self.starts_with(b"/*") && (nests || depth == 0) {
+                depth += 1;
+                self.advance(2);
+            } else if self.starts_with(b"*/") {
+                depth -= 1;
+                self.advance(2);
+                if depth == 0 {
+                    return;
+                }
+            } else {
+                self.advance(1);
+            }
+        }
+    }
+
+    fn at_string(&self) -> bool {
+        let Some(b) = self.peek(0) else {
+            return false;
+        };
+        match self.syntax {
+            Syntax::Rust => {
+                b == b'"' || self.raw_string_hashes().is_some() || self.char_literal_len().is_some()
+            }
+            Syntax::Python | Syntax::Shell => b == b'"' || b == b'\'',
+            Syntax::JavaScript => b == b'"' || b == b'\'' || b == b'`',
+            Syntax::Go => b == b'"' || b == b'`' || b == b'\'',
+        }
+    }
+
+    fn skip_string(&mut self) {
+        if self.syntax == Syntax::Rust {
+            if let Some(hashes) = self.raw_string_hashes() {
+                return self.skip_raw_string(hashes);
+            }
+            if let Some(len) = self.char_literal_len() {
+                return self.advance(len);
+            }
+        }
+
+        let quote = self.bytes[self.pos];
+        if self.syntax == Syntax::Python
+            && (self.starts_with(b"\"\"\"") || self.starts_with(b"'''"))
+        {
+            let delimiter = [quote; 3];
+            self.advance(3);
+            while self.pos < self.bytes.len() && !self.starts_with(&delimiter) {
+                self.advance(if self.peek(0) == Some(b'\\') { 2 } else { 1 });
+            }
+            return self.advance(3);
+        }
+
+        // Go raw strings and shell single quotes have no escapes
+        let escapes = !matches!(
+            (self.syntax, quote),
+            (Syntax::Go, b'`') | (Syntax::Shell, b'\'')
+        );
+        // Only some literals may span lines
+        let multiline = matches!(
+            (self.syntax, quote),
+            (Syntax::Rust, _)
+                | (Syntax::Shell, _)
+                | (Syntax::JavaScript, b'`')
+                | (Syntax::Go, b'`')
+        );
+
+        self.advance(1);
+        while let Some(b) = self.peek(0) {
+            if escapes && b == b'\\' {
+                self.advance(2);
+            } else if b == quote {
+                self.advance(1);
+                return;
+            } else if b == b'\n' && !multiline {
+                // Unterminated; stop at the end of the line
+                return;
+            } else {
+                self.advance(1);
+            }
+        }
+    }
+
+    /// Number of `#`s of a Rust raw string starting here (`r"`, `br#"`...).
+    fn raw_string_hashes(&self) -> Option<usize> {
+        // An identifier ending in `r` (e.g. `bar"`) is not a prefix
+        if self
+            .previous()
+            .is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_')
+        {
+            return None;
+        }
+        let mut offset = match (self.peek(0), self.peek(1)) {
+            (Some(b'r'), _) => 1,
+            (Some(b'b' | b'c'), Some(b'r')) => 2,
+            _ => return None,
+        };
+        let mut hashes = 0;
+        while self.peek(offset) == Some(b'#') {
+            hashes += 1;
+            offset += 1;
+        }
+        (self.peek(offset) == Some(b'"')).then_some(hashes)
+    }
+
+    fn skip_raw_string(&mut self, hashes: usize) {
+        while self.peek(0) != Some(b'"') {
+            self.advance(1);
+        }
+        self.advance(1);
+        let mut closing = vec![b'"'];
+        closing.extend(std::iter::repeat_n(b'#', hashes));
+        while self.pos < self.bytes.len() && !self.starts_with(&closing) {
+            self.advance(1);
+        }
+        self.advance(closing.len());
+    }
+
+    /// Length of a Rust char literal starting here; `None` for lifetimes.
+    fn char_literal_len(&self) -> Option<usize> {
+        if self.peek(0) != Some(b'\'') {
+            return None;
+        }
+        if self.peek(1) == Some(b'\\') {
+            // '\n', '\'', '\u{1F600}'
+            let mut offset = 3;
+            while let Some(b) = self.peek(offset) {
+                if b == b'\'' {
+                    return Some(offset + 1);
+                }
+                if b == b'\n' || offset > 12 {
+                    return None;
+                }
+                offset += 1;
+            }
+            return None;
+        }
+        // A single (possibly multi-byte) character followed by a quote; only
+        // that character is decoded, the bytes come from a `&str`
+        let start = self.pos + 1;
+        let end = (start + 4).min(self.bytes.len());
+        let head = match std::str::from_utf8(&self.bytes[start..end]) {
+            Ok(head) => head,
+            Err(e) => std::str::from_utf8(&self.bytes[start..start + e.valid_up_to()]).ok()?,
+        };
+        let len = head.chars().next()?.len_utf8();
+        (self.peek(1 + len) == Some(b'\'')).then_some(len + 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str, syntax: Syntax) -> Vec<(SegmentKind, &str)> {
+        segments(source, syntax)
+            .into_iter()
+            .map(|s| (s.kind, &source[s.start..s.end]))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_segments() {
+        let source = "let s = \"a // b\"; // note\nlet r = r#\"x \"q\" y\"#; /* outer /* inner */ done */ 'a' fn f<'a>()";
+        let comments: Vec<&str> = kinds(source, Syntax::Rust)
+            .into_iter()
+            .filter(|(k, _)| *k != SegmentKind::Code)
+            .map(|(_, text)| text)
+            .collect();
+        assert_eq!(
+            comments,
+            vec![
+                "\"a // b\"",
+                "// note",
+                "r#\"x \"q\" y\"#",
+                "/* outer /* inner */ done */",
+                "'a'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rust_multibyte_char_literals() {
+        let source = "fn f<'a>(x: &'a str) -> [char; 3] { ['é', '😀', 'z'] } // ünï";
+        let literals: Vec<&str> = kinds(source, Syntax::Rust)
+            .into_iter()
+            .filter(|(k, _)| *k != SegmentKind::Code)
+            .map(|(_, text)| text)
+            .collect();
+        assert_eq!(literals, vec!["'é'", "'😀'", "'z'", "// ünï"]);
+    }
+
+    #[test]
+    fn test_segments_cover_source_and_track_lines() {
+        let source = "x = 1\n# first\ny = '''doc\n# not a comment\n'''\nz = 2  # last";
+        let parts = segments(source, Syntax::Python);
+        assert_eq!(parts.first().unwrap().start, 0);
+        assert_eq!(parts.last().unwrap().end, source.len());
+        assert!(parts.windows(2).all(|w| w[0].end == w[1].start));
+
+        let comments: Vec<(usize, &str)> = parts
+            .iter()
+            .filter(|s| s.kind == SegmentKind::Comment)
+            .map(|s| (s.line, &source[s.start..s.end]))
+            .collect();
+        assert_eq!(comments, vec![(2, "# first"), (6, "# last")]);
+    }
+
+    #[test]
+    fn test_shell_hash_needs_word_start() {
+        let source = "echo $# ${#arr} 'x # y' # real";
+        let comments: Vec<&str> = kinds(source, Syntax::Shell)
+            .into_iter()
+            .filter(|(k, _)| *k == SegmentKind::Comment)
+            .map(|(_, text)| text)
+            .collect();
+        assert_eq!(comments, vec!["# real"]);
+    }
+
+    #[test]
+    fn test_javascript_and_go_literals() {
+        let js = "const u = `http://x ${y}`; // c";
+        assert_eq!(
+            kinds(js, Syntax::JavaScript)
+                .iter()
+                .filter(|(k, _)| *k == SegmentKind::Comment)
+                .count(),
+            1
+        );
+
+        let go = "s := `raw // \\` // c";
+        let comments: Vec<(SegmentKind, &str)> = kinds(go, Syntax::Go)
+            .into_iter()
+            .filter(|(k, _)| *k != SegmentKind::Code)
+            .collect();
+        assert_eq!(
+            comments,
+            vec![
+                (SegmentKind::String, "`raw // \\`"),
+                (SegmentKind::Comment, "// c")
+            ]
+        );
+        assert_eq!(
+            Syntax::from_path(Path::new("a/b.tsx")),
+            Some(Syntax::JavaScript)
+        );
+    }
+}
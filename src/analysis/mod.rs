@@ -0,0 +1,103 @@
+//! Static analysis of workspace sources.
+//!
+//! These analyzers back the quality tools (`pmat`, `fix`, `refactor`). They
+//! work on source text directly and never execute project code.
+
+pub mod big_o;
+pub mod complexity;
+pub mod coverage_report;
+pub mod dead_code;
+pub mod dependencies;
+pub mod diagnostics;
+pub mod lexer;
+pub mod satd;
+pub mod structural;
+pub mod symbols;
+pub mod tdg;
+
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// Directories that hold build output, dependencies or caches.
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "__pycache__", "vendor", "dist"];
+
+/// Whether a directory called `name` is hidden or holds build output.
+pub fn is_skipped_dir(name: &str) -> bool {
+    name.starts_with('.') || SKIPPED_DIRS.contains(&name)
+}
+
+fn is_skipped(entry: &DirEntry) -> bool {
+    // Never skip the root itself, even when it is e.g. "."
+    if entry.depth() == 0 || !entry.file_type().is_dir() {
+        return false;
+    }
+    is_skipped_dir(&entry.file_name().to_string_lossy())
+}
+
+/// Source files under `target` with one of `extensions`, sorted by path.
+///
+/// `target` may be a single file, which is returned when its extension
+/// matches.
+pub fn source_files(target: &Path, extensions: &[&str]) -> Vec<PathBuf> {
+    let matches = |path: &Path| {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| extensions.contains(&ext))
+    };
+
+    if target.is_file() {
+        return if matches(target) {
+            vec![target.to_path_buf()]
+        } else {
+            Vec::new()
+        };
+    }
+
+    let mut files: Vec<PathBuf> = WalkDir::new(target)
+        .into_iter()
+        .filter_entry(|e| !is_skipped(e))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && matches(e.path()))
+        .map(|e| e.into_path())
+        .collect();
+    files.sort();
+    files
+}
+
+/// `path` relative to `root` for reporting, or unchanged if outside it.
+pub fn display_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_source_files_skips_build_dirs() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        for file in [
+            "src/lib.rs",
+            "target/debug/gen.rs",
+            ".git/hook.rs",
+            "README.md",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let files = source_files(root, &["rs"]);
+        assert_eq!(files, vec![root.join("src/lib.rs")]);
+        assert_eq!(display_path(root, &files[0]), "src/lib.rs");
+        assert_eq!(
+            source_files(&root.join("README.md"), &["rs"]),
+            Vec::<PathBuf>::new()
+        );
+    }
+}
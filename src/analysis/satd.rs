@@ -0,0 +1,359 @@
+//! Self-admitted technical debt: comments in which the author flags their
+//! own code as unfinished, broken or a stopgap.
+//!
+//! Only real comments are scanned (see [`super::lexer`]), so markers inside
+//! string literals are ignored. Upper-case tags count when they open the
+//! comment or are followed by `:` or `(`; phrases that admit a stopgap
+//! count anywhere in a comment.
+
+use super::lexer::{segments, SegmentKind, Syntax};
+use super::{display_path, source_files};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DebtItem {
+    pub line: usize,
+    /// The tag or normalized phrase that matched
+    pub marker: String,
+    pub category: &'static str,
+    pub severity: &'static str,
+    /// The comment line with its comment leader stripped
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_days: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDebt {
+    pub file: String,
+    pub items: Vec<DebtItem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub total_items: usize,
+    pub files_scanned: usize,
+    pub files_with_debt: usize,
+    /// Item count per marker
+    pub categories: BTreeMap<String, usize>,
+    pub by_severity: BTreeMap<&'static str, usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SatdReport {
+    pub summary: Summary,
+    pub files: Vec<FileDebt>,
+}
+
+struct Marker {
+    category: &'static str,
+    severity: &'static str,
+}
+
+fn classify(marker: &str) -> Marker {
+    let (category, severity) = match marker.to_ascii_uppercase().as_str() {
+        "FIXME" | "BUG" | "XXX" => ("defect", "high"),
+        "HACK" | "KLUDGE" => ("design", "medium"),
+        "TODO" => ("requirement", "low"),
+        _ => ("design", "medium"),
+    };
+    Marker { category, severity }
+}
+
+fn tag_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\b(TODO|FIXME|HACK|XXX|BUG|KLUDGE)\b").unwrap())
+}
+
+fn phrase_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)\b(work[- ]?around|temporary (?:fix|hack|solution)|quick and dirty)\b")
+            .unwrap()
+    })
+}
+
+fn security_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)\b(security|vulnerab\w*|injection)\b").unwrap())
+}
+
+/// Strip comment leaders (`//`, `///`, `/*`, `*`, `#`) from a comment line.
+fn comment_text(line: &str) -> &str {
+    let text = line.trim();
+    let text = text
+        .trim_start_matches('/')
+        .trim_start_matches('*')
+        .trim_start_matches('!')
+        .trim_start_matches('#');
+    text.trim_end_matches("*/").trim()
+}
+
+fn match_marker(text: &str) -> Option<String> {
+    for found in tag_pattern().find_iter(text) {
+        let opens = found.start() == 0;
+        let tagged = text[found.end()..].starts_with([':', '(']);
+        if opens || tagged {
+            return Some(found.as_str().to_string());
+        }
+    }
+    phrase_pattern().find(text).map(|found| {
+        let phrase = found.as_str().to_lowercase();
+        if phrase.starts_with("work") {
+            "workaround".to_string()
+        } else {
+            phrase
+        }
+    })
+}
+
+/// Debt items in one source file.
+pub fn scan_source(source: &str, syntax: Syntax) -> Vec<DebtItem> {
+    let mut items = Vec::new();
+    for segment in segments(source, syntax) {
+        if segment.kind != SegmentKind::Comment {
+            continue;
+        }
+        let comment = &source[segment.start..segment.end];
+        for (offset, line) in comment.lines().enumerate() {
+            let text = comment_text(line);
+            let Some(marker) = match_marker(text) else {
+                continue;
+            };
+            let Marker {
+                mut category,
+                mut severity,
+            } = classify(&marker);
+            if security_pattern().is_match(text) {
+                category = "security";
+                severity = "critical";
+            }
+            items.push(DebtItem {
+                line: segment.line + offset,
+                marker,
+                category,
+                severity,
+                text: text.to_string(),
+                author: None,
+                age_days: None,
+            });
+        }
+    }
+    items
+}
+
+/// Scan every supported source file under `target`.
+///
+/// With `blame`, each item is annotated with the author and age of its line
+/// according to `git blame`, when the file is tracked.
+pub fn scan_path(root: &Path, target: &Path, blame: bool) -> SatdReport {
+    let paths = source_files(target, Syntax::EXTENSIONS);
+    let mut files = Vec::new();
+
+    for path in &paths {
+        let (Some(syntax), Ok(source)) = (Syntax::from_path(path), std::fs::read_to_string(path))
+        else {
+            continue;
+        };
+        let mut items = scan_source(&source, syntax);
+        if items.is_empty() {
+            continue;
+        }
+        if blame {
+            annotate_blame(root, path, &mut items);
+        }
+        files.push(FileDebt {
+            file: display_path(root, path),
+            items,
+        });
+    }
+
+    let mut categories = BTreeMap::new();
+    let mut by_severity = BTreeMap::new();
+    for item in files.iter().flat_map(|f| &f.items) {
+        *categories.entry(item.marker.clone()).or_insert(0) += 1;
+        *by_severity.entry(item.severity).or_insert(0) += 1;
+    }
+
+    SatdReport {
+        summary: Summary {
+            total_items: files.iter().map(|f| f.items.len()).sum(),
+            files_scanned: paths.len(),
+            files_with_debt: files.len(),
+            categories,
+            by_severity,
+        },
+        files,
+    }
+}
+
+struct BlameLine {
+    author: String,
+    time: u64,
+}
+
+fn annotate_blame(root: &Path, path: &Path, items: &mut [DebtItem]) {
+    let Some(lines) = blame_lines(root, path) else {
+        return;
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    for item in items {
+        if let Some(blame) = lines.get(&item.line) {
+            item.author = Some(blame.author.clone());
+            item.age_days = Some(now.saturating_sub(blame.time) / 86_400);
+        }
+    }
+}
+
+/// Run `git blame --line-porcelain` and index the result by final line.
+fn blame_lines(root: &Path, path: &Path) -> Option<HashMap<usize, BlameLine>> {
+    let output = Command::new("git")
+        .arg("blame")
+        .arg("--line-porcelain")
+        .arg("--")
+        .arg(path)
+        .current_dir(root)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_blame(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_blame(porcelain: &str) -> HashMap<usize, BlameLine> {
+    let mut lines = HashMap::new();
+    let mut current_line = None;
+    let mut author = String::new();
+    let mut time = 0;
+
+    for line in porcelain.lines() {
+        if line.starts_with('\t') {
+            // Content line; closes the current entry
+            if let Some(number) = current_line.take() {
+                lines.insert(
+                    number,
+                    BlameLine {
+                        author: std::mem::take(&mut author),
+                        time,
+                    },
+                );
+            }
+        } else if let Some(name) = line.strip_prefix("author ") {
+            author = name.to_string();
+        } else if let Some(value) = line.strip_prefix("author-time ") {
+            time = value.parse().unwrap_or(0);
+        } else if current_line.is_none() {
+            // Header: <sha> <original line> <final line> [<group size>]
+            let mut fields = line.split_whitespace();
+            let is_header = fields
+                .next()
+                .is_some_and(|sha| sha.len() >= 40 && sha.chars().all(|c| c.is_ascii_hexdigit()));
+            if is_header {
+                current_line = fields.nth(1).and_then(|n| n.parse().ok());
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markers(source: &str, syntax: Syntax) -> Vec<(usize, String, &'static str)> {
+        scan_source(source, syntax)
+            .into_iter()
+            .map(|item| (item.line, item.marker, item.severity))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_comments_only() {
+        let source = r#"
+// TODO: handle empty input
+fn parse() {
+    let msg = "TODO: not a comment";
+    /* FIXME(alice): off by one
+     * HACK: second line of block */
+    // Mentions of TODO mid-sentence are prose, not debt
+}
+"#;
+        assert_eq!(
+            markers(source, Syntax::Rust),
+            vec![
+                (2, "TODO".to_string(), "low"),
+                (5, "FIXME".to_string(), "high"),
+                (6, "HACK".to_string(), "medium"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_other_languages() {
+        let python = "x = '# TODO: string'\n# XXX: broken for negatives\n";
+        assert_eq!(
+            markers(python, Syntax::Python),
+            vec![(2, "XXX".to_string(), "high")]
+        );
+
+        let js = "// Work around a Safari layout bug\nconst a = 1;";
+        let items = scan_source(js, Syntax::JavaScript);
+        assert_eq!(items[0].marker, "workaround");
+        assert_eq!(items[0].category, "design");
+
+        let go = "func f() {} // TODO: security review of token parsing";
+        let items = scan_source(go, Syntax::Go);
+        assert_eq!(items[0].category, "security");
+        assert_eq!(items[0].severity, "critical");
+
+        let shell = "echo \"# FIXME: quoted\"\nrm -rf \"$dir\" # temporary fix until cleanup";
+        assert_eq!(
+            markers(shell, Syntax::Shell),
+            vec![(2, "temporary fix".to_string(), "medium")]
+        );
+    }
+
+    #[test]
+    fn test_parse_blame() {
+        let porcelain = "\
+0123456789abcdef0123456789abcdef01234567 1 1 1
+author Alice
+author-time 1700000000
+\tfn main() {}
+89abcdef0123456789abcdef0123456789abcdef 5 2 1
+author Bob
+author-time 1600000000
+\t// TODO: later
+";
+        let lines = parse_blame(porcelain);
+        assert_eq!(lines[&1].author, "Alice");
+        assert_eq!(lines[&2].author, "Bob");
+        assert_eq!(lines[&2].time, 1600000000);
+    }
+
+    #[test]
+    fn test_scan_path_summary() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "// TODO: one\n// FIXME: two\n").unwrap();
+        std::fs::write(dir.path().join("b.py"), "print('clean')\n").unwrap();
+
+        let report = scan_path(dir.path(), dir.path(), false);
+        assert_eq!(report.summary.total_items, 2);
+        assert_eq!(report.summary.files_scanned, 2);
+        assert_eq!(report.summary.files_with_debt, 1);
+        assert_eq!(report.summary.categories["TODO"], 1);
+        assert_eq!(report.files[0].file, "a.rs");
+    }
+}
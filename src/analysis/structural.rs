@@ -0,0 +1,698 @@
+//! Structural search over tree-sitter syntax trees.
+//!
+//! A pattern is a snippet of code in the searched language in which
+//! `$NAME` stands for any single node and `$$$NAME` for any run of sibling
+//! nodes, including none; `$_` and `$$$` match without capturing. A name
+//! used twice must match the same text both times. Pattern and code are
+//! compared node by node, and the code may carry parts the pattern leaves
+//! out: punctuation, comments, fields such as a return type or a type
+//! annotation, and modifiers such as `pub` or `async`.
+
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::OnceLock;
+use tree_sitter::{Node, Parser, Tree};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+}
+
+impl Language {
+    pub const NAMES: &'static [&'static str] = &["rust", "python", "javascript", "typescript"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.trim().to_ascii_lowercase().as_str() {
+            "rust" | "rs" => Self::Rust,
+            "python" | "py" => Self::Python,
+            "javascript" | "js" | "jsx" => Self::JavaScript,
+            "typescript" | "ts" | "tsx" => Self::TypeScript,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::Python => "python",
+            Self::JavaScript => "javascript",
+            Self::TypeScript => "typescript",
+        }
+    }
+
+    /// Extensions of the files searched with a pattern in this language.
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Rust => &["rs"],
+            Self::Python => &["py", "pyi"],
+            Self::JavaScript => &["js", "jsx", "mjs", "cjs"],
+            Self::TypeScript => &["ts", "tsx", "mts", "cts"],
+        }
+    }
+
+    /// Grammar for `path`, or for patterns when there is none.
+    fn grammar(self, path: Option<&Path>) -> tree_sitter::Language {
+        let tsx = path.and_then(|p| p.extension()).is_some_and(|e| e == "tsx");
+        match self {
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+            Self::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Self::TypeScript if tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Self::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+        }
+    }
+
+    /// How metavariables are spelled when a pattern is parsed: as is where
+    /// `$` may start an identifier, otherwise with a letter the grammar
+    /// accepts in identifiers.
+    fn expando(self) -> char {
+        match self {
+            Self::Rust | Self::Python => 'µ',
+            Self::JavaScript | Self::TypeScript => '$',
+        }
+    }
+
+    /// Code placed before and after a pattern, tried in order until the
+    /// pattern parses as a single node. Python patterns are indented when
+    /// the code before them ends a line.
+    fn contexts(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Rust => &[
+                ("", ""),
+                ("fn __pcode() { ", " }"),
+                ("match __pcode { ", " }"),
+                ("impl __Pcode { ", " }"),
+                ("struct __Pcode { ", " }"),
+            ],
+            Self::Python => &[("", ""), ("def __pcode():\n", ""), ("class __Pcode:\n", "")],
+            Self::JavaScript | Self::TypeScript => &[
+                ("", ""),
+                ("function __pcode() { ", " }"),
+                ("class __Pcode { ", " }"),
+                ("({ ", " })"),
+            ],
+        }
+    }
+
+    /// Named nodes the code may have where the pattern has none.
+    fn optional_kinds(self) -> &'static [&'static str] {
+        match self {
+            Self::Rust => &["visibility_modifier", "function_modifiers", "where_clause"],
+            Self::TypeScript => &["accessibility_modifier", "override_modifier"],
+            Self::Python | Self::JavaScript => &[],
+        }
+    }
+}
+
+fn parse(language: Language, path: Option<&Path>, source: &str) -> Result<Tree, String> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&language.grammar(path))
+        .map_err(|e| e.to_string())?;
+    parser
+        .parse(source, None)
+        .ok_or_else(|| format!("Failed to parse {} source", language.name()))
+}
+
+fn metavariable_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"\$\$\$([A-Z_][A-Z0-9_]*)?|\$([A-Z_][A-Z0-9_]*)").expect("valid regex")
+    })
+}
+
+/// Names of the capturing metavariables in `text`.
+pub fn metavariables(text: &str) -> BTreeSet<String> {
+    metavariable_pattern()
+        .captures_iter(text)
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)))
+        .map(|name| name.as_str().to_string())
+        .filter(|name| name != "_")
+        .collect()
+}
+
+/// `template` with each `$NAME` and `$$$NAME` replaced by the text it
+/// captured. Names without a capture are left as they are.
+pub fn rewrite(template: &str, captures: &BTreeMap<String, String>) -> String {
+    metavariable_pattern()
+        .replace_all(template, |caps: &regex::Captures| {
+            caps.get(1)
+                .or_else(|| caps.get(2))
+                .and_then(|name| captures.get(name.as_str()))
+                .cloned()
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MetaVar {
+    /// `$NAME`, or `$_` without a name
+    Single(Option<String>),
+    /// `$$$NAME`, or `$$$` without a name
+    Multi(Option<String>),
+}
+
+/// The metavariable a pattern node's text spells, if any.
+fn metavar(text: &str, expando: char) -> Option<MetaVar> {
+    let rest = text.strip_prefix(expando)?;
+    let (multi, name) = match rest
+        .strip_prefix(expando)
+        .and_then(|r| r.strip_prefix(expando))
+    {
+        Some(name) => (true, name),
+        None => (false, rest),
+    };
+    let valid = |name: &str| {
+        let mut chars = name.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_uppercase() || c == '_')
+            && chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+    };
+    let name = match name {
+        "" if multi => None,
+        "_" => None,
+        name if valid(name) => Some(name.to_string()),
+        _ => return None,
+    };
+    Some(if multi {
+        MetaVar::Multi(name)
+    } else {
+        MetaVar::Single(name)
+    })
+}
+
+/// A pattern parsed into a syntax tree.
+pub struct Pattern {
+    language: Language,
+    /// The pattern as parsed, inside its context, with metavariables
+    /// respelled
+    source: String,
+    tree: Tree,
+    /// Byte range of the pattern's node in `source`
+    start: usize,
+    end: usize,
+}
+
+impl Pattern {
+    pub fn new(language: Language, pattern: &str) -> Result<Self, String> {
+        let expando = language.expando();
+        let text = metavariable_pattern()
+            .replace_all(pattern.trim(), |caps: &regex::Captures| {
+                let marker = if caps.get(2).is_some() { 1 } else { 3 };
+                let name = caps
+                    .get(1)
+                    .or_else(|| caps.get(2))
+                    .map_or("", |m| m.as_str());
+                format!("{}{}", expando.to_string().repeat(marker), name)
+            })
+            .into_owned();
+        if text.is_empty() {
+            return Err("Pattern is empty".to_string());
+        }
+
+        for (before, after) in language.contexts() {
+            let body = if language == Language::Python && before.ends_with('\n') {
+                text.lines()
+                    .map(|line| format!("    {}", line))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            } else {
+                text.clone()
+            };
+            let start = before.len() + body.len() - body.trim_start().len();
+            let end = before.len() + body.len();
+            let source = format!("{}{}{}", before, body, after);
+            let tree = parse(language, None, &source)?;
+            let root = tree.root_node();
+            let parsed = root
+                .descendant_for_byte_range(start, end)
+                .is_some_and(|node| {
+                    node.start_byte() == start
+                        && node.end_byte() == end
+                        && node.id() != root.id()
+                        && !node.has_error()
+                });
+            if parsed {
+                return Ok(Self {
+                    language,
+                    source,
+                    tree,
+                    start,
+                    end,
+                });
+            }
+        }
+        Err(format!(
+            "Pattern is not a single {} expression, statement or item: {}",
+            language.name(),
+            pattern.trim()
+        ))
+    }
+
+    fn root(&self) -> Node<'_> {
+        let root = self.tree.root_node();
+        root.descendant_for_byte_range(self.start, self.end)
+            .unwrap_or(root)
+    }
+
+    fn text(&self, node: Node) -> &str {
+        &self.source[node.byte_range()]
+    }
+}
+
+/// Byte ranges of the captured metavariables in the searched source.
+type Captures = BTreeMap<String, (usize, usize)>;
+
+/// A node and the field it fills in its parent.
+type Child<'t> = (Node<'t>, Option<&'static str>);
+
+fn children<'t>(node: Node<'t>) -> Vec<Child<'t>> {
+    let mut cursor = node.walk();
+    let mut children = Vec::new();
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            if !child.is_extra() && !child.is_missing() {
+                children.push((child, cursor.field_name()));
+            }
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+    children
+}
+
+/// `node` and the nodes below it, in document order.
+fn preorder(node: Node) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        nodes.push(node);
+        let mut cursor = node.walk();
+        let mut below: Vec<Node> = node.children(&mut cursor).collect();
+        below.reverse();
+        stack.extend(below);
+    }
+    nodes
+}
+
+/// Matches one pattern against nodes of one source file.
+struct Matcher<'a> {
+    pattern: &'a Pattern,
+    source: &'a str,
+}
+
+impl Matcher<'_> {
+    fn bind(&self, name: &Option<String>, range: (usize, usize), env: &mut Captures) -> bool {
+        let Some(name) = name else {
+            return true;
+        };
+        match env.get(name) {
+            Some(&(start, end)) => self.source[start..end] == self.source[range.0..range.1],
+            None => {
+                env.insert(name.clone(), range);
+                true
+            }
+        }
+    }
+
+    fn matches(&self, pattern: Node, node: Node, env: &mut Captures) -> bool {
+        match metavar(self.pattern.text(pattern), self.pattern.language.expando()) {
+            Some(MetaVar::Single(name)) => {
+                return node.is_named()
+                    && self.bind(&name, (node.start_byte(), node.end_byte()), env)
+            }
+            Some(MetaVar::Multi(name)) => {
+                return self.bind(&name, (node.start_byte(), node.end_byte()), env)
+            }
+            None => {}
+        }
+        if pattern.kind() != node.kind() || pattern.is_named() != node.is_named() {
+            return false;
+        }
+        let expected = children(pattern);
+        let actual = children(node);
+        if expected.is_empty() || actual.is_empty() {
+            return self.pattern.text(pattern) == &self.source[node.byte_range()];
+        }
+        let fields: Vec<&str> = expected.iter().filter_map(|(_, field)| *field).collect();
+        self.matches_children(&expected, &actual, &fields, env)
+    }
+
+    /// Whether the pattern may leave `child` out: punctuation and keywords,
+    /// fields the pattern does not fill, and optional modifiers.
+    fn skippable(&self, (node, field): &Child, fields: &[&str]) -> bool {
+        !node.is_named()
+            || field.is_some_and(|f| !fields.contains(&f))
+            || self
+                .pattern
+                .language
+                .optional_kinds()
+                .contains(&node.kind())
+    }
+
+    fn matches_children(
+        &self,
+        expected: &[Child],
+        actual: &[Child],
+        fields: &[&str],
+        env: &mut Captures,
+    ) -> bool {
+        let Some(((pattern, field), rest)) = expected.split_first() else {
+            return actual.iter().all(|child| self.skippable(child, fields));
+        };
+
+        let expando = self.pattern.language.expando();
+        if let Some(MetaVar::Multi(name)) = metavar(self.pattern.text(*pattern), expando) {
+            let at = actual.first().map_or(0, |(node, _)| node.start_byte());
+            for taken in 0..=actual.len() {
+                let range = match (actual[..taken].first(), actual[..taken].last()) {
+                    (Some((first, _)), Some((last, _))) => (first.start_byte(), last.end_byte()),
+                    _ => (at, at),
+                };
+                let mut attempt = env.clone();
+                if self.bind(&name, range, &mut attempt)
+                    && self.matches_children(rest, &actual[taken..], fields, &mut attempt)
+                {
+                    *env = attempt;
+                    return true;
+                }
+            }
+            return false;
+        }
+
+        let Some((head, tail)) = actual.split_first() else {
+            return false;
+        };
+        let mut attempt = env.clone();
+        if (field.is_none() || *field == head.1)
+            && self.matches(*pattern, head.0, &mut attempt)
+            && self.matches_children(rest, tail, fields, &mut attempt)
+        {
+            *env = attempt;
+            return true;
+        }
+        self.skippable(head, fields) && self.matches_children(expected, tail, fields, env)
+    }
+
+    /// Whether any of `nodes` matches, adding its captures to `env`.
+    fn matches_any<'t>(&self, nodes: impl Iterator<Item = Node<'t>>, env: &mut Captures) -> bool {
+        for node in nodes {
+            let mut attempt = env.clone();
+            if self.matches(self.pattern.root(), node, &mut attempt) {
+                *env = attempt;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// How a matched node must relate to the nodes another pattern matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    /// Some enclosing node matches
+    Inside,
+    /// No enclosing node matches
+    NotInside,
+    /// Some node below matches
+    Has,
+    /// No node below matches
+    NotHas,
+}
+
+/// A node matching a query, with the text of each named metavariable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub captures: BTreeMap<String, String>,
+}
+
+/// A pattern or node kind, narrowed by patterns for enclosing or enclosed
+/// nodes.
+pub struct Query {
+    language: Language,
+    pattern: Option<Pattern>,
+    kind: Option<String>,
+    relations: Vec<(Relation, Pattern)>,
+}
+
+impl Query {
+    pub fn new(
+        language: Language,
+        pattern: Option<&str>,
+        kind: Option<&str>,
+    ) -> Result<Self, String> {
+        if pattern.is_none() && kind.is_none() {
+            return Err("A pattern or a node kind is required".to_string());
+        }
+        if let Some(kind) = kind {
+            if language.grammar(None).id_for_node_kind(kind, true) == 0 {
+                return Err(format!("Unknown {} node kind: {}", language.name(), kind));
+            }
+        }
+        Ok(Self {
+            language,
+            pattern: pattern.map(|p| Pattern::new(language, p)).transpose()?,
+            kind: kind.map(str::to_string),
+            relations: Vec::new(),
+        })
+    }
+
+    pub fn with(mut self, relation: Relation, pattern: &str) -> Result<Self, String> {
+        self.relations
+            .push((relation, Pattern::new(self.language, pattern)?));
+        Ok(self)
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Names captured by a match: those of the pattern and of the
+    /// `Inside` and `Has` patterns.
+    pub fn metavariables(&self) -> BTreeSet<String> {
+        let positive = self
+            .relations
+            .iter()
+            .filter(|(relation, _)| matches!(relation, Relation::Inside | Relation::Has))
+            .map(|(_, pattern)| pattern);
+        let mut names = BTreeSet::new();
+        for pattern in self.pattern.iter().chain(positive) {
+            let text = &pattern.source[pattern.start..pattern.end];
+            let expando = pattern.language.expando().to_string();
+            names.extend(metavariables(&text.replace(&expando, "$")));
+        }
+        names
+    }
+
+    /// Matches in `source`, the contents of `path`, in document order;
+    /// nodes inside a match are matched too.
+    pub fn find(&self, path: &Path, source: &str) -> Result<Vec<Match>, String> {
+        let tree = parse(self.language, Some(path), source)?;
+        let mut matches = Vec::new();
+        for node in preorder(tree.root_node()) {
+            if !node.is_named() {
+                continue;
+            }
+            if let Some(env) = self.match_node(node, source) {
+                matches.push(Match {
+                    start: node.start_byte(),
+                    end: node.end_byte(),
+                    captures: env
+                        .into_iter()
+                        .map(|(name, (start, end))| (name, source[start..end].to_string()))
+                        .collect(),
+                });
+            }
+        }
+        Ok(matches)
+    }
+
+    fn match_node(&self, node: Node, source: &str) -> Option<Captures> {
+        if self.kind.as_ref().is_some_and(|kind| node.kind() != kind) {
+            return None;
+        }
+        let mut env = Captures::new();
+        if let Some(pattern) = &self.pattern {
+            let matcher = Matcher { pattern, source };
+            if !matcher.matches(pattern.root(), node, &mut env) {
+                return None;
+            }
+        }
+        for (relation, pattern) in &self.relations {
+            let matcher = Matcher { pattern, source };
+            let ancestors = std::iter::successors(node.parent(), |n| n.parent());
+            let below = || preorder(node).into_iter().skip(1);
+            let holds = match relation {
+                Relation::Inside => matcher.matches_any(ancestors, &mut env),
+                Relation::Has => matcher.matches_any(below(), &mut env),
+                Relation::NotInside => !matcher.matches_any(ancestors, &mut env.clone()),
+                Relation::NotHas => !matcher.matches_any(below(), &mut env.clone()),
+            };
+            if !holds {
+                return None;
+            }
+        }
+        Some(env)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(query: &Query, file: &str, source: &str) -> Vec<(String, BTreeMap<String, String>)> {
+        query
+            .find(Path::new(file), source)
+            .unwrap()
+            .into_iter()
+            .map(|m| (source[m.start..m.end].to_string(), m.captures))
+            .collect()
+    }
+
+    fn captures(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_unwrap_inside_async_fn() {
+        let source = r#"
+pub async fn load(path: &str) -> Result<String, Error> {
+    let text = read(path).await.unwrap();
+    Ok(text)
+}
+
+fn parse(text: &str) -> u32 {
+    text.parse().unwrap()
+}
+
+async fn quiet() {
+    // ignore.unwrap() in comments
+    let _ = CONFIG.get().unwrap();
+}
+"#;
+        let query = Query::new(Language::Rust, Some("$X.unwrap()"), None)
+            .unwrap()
+            .with(Relation::Inside, "async fn $F($$$ARGS) { $$$ }")
+            .unwrap();
+        assert_eq!(
+            query.metavariables().into_iter().collect::<Vec<_>>(),
+            ["ARGS", "F", "X"]
+        );
+        assert_eq!(
+            find(&query, "lib.rs", source),
+            [
+                (
+                    "read(path).await.unwrap()".to_string(),
+                    captures(&[
+                        ("ARGS", "path: &str"),
+                        ("F", "load"),
+                        ("X", "read(path).await")
+                    ])
+                ),
+                (
+                    "CONFIG.get().unwrap()".to_string(),
+                    captures(&[("ARGS", ""), ("F", "quiet"), ("X", "CONFIG.get()")])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_kind_with_relations() {
+        let source = r#"
+fn kind(n: u8) -> &'static str {
+    match n {
+        0 => "zero",
+        _ => "many",
+    }
+}
+fn exact(b: bool) -> u8 {
+    match b {
+        true => 1,
+        false => 0,
+    }
+}
+"#;
+        let wildcard = Query::new(Language::Rust, None, Some("match_expression"))
+            .unwrap()
+            .with(Relation::Has, "_ => $$$")
+            .unwrap();
+        let found = find(&wildcard, "lib.rs", source);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].0.starts_with("match n {"));
+
+        let exhaustive = Query::new(Language::Rust, Some("match $E { $$$ARMS }"), None);
+        assert!(exhaustive.is_err());
+        let exhaustive = Query::new(Language::Rust, None, Some("match_expression"))
+            .unwrap()
+            .with(Relation::NotHas, "_ => $$$")
+            .unwrap();
+        assert!(find(&exhaustive, "lib.rs", source)[0]
+            .0
+            .starts_with("match b {"));
+    }
+
+    #[test]
+    fn test_metavariables_and_languages() {
+        let same = Query::new(Language::Rust, Some("$A == $A"), None).unwrap();
+        let found = find(&same, "lib.rs", "fn f() { x == x; x == y; a.b == a.b; }");
+        let texts: Vec<&str> = found.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(texts, ["x == x", "a.b == a.b"]);
+
+        let call = Query::new(Language::Python, Some("requests.get($URL, $$$REST)"), None).unwrap();
+        let found = find(
+            &call,
+            "app.py",
+            "import requests\nr = requests.get(url, timeout=5, verify=False)\nrequests.post(url)\n",
+        );
+        assert_eq!(
+            found[0].1,
+            captures(&[("REST", "timeout=5, verify=False"), ("URL", "url")])
+        );
+
+        let then = Query::new(Language::TypeScript, Some("$P.then($$$)"), None).unwrap();
+        let source = "const x: Promise<number> = load().then((v: number) => <b>{v}</b>);\n";
+        assert_eq!(
+            find(&then, "view.tsx", source)[0].0,
+            "load().then((v: number) => <b>{v}</b>)"
+        );
+        assert_eq!(
+            find(&then, "view.ts", "fetch(u).then(r => r.json());")[0].1["P"],
+            "fetch(u)"
+        );
+    }
+
+    #[test]
+    fn test_invalid_queries_and_rewrite() {
+        assert!(Query::new(Language::Rust, None, None).is_err());
+        assert!(Query::new(Language::Rust, Some("  "), None).is_err());
+        assert!(Query::new(Language::Rust, Some("fn ("), None).is_err());
+        assert!(Query::new(Language::Rust, None, Some("no_such_kind")).is_err());
+        assert_eq!(Language::parse("TS"), Some(Language::TypeScript));
+
+        let captured = captures(&[("X", "opt"), ("ARGS", "a, b")]);
+        assert_eq!(
+            rewrite("$X.expect(\"$MSG\") + f($$$ARGS) + $_", &captured),
+            "opt.expect(\"$MSG\") + f(a, b) + $_"
+        );
+        assert_eq!(
+            metavariables("$X + $$$ARGS + $_ + $$$")
+                .into_iter()
+                .collect::<Vec<_>>(),
+            ["ARGS", "X"]
+        );
+    }
+}
@@ -0,0 +1,572 @@
+//! Symbol extraction for the workspace index.
+//!
+//! Rust is parsed with `syn`. Python and JS/TS are scanned line by line
+//! over the source with comments and string literals blanked out, which
+//! finds declarations at the start of a line and nothing more exotic.
+
+use super::lexer::{segments, SegmentKind, Syntax};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::OnceLock;
+use syn::spanned::Spanned;
+use syn::{ImplItem, Item, TraitItem};
+
+/// Longest signature kept; the rest of the line is cut.
+const MAX_SIGNATURE: usize = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Module,
+    Function,
+    Method,
+    Struct,
+    Enum,
+    Trait,
+    Impl,
+    Class,
+    Interface,
+}
+
+impl SymbolKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        Some(match kind {
+            "module" | "mod" => Self::Module,
+            "function" | "fn" => Self::Function,
+            "method" => Self::Method,
+            "struct" => Self::Struct,
+            "enum" => Self::Enum,
+            "trait" => Self::Trait,
+            "impl" => Self::Impl,
+            "class" => Self::Class,
+            "interface" => Self::Interface,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Enclosing type, trait, class or module, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    /// 1-based line of the declaration
+    pub line: usize,
+    pub end_line: usize,
+    /// The declaration's first line, trimmed
+    pub signature: String,
+}
+
+impl Symbol {
+    /// `Container::name` for Rust, `Container.name` elsewhere.
+    pub fn qualified_name(&self, syntax: Syntax) -> String {
+        match &self.container {
+            Some(container) if syntax == Syntax::Rust => format!("{}::{}", container, self.name),
+            Some(container) => format!("{}.{}", container, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Languages with symbol support; other files yield no symbols.
+pub const EXTENSIONS: &[&str] = &[
+    "rs", "py", "pyi", "js", "jsx", "mjs", "cjs", "ts", "tsx", "mts", "cts",
+];
+
+/// Symbols declared in `source`, in declaration order.
+pub fn extract(path: &Path, source: &str) -> Result<Vec<Symbol>, String> {
+    match Syntax::from_path(path) {
+        Some(Syntax::Rust) => rust_symbols(source).map_err(|e| e.to_string()),
+        Some(Syntax::Python) => Ok(python_symbols(source)),
+        Some(Syntax::JavaScript) => Ok(javascript_symbols(source)),
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn signature(lines: &[&str], line: usize) -> String {
+    let text = lines.get(line.wrapping_sub(1)).copied().unwrap_or_default();
+    let text = text.trim().trim_end_matches('{').trim_end();
+    match text.char_indices().nth(MAX_SIGNATURE) {
+        Some((cut, _)) => format!("{}...", &text[..cut]),
+        None => text.to_string(),
+    }
+}
+
+fn rust_symbols(source: &str) -> syn::Result<Vec<Symbol>> {
+    let file = syn::parse_file(source)?;
+    let lines: Vec<&str> = source.lines().collect();
+    let mut collector = RustSymbols {
+        lines: &lines,
+        out: Vec::new(),
+    };
+    collector.items(&file.items, None);
+    Ok(collector.out)
+}
+
+struct RustSymbols<'a> {
+    lines: &'a [&'a str],
+    out: Vec<Symbol>,
+}
+
+impl RustSymbols<'_> {
+    fn push(
+        &mut self,
+        ident: &syn::Ident,
+        kind: SymbolKind,
+        container: Option<&str>,
+        end_line: usize,
+    ) {
+        let line = ident.span().start().line;
+        self.out.push(Symbol {
+            name: ident.to_string(),
+            kind,
+            container: container.map(str::to_string),
+            line,
+            end_line,
+            signature: signature(self.lines, line),
+        });
+    }
+
+    /// Items of a file or inline module, `module` being its path.
+    fn items(&mut self, items: &[Item], module: Option<&str>) {
+        for item in items {
+            let end_line = item.span().end().line;
+            match item {
+                Item::Fn(f) => self.push(&f.sig.ident, SymbolKind::Function, module, end_line),
+                Item::Struct(s) => self.push(&s.ident, SymbolKind::Struct, module, end_line),
+                Item::Enum(e) => self.push(&e.ident, SymbolKind::Enum, module, end_line),
+                Item::Trait(t) => {
+                    self.push(&t.ident, SymbolKind::Trait, module, end_line);
+                    let name = t.ident.to_string();
+                    for trait_item in &t.items {
+                        if let TraitItem::Fn(f) = trait_item {
+                            let end_line = trait_item.span().end().line;
+                            self.push(&f.sig.ident, SymbolKind::Method, Some(&name), end_line);
+                        }
+                    }
+                }
+                Item::Impl(i) => {
+                    let Some(name) = type_name(&i.self_ty) else {
+                        continue;
+                    };
+                    let line = i.impl_token.span.start().line;
+                    self.out.push(Symbol {
+                        name: name.clone(),
+                        kind: SymbolKind::Impl,
+                        container: module.map(str::to_string),
+                        line,
+                        end_line,
+                        signature: signature(self.lines, line),
+                    });
+                    for impl_item in &i.items {
+                        if let ImplItem::Fn(f) = impl_item {
+                            let end_line = impl_item.span().end().line;
+                            self.push(&f.sig.ident, SymbolKind::Method, Some(&name), end_line);
+                        }
+                    }
+                }
+                Item::Mod(m) => {
+                    self.push(&m.ident, SymbolKind::Module, module, end_line);
+                    if let Some((_, nested)) = &m.content {
+                        let path = match module {
+                            Some(parent) => format!("{}::{}", parent, m.ident),
+                            None => m.ident.to_string(),
+                        };
+                        self.items(nested, Some(&path));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        syn::Type::Reference(reference) => type_name(&reference.elem),
+        _ => None,
+    }
+}
+
+/// `source` with comments and string literals replaced by spaces, keeping
+/// every newline so lines and offsets still match.
+fn code_only(source: &str, syntax: Syntax) -> String {
+    let mut bytes = source.as_bytes().to_vec();
+    for segment in segments(source, syntax) {
+        if segment.kind != SegmentKind::Code {
+            for byte in &mut bytes[segment.start..segment.end] {
+                if *byte != b'\n' {
+                    *byte = b' ';
+                }
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn python_symbols(source: &str) -> Vec<Symbol> {
+    static DECLARATION: OnceLock<Regex> = OnceLock::new();
+    let declaration = DECLARATION.get_or_init(|| {
+        Regex::new(r"^[ \t]*(?:async[ \t]+)?(def|class)[ \t]+([A-Za-z_]\w*)").expect("valid regex")
+    });
+    let code = code_only(source, Syntax::Python);
+    let lines: Vec<&str> = code.lines().collect();
+    let original: Vec<&str> = source.lines().collect();
+
+    let mut out = Vec::new();
+    // Enclosing declarations: indent, name, whether a class
+    let mut scopes: Vec<(usize, String, bool)> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let Some(caps) = declaration.captures(line) else {
+            continue;
+        };
+        let indent = indent_of(line);
+        while scopes.last().is_some_and(|(i, _, _)| *i >= indent) {
+            scopes.pop();
+        }
+        let is_class = &caps[1] == "class";
+        let kind = match (is_class, scopes.last()) {
+            (true, _) => SymbolKind::Class,
+            (false, Some((_, _, true))) => SymbolKind::Method,
+            (false, _) => SymbolKind::Function,
+        };
+        let name = caps[2].to_string();
+        // The body runs until the next line indented no deeper
+        let end = (index + 1..lines.len())
+            .find(|&i| !lines[i].trim().is_empty() && indent_of(lines[i]) <= indent)
+            .unwrap_or(lines.len());
+        let end_line = (index + 1..end)
+            .rev()
+            .find(|&i| !lines[i].trim().is_empty())
+            .map_or(index + 1, |i| i + 1);
+        out.push(Symbol {
+            name: name.clone(),
+            kind,
+            container: scopes.last().map(|(_, name, _)| name.clone()),
+            line: index + 1,
+            end_line,
+            signature: signature(&original, index + 1)
+                .trim_end_matches(':')
+                .to_string(),
+        });
+        scopes.push((indent, name, is_class));
+    }
+    out
+}
+
+struct JsPatterns {
+    class: Regex,
+    interface: Regex,
+    enumeration: Regex,
+    function: Regex,
+    arrow: Regex,
+    method: Regex,
+}
+
+fn js_patterns() -> &'static JsPatterns {
+    static PATTERNS: OnceLock<JsPatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        let re = |pattern: &str| Regex::new(pattern).expect("valid regex");
+        JsPatterns {
+            class: re(r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?class\s+([\w$]+)"),
+            interface: re(r"^\s*(?:export\s+)?(?:declare\s+)?interface\s+([\w$]+)"),
+            enumeration: re(r"^\s*(?:export\s+)?(?:declare\s+)?(?:const\s+)?enum\s+([\w$]+)"),
+            function: re(
+                r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:async\s+)?function\s*\*?\s*([\w$]+)",
+            ),
+            arrow: re(
+                r"^\s*(?:export\s+)?(?:const|let|var)\s+([\w$]+)\s*(?::[^=]+)?=\s*(?:async\s+)?(?:function\b|[\w$]+\s*=>|\(.*=>|\(\s*$)",
+            ),
+            method: re(
+                r"^\s*(?:(?:public|private|protected|static|async|override|readonly|abstract|get|set)\s+)*\*?\s*(#?[\w$]+)\s*(?:<[^>]*>)?\s*(?:\(|=\s*(?:async\s+)?(?:\(.*=>|[\w$]+\s*=>))",
+            ),
+        }
+    })
+}
+
+/// Words a method pattern matches at the start of a statement.
+const JS_KEYWORDS: &[&str] = &[
+    "if", "for", "while", "switch", "catch", "return", "function", "await", "new", "typeof",
+    "super", "this",
+];
+
+fn javascript_symbols(source: &str) -> Vec<Symbol> {
+    let patterns = js_patterns();
+    let code = code_only(source, Syntax::JavaScript);
+    let original: Vec<&str> = source.lines().collect();
+
+    let mut out = Vec::new();
+    // Enclosing classes: brace depth of the declaration, name
+    let mut classes: Vec<(usize, String)> = Vec::new();
+    let mut depth = 0usize;
+    let mut offset = 0usize;
+    for (index, line) in code.split('\n').enumerate() {
+        let line_start = offset;
+        offset += line.len() + 1;
+        while classes.last().is_some_and(|(d, _)| depth <= *d) {
+            classes.pop();
+        }
+
+        let in_class_body = classes.last().is_some_and(|(d, _)| depth == d + 1);
+        let found = if let Some(caps) = patterns.class.captures(line) {
+            Some((SymbolKind::Class, caps.get(1)))
+        } else if let Some(caps) = patterns.interface.captures(line) {
+            Some((SymbolKind::Interface, caps.get(1)))
+        } else if let Some(caps) = patterns.enumeration.captures(line) {
+            Some((SymbolKind::Enum, caps.get(1)))
+        } else if let Some(caps) = patterns.function.captures(line) {
+            Some((SymbolKind::Function, caps.get(1)))
+        } else if let Some(caps) = patterns.arrow.captures(line) {
+            Some((SymbolKind::Function, caps.get(1)))
+        } else if in_class_body {
+            patterns
+                .method
+                .captures(line)
+                .filter(|caps| !JS_KEYWORDS.contains(&&caps[1]))
+                .map(|caps| (SymbolKind::Method, caps.get(1)))
+        } else {
+            None
+        };
+
+        if let Some((kind, Some(name))) = found {
+            let start = line_start + name.end();
+            let end_line = js_block_end(&code, start).unwrap_or(index + 1);
+            let container = match kind {
+                SymbolKind::Method => classes.last().map(|(_, name)| name.clone()),
+                _ => None,
+            };
+            if kind == SymbolKind::Class {
+                classes.push((depth, name.as_str().to_string()));
+            }
+            out.push(Symbol {
+                name: name.as_str().to_string(),
+                kind,
+                container,
+                line: index + 1,
+                end_line,
+                signature: signature(&original, index + 1),
+            });
+        }
+
+        for c in line.chars() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+    out
+}
+
+/// Last line of the declaration whose name ends at byte `start` of `code`:
+/// its braced body, or the statement up to `;` or the end of an expression
+/// body's line.
+fn js_block_end(code: &str, start: usize) -> Option<usize> {
+    let bytes = code.as_bytes();
+    let line_at = |pos: usize| code[..pos].matches('\n').count() + 1;
+    let mut parens = 0usize;
+    let mut pos = start;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'(' | b'[' => parens += 1,
+            b')' | b']' => parens = parens.saturating_sub(1),
+            b';' if parens == 0 => return Some(line_at(pos)),
+            b'=' if parens == 0 && bytes.get(pos + 1) == Some(&b'>') => {
+                let body = code[pos + 2..].trim_start_matches([' ', '\t']);
+                if !body.starts_with('{') {
+                    let line_end = code[pos..].find('\n').map_or(code.len(), |i| pos + i);
+                    return Some(line_at(line_end));
+                }
+                pos += 1;
+            }
+            b'{' if parens == 0 => {
+                let mut depth = 0usize;
+                for (i, &byte) in bytes.iter().enumerate().skip(pos) {
+                    match byte {
+                        b'{' => depth += 1,
+                        b'}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                return Some(line_at(i));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                return None;
+            }
+            _ => {}
+        }
+        pos += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(symbols: &[Symbol]) -> Vec<(String, SymbolKind, usize, usize)> {
+        symbols
+            .iter()
+            .map(|s| {
+                let name = match &s.container {
+                    Some(c) => format!("{}::{}", c, s.name),
+                    None => s.name.clone(),
+                };
+                (name, s.kind, s.line, s.end_line)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_symbols() {
+        let source = r#"
+pub struct Registry {
+    tools: Vec<String>,
+}
+
+impl Registry {
+    pub async fn execute(&self) -> bool {
+        true
+    }
+}
+
+pub trait Tool {
+    fn name(&self) -> &str;
+}
+
+impl Tool for &Registry {
+    fn name(&self) -> &str { "r" }
+}
+
+mod inner {
+    pub enum Mode { A, B }
+    fn helper() {}
+}
+"#;
+        let symbols = extract(Path::new("lib.rs"), source).unwrap();
+        assert_eq!(
+            summary(&symbols),
+            vec![
+                ("Registry".to_string(), SymbolKind::Struct, 2, 4),
+                ("Registry".to_string(), SymbolKind::Impl, 6, 10),
+                ("Registry::execute".to_string(), SymbolKind::Method, 7, 9),
+                ("Tool".to_string(), SymbolKind::Trait, 12, 14),
+                ("Tool::name".to_string(), SymbolKind::Method, 13, 13),
+                ("Registry".to_string(), SymbolKind::Impl, 16, 18),
+                ("Registry::name".to_string(), SymbolKind::Method, 17, 17),
+                ("inner".to_string(), SymbolKind::Module, 20, 23),
+                ("inner::Mode".to_string(), SymbolKind::Enum, 21, 21),
+                ("inner::helper".to_string(), SymbolKind::Function, 22, 22),
+            ]
+        );
+        assert_eq!(symbols[2].signature, "pub async fn execute(&self) -> bool");
+        assert_eq!(symbols[2].qualified_name(Syntax::Rust), "Registry::execute");
+        assert!(extract(Path::new("bad.rs"), "fn (").is_err());
+    }
+
+    #[test]
+    fn test_python_symbols() {
+        let source = r#"import os
+
+class Config:
+    """A docstring mentioning
+    def not_a_function():
+    """
+
+    def load(self, path):
+        def inner():
+            return 1
+        return inner()
+
+    async def save(self):
+        pass
+
+
+def main():
+    # class Commented:
+    print("def nope():")
+"#;
+        let symbols = extract(Path::new("app.py"), source).unwrap();
+        assert_eq!(
+            summary(&symbols),
+            vec![
+                ("Config".to_string(), SymbolKind::Class, 3, 14),
+                ("Config::load".to_string(), SymbolKind::Method, 8, 11),
+                ("load::inner".to_string(), SymbolKind::Function, 9, 10),
+                ("Config::save".to_string(), SymbolKind::Method, 13, 14),
+                ("main".to_string(), SymbolKind::Function, 17, 19),
+            ]
+        );
+        assert_eq!(symbols[3].signature, "async def save(self)");
+        assert_eq!(symbols[1].qualified_name(Syntax::Python), "Config.load");
+    }
+
+    #[test]
+    fn test_javascript_symbols() {
+        let source = r#"import { x } from "./x";
+
+export class Store extends Base {
+  #items = [];
+
+  constructor(items) {
+    super();
+    if (items) {
+      this.#items = items;
+    }
+  }
+
+  async get(key) {
+    return this.#items.find((i) => i.key === key);
+  }
+
+  static create = () => new Store([]);
+}
+
+export interface Options {
+  verbose: boolean;
+}
+
+export default function render({ root }) {
+  // function commented() {}
+  const label = "function fake() {}";
+  return root;
+}
+
+const double = (n) => n * 2;
+export const load = async (path) => {
+  return path;
+};
+enum Color { Red, Green }
+"#;
+        let symbols = extract(Path::new("store.ts"), source).unwrap();
+        assert_eq!(
+            summary(&symbols),
+            vec![
+                ("Store".to_string(), SymbolKind::Class, 3, 18),
+                ("Store::constructor".to_string(), SymbolKind::Method, 6, 11),
+                ("Store::get".to_string(), SymbolKind::Method, 13, 15),
+                ("Store::create".to_string(), SymbolKind::Method, 17, 17),
+                ("Options".to_string(), SymbolKind::Interface, 20, 22),
+                ("render".to_string(), SymbolKind::Function, 24, 28),
+                ("double".to_string(), SymbolKind::Function, 30, 30),
+                ("load".to_string(), SymbolKind::Function, 31, 33),
+                ("Color".to_string(), SymbolKind::Enum, 34, 34),
+            ]
+        );
+        assert_eq!(
+            symbols[5].signature,
+            "export default function render({ root })"
+        );
+    }
+}
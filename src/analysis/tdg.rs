@@ -0,0 +1,167 @@
+//! Technical debt gradient: a per-file debt score from 0 (clean) to 5.
+//!
+//! The score blends four components, each normalized to 0..1: the worst
+//! cyclomatic and cognitive complexity relative to twice their thresholds,
+//! the number of debt comments (five or more saturate) and the file length
+//! (1000 lines saturate).
+
+use super::complexity::{self, Thresholds};
+use super::lexer::Syntax;
+use super::{display_path, satd, source_files};
+use serde::Serialize;
+use std::path::Path;
+
+const WEIGHTS: Components = Components {
+    cyclomatic: 0.35,
+    cognitive: 0.25,
+    debt: 0.2,
+    size: 0.2,
+};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Components {
+    pub cyclomatic: f64,
+    pub cognitive: f64,
+    pub debt: f64,
+    pub size: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileScore {
+    pub file: String,
+    pub score: f64,
+    pub grade: &'static str,
+    pub components: Components,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub total_files: usize,
+    pub average_score: f64,
+    pub max_score: f64,
+    pub grade: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TdgReport {
+    pub summary: Summary,
+    /// Sorted worst first
+    pub files: Vec<FileScore>,
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn ratio(value: f64, saturation: f64) -> f64 {
+    round2((value / saturation).min(1.0))
+}
+
+pub fn grade(score: f64) -> &'static str {
+    match score {
+        s if s < 1.0 => "A",
+        s if s < 2.0 => "B",
+        s if s < 3.0 => "C",
+        s if s < 4.0 => "D",
+        _ => "F",
+    }
+}
+
+/// Score one source file.
+pub fn score_source(source: &str, syntax: Syntax, thresholds: Thresholds) -> Components {
+    let functions = match syntax {
+        Syntax::Rust => complexity::analyze_source(source).unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    let worst = |metric: fn(&complexity::FunctionComplexity) -> u32| {
+        functions.iter().map(metric).max().unwrap_or(0) as f64
+    };
+
+    Components {
+        cyclomatic: ratio(worst(|f| f.cyclomatic), 2.0 * thresholds.cyclomatic as f64),
+        cognitive: ratio(worst(|f| f.cognitive), 2.0 * thresholds.cognitive as f64),
+        debt: ratio(satd::scan_source(source, syntax).len() as f64, 5.0),
+        size: ratio(source.lines().count() as f64, 1000.0),
+    }
+}
+
+fn weighted(components: &Components) -> f64 {
+    let blended = components.cyclomatic * WEIGHTS.cyclomatic
+        + components.cognitive * WEIGHTS.cognitive
+        + components.debt * WEIGHTS.debt
+        + components.size * WEIGHTS.size;
+    round2(blended * 5.0)
+}
+
+/// Score every supported source file under `target`.
+pub fn analyze_path(root: &Path, target: &Path, thresholds: Thresholds) -> TdgReport {
+    let mut files: Vec<FileScore> = source_files(target, Syntax::EXTENSIONS)
+        .into_iter()
+        .filter_map(|path| {
+            let syntax = Syntax::from_path(&path)?;
+            let source = std::fs::read_to_string(&path).ok()?;
+            let components = score_source(&source, syntax, thresholds);
+            let score = weighted(&components);
+            Some(FileScore {
+                file: display_path(root, &path),
+                score,
+                grade: grade(score),
+                components,
+            })
+        })
+        .collect();
+    files.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let total = files.len();
+    let average = if total == 0 {
+        0.0
+    } else {
+        round2(files.iter().map(|f| f.score).sum::<f64>() / total as f64)
+    };
+
+    TdgReport {
+        summary: Summary {
+            total_files: total,
+            average_score: average,
+            max_score: files.first().map(|f| f.score).unwrap_or(0.0),
+            grade: grade(average),
+        },
+        files,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_components() {
+        let mut source = String::from("// FIXME: overflow\nfn f(x: u32) -> u32 {\n");
+        for i in 0..19 {
+            source.push_str(&format!("    if x == {} {{ return {}; }}\n", i, i));
+        }
+        source.push_str("    0\n}\n");
+
+        let components = score_source(&source, Syntax::Rust, Thresholds::default());
+        // 20 branches against twice the threshold of 20
+        assert_eq!(components.cyclomatic, 0.5);
+        assert_eq!(components.debt, 0.2);
+        assert_eq!(components.size, 0.02);
+
+        let clean = score_source("fn g() {}\n", Syntax::Rust, Thresholds::default());
+        assert!(weighted(&clean) < 0.1);
+        assert_eq!(grade(weighted(&clean)), "A");
+    }
+
+    #[test]
+    fn test_report_sorted_worst_first() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("clean.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(dir.path().join("debt.py"), "# TODO: a\n# TODO: b\n").unwrap();
+
+        let report = analyze_path(dir.path(), dir.path(), Thresholds::default());
+        assert_eq!(report.summary.total_files, 2);
+        assert_eq!(report.files[0].file, "debt.py");
+        assert!(report.summary.max_score > 0.0);
+    }
+}
@@ -1,18 +1,22 @@
 use crate::{
     config::Config,
-    context::{PROJECT_CONTEXT, SYSTEM_PROMPT},
-    tools::{ToolRegistry, ToolRequest, ToolResponse},
+    context::SYSTEM_PROMPT,
+    tools::{params, repo_map, ToolRegistry, ToolRequest, ToolResponse},
+    watcher::WorkspaceWatcher,
 };
 use anyhow::Result;
 use rustyline::error::ReadlineError;
 use rustyline::DefaultEditor;
 use serde_json::json;
+use std::path::PathBuf;
 use tracing::error;
 
 pub struct InteractiveChat {
     registry: ToolRegistry,
     config: Config,
     history_file: String,
+    workspace: PathBuf,
+    watcher: Option<WorkspaceWatcher>,
 }
 
 impl InteractiveChat {
@@ -21,9 +25,17 @@ impl InteractiveChat {
             registry,
             config: Config::from_env(),
             history_file: ".pcode_history".to_string(),
+            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            watcher: None,
         }
     }
 
+    /// Report files changed outside pcode to the agent at each turn.
+    pub fn with_watcher(mut self, watcher: WorkspaceWatcher) -> Self {
+        self.watcher = Some(watcher);
+        self
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         // Initialize readline editor
         let mut rl = DefaultEditor::new()?;
@@ -95,21 +107,39 @@ impl InteractiveChat {
         Ok(())
     }
 
-    async fn build_enhanced_prompt(&self, input: &str) -> String {
+    async fn build_enhanced_prompt(&self, input: &str, changed: &[String]) -> String {
+        // A map of the workspace the user is in, sized to the configured budget
+        let workspace = self.workspace.clone();
+        let budget = self.config.repo_map_tokens;
+        let mut context = tokio::task::spawn_blocking(move || repo_map::render(&workspace, budget))
+            .await
+            .unwrap_or_default();
+
+        if !changed.is_empty() {
+            // Anything the agent saw of these files earlier may be outdated
+            context.push_str(
+                "\n\nFiles changed outside pcode since the last turn; re-read them before editing:",
+            );
+            for file in changed {
+                context.push_str(&format!("\n- {}", file));
+            }
+        }
+
         if input.to_lowercase().contains("readme") {
             // Read README.md and include it in context
-            let readme_content = match self.read_file("README.md").await {
-                Ok(content) => format!("\n\nREADME.md content:\n{}", content),
-                Err(_) => String::new(),
-            };
-            format!(
-                "{}\n\nContext:\n{}{}\n\nUser: {}\n\nAssistant:",
-                SYSTEM_PROMPT, PROJECT_CONTEXT, readme_content, input
-            )
+            if let Ok(content) = self.read_file("README.md").await {
+                context.push_str(&format!("\n\nREADME.md content:\n{}", content));
+            }
+        }
+
+        if context.trim().is_empty() {
+            format!("{}\n\nUser: {}\n\nAssistant:", SYSTEM_PROMPT, input)
         } else {
             format!(
                 "{}\n\nContext:\n{}\n\nUser: {}\n\nAssistant:",
-                SYSTEM_PROMPT, PROJECT_CONTEXT, input
+                SYSTEM_PROMPT,
+                context.trim_start(),
+                input
             )
         }
     }
@@ -137,7 +167,17 @@ impl InteractiveChat {
     }
 
     async fn process_with_llm(&self, input: &str) -> Result<()> {
-        let enhanced_prompt = self.build_enhanced_prompt(input).await;
+        // Only the agent needs to hear about changes, so tool commands and
+        // offline answers leave them queued
+        let changed = self
+            .watcher
+            .as_ref()
+            .map(|w| w.take_changes())
+            .unwrap_or_default();
+        if !changed.is_empty() {
+            println!("📝 Changed outside pcode: {}", changed.join(", "));
+        }
+        let enhanced_prompt = self.build_enhanced_prompt(input, &changed).await;
 
         let request = ToolRequest {
             tool: "llm".to_string(),
@@ -149,6 +189,12 @@ impl InteractiveChat {
         };
 
         let response = self.registry.execute(request).await;
+        if !response.success {
+            // The agent never saw them; report them again next turn
+            if let Some(watcher) = &self.watcher {
+                watcher.requeue(changed);
+            }
+        }
         self.print_llm_response(response)
     }
 
@@ -205,95 +251,6 @@ impl InteractiveChat {
         Ok(())
     }
 
-    fn parse_file_write_params(&self, params_str: &str) -> Option<serde_json::Value> {
-        let parts: Vec<&str> = params_str.splitn(2, ' ').collect();
-        if parts.len() == 2 {
-            Some(json!({ "path": parts[0], "content": parts[1] }))
-        } else {
-            println!("❌ Usage: /file_write <path> <content>");
-            None
-        }
-    }
-
-    fn parse_process_params(&self, params_str: &str) -> Option<serde_json::Value> {
-        let parts: Vec<&str> = params_str.split_whitespace().collect();
-        if parts.is_empty() {
-            println!("❌ Usage: /process <command> [args...]");
-            None
-        } else {
-            let command = parts[0];
-            let args = if parts.len() > 1 {
-                Some(parts[1..].to_vec())
-            } else {
-                None
-            };
-            Some(json!({ "command": command, "args": args }))
-        }
-    }
-
-    fn parse_pmat_params(&self, params_str: &str) -> Option<serde_json::Value> {
-        let parts: Vec<&str> = params_str.split_whitespace().collect();
-        if parts.len() < 2 {
-            println!("❌ Usage: /pmat <command> <path>");
-            println!("   Commands: complexity, satd, coverage, tdg");
-            None
-        } else {
-            Some(json!({ "command": parts[0], "path": parts[1] }))
-        }
-    }
-
-    fn parse_dev_cli_params(&self, params_str: &str) -> Option<serde_json::Value> {
-        let parts: Vec<&str> = params_str.split_whitespace().collect();
-        if parts.is_empty() {
-            println!("❌ Usage: /dev_cli <tool> [args...]");
-            println!("   Tools: rg, fd, cargo, git, make, etc.");
-            None
-        } else {
-            Some(json!({
-                "tool": parts[0],
-                "args": parts[1..].to_vec()
-            }))
-        }
-    }
-
-    fn parse_fix_params(&self, params_str: &str) -> Option<serde_json::Value> {
-        let parts: Vec<&str> = params_str.split_whitespace().collect();
-        if parts.len() < 2 {
-            println!("❌ Usage: /fix <type> <path> [--dry-run]");
-            println!("   Types: complexity, format, lint");
-            None
-        } else {
-            let dry_run = parts.get(2).is_some_and(|&s| s == "--dry-run");
-            Some(json!({
-                "fix_type": parts[0],
-                "path": parts[1],
-                "dry_run": dry_run
-            }))
-        }
-    }
-
-    fn parse_tool_params(
-        &self,
-        tool_name: &str,
-        params_str: &str,
-    ) -> Result<Option<serde_json::Value>> {
-        match tool_name {
-            "file_read" => Ok(Some(json!({ "path": params_str }))),
-            "file_write" => Ok(self.parse_file_write_params(params_str)),
-            "process" => Ok(self.parse_process_params(params_str)),
-            "llm" => Ok(Some(json!({ "prompt": params_str }))),
-            "token_estimate" => Ok(Some(json!({ "text": params_str }))),
-            "pmat" => Ok(self.parse_pmat_params(params_str)),
-            "bash" => Ok(Some(json!({ "command": params_str }))),
-            "dev_cli" => Ok(self.parse_dev_cli_params(params_str)),
-            "fix" => Ok(self.parse_fix_params(params_str)),
-            _ => {
-                println!("❌ Unknown parameter format for tool: {}", tool_name);
-                Ok(None)
-            }
-        }
-    }
-
     async fn execute_tool(&self, tool_name: &str, params: serde_json::Value) -> Result<()> {
         let request = ToolRequest {
             tool: tool_name.to_string(),
@@ -338,7 +295,13 @@ impl InteractiveChat {
             Some(serde_json::from_str(params_str)?)
         } else {
             // Simple parameter handling
-            self.parse_tool_params(tool_name, params_str)?
+            match params::parse_tool_params(tool_name, params_str) {
+                Ok(params) => Some(params),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    None
+                }
+            }
         };
 
         if let Some(params) = params {
@@ -361,10 +324,27 @@ impl InteractiveChat {
         println!("  /process <command>              - Execute a command");
         println!("  /llm <prompt>                   - Query the LLM (requires API key)");
         println!("  /token_estimate <text>          - Estimate token count");
-        println!("  /pmat <command> <path>          - Run PMAT analysis (complexity, satd, coverage, tdg)");
+        println!(
+            "  /pmat <command> <path>          - Run PMAT analysis (complexity, satd, tdg, big-o)"
+        );
         println!("  /bash <command>                 - Execute bash commands");
         println!("  /dev_cli <tool> [args...]       - Run dev tools (rg, cargo, git, etc.)");
         println!("  /fix <type> <path> [--dry-run] - Fix code issues (complexity, format, lint)");
+        println!("  /gate <path>                    - Run the quality gate (complexity, satd, coverage, clippy, fmt)");
+        println!("  /testgen <path>                 - Generate tests for uncovered functions (requires API key)");
+        println!("  /trends [commits]               - Show metric trends and regressions against the branch base");
+        println!("  /deps [path]                    - Analyze dependencies (duplicates, heaviest subtrees, unused)");
+        println!("  /symbols [lookup|outline] <q>   - Find symbols by fuzzy name, exact name or per-file outline");
+        println!("  /definition <path>:<line>:<col> - Go to definition via the language server (also /references, /hover)");
+        println!("  /workspace_symbols <query>      - Search symbols through the language servers");
+        println!("  /rename <old|path:line:col> <new> [--dry-run] - Rename a symbol across files");
+        println!(
+            "  /replace <regex> <repl> [glob...] [--dry-run] - Search and replace across files"
+        );
+        println!("  /ast_search <pattern> [--lang <l>] [--inside <p>] [--rewrite <t>] [--dry-run] - Structural search and rewrite");
+        println!(
+            "  /diagnostics [path]             - Errors and warnings from the language servers"
+        );
         println!();
         println!("💡 Tips:");
         println!("  - Use Tab for command completion");
@@ -411,4 +391,61 @@ mod tests {
         let chat = InteractiveChat::new(registry);
         assert_eq!(chat.history_file, ".pcode_history");
     }
+
+    #[tokio::test]
+    async fn test_prompt_includes_repository_map() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub struct WorkspaceOnly;\n").unwrap();
+        std::fs::write(dir.path().join("main.rs"), "use lib::WorkspaceOnly;\n").unwrap();
+        let mut chat = InteractiveChat::new(ToolRegistry::new());
+        chat.workspace = dir.path().to_path_buf();
+
+        chat.config.repo_map_tokens = 200;
+        let prompt = chat.build_enhanced_prompt("what is this?", &[]).await;
+        assert!(prompt.starts_with(SYSTEM_PROMPT));
+        assert!(prompt.contains("Context:\nRepository map (2 source files"));
+        assert!(prompt.contains("lib.rs:\n  pub struct WorkspaceOnly;"));
+        assert!(prompt.ends_with("User: what is this?\n\nAssistant:"));
+
+        chat.config.repo_map_tokens = 0;
+        let prompt = chat.build_enhanced_prompt("what is this?", &[]).await;
+        assert!(!prompt.contains("Context:"));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_lists_files_changed_outside() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut chat = InteractiveChat::new(ToolRegistry::new());
+        chat.workspace = dir.path().to_path_buf();
+        chat.config.repo_map_tokens = 0;
+
+        let changed = ["src/lib.rs".to_string(), "README.md".to_string()];
+        let prompt = chat.build_enhanced_prompt("fix it", &changed).await;
+        assert!(prompt.contains(
+            "Context:\nFiles changed outside pcode since the last turn; re-read them before editing:\n- src/lib.rs\n- README.md\n\nUser: fix it"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_changes_stay_queued_until_delivered() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let watcher = WorkspaceWatcher::start(dir.path().to_path_buf(), None).unwrap();
+        let mut chat = InteractiveChat::new(ToolRegistry::new()).with_watcher(watcher);
+        chat.workspace = dir.path().to_path_buf();
+        chat.config = Config::from_vars(|_| None);
+        chat.config.repo_map_tokens = 0;
+        let queued = || chat.watcher.as_ref().unwrap().take_changes();
+
+        let changed = vec!["src/lib.rs".to_string()];
+        chat.watcher.as_ref().unwrap().requeue(changed.clone());
+        chat.process_input("/file_read missing.rs").await.unwrap();
+        chat.process_input("what tools are there?").await.unwrap();
+        assert_eq!(queued(), changed);
+
+        // Without an `llm` tool the request fails, so nothing is delivered
+        chat.watcher.as_ref().unwrap().requeue(changed.clone());
+        chat.config.ai_studio_api_key = Some("key".to_string());
+        chat.process_input("fix it").await.unwrap();
+        assert_eq!(queued(), changed);
+    }
 }
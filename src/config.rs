@@ -3,12 +3,23 @@ use std::env;
 #[derive(Debug, Clone)]
 pub struct Config {
     pub ai_studio_api_key: Option<String>,
+    /// Token budget of the repository map sent with prompts; 0 disables it
+    pub repo_map_tokens: usize,
 }
 
+/// Repository map budget, in tokens, when `PCODE_REPO_MAP_TOKENS` is unset
+pub const DEFAULT_REPO_MAP_TOKENS: usize = 1024;
+
 impl Config {
     pub fn from_env() -> Self {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// [`Self::from_env`] over any source of variables.
+    pub fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Self {
         Self {
-            ai_studio_api_key: env::var("AI_STUDIO_API_KEY").ok(),
+            ai_studio_api_key: lookup("AI_STUDIO_API_KEY"),
+            repo_map_tokens: parse_or(lookup("PCODE_REPO_MAP_TOKENS"), DEFAULT_REPO_MAP_TOKENS),
         }
     }
 
@@ -23,6 +34,55 @@ impl Default for Config {
     }
 }
 
+/// `value` parsed, or `default` when it is unset or invalid.
+fn parse_or<T: std::str::FromStr>(value: Option<String>, default: T) -> T {
+    value.and_then(|v| v.trim().parse().ok()).unwrap_or(default)
+}
+
+/// Limits enforced by the quality gate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityThresholds {
+    /// Highest allowed cyclomatic complexity per function
+    pub max_complexity: u32,
+    /// Highest allowed cognitive complexity per function
+    pub max_cognitive: u32,
+    /// Lowest allowed line coverage, in percent
+    pub min_coverage: f64,
+    /// Most self-admitted debt comments allowed
+    pub max_satd: usize,
+}
+
+impl QualityThresholds {
+    /// Read `PCODE_MAX_COMPLEXITY`, `PCODE_MAX_COGNITIVE`,
+    /// `PCODE_MIN_COVERAGE` and `PCODE_MAX_SATD`, keeping the QUALITY.md
+    /// targets for unset or invalid values.
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// [`Self::from_env`] over any source of variables.
+    pub fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let defaults = Self::default();
+        Self {
+            max_complexity: parse_or(lookup("PCODE_MAX_COMPLEXITY"), defaults.max_complexity),
+            max_cognitive: parse_or(lookup("PCODE_MAX_COGNITIVE"), defaults.max_cognitive),
+            min_coverage: parse_or(lookup("PCODE_MIN_COVERAGE"), defaults.min_coverage),
+            max_satd: parse_or(lookup("PCODE_MAX_SATD"), defaults.max_satd),
+        }
+    }
+}
+
+impl Default for QualityThresholds {
+    fn default() -> Self {
+        Self {
+            max_complexity: 20,
+            max_cognitive: 15,
+            min_coverage: 80.0,
+            max_satd: 0,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -33,4 +93,28 @@ mod tests {
         // Just verify it doesn't panic
         let _ = config.has_api_key();
     }
+
+    #[test]
+    fn test_repo_map_budget_from_vars() {
+        let config =
+            Config::from_vars(|name| (name == "PCODE_REPO_MAP_TOKENS").then(|| "0".to_string()));
+        assert_eq!(config.repo_map_tokens, 0);
+        assert!(!config.has_api_key());
+
+        let config = Config::from_vars(|_| Some("lots".to_string()));
+        assert_eq!(config.repo_map_tokens, DEFAULT_REPO_MAP_TOKENS);
+    }
+
+    #[test]
+    fn test_quality_thresholds_from_vars() {
+        let thresholds = QualityThresholds::from_vars(|name| match name {
+            "PCODE_MAX_COMPLEXITY" => Some(" 12".to_string()),
+            "PCODE_MIN_COVERAGE" => Some("not a number".to_string()),
+            _ => None,
+        });
+
+        assert_eq!(thresholds.max_complexity, 12);
+        assert_eq!(thresholds.min_coverage, 80.0);
+        assert_eq!(thresholds.max_satd, 0);
+    }
 }
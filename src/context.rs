@@ -16,31 +16,6 @@ When users ask about the project or codebase, you can reference:
 
 Be helpful, concise, and technical. Focus on practical assistance with coding tasks."#;
 
-pub const PROJECT_CONTEXT: &str = r#"pcode Project Information:
-
-Architecture:
-- Built with Rust and Tokio async runtime
-- Uses MCP (Model Context Protocol) for tool communication
-- Cap'n Proto for efficient serialization
-- Platform-specific security sandboxing
-- Token estimation with perfect hash tables
-
-Available Tools:
-- file_read: Read file contents with optional offset/limit
-- file_write: Write content to files
-- process: Execute system commands with timeout
-- llm: Query language models (requires API key)
-- token_estimate: Estimate token count for text
-
-Quality Standards (PMAT):
-- Cyclomatic complexity: ≤20 per function
-- Test coverage: ≥80% (currently ~80.9%)
-- Technical debt: Zero tolerance
-- Algorithm complexity: O(n) or better
-- Binary size: <12MB target
-
-The project is open source (MIT license) and designed for production use with extreme performance and security requirements."#;
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -61,68 +36,20 @@ mod tests {
         assert!(SYSTEM_PROMPT.contains("QUALITY.md"));
     }
 
-    #[test]
-    fn test_project_context_contains_architecture_info() {
-        // Verify PROJECT_CONTEXT contains architecture details
-        assert!(PROJECT_CONTEXT.contains("pcode Project Information"));
-        assert!(PROJECT_CONTEXT.contains("Rust and Tokio"));
-        assert!(PROJECT_CONTEXT.contains("MCP (Model Context Protocol)"));
-        assert!(PROJECT_CONTEXT.contains("Cap'n Proto"));
-        assert!(PROJECT_CONTEXT.contains("security sandboxing"));
-        assert!(PROJECT_CONTEXT.contains("perfect hash tables"));
-    }
-
-    #[test]
-    fn test_project_context_lists_all_tools() {
-        // Verify all tools are documented
-        assert!(PROJECT_CONTEXT.contains("file_read"));
-        assert!(PROJECT_CONTEXT.contains("file_write"));
-        assert!(PROJECT_CONTEXT.contains("process"));
-        assert!(PROJECT_CONTEXT.contains("llm"));
-        assert!(PROJECT_CONTEXT.contains("token_estimate"));
-        assert!(PROJECT_CONTEXT.contains("optional offset/limit"));
-        assert!(PROJECT_CONTEXT.contains("Execute system commands"));
-        assert!(PROJECT_CONTEXT.contains("requires API key"));
-    }
-
-    #[test]
-    fn test_project_context_quality_standards() {
-        // Verify PMAT standards are documented
-        assert!(PROJECT_CONTEXT.contains("Quality Standards (PMAT)"));
-        assert!(PROJECT_CONTEXT.contains("Cyclomatic complexity: ≤20"));
-        assert!(PROJECT_CONTEXT.contains("Test coverage: ≥80%"));
-        assert!(PROJECT_CONTEXT.contains("currently ~80.9%"));
-        assert!(PROJECT_CONTEXT.contains("Technical debt: Zero tolerance"));
-        assert!(PROJECT_CONTEXT.contains("O(n) or better"));
-        assert!(PROJECT_CONTEXT.contains("<12MB target"));
-    }
-
-    #[test]
-    fn test_project_context_license_info() {
-        // Verify license and usage info
-        assert!(PROJECT_CONTEXT.contains("open source"));
-        assert!(PROJECT_CONTEXT.contains("MIT license"));
-        assert!(PROJECT_CONTEXT.contains("production use"));
-        assert!(PROJECT_CONTEXT.contains("extreme performance"));
-    }
-
     #[test]
     fn test_prompts_are_not_empty() {
         // Basic sanity checks - these are compile-time constants
         // so we just verify they have reasonable length
         assert!(SYSTEM_PROMPT.len() > 100);
-        assert!(PROJECT_CONTEXT.len() > 100);
     }
 
     #[test]
     fn test_prompts_formatting() {
         // Verify prompts are properly formatted
         assert!(SYSTEM_PROMPT.starts_with("You are pcode"));
-        assert!(PROJECT_CONTEXT.starts_with("pcode Project Information:"));
 
         // Check for proper line breaks and formatting
         assert!(SYSTEM_PROMPT.contains('\n'));
-        assert!(PROJECT_CONTEXT.contains('\n'));
-        assert!(PROJECT_CONTEXT.contains("- "));
+        assert!(SYSTEM_PROMPT.contains("- "));
     }
 }
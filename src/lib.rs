@@ -1,11 +1,14 @@
+pub mod analysis;
 pub mod chat;
 pub mod config;
 pub mod context;
+pub mod lsp;
 pub mod mcp;
 pub mod runtime;
 pub mod security;
 pub mod token_estimation;
 pub mod tools;
+pub mod watcher;
 
 pub use mcp::McpProtocol;
 pub use runtime::Runtime;
@@ -22,6 +25,9 @@ pub enum PcodeError {
     #[error("MCP protocol error: {0}")]
     Mcp(#[from] mcp::McpError),
 
+    #[error("LSP error: {0}")]
+    Lsp(#[from] lsp::LspError),
+
     #[error("Tool error: {0}")]
     Tool(#[from] tools::ToolError),
 
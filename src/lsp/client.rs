@@ -0,0 +1,554 @@
+use super::config::{language_id, ServerConfig};
+use super::transport::{read_message, write_message};
+use super::{path_to_uri, LspError, Position};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::BufReader;
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{oneshot, Notify};
+use tokio::time::{timeout, Instant};
+use tracing::{debug, info, warn};
+
+/// How long a request may wait for its response. Servers answer slowly
+/// while they index a workspace for the first time.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+type Reply = Result<Value, LspError>;
+type Pending = Arc<Mutex<HashMap<i64, oneshot::Sender<Reply>>>>;
+
+/// Diagnostics as last published for each document, with a count of
+/// publications so callers can wait for the next one.
+#[derive(Debug, Default)]
+struct DiagnosticStore {
+    by_uri: HashMap<String, Vec<Value>>,
+    published: HashMap<String, u64>,
+}
+
+impl DiagnosticStore {
+    fn generation(&self, uri: &str) -> u64 {
+        self.published.get(uri).copied().unwrap_or(0)
+    }
+}
+
+/// State shared with the task reading the server's output.
+#[derive(Clone)]
+struct Shared {
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+    /// Set, under the `pending` lock, once the server's output has ended
+    closed: Arc<AtomicBool>,
+    diagnostics: Arc<Mutex<DiagnosticStore>>,
+    published: Arc<Notify>,
+}
+
+impl Shared {
+    async fn send(&self, message: Value) -> Result<(), LspError> {
+        let mut stdin = self.stdin.lock().await;
+        write_message(&mut *stdin, &message)
+            .await
+            .map_err(|_| LspError::Exited)
+    }
+
+    async fn dispatch(&self, message: Value) {
+        let id = message.get("id").cloned();
+        match (message.get("method").and_then(|m| m.as_str()), id) {
+            (Some(method), Some(id)) => {
+                // Requests from the server; nothing is configured on our side
+                let result = match method {
+                    "workspace/configuration" => {
+                        let items = message["params"]["items"].as_array().map_or(0, Vec::len);
+                        json!(vec![Value::Null; items])
+                    }
+                    _ => Value::Null,
+                };
+                let _ = self
+                    .send(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+                    .await;
+            }
+            (Some("textDocument/publishDiagnostics"), None) => {
+                let params = &message["params"];
+                let Some(uri) = params["uri"].as_str() else {
+                    return;
+                };
+                let diagnostics = params["diagnostics"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                {
+                    let mut store = self.diagnostics.lock().unwrap();
+                    store.by_uri.insert(uri.to_string(), diagnostics);
+                    *store.published.entry(uri.to_string()).or_default() += 1;
+                }
+                self.published.notify_waiters();
+            }
+            (Some(method), None) => debug!("Language server notification: {}", method),
+            (None, Some(id)) => {
+                let Some(id) = id.as_i64() else {
+                    return;
+                };
+                let Some(reply) = self.pending.lock().unwrap().remove(&id) else {
+                    return;
+                };
+                let result = match message.get("error") {
+                    Some(error) => Err(LspError::Server {
+                        code: error["code"].as_i64().unwrap_or(0),
+                        message: error["message"].as_str().unwrap_or("").to_string(),
+                    }),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                let _ = reply.send(result);
+            }
+            (None, None) => warn!("Malformed message from language server"),
+        }
+    }
+
+    async fn read_loop(self, mut stdout: BufReader<ChildStdout>) {
+        loop {
+            match read_message(&mut stdout).await {
+                Ok(Some(message)) => self.dispatch(message).await,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Language server stream error: {}", e);
+                    break;
+                }
+            }
+        }
+        let mut pending = self.pending.lock().unwrap();
+        self.closed.store(true, Ordering::SeqCst);
+        for (_, reply) in pending.drain() {
+            let _ = reply.send(Err(LspError::Exited));
+        }
+    }
+}
+
+/// An open document: the version and text the server was last sent.
+#[derive(Debug)]
+struct Document {
+    version: i64,
+    text: String,
+}
+
+/// A running language server.
+pub struct LspClient {
+    config: ServerConfig,
+    child: Mutex<Child>,
+    shared: Shared,
+    next_id: AtomicI64,
+    documents: tokio::sync::Mutex<HashMap<PathBuf, Document>>,
+    capabilities: Value,
+}
+
+impl LspClient {
+    /// Launch the server for `config` in `root` and complete the
+    /// initialize handshake.
+    pub async fn start(config: &ServerConfig, root: &Path) -> Result<Self, LspError> {
+        let mut cmd = Command::new(&config.command);
+        cmd.args(&config.args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+
+        // Servers build and cache inside the workspace, so it stays writable
+        #[cfg(target_os = "linux")]
+        if let Some(jail) = crate::security::jail::NamespaceJail::for_active_policy() {
+            jail.with_writable(root).apply(&mut cmd);
+        }
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| LspError::Spawn(format!("{}: {}", config.command, e)))?;
+        let stdin = child.stdin.take().ok_or(LspError::Exited)?;
+        let stdout = child.stdout.take().ok_or(LspError::Exited)?;
+
+        let shared = Shared {
+            stdin: Arc::new(tokio::sync::Mutex::new(stdin)),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            closed: Arc::new(AtomicBool::new(false)),
+            diagnostics: Arc::new(Mutex::new(DiagnosticStore::default())),
+            published: Arc::new(Notify::new()),
+        };
+        tokio::spawn(shared.clone().read_loop(BufReader::new(stdout)));
+
+        let mut client = Self {
+            config: config.clone(),
+            child: Mutex::new(child),
+            shared,
+            next_id: AtomicI64::new(1),
+            documents: tokio::sync::Mutex::new(HashMap::new()),
+            capabilities: Value::Null,
+        };
+        client.initialize(root).await.map_err(|e| match e {
+            LspError::Exited => {
+                LspError::Spawn(format!("{} exited during initialization", config.command))
+            }
+            e => e,
+        })?;
+        info!(
+            "Started {} language server: {}",
+            config.language, config.command
+        );
+        Ok(client)
+    }
+
+    async fn initialize(&mut self, root: &Path) -> Result<(), LspError> {
+        let uri = path_to_uri(root);
+        let name = root
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "processId": std::process::id(),
+                    "clientInfo": { "name": "pcode", "version": env!("CARGO_PKG_VERSION") },
+                    "rootUri": uri,
+                    "rootPath": root,
+                    "workspaceFolders": [{ "uri": uri, "name": name }],
+                    "capabilities": {
+                        "general": { "positionEncodings": ["utf-16"] },
+                        "textDocument": {
+                            "synchronization": { "didSave": false },
+                            "definition": { "linkSupport": true },
+                            "references": {},
+                            "hover": { "contentFormat": ["markdown", "plaintext"] },
+                            "publishDiagnostics": { "versionSupport": true }
+                        },
+                        "workspace": {
+                            "symbol": {},
+                            "workspaceFolders": true,
+                            "configuration": true
+                        }
+                    }
+                }),
+            )
+            .await?;
+        self.capabilities = result["capabilities"].clone();
+        self.notify("initialized", json!({})).await
+    }
+
+    pub fn language(&self) -> &str {
+        &self.config.language
+    }
+
+    /// What the server said it supports in its initialize result.
+    pub fn capabilities(&self) -> &Value {
+        &self.capabilities
+    }
+
+    pub fn is_alive(&self) -> bool {
+        matches!(self.child.lock().unwrap().try_wait(), Ok(None))
+    }
+
+    /// Send a request and wait for its result.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, LspError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (reply, response) = oneshot::channel();
+        {
+            let mut pending = self.shared.pending.lock().unwrap();
+            if self.shared.closed.load(Ordering::SeqCst) {
+                return Err(LspError::Exited);
+            }
+            pending.insert(id, reply);
+        }
+        debug!("Language server request {}: {}", id, method);
+
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if let Err(e) = self.shared.send(message).await {
+            self.shared.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+        match timeout(REQUEST_TIMEOUT, response).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(LspError::Exited),
+            Err(_) => {
+                self.shared.pending.lock().unwrap().remove(&id);
+                let _ = self.notify("$/cancelRequest", json!({ "id": id })).await;
+                Err(LspError::Timeout(method.to_string()))
+            }
+        }
+    }
+
+    pub async fn notify(&self, method: &str, params: Value) -> Result<(), LspError> {
+        self.shared
+            .send(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+            .await
+    }
+
+    /// Bring the server's copy of `path` in line with the file on disk,
+    /// opening it first if needed. Returns whether anything was sent.
+    pub async fn sync(&self, path: &Path) -> Result<bool, LspError> {
+        let text = tokio::fs::read_to_string(path).await?;
+        let uri = path_to_uri(path);
+        let mut documents = self.documents.lock().await;
+        match documents.get_mut(path) {
+            Some(document) if document.text == text => Ok(false),
+            Some(document) => {
+                document.version += 1;
+                let params = json!({
+                    "textDocument": { "uri": uri, "version": document.version },
+                    "contentChanges": [{ "text": text }]
+                });
+                document.text = text;
+                self.notify("textDocument/didChange", params).await?;
+                Ok(true)
+            }
+            None => {
+                let params = json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": language_id(path, &self.config),
+                        "version": 1,
+                        "text": text
+                    }
+                });
+                documents.insert(path.to_path_buf(), Document { version: 1, text });
+                self.notify("textDocument/didOpen", params).await?;
+                Ok(true)
+            }
+        }
+    }
+
+    async fn at_position(
+        &self,
+        method: &str,
+        path: &Path,
+        position: Position,
+        extra: Value,
+    ) -> Result<Value, LspError> {
+        self.sync(path).await?;
+        let mut params = json!({
+            "textDocument": { "uri": path_to_uri(path) },
+            "position": position
+        });
+        if let (Some(params), Some(extra)) = (params.as_object_mut(), extra.as_object()) {
+            params.extend(extra.clone());
+        }
+        self.request(method, params).await
+    }
+
+    /// Re-send open documents whose files changed on disk since the server
+    /// last saw them, e.g. after edits applied from a `WorkspaceEdit`.
+    pub async fn resync(&self) -> Result<(), LspError> {
+        let paths: Vec<PathBuf> = self.documents.lock().await.keys().cloned().collect();
+        for path in paths.iter().filter(|p| p.is_file()) {
+            self.sync(path).await?;
+        }
+        Ok(())
+    }
+
+    /// Tell the server that `paths` changed on disk: open documents are
+    /// re-sent and every path is announced with `didChangeWatchedFiles`.
+    pub async fn files_changed(&self, paths: &[PathBuf]) -> Result<(), LspError> {
+        self.resync().await?;
+        let changes: Vec<Value> = paths
+            .iter()
+            .map(|path| {
+                // 2 = Changed, 3 = Deleted; new files are reported as changed
+                let kind = if path.exists() { 2 } else { 3 };
+                json!({ "uri": path_to_uri(path), "type": kind })
+            })
+            .collect();
+        self.notify(
+            "workspace/didChangeWatchedFiles",
+            json!({ "changes": changes }),
+        )
+        .await
+    }
+
+    /// `Location`, `Location[]`, `LocationLink[]` or null.
+    pub async fn definition(&self, path: &Path, position: Position) -> Result<Value, LspError> {
+        self.at_position("textDocument/definition", path, position, json!({}))
+            .await
+    }
+
+    pub async fn references(
+        &self,
+        path: &Path,
+        position: Position,
+        include_declaration: bool,
+    ) -> Result<Value, LspError> {
+        let context = json!({ "context": { "includeDeclaration": include_declaration } });
+        self.at_position("textDocument/references", path, position, context)
+            .await
+    }
+
+    pub async fn hover(&self, path: &Path, position: Position) -> Result<Value, LspError> {
+        self.at_position("textDocument/hover", path, position, json!({}))
+            .await
+    }
+
+    /// The `WorkspaceEdit` renaming the symbol at `position`, or null.
+    pub async fn rename(
+        &self,
+        path: &Path,
+        position: Position,
+        new_name: &str,
+    ) -> Result<Value, LspError> {
+        let new_name = json!({ "newName": new_name });
+        self.at_position("textDocument/rename", path, position, new_name)
+            .await
+    }
+
+    /// `SymbolInformation[]` or `WorkspaceSymbol[]` matching `query`.
+    pub async fn workspace_symbols(&self, query: &str) -> Result<Value, LspError> {
+        self.request("workspace/symbol", json!({ "query": query }))
+            .await
+    }
+
+    /// Diagnostics for `path`. When the server has just been sent new
+    /// contents, waits up to `wait` for it to publish a fresh report.
+    pub async fn diagnostics(&self, path: &Path, wait: Duration) -> Result<Vec<Value>, LspError> {
+        let uri = path_to_uri(path);
+        let store = &self.shared.diagnostics;
+        let before = store.lock().unwrap().generation(&uri);
+        let changed = self.sync(path).await?;
+
+        if changed || before == 0 {
+            let deadline = Instant::now() + wait;
+            loop {
+                let published = self.shared.published.notified();
+                if store.lock().unwrap().generation(&uri) > before {
+                    break;
+                }
+                if timeout(
+                    deadline.saturating_duration_since(Instant::now()),
+                    published,
+                )
+                .await
+                .is_err()
+                {
+                    break;
+                }
+            }
+        }
+        let diagnostics = store.lock().unwrap().by_uri.get(&uri).cloned();
+        Ok(diagnostics.unwrap_or_default())
+    }
+
+    /// Every non-empty report published so far, by document URI.
+    pub fn published_diagnostics(&self) -> Vec<(String, Vec<Value>)> {
+        let store = self.shared.diagnostics.lock().unwrap();
+        let mut reports: Vec<_> = store
+            .by_uri
+            .iter()
+            .filter(|(_, diagnostics)| !diagnostics.is_empty())
+            .map(|(uri, diagnostics)| (uri.clone(), diagnostics.clone()))
+            .collect();
+        reports.sort_by(|a, b| a.0.cmp(&b.0));
+        reports
+    }
+
+    /// Ask the server to shut down and exit.
+    pub async fn shutdown(&self) -> Result<(), LspError> {
+        self.request("shutdown", Value::Null).await?;
+        self.notify("exit", Value::Null).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::fake_server;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_requests_against_fake_server() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("app.py");
+        std::fs::write(&file, "def greet(name):\n    return name\n\ngreet('x')\n").unwrap();
+        let client = LspClient::start(&fake_server::config(&dir), dir.path())
+            .await
+            .unwrap();
+        assert_eq!(client.capabilities()["hoverProvider"], true);
+
+        let call = Position {
+            line: 3,
+            character: 1,
+        };
+        let definition = client.definition(&file, call).await.unwrap();
+        assert_eq!(definition["uri"], path_to_uri(&file));
+        assert_eq!(
+            definition["range"]["start"],
+            json!({"line": 0, "character": 4})
+        );
+
+        let references = client.references(&file, call, true).await.unwrap();
+        assert_eq!(references.as_array().unwrap().len(), 2);
+        let hover = client.hover(&file, call).await.unwrap();
+        assert_eq!(hover["contents"]["value"], "def greet(name):");
+        let symbols = client.workspace_symbols("gre").await.unwrap();
+        assert_eq!(symbols[0]["name"], "greet");
+
+        let err = client
+            .request("textDocument/formatting", json!({}))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, LspError::Server { code: -32601, .. }),
+            "{}",
+            err
+        );
+
+        client.shutdown().await.unwrap();
+        let err = client.hover(&file, call).await.unwrap_err();
+        assert!(matches!(err, LspError::Exited), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_document_sync_and_diagnostics() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("todo.py");
+        std::fs::write(&file, "x = 1  # TODO: name\n").unwrap();
+        let client = LspClient::start(&fake_server::config(&dir), dir.path())
+            .await
+            .unwrap();
+        let wait = Duration::from_secs(5);
+
+        let diagnostics = client.diagnostics(&file, wait).await.unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["range"]["start"]["character"], 9);
+        assert!(!client.sync(&file).await.unwrap());
+
+        std::fs::write(&file, "x = 1\n").unwrap();
+        assert!(client.diagnostics(&file, wait).await.unwrap().is_empty());
+        assert_eq!(client.documents.lock().await[&file].version, 2);
+        assert!(client.published_diagnostics().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_files_changed_outside_documents() {
+        let dir = TempDir::new().unwrap();
+        let old = dir.path().join("old.py");
+        std::fs::write(&old, "def stale():\n    pass\n").unwrap();
+        let client = LspClient::start(&fake_server::config(&dir), dir.path())
+            .await
+            .unwrap();
+        let names = |symbols: Value| -> Vec<String> {
+            let symbols = symbols.as_array().cloned().unwrap_or_default();
+            symbols.iter().map(|s| s["name"].to_string()).collect()
+        };
+        assert_eq!(names(client.workspace_symbols("").await.unwrap()).len(), 1);
+
+        let new = dir.path().join("new.py");
+        std::fs::write(&new, "def fresh():\n    pass\n").unwrap();
+        std::fs::remove_file(&old).unwrap();
+        assert_eq!(
+            names(client.workspace_symbols("").await.unwrap()),
+            ["\"stale\""]
+        );
+
+        client.files_changed(&[new, old]).await.unwrap();
+        assert_eq!(
+            names(client.workspace_symbols("").await.unwrap()),
+            ["\"fresh\""]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::Path;
+
+/// How to launch the language server for one language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    /// Name used in tool parameters and for `PCODE_LSP_<LANGUAGE>`
+    pub language: String,
+    pub command: String,
+    pub args: Vec<String>,
+    /// File extensions the server handles
+    pub extensions: Vec<String>,
+    /// Files at the workspace root that show the language is in use
+    pub markers: Vec<String>,
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+impl ServerConfig {
+    pub fn new(language: &str, command: &str, args: &[&str], extensions: &[&str]) -> Self {
+        Self {
+            language: language.to_string(),
+            command: command.to_string(),
+            args: strings(args),
+            extensions: strings(extensions),
+            markers: Vec::new(),
+        }
+    }
+
+    fn with_markers(mut self, markers: &[&str]) -> Self {
+        self.markers = strings(markers);
+        self
+    }
+
+    /// rust-analyzer, pyright and typescript-language-server.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new("rust", "rust-analyzer", &[], &["rs"]).with_markers(&["Cargo.toml"]),
+            Self::new("python", "pyright-langserver", &["--stdio"], &["py", "pyi"]).with_markers(
+                &[
+                    "pyproject.toml",
+                    "setup.py",
+                    "requirements.txt",
+                    "pyrightconfig.json",
+                ],
+            ),
+            Self::new(
+                "typescript",
+                "typescript-language-server",
+                &["--stdio"],
+                &["ts", "tsx", "mts", "cts", "js", "jsx", "mjs", "cjs"],
+            )
+            .with_markers(&["package.json", "tsconfig.json", "jsconfig.json"]),
+        ]
+    }
+
+    /// The defaults, each replaced by the command line in
+    /// `PCODE_LSP_<LANGUAGE>` when that is set (e.g.
+    /// `PCODE_LSP_PYTHON="pylsp"`). An empty value disables the language.
+    pub fn from_env() -> Vec<Self> {
+        Self::defaults()
+            .into_iter()
+            .filter_map(|config| {
+                let var = format!("PCODE_LSP_{}", config.language.to_uppercase());
+                match std::env::var(var) {
+                    Ok(line) => config.with_command_line(&line),
+                    Err(_) => Some(config),
+                }
+            })
+            .collect()
+    }
+
+    fn with_command_line(mut self, line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace().map(str::to_string);
+        self.command = words.next()?;
+        self.args = words.collect();
+        Some(self)
+    }
+
+    pub fn handles(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| self.extensions.iter().any(|e| e == ext))
+    }
+}
+
+/// The `languageId` sent when opening `path`.
+pub fn language_id(path: &Path, config: &ServerConfig) -> String {
+    let id = match path.extension().and_then(|e| e.to_str()).unwrap_or("") {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        _ => return config.language.clone(),
+    };
+    id.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_for_path() {
+        let servers = ServerConfig::defaults();
+        let server = |path: &str| {
+            servers
+                .iter()
+                .find(|s| s.handles(Path::new(path)))
+                .map(|s| s.command.as_str())
+        };
+        assert_eq!(server("src/main.rs"), Some("rust-analyzer"));
+        assert_eq!(server("app/models.py"), Some("pyright-langserver"));
+        assert_eq!(server("web/App.tsx"), Some("typescript-language-server"));
+        assert_eq!(server("README.md"), None);
+
+        let ts = &servers[2];
+        assert_eq!(language_id(Path::new("App.tsx"), ts), "typescriptreact");
+        assert_eq!(language_id(Path::new("index.mjs"), ts), "javascript");
+    }
+
+    #[test]
+    fn test_command_line_override() {
+        let python = ServerConfig::defaults().remove(1);
+        let pylsp = python
+            .clone()
+            .with_command_line("pylsp -v --check-parent-process")
+            .unwrap();
+        assert_eq!(pylsp.command, "pylsp");
+        assert_eq!(pylsp.args, vec!["-v", "--check-parent-process"]);
+        assert_eq!(pylsp.extensions, python.extensions);
+        assert!(python.with_command_line("  ").is_none());
+    }
+}
@@ -0,0 +1,159 @@
+//! A minimal language server for tests, run with `python3`.
+//!
+//! It handles Python-looking files: definitions are `def` and `class`
+//! lines, references and renames are whole-word matches in the `.py` files
+//! of the workspace root, and every `TODO` is reported as a warning.
+//! Files announced by `didChangeWatchedFiles` are read again.
+
+use super::ServerConfig;
+use tempfile::TempDir;
+
+pub const SCRIPT: &str = r#"
+import json
+import os
+import re
+import sys
+from urllib.parse import quote, unquote
+
+documents = {}
+stdin, stdout = sys.stdin.buffer, sys.stdout.buffer
+
+
+def read():
+    length = None
+    while True:
+        line = stdin.readline()
+        if not line:
+            return None
+        line = line.strip()
+        if not line:
+            break
+        name, _, value = line.partition(b":")
+        if name.lower() == b"content-length":
+            length = int(value)
+    return json.loads(stdin.read(length))
+
+
+def send(message):
+    message["jsonrpc"] = "2.0"
+    body = json.dumps(message).encode()
+    stdout.write(b"Content-Length: %d\r\n\r\n" % len(body) + body)
+    stdout.flush()
+
+
+def range_of(line, start, end):
+    return {"start": {"line": line, "character": start}, "end": {"line": line, "character": end}}
+
+
+def span(uri, line, start, end):
+    return {"uri": uri, "range": range_of(line, start, end)}
+
+
+def word_at(params):
+    lines = documents[params["textDocument"]["uri"]].split("\n")
+    position = params["position"]
+    for match in re.finditer(r"\w+", lines[position["line"]]):
+        if match.start() <= position["character"] <= match.end():
+            return match.group()
+    return None
+
+
+def matches(pattern):
+    for uri, text in sorted(documents.items()):
+        for number, line in enumerate(text.split("\n")):
+            for match in re.finditer(pattern, line):
+                yield uri, number, line, match
+
+
+def definitions(word):
+    return matches(r"^\s*(?:def|class)\s+(%s)\b" % word)
+
+
+def result(method, params):
+    if method == "initialize":
+        root = unquote(params["rootUri"][len("file://"):])
+        for name in sorted(os.listdir(root)):
+            if name.endswith(".py") and not name.startswith("."):
+                with open(os.path.join(root, name)) as source:
+                    documents["file://" + quote(os.path.join(root, name))] = source.read()
+        return {"capabilities": {"textDocumentSync": 1, "definitionProvider": True,
+                                 "referencesProvider": True, "hoverProvider": True,
+                                 "workspaceSymbolProvider": True}}
+    if method == "shutdown":
+        return None
+    if method == "workspace/symbol":
+        return [{"name": m.group(1), "kind": 12 if "def" in line else 5,
+                 "location": span(uri, number, m.start(1), m.end(1))}
+                for uri, number, line, m in matches(r"^\s*(?:def|class)\s+(\w+)")
+                if params["query"].lower() in m.group(1).lower()]
+    word = word_at(params)
+    if word is None:
+        return None
+    if method == "textDocument/definition":
+        for uri, number, line, m in definitions(re.escape(word)):
+            return span(uri, number, m.start(1), m.end(1))
+        return None
+    if method == "textDocument/references":
+        return [span(uri, number, m.start(), m.end())
+                for uri, number, line, m in matches(r"\b%s\b" % re.escape(word))]
+    if method == "textDocument/rename":
+        changes = {}
+        for uri, number, line, m in matches(r"\b%s\b" % re.escape(word)):
+            edit = {"range": range_of(number, m.start(), m.end()), "newText": params["newName"]}
+            changes.setdefault(uri, []).append(edit)
+        return {"changes": changes}
+    if method == "textDocument/hover":
+        for uri, number, line, m in definitions(re.escape(word)):
+            return {"contents": {"kind": "markdown", "value": line.strip()}}
+        return None
+    raise KeyError(method)
+
+
+def publish(uri, version):
+    diagnostics = []
+    for number, line in enumerate(documents[uri].split("\n")):
+        column = line.find("TODO")
+        if column >= 0:
+            diagnostics.append({"range": range_of(number, column, column + 4), "severity": 2,
+                                "source": "fake", "code": "todo", "message": "Unfinished work"})
+    send({"method": "textDocument/publishDiagnostics",
+          "params": {"uri": uri, "version": version, "diagnostics": diagnostics}})
+
+
+while True:
+    message = read()
+    if message is None or message.get("method") == "exit":
+        break
+    method, params = message.get("method"), message.get("params") or {}
+    if method == "initialized":
+        send({"id": "config", "method": "workspace/configuration", "params": {"items": [{"section": "fake"}]}})
+    elif method == "textDocument/didOpen":
+        document = params["textDocument"]
+        documents[document["uri"]] = document["text"]
+        publish(document["uri"], document["version"])
+    elif method == "textDocument/didChange":
+        document = params["textDocument"]
+        documents[document["uri"]] = params["contentChanges"][-1]["text"]
+        publish(document["uri"], document["version"])
+    elif method == "workspace/didChangeWatchedFiles":
+        for change in params["changes"]:
+            if change["type"] == 3:
+                documents.pop(change["uri"], None)
+            else:
+                with open(unquote(change["uri"][len("file://"):])) as source:
+                    documents[change["uri"]] = source.read()
+    elif method is not None and "id" in message:
+        try:
+            send({"id": message["id"], "result": result(method, params)})
+        except KeyError:
+            send({"id": message["id"], "error": {"code": -32601, "message": "Unhandled method: " + method}})
+"#;
+
+/// Write the fake server into `dir` and configure it for `.py` files.
+pub fn config(dir: &TempDir) -> ServerConfig {
+    let script = dir.path().join(".fake_lsp.py");
+    std::fs::write(&script, SCRIPT).unwrap();
+    let mut config = ServerConfig::new("python", "python3", &[], &["py"]);
+    config.args = vec![script.to_string_lossy().to_string()];
+    config
+}
@@ -0,0 +1,241 @@
+//! Language Server Protocol client.
+//!
+//! Servers are started on demand, one per language, over stdio. Documents
+//! are sent to a server with full-text sync whenever a request touches them,
+//! so the server always sees what is on disk.
+
+pub mod client;
+pub mod config;
+#[cfg(test)]
+pub(crate) mod fake_server;
+pub mod transport;
+
+pub use client::LspClient;
+pub use config::ServerConfig;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::info;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LspError {
+    #[error("No language server configured for {0}")]
+    Unsupported(String),
+
+    #[error("Failed to start language server: {0}")]
+    Spawn(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Server error {code}: {message}")]
+    Server { code: i64, message: String },
+
+    #[error("Request timed out: {0}")]
+    Timeout(String),
+
+    #[error("Language server exited")]
+    Exited,
+}
+
+/// Zero-based line and UTF-16 offset, as the protocol counts them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+/// UTF-16 offset of the `column`th character (zero-based) of `line`.
+pub fn utf16_offset(line: &str, column: usize) -> u32 {
+    line.chars()
+        .take(column)
+        .map(|c| c.len_utf16() as u32)
+        .sum()
+}
+
+/// Character index (zero-based) at UTF-16 offset `offset` of `line`.
+pub fn char_column(line: &str, offset: u32) -> usize {
+    let mut units = 0;
+    for (column, c) in line.chars().enumerate() {
+        if units >= offset {
+            return column;
+        }
+        units += c.len_utf16() as u32;
+    }
+    line.chars().count()
+}
+
+/// Byte offset of `position` in `text`, or `None` past its last line.
+pub fn byte_offset(text: &str, position: Position) -> Option<usize> {
+    let mut start = 0;
+    for _ in 0..position.line {
+        start += text[start..].find('\n')? + 1;
+    }
+    let rest = &text[start..];
+    let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character {
+            return Some(start + i);
+        }
+        units += c.len_utf16() as u32;
+    }
+    Some(start + line.len())
+}
+
+fn unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte)
+}
+
+/// `file://` URI of an absolute path.
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.to_string_lossy().as_bytes() {
+        if unreserved(byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// Path of a `file://` URI; `None` for other schemes.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let escaped = (encoded[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            None => {
+                bytes.push(encoded[i]);
+                i += 1;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// Language servers for one workspace, started the first time a file of
+/// their language is asked about and restarted if they exit.
+pub struct LspManager {
+    workspace: PathBuf,
+    servers: Vec<ServerConfig>,
+    clients: Mutex<HashMap<String, Arc<LspClient>>>,
+}
+
+impl LspManager {
+    /// Servers from `PCODE_LSP_*` or the defaults.
+    pub fn new(workspace: PathBuf) -> Self {
+        Self::with_servers(workspace, ServerConfig::from_env())
+    }
+
+    pub fn with_servers(workspace: PathBuf, servers: Vec<ServerConfig>) -> Self {
+        Self {
+            workspace,
+            servers,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    pub fn languages(&self) -> Vec<&str> {
+        self.servers.iter().map(|s| s.language.as_str()).collect()
+    }
+
+    /// Languages whose marker files (Cargo.toml, package.json, ...) are at
+    /// the workspace root.
+    pub fn detected_languages(&self) -> Vec<&str> {
+        self.servers
+            .iter()
+            .filter(|s| s.markers.iter().any(|m| self.workspace.join(m).exists()))
+            .map(|s| s.language.as_str())
+            .collect()
+    }
+
+    /// The running server for `language`, started if needed.
+    pub async fn client(&self, language: &str) -> Result<Arc<LspClient>, LspError> {
+        let config = self
+            .servers
+            .iter()
+            .find(|s| s.language == language)
+            .ok_or_else(|| LspError::Unsupported(language.to_string()))?;
+
+        let mut clients = self.clients.lock().await;
+        if let Some(client) = clients.get(language) {
+            if client.is_alive() {
+                return Ok(client.clone());
+            }
+            info!("Language server for {} exited, restarting", language);
+        }
+        let client = Arc::new(LspClient::start(config, &self.workspace).await?);
+        clients.insert(language.to_string(), client.clone());
+        Ok(client)
+    }
+
+    /// The server handling `path`, by file extension.
+    pub async fn client_for(&self, path: &Path) -> Result<Arc<LspClient>, LspError> {
+        let config = self
+            .servers
+            .iter()
+            .find(|s| s.handles(path))
+            .ok_or_else(|| LspError::Unsupported(path.display().to_string()))?;
+        self.client(&config.language).await
+    }
+
+    /// Servers started so far that are still running.
+    pub async fn running(&self) -> Vec<Arc<LspClient>> {
+        let clients = self.clients.lock().await;
+        let mut running: Vec<_> = clients.values().filter(|c| c.is_alive()).cloned().collect();
+        running.sort_by(|a, b| a.language().cmp(b.language()));
+        running
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri_round_trip() {
+        let path = Path::new("/tmp/my project/src/naïve#1.rs");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///tmp/my%20project/src/na%C3%AFve%231.rs");
+        assert_eq!(uri_to_path(&uri).unwrap(), path);
+        assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+    }
+
+    #[test]
+    fn test_utf16_columns() {
+        let line = "let 𝜋 = \"é\"; x";
+        // 𝜋 is one character but two UTF-16 units
+        assert_eq!(utf16_offset(line, 4), 4);
+        assert_eq!(utf16_offset(line, 5), 6);
+        assert_eq!(utf16_offset(line, 14), 15);
+        assert_eq!(char_column(line, 6), 5);
+        assert_eq!(char_column(line, 15), 14);
+        assert_eq!(char_column(line, 99), line.chars().count());
+
+        let text = format!("fn f() {{}}\n{}\n", line);
+        let at = |line, character| byte_offset(&text, Position { line, character });
+        assert_eq!(at(0, 3), Some(3));
+        assert_eq!(at(1, 6), Some(text.find(" = ").unwrap()));
+        assert_eq!(at(1, 99), Some(text.len() - 1));
+        assert_eq!(at(2, 0), Some(text.len()));
+        assert_eq!(at(3, 0), None);
+    }
+}
@@ -0,0 +1,112 @@
+//! Base protocol framing: each JSON-RPC message is preceded by a
+//! `Content-Length` header and a blank line.
+
+use serde_json::Value;
+use std::io;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Largest message body accepted from a server.
+const MAX_MESSAGE_BYTES: usize = 64 * 1024 * 1024;
+
+pub async fn write_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &Value,
+) -> io::Result<()> {
+    let body = serde_json::to_vec(message)?;
+    let header = format!("Content-Length: {}\r\n\r\n", body.len());
+    writer.write_all(header.as_bytes()).await?;
+    writer.write_all(&body).await?;
+    writer.flush().await
+}
+
+/// Read the next message, or `None` once the stream ends between messages.
+pub async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            if length.is_none() {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        // Other headers (Content-Type) carry nothing we need
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = Some(value.trim().parse::<usize>().map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Bad Content-Length: {}", e),
+                    )
+                })?);
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Message without Content-Length")
+    })?;
+    if length > MAX_MESSAGE_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Message of {} bytes exceeds the limit", length),
+        ));
+    }
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).await?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::BufReader;
+
+    #[tokio::test]
+    async fn test_message_round_trip() {
+        let mut buffer = Vec::new();
+        let first =
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"é": "ü"}});
+        let second = json!({"jsonrpc": "2.0", "method": "exit"});
+        write_message(&mut buffer, &first).await.unwrap();
+        write_message(&mut buffer, &second).await.unwrap();
+        assert!(buffer.starts_with(b"Content-Length: "));
+
+        let mut reader = BufReader::new(buffer.as_slice());
+        assert_eq!(read_message(&mut reader).await.unwrap(), Some(first));
+        assert_eq!(read_message(&mut reader).await.unwrap(), Some(second));
+        assert_eq!(read_message(&mut reader).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_headers() {
+        let body = r#"{"id":1}"#;
+        let framed = format!(
+            "content-length: {}\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut reader = BufReader::new(framed.as_bytes());
+        assert_eq!(
+            read_message(&mut reader).await.unwrap(),
+            Some(json!({"id": 1}))
+        );
+
+        for input in [
+            "Content-Type: x\r\n\r\n{}",
+            "Content-Length: ten\r\n\r\n",
+            "Content-Length: 10\r\n",
+        ] {
+            let mut reader = BufReader::new(input.as_bytes());
+            assert!(read_message(&mut reader).await.is_err(), "{:?}", input);
+        }
+    }
+}
@@ -1,27 +1,41 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use pcode::{
     chat::InteractiveChat,
     config::Config,
+    lsp::LspManager,
     mcp::{discovery::RobustToolDiscovery, McpProtocol},
     runtime::Runtime,
     security::{SecurityContext, SecurityPolicy},
     tools::{
+        ast_search::AstSearchTool,
         bash::BashTool,
         coverage::CoverageTool,
+        deps::DepsTool,
         dev_cli::DevCliTool,
         file::{FileReadTool, FileWriteTool},
         fix::FixTool,
+        gate::GateTool,
         javascript::JavaScriptTool,
         llm::{LlmTool, TokenEstimateTool},
+        lsp::{DefinitionTool, DiagnosticsTool, HoverTool, ReferencesTool, WorkspaceSymbolsTool},
+        params,
         pmat::PmatTool,
         process::ProcessTool,
         python::PythonTool,
         refactor::RefactorTool,
+        rename::RenameTool,
+        replace::ReplaceTool,
+        runner::{RunnerTool, EXTENDED_LANGUAGES},
+        symbols::SymbolsTool,
+        testgen::TestgenTool,
+        trends::TrendsTool,
         ToolRegistry,
     },
+    watcher::WorkspaceWatcher,
 };
 use std::path::PathBuf;
+use std::sync::Arc;
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 
@@ -49,6 +63,50 @@ struct Args {
 
     #[arg(short, long, help = "Execute a command and exit")]
     command: Option<String>,
+
+    #[command(subcommand)]
+    subcommand: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Check quality thresholds and exit non-zero on violation
+    Gate {
+        #[arg(default_value = ".", help = "Path to check")]
+        path: String,
+
+        #[arg(long, value_delimiter = ',', help = "Checks to run (default: all)")]
+        checks: Vec<String>,
+
+        #[arg(long, help = "Use an existing LCOV or Cobertura report")]
+        coverage_report: Option<String>,
+
+        #[arg(long, help = "Write findings as SARIF 2.1.0 to this file")]
+        sarif: Option<String>,
+
+        #[arg(
+            long,
+            help = "Pass even if a check cannot run, e.g. without a coverage backend"
+        )]
+        allow_skipped: bool,
+    },
+    /// Show how recorded quality metrics moved over recent commits
+    Trends {
+        #[arg(long, default_value = "10", help = "Number of recent commits to show")]
+        limit: usize,
+
+        #[arg(
+            long,
+            help = "Branch to compare against (default: origin/HEAD, main or master)"
+        )]
+        base: Option<String>,
+
+        #[arg(
+            long,
+            help = "Scope the metrics were recorded at (default: latest run's)"
+        )]
+        path: Option<String>,
+    },
 }
 
 fn main() -> Result<()> {
@@ -84,78 +142,17 @@ fn main() -> Result<()> {
     runtime.block_on(async_main(args))
 }
 
-fn parse_tool_params(tool_name: &str, params_str: &str) -> Result<serde_json::Value> {
-    use serde_json::json;
-
-    match tool_name {
-        "file_read" => Ok(json!({ "path": params_str })),
-        "file_write" => {
-            let parts: Vec<&str> = params_str.splitn(2, ' ').collect();
-            if parts.len() == 2 {
-                Ok(json!({ "path": parts[0], "content": parts[1] }))
-            } else {
-                anyhow::bail!("Usage: /file_write <path> <content>");
-            }
-        }
-        "process" => {
-            let parts: Vec<&str> = params_str.split_whitespace().collect();
-            if parts.is_empty() {
-                anyhow::bail!("Usage: /process <command> [args...]");
-            }
-            Ok(json!({
-                "command": parts[0],
-                "args": if parts.len() > 1 { Some(parts[1..].to_vec()) } else { None }
-            }))
-        }
-        "llm" => Ok(json!({ "prompt": params_str })),
-        "token_estimate" => Ok(json!({ "text": params_str })),
-        "pmat" => {
-            let parts: Vec<&str> = params_str.split_whitespace().collect();
-            if parts.len() < 2 {
-                anyhow::bail!("Usage: /pmat <command> <path>");
-            }
-            Ok(json!({ "command": parts[0], "path": parts[1] }))
-        }
-        "bash" => Ok(json!({ "command": params_str })),
-        "dev_cli" => {
-            let parts: Vec<&str> = params_str.split_whitespace().collect();
-            if parts.is_empty() {
-                anyhow::bail!("Usage: /dev_cli <tool> [args...]");
-            }
-            Ok(json!({ "tool": parts[0], "args": parts[1..].to_vec() }))
-        }
-        "fix" => {
-            let parts: Vec<&str> = params_str.split_whitespace().collect();
-            if parts.len() < 2 {
-                anyhow::bail!("Usage: /fix <type> <path> [--dry-run]");
-            }
-            let dry_run = parts.get(2).is_some_and(|&s| s == "--dry-run");
-            Ok(json!({
-                "fix_type": parts[0],
-                "path": parts[1],
-                "dry_run": dry_run
-            }))
-        }
-        "python" => Ok(json!({ "code": params_str })),
-        "javascript" => {
-            // Check if params contain use_deno flag
-            if params_str.ends_with(" --deno") {
-                let code = params_str.trim_end_matches(" --deno");
-                Ok(json!({ "code": code, "use_deno": true }))
-            } else {
-                Ok(json!({ "code": params_str }))
-            }
-        }
-        _ => anyhow::bail!("Unknown tool: {}", tool_name),
-    }
-}
-
 fn format_tool_result(tool_name: &str, result: &serde_json::Value) -> Result<String> {
     if tool_name == "llm" {
         if let Some(text) = result.get("response").and_then(|v| v.as_str()) {
             return Ok(text.to_string());
         }
     }
+    if tool_name == "gate" || tool_name == "trends" {
+        if let Some(table) = result.get("table").and_then(|v| v.as_str()) {
+            return Ok(table.to_string());
+        }
+    }
     serde_json::to_string_pretty(result).map_err(Into::into)
 }
 
@@ -200,7 +197,7 @@ async fn execute_single_command(registry: ToolRegistry, command: &str) -> Result
 
         let tool_name = parts[0];
         let params_str = parts.get(1).unwrap_or(&"{}");
-        let params = parse_tool_params(tool_name, params_str)?;
+        let params = params::parse_tool_params(tool_name, params_str)?;
 
         execute_tool_command(registry, tool_name, params).await
     } else {
@@ -231,7 +228,12 @@ async fn async_main(args: Args) -> Result<()> {
         };
 
         match SecurityContext::new(policy) {
-            Ok(_) => info!("Security sandbox initialized"),
+            Ok(_) => {
+                info!("Security sandbox initialized");
+                // Learn which namespaces jailed tools can use before any spawns
+                #[cfg(target_os = "linux")]
+                pcode::security::jail::NamespaceJail::probe_host().await;
+            }
             Err(e) => {
                 error!("Failed to initialize security sandbox: {}", e);
                 if cfg!(target_os = "linux") || cfg!(target_os = "macos") {
@@ -244,7 +246,9 @@ async fn async_main(args: Args) -> Result<()> {
     }
 
     // Initialize tool registry with discovery
-    let registry = initialize_tool_registry().await?;
+    // Language servers are shared by the navigation tools and started on first use
+    let lsp = Arc::new(LspManager::new(std::env::current_dir()?));
+    let registry = initialize_tool_registry(lsp.clone()).await?;
     info!("Registered {} tools", registry.list_tools().len());
 
     // Initialize MCP protocol
@@ -252,10 +256,39 @@ async fn async_main(args: Args) -> Result<()> {
 
     info!("pcode ready");
 
+    match args.subcommand {
+        Some(Commands::Gate {
+            path,
+            checks,
+            coverage_report,
+            sarif,
+            allow_skipped,
+        }) => {
+            let params = serde_json::json!({
+                "path": path,
+                "checks": checks,
+                "coverage_report": coverage_report,
+                "sarif": sarif,
+                "allow_skipped": allow_skipped,
+            });
+            return run_gate(registry, params).await;
+        }
+        Some(Commands::Trends { limit, base, path }) => {
+            let params = serde_json::json!({ "limit": limit, "base": base, "path": path });
+            return execute_tool_command(registry, "trends", params).await;
+        }
+        None => {}
+    }
+
     // Check if we're in interactive mode or have a command
     if args.interactive || args.command.is_none() {
         // Run interactive chat
         let mut chat = InteractiveChat::new(registry);
+        // Edits made in the user's editor invalidate indexes and are shown to the agent
+        match WorkspaceWatcher::start(std::env::current_dir()?, Some(lsp)) {
+            Ok(watcher) => chat = chat.with_watcher(watcher),
+            Err(e) => warn!("File watcher unavailable: {}", e),
+        }
         chat.run().await?;
     } else if let Some(command) = args.command {
         // Execute single command
@@ -266,7 +299,36 @@ async fn async_main(args: Args) -> Result<()> {
     Ok(())
 }
 
-async fn initialize_tool_registry() -> Result<ToolRegistry> {
+async fn run_gate(registry: ToolRegistry, params: serde_json::Value) -> Result<()> {
+    use pcode::tools::ToolRequest;
+
+    let response = registry
+        .execute(ToolRequest {
+            tool: "gate".to_string(),
+            params,
+        })
+        .await;
+    let result = match response.result {
+        Some(result) if response.success => result,
+        _ => anyhow::bail!(
+            "Error: {}",
+            response
+                .error
+                .unwrap_or_else(|| "Unknown error".to_string())
+        ),
+    };
+
+    print!("{}", format_tool_result("gate", &result)?);
+    if let Some(path) = result.get("sarif").and_then(|v| v.as_str()) {
+        println!("SARIF written to {}", path);
+    }
+    if result["passed"] != true {
+        anyhow::bail!("Quality gate failed");
+    }
+    Ok(())
+}
+
+async fn initialize_tool_registry(lsp: Arc<LspManager>) -> Result<ToolRegistry> {
     let mut registry = ToolRegistry::new();
 
     // Register built-in tools first
@@ -279,10 +341,28 @@ async fn initialize_tool_registry() -> Result<ToolRegistry> {
     registry.register(Box::new(BashTool::new()));
     registry.register(Box::new(DevCliTool::new()));
     registry.register(Box::new(FixTool::new()));
+    registry.register(Box::new(GateTool::new()));
     registry.register(Box::new(CoverageTool::new()));
     registry.register(Box::new(RefactorTool::new()));
+    registry.register(Box::new(TestgenTool::new()));
+    registry.register(Box::new(TrendsTool::new()));
+    registry.register(Box::new(DepsTool::new()));
+    registry.register(Box::new(SymbolsTool::new()));
+    registry.register(Box::new(ReplaceTool::new()));
+    registry.register(Box::new(AstSearchTool::new()));
+
+    registry.register(Box::new(DefinitionTool::new(lsp.clone())));
+    registry.register(Box::new(ReferencesTool::new(lsp.clone())));
+    registry.register(Box::new(HoverTool::new(lsp.clone())));
+    registry.register(Box::new(WorkspaceSymbolsTool::new(lsp.clone())));
+    registry.register(Box::new(DiagnosticsTool::new(lsp.clone())));
+    registry.register(Box::new(RenameTool::new(lsp)));
+
     registry.register(Box::new(PythonTool::new()));
     registry.register(Box::new(JavaScriptTool::new()));
+    for profile in EXTENDED_LANGUAGES {
+        registry.register(Box::new(RunnerTool::new(profile)));
+    }
 
     debug!("Registered {} built-in tools", registry.list_tools().len());
 
@@ -321,4 +401,42 @@ mod tests {
         assert!(args.debug);
         assert_eq!(args.max_memory, 1024);
     }
+
+    #[test]
+    fn test_gate_subcommand_parsing() {
+        let args = Args::parse_from([
+            "pcode",
+            "gate",
+            "src",
+            "--checks",
+            "satd,fmt",
+            "--allow-skipped",
+        ]);
+        match args.subcommand {
+            Some(Commands::Gate {
+                path,
+                checks,
+                allow_skipped,
+                ..
+            }) => {
+                assert_eq!(path, "src");
+                assert_eq!(checks, vec!["satd", "fmt"]);
+                assert!(allow_skipped);
+            }
+            other => panic!("unexpected subcommand: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_trends_subcommand_parsing() {
+        let args = Args::parse_from(["pcode", "trends", "--limit", "5", "--base", "develop"]);
+        match args.subcommand {
+            Some(Commands::Trends { limit, base, path }) => {
+                assert_eq!(limit, 5);
+                assert_eq!(base.as_deref(), Some("develop"));
+                assert_eq!(path, None);
+            }
+            other => panic!("unexpected subcommand: {:?}", other),
+        }
+    }
 }
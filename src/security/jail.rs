@@ -0,0 +1,586 @@
+//! Namespace jail for subprocess-spawning tools.
+//!
+//! Builds on unprivileged user namespaces: the child unshares its user,
+//! mount, PID and (unless networking is allowed) network namespaces before
+//! `exec`. PID and network namespaces are left out on hosts that refuse them. Everything the child needs is computed up front so the code that
+//! runs between `fork` and `exec` performs no allocation.
+
+use super::SecurityPolicy;
+use nix::mount::{mount, MsFlags};
+use nix::sched::{unshare, CloneFlags};
+use nix::sys::stat::Mode;
+use nix::sys::statvfs::{statvfs, FsFlags};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{fork, mkdir, ForkResult};
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+/// System directories exposed read-only inside the jail.
+const SYSTEM_DIRS: &[&str] = &[
+    "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc", "/opt",
+];
+
+/// Upper bound on writable bind mounts, so the child can track their
+/// descriptors in a fixed-size array.
+const MAX_WRITABLE: usize = 16;
+
+/// Namespaces every jail needs; network and PID namespaces are added on top.
+const BASE_FLAGS: CloneFlags = CloneFlags::CLONE_NEWUSER.union(CloneFlags::CLONE_NEWNS);
+
+/// Probe results by the bits of the namespace flags probed.
+static SUPPORTED: Mutex<BTreeMap<i32, bool>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone)]
+pub struct NamespaceJail {
+    writable_paths: Vec<PathBuf>,
+    allow_network: bool,
+    private_tmp: bool,
+    pid_namespace: bool,
+}
+
+impl NamespaceJail {
+    /// Jail with no network, a private /tmp and its own PID namespace.
+    pub fn new() -> Self {
+        Self {
+            writable_paths: Vec::new(),
+            allow_network: false,
+            private_tmp: true,
+            pid_namespace: true,
+        }
+    }
+
+    /// Jail mirroring a security policy: allowed paths stay writable and the
+    /// network namespace is only kept when the policy allows networking.
+    pub fn from_policy(policy: &SecurityPolicy) -> Self {
+        Self {
+            writable_paths: policy.allowed_paths.clone(),
+            allow_network: policy.allow_network,
+            ..Self::new()
+        }
+    }
+
+    /// Jail for the policy installed by [`super::SecurityContext`], if any.
+    ///
+    /// Returns `None` when no sandbox is active or `PCODE_NO_SANDBOX` is set.
+    pub fn for_active_policy() -> Option<Self> {
+        if std::env::var("PCODE_NO_SANDBOX").is_ok() {
+            return None;
+        }
+        super::active_policy().map(Self::from_policy)
+    }
+
+    pub fn with_writable(mut self, path: impl Into<PathBuf>) -> Self {
+        self.writable_paths.push(path.into());
+        self
+    }
+
+    pub fn allow_network(mut self, allow: bool) -> Self {
+        self.allow_network = allow;
+        self
+    }
+
+    pub fn private_tmp(mut self, private: bool) -> Self {
+        self.private_tmp = private;
+        self
+    }
+
+    pub fn pid_namespace(mut self, enabled: bool) -> Self {
+        self.pid_namespace = enabled;
+        self
+    }
+
+    /// Whether unprivileged user namespaces can be created on this host.
+    pub fn is_supported() -> bool {
+        supports(BASE_FLAGS)
+    }
+
+    /// Probe every namespace combination a jail may use, off the async
+    /// runtime, so that [`Self::apply`] later answers from the cache.
+    pub async fn probe_host() {
+        let _ = tokio::task::spawn_blocking(|| {
+            for network in [false, true] {
+                for pid in [false, true] {
+                    supports(
+                        Self::new()
+                            .allow_network(network)
+                            .pid_namespace(pid)
+                            .flags(),
+                    );
+                }
+            }
+        })
+        .await;
+    }
+
+    /// Install the jail on a command. Returns `false`, leaving the command
+    /// untouched, when user namespaces are unavailable.
+    pub fn apply(&self, cmd: &mut Command) -> bool {
+        let Some(flags) = self.supported_flags() else {
+            return false;
+        };
+
+        let setup = match self.prepare(flags) {
+            Ok(setup) => Arc::new(setup),
+            Err(e) => {
+                warn!("Failed to prepare namespace jail: {}", e);
+                return false;
+            }
+        };
+
+        debug!("Applying namespace jail: {:?}", self);
+        unsafe {
+            cmd.pre_exec(move || setup.enter());
+        }
+        true
+    }
+
+    /// Namespaces this jail asks for.
+    fn flags(&self) -> CloneFlags {
+        let mut flags = BASE_FLAGS;
+        if !self.allow_network {
+            flags |= CloneFlags::CLONE_NEWNET;
+        }
+        if self.pid_namespace {
+            flags |= CloneFlags::CLONE_NEWPID;
+        }
+        flags
+    }
+
+    /// The namespaces this host lets the jail create. Containers often
+    /// refuse PID or network namespaces while allowing user and mount ones,
+    /// so those are dropped, in that order, before giving up on the jail.
+    fn supported_flags(&self) -> Option<CloneFlags> {
+        let wanted = self.flags();
+        let without_pid = wanted - CloneFlags::CLONE_NEWPID;
+        [wanted, without_pid, without_pid - CloneFlags::CLONE_NEWNET]
+            .into_iter()
+            .find(|&flags| supports(flags))
+    }
+
+    fn prepare(&self, flags: CloneFlags) -> io::Result<JailSetup> {
+        let uid = unsafe { libc::getuid() };
+        let gid = unsafe { libc::getgid() };
+        let mut ops = vec![MountOp::MakePrivate];
+
+        for dir in SYSTEM_DIRS {
+            let path = Path::new(dir);
+            let is_real_dir = path
+                .symlink_metadata()
+                .is_ok_and(|m| m.file_type().is_dir());
+            if !is_real_dir {
+                continue;
+            }
+            // Remounting inside a user namespace must keep locked flags
+            let locked = statvfs(path)
+                .map(|s| locked_flags(s.flags()))
+                .unwrap_or(MsFlags::empty());
+            ops.push(MountOp::ReadOnlyBind {
+                path: cstring(path)?,
+                locked,
+            });
+        }
+
+        if self.private_tmp {
+            ops.push(MountOp::Tmpfs {
+                path: cstring(Path::new("/tmp"))?,
+            });
+        }
+
+        // Writable paths are bound last, from descriptors the child opens
+        // before any other mount so they reference the original mounts.
+        let mut writable = Vec::new();
+        for path in &self.writable_paths {
+            let Ok(path) = path.canonicalize() else {
+                continue;
+            };
+            if path == Path::new("/") || writable.len() == MAX_WRITABLE {
+                continue;
+            }
+            let mut create = Vec::new();
+            if self.private_tmp && path.starts_with("/tmp") {
+                for ancestor in path.ancestors().collect::<Vec<_>>().into_iter().rev() {
+                    if ancestor.starts_with("/tmp") && ancestor != Path::new("/tmp") {
+                        create.push(cstring(ancestor)?);
+                    }
+                }
+            }
+            writable.push(WritablePath {
+                path: cstring(&path)?,
+                create,
+            });
+        }
+
+        Ok(JailSetup {
+            flags,
+            uid_map: format!("{} {} 1\n", uid, uid).into_bytes(),
+            gid_map: format!("{} {} 1\n", gid, gid).into_bytes(),
+            ops,
+            writable,
+            pid_namespace: flags.contains(CloneFlags::CLONE_NEWPID),
+        })
+    }
+}
+
+impl Default for NamespaceJail {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether a child can unshare exactly `flags` on this host. Each
+/// combination is probed once, by spawning a trivial child.
+fn supports(flags: CloneFlags) -> bool {
+    if let Some(&supported) = SUPPORTED.lock().unwrap().get(&flags.bits()) {
+        return supported;
+    }
+    let mut cmd = std::process::Command::new("true");
+    unsafe {
+        std::os::unix::process::CommandExt::pre_exec(&mut cmd, move || {
+            unshare(flags).map_err(io::Error::from)
+        });
+    }
+    let supported = cmd.status().is_ok_and(|s| s.success());
+    if !supported {
+        if flags == BASE_FLAGS {
+            warn!("User namespaces unavailable; subprocesses run without a jail");
+        } else {
+            warn!("Host refuses namespaces {:?}; the jail drops some", flags);
+        }
+    }
+    SUPPORTED.lock().unwrap().insert(flags.bits(), supported);
+    supported
+}
+
+#[derive(Debug)]
+enum MountOp {
+    MakePrivate,
+    ReadOnlyBind { path: CString, locked: MsFlags },
+    Tmpfs { path: CString },
+}
+
+#[derive(Debug)]
+struct WritablePath {
+    path: CString,
+    /// Directories to create first when the path lives on the private /tmp
+    create: Vec<CString>,
+}
+
+/// Pre-computed state consumed between `fork` and `exec`.
+#[derive(Debug)]
+struct JailSetup {
+    flags: CloneFlags,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    ops: Vec<MountOp>,
+    writable: Vec<WritablePath>,
+    pid_namespace: bool,
+}
+
+impl JailSetup {
+    /// Runs in the forked child. Namespace creation is mandatory; individual
+    /// mounts are best effort so a missing directory cannot block execution.
+    fn enter(&self) -> io::Result<()> {
+        unshare(self.flags)?;
+        write_proc_file(c"/proc/self/setgroups", b"deny\n")?;
+        write_proc_file(c"/proc/self/uid_map", &self.uid_map)?;
+        write_proc_file(c"/proc/self/gid_map", &self.gid_map)?;
+
+        let mut fds = [-1; MAX_WRITABLE];
+        for (fd, writable) in fds.iter_mut().zip(&self.writable) {
+            *fd = unsafe {
+                libc::open(
+                    writable.path.as_ptr(),
+                    libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC,
+                )
+            };
+        }
+
+        for op in &self.ops {
+            let _ = op.apply();
+        }
+
+        for (&fd, writable) in fds.iter().zip(&self.writable) {
+            if fd >= 0 {
+                let _ = writable.bind_from(fd);
+                unsafe {
+                    libc::close(fd);
+                }
+            }
+        }
+
+        if self.pid_namespace {
+            enter_pid_namespace()?;
+        }
+        Ok(())
+    }
+}
+
+impl MountOp {
+    fn apply(&self) -> nix::Result<()> {
+        const NONE: Option<&'static CStr> = None;
+        match self {
+            MountOp::MakePrivate => mount(
+                NONE,
+                c"/",
+                NONE,
+                MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+                NONE,
+            ),
+            MountOp::ReadOnlyBind { path, locked } => {
+                mount(
+                    Some(path.as_c_str()),
+                    path.as_c_str(),
+                    NONE,
+                    MsFlags::MS_BIND | MsFlags::MS_REC,
+                    NONE,
+                )?;
+                mount(
+                    NONE,
+                    path.as_c_str(),
+                    NONE,
+                    MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY | *locked,
+                    NONE,
+                )
+            }
+            MountOp::Tmpfs { path } => mount(
+                Some(c"tmpfs"),
+                path.as_c_str(),
+                Some(c"tmpfs"),
+                MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+                Some(c"mode=1777"),
+            ),
+        }
+    }
+}
+
+impl WritablePath {
+    fn bind_from(&self, fd: libc::c_int) -> nix::Result<()> {
+        for dir in &self.create {
+            let _ = mkdir(dir.as_c_str(), Mode::from_bits_truncate(0o755));
+        }
+        let mut buf = [0u8; 32];
+        mount(
+            Some(fd_path(fd, &mut buf)),
+            self.path.as_c_str(),
+            None::<&CStr>,
+            MsFlags::MS_BIND | MsFlags::MS_REC,
+            None::<&CStr>,
+        )
+    }
+}
+
+/// Formats `/proc/self/fd/<fd>` into `buf` without allocating.
+fn fd_path(fd: libc::c_int, buf: &mut [u8; 32]) -> &CStr {
+    const PREFIX: &[u8] = b"/proc/self/fd/";
+    buf[..PREFIX.len()].copy_from_slice(PREFIX);
+
+    let mut digits = [0u8; 10];
+    let mut len = 0;
+    let mut value = fd.unsigned_abs();
+    loop {
+        digits[len] = b'0' + (value % 10) as u8;
+        len += 1;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    for (i, digit) in digits[..len].iter().rev().enumerate() {
+        buf[PREFIX.len() + i] = *digit;
+    }
+    buf[PREFIX.len() + len] = 0;
+    CStr::from_bytes_until_nul(&buf[..]).expect("buffer is nul-terminated")
+}
+
+/// Fork so the command becomes PID 1 of the new namespace. The original
+/// child stays behind as a reaper that mirrors the jailed process's exit.
+fn enter_pid_namespace() -> io::Result<()> {
+    match unsafe { fork() }? {
+        ForkResult::Child => {
+            unsafe {
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+            }
+            // A private /proc matching the new PID namespace, when permitted
+            let _ = mount(
+                Some(c"proc"),
+                c"/proc",
+                Some(c"proc"),
+                MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+                None::<&CStr>,
+            );
+            Ok(())
+        }
+        ForkResult::Parent { child } => {
+            // Release the spawn error pipe and stdio copies held by the reaper
+            close_inherited_fds();
+            let code = loop {
+                match waitpid(child, None) {
+                    Ok(WaitStatus::Exited(_, code)) => break code,
+                    Ok(WaitStatus::Signaled(_, signal, _)) => break 128 + signal as i32,
+                    Ok(_) | Err(nix::errno::Errno::EINTR) => continue,
+                    Err(_) => break 1,
+                }
+            };
+            unsafe { libc::_exit(code) }
+        }
+    }
+}
+
+fn close_inherited_fds() {
+    let closed = unsafe { libc::syscall(libc::SYS_close_range, 3u32, u32::MAX, 0u32) };
+    if closed != 0 {
+        for fd in 3..1024 {
+            unsafe {
+                libc::close(fd);
+            }
+        }
+    }
+}
+
+fn write_proc_file(path: &CStr, contents: &[u8]) -> io::Result<()> {
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+        let result = if written < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        };
+        libc::close(fd);
+        result
+    }
+}
+
+fn cstring(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(io::Error::from)
+}
+
+fn locked_flags(flags: FsFlags) -> MsFlags {
+    let mut locked = MsFlags::empty();
+    if flags.contains(FsFlags::ST_NOSUID) {
+        locked |= MsFlags::MS_NOSUID;
+    }
+    if flags.contains(FsFlags::ST_NODEV) {
+        locked |= MsFlags::MS_NODEV;
+    }
+    if flags.contains(FsFlags::ST_NOEXEC) {
+        locked |= MsFlags::MS_NOEXEC;
+    }
+    if flags.contains(FsFlags::ST_NOATIME) {
+        locked |= MsFlags::MS_NOATIME;
+    }
+    if flags.contains(FsFlags::ST_NODIRATIME) {
+        locked |= MsFlags::MS_NODIRATIME;
+    }
+    if flags.contains(FsFlags::ST_RELATIME) {
+        locked |= MsFlags::MS_RELATIME;
+    }
+    locked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+    use tempfile::TempDir;
+
+    async fn run_jailed(jail: &NamespaceJail, script: &str) -> Option<(bool, String)> {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script);
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        if !jail.apply(&mut cmd) {
+            return None;
+        }
+        let output = cmd.output().await.unwrap();
+        Some((
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_fd_path_formatting() {
+        let mut buf = [0u8; 32];
+        assert_eq!(fd_path(7, &mut buf), c"/proc/self/fd/7");
+        assert_eq!(fd_path(1024, &mut buf), c"/proc/self/fd/1024");
+    }
+
+    #[test]
+    fn test_jail_from_policy() {
+        let policy = SecurityPolicy {
+            allowed_paths: vec![PathBuf::from("/workspace")],
+            allow_network: true,
+            ..Default::default()
+        };
+
+        let jail = NamespaceJail::from_policy(&policy);
+        assert!(jail.allow_network);
+        assert_eq!(jail.writable_paths, vec![PathBuf::from("/workspace")]);
+        assert!(jail.private_tmp && jail.pid_namespace);
+    }
+
+    #[tokio::test]
+    async fn test_jail_isolation() {
+        let workspace = TempDir::new().unwrap();
+        let jail = NamespaceJail::new().with_writable(workspace.path());
+        let script = format!(
+            "echo $$; touch /usr/.pcode_jail 2>/dev/null || echo usr-ro; \
+             touch {}/written && echo ws-rw; touch /tmp/.pcode_private; \
+             cat /proc/net/dev",
+            workspace.path().display()
+        );
+
+        // Skipped when user namespaces are disabled on this host
+        let Some((success, stdout)) = run_jailed(&jail, &script).await else {
+            return;
+        };
+        assert!(success);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines[0], "1");
+        assert!(lines.contains(&"usr-ro"));
+        assert!(lines.contains(&"ws-rw"));
+        assert!(workspace.path().join("written").exists());
+        assert!(!Path::new("/tmp/.pcode_private").exists());
+        assert!(!lines.iter().any(|l| l.starts_with("eth")));
+    }
+
+    #[tokio::test]
+    async fn test_probe_covers_every_flag_set() {
+        NamespaceJail::probe_host().await;
+        let probed = SUPPORTED.lock().unwrap().clone();
+        for jail in [
+            NamespaceJail::new(),
+            NamespaceJail::new().pid_namespace(false),
+            NamespaceJail::new().allow_network(true),
+        ] {
+            assert!(probed.contains_key(&jail.flags().bits()));
+            // Whatever is chosen was probed and includes the base namespaces
+            if let Some(flags) = jail.supported_flags() {
+                assert!(probed[&flags.bits()]);
+                assert!(flags.contains(BASE_FLAGS));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jail_exit_status_and_missing_binary() {
+        let jail = NamespaceJail::new();
+        if let Some((success, _)) = run_jailed(&jail, "exit 3").await {
+            assert!(!success);
+        }
+
+        let mut cmd = Command::new("nonexistent_command_xyz123");
+        if jail.apply(&mut cmd) {
+            assert!(cmd.output().await.is_err());
+        }
+    }
+}
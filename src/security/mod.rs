@@ -1,4 +1,5 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use tracing::debug;
 
 #[cfg(target_os = "linux")]
@@ -8,6 +9,8 @@ mod macos;
 #[cfg(target_os = "windows")]
 mod windows;
 
+#[cfg(target_os = "linux")]
+pub mod jail;
 pub mod manifest;
 pub mod sandbox;
 pub mod verified_sandbox;
@@ -66,6 +69,51 @@ impl Default for SecurityPolicy {
     }
 }
 
+impl SecurityPolicy {
+    /// Whether `path` lies under one of the allowed paths.
+    pub fn allows_path(&self, path: &Path) -> bool {
+        self.allowed_paths
+            .iter()
+            .any(|allowed| path.starts_with(allowed))
+    }
+
+    /// A copy with allowed paths made absolute and resolved through symlinks
+    /// where they exist.
+    pub fn canonicalized(&self) -> Self {
+        let allowed_paths = self
+            .allowed_paths
+            .iter()
+            .map(|path| {
+                std::fs::canonicalize(path)
+                    .or_else(|_| std::path::absolute(path))
+                    .unwrap_or_else(|_| path.clone())
+            })
+            .collect();
+        Self {
+            allowed_paths,
+            ..self.clone()
+        }
+    }
+}
+
+static ACTIVE_POLICY: OnceLock<SecurityPolicy> = OnceLock::new();
+
+/// Policy of the first successfully initialized [`SecurityContext`].
+///
+/// Tools consult it to confine the subprocesses they spawn.
+pub fn active_policy() -> Option<&'static SecurityPolicy> {
+    ACTIVE_POLICY.get()
+}
+
+/// The active policy, or a workspace-only default when no sandbox is
+/// installed.
+pub fn active_policy_or_default(workspace: &Path) -> SecurityPolicy {
+    active_policy().cloned().unwrap_or_else(|| SecurityPolicy {
+        allowed_paths: vec![workspace.to_path_buf()],
+        ..Default::default()
+    })
+}
+
 pub struct SecurityContext {
     policy: SecurityPolicy,
 }
@@ -76,6 +124,7 @@ impl SecurityContext {
 
         let context = Self { policy };
         context.apply_sandbox()?;
+        let _ = ACTIVE_POLICY.set(context.policy.clone());
 
         Ok(context)
     }
@@ -102,10 +151,8 @@ impl SecurityContext {
     }
 
     pub fn check_path_access(&self, path: &PathBuf) -> Result<(), SecurityError> {
-        for allowed_path in &self.policy.allowed_paths {
-            if path.starts_with(allowed_path) {
-                return Ok(());
-            }
+        if self.policy.allows_path(path) {
+            return Ok(());
         }
 
         Err(SecurityError::PermissionDenied(format!(
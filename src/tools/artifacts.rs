@@ -0,0 +1,410 @@
+//! Files produced by sandboxed code.
+//!
+//! Snippets run in a throwaway [`Sandbox`] directory, so anything they write
+//! (a CSV, a plot, generated source) is lost when it is dropped. This module
+//! collects those files, inlines their content within size limits and can
+//! copy them into the workspace.
+
+use crate::security::{active_policy_or_default, SecurityPolicy};
+use crate::tools::runner::Sandbox;
+use crate::tools::ToolError;
+use crate::watcher;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+use tracing::debug;
+
+/// Directories created by runtimes rather than by the snippet.
+const IGNORED_DIRS: &[&str] = &["__pycache__", "node_modules"];
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArtifactLimits {
+    /// Largest file whose content is returned inline
+    pub max_file_bytes: u64,
+    /// Budget for inline content across all files
+    pub max_total_bytes: u64,
+    /// Files beyond this count are not collected
+    pub max_files: usize,
+}
+
+impl Default for ArtifactLimits {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 1024 * 1024,
+            max_total_bytes: 4 * 1024 * 1024,
+            max_files: 32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Artifact {
+    /// Path relative to the sandbox directory
+    pub path: String,
+    pub size: u64,
+    /// "text" for UTF-8 content, "base64" for binary content
+    pub encoding: &'static str,
+    /// Omitted when the file exceeds the size limits
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub omitted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exported_to: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ArtifactSet {
+    pub files: Vec<Artifact>,
+    /// Whether files were left out because of `max_files`
+    pub truncated: bool,
+}
+
+/// Collect the files the snippet left in its sandbox directory.
+///
+/// The snippet itself, compiled binaries, hidden and runtime cache
+/// directories and symlinks are skipped.
+pub async fn collect(sandbox: &Sandbox, limits: &ArtifactLimits) -> Result<ArtifactSet, ToolError> {
+    let mut paths = Vec::new();
+    let mut pending = vec![sandbox.path().to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir)
+            .await
+            .map_err(|e| ToolError::Execution(format!("Failed to list sandbox: {}", e)))?;
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| ToolError::Execution(format!("Failed to list sandbox: {}", e)))?
+        {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if !name.starts_with('.') && !IGNORED_DIRS.contains(&name.as_str()) {
+                    pending.push(path);
+                }
+            } else if file_type.is_file() && !sandbox.is_runner_file(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+
+    let mut set = ArtifactSet {
+        truncated: paths.len() > limits.max_files,
+        ..Default::default()
+    };
+    let mut budget = limits.max_total_bytes;
+    for path in paths.into_iter().take(limits.max_files) {
+        let relative = path
+            .strip_prefix(sandbox.path())
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .to_string();
+        let size = fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+
+        let omitted = if size > limits.max_file_bytes {
+            Some(format!("larger than {} bytes", limits.max_file_bytes))
+        } else if size > budget {
+            Some("total artifact size limit reached".to_string())
+        } else {
+            None
+        };
+
+        let (encoding, content) = match omitted {
+            Some(_) => (guess_encoding(&path).await, None),
+            None => {
+                let bytes = fs::read(&path)
+                    .await
+                    .map_err(|e| ToolError::Execution(format!("Failed to read artifact: {}", e)))?;
+                budget -= size;
+                let (encoding, content) = encode(bytes);
+                (encoding, Some(content))
+            }
+        };
+
+        set.files.push(Artifact {
+            path: relative,
+            size,
+            encoding,
+            content,
+            omitted,
+            exported_to: None,
+        });
+    }
+
+    debug!("Collected {} artifacts", set.files.len());
+    Ok(set)
+}
+
+/// Copy collected artifacts into `export_to`, keeping their relative paths.
+///
+/// `export_to` is resolved against `workspace` and must fall inside the
+/// paths the policy allows writing to.
+pub async fn export(
+    sandbox: &Sandbox,
+    set: &mut ArtifactSet,
+    export_to: &str,
+    workspace: &Path,
+    policy: &SecurityPolicy,
+) -> Result<PathBuf, ToolError> {
+    let target = resolve(&normalize(&workspace.join(export_to)));
+    if !policy.canonicalized().allows_path(&target) {
+        return Err(ToolError::PermissionDenied(format!(
+            "Export path {} is outside the allowed paths",
+            target.display()
+        )));
+    }
+
+    fs::create_dir_all(&target)
+        .await
+        .map_err(|e| ToolError::Execution(format!("Failed to create export dir: {}", e)))?;
+
+    for artifact in &mut set.files {
+        let destination = target.join(&artifact.path);
+        refuse_symlinks(&target, Path::new(&artifact.path)).await?;
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| ToolError::Execution(format!("Failed to create export dir: {}", e)))?;
+        }
+        let contents = fs::read(sandbox.path().join(&artifact.path))
+            .await
+            .map_err(|e| ToolError::Execution(format!("Failed to export artifact: {}", e)))?;
+        fs::write(&destination, &contents)
+            .await
+            .map_err(|e| ToolError::Execution(format!("Failed to export artifact: {}", e)))?;
+        watcher::record_write(&destination, &contents);
+        artifact.exported_to = Some(destination.to_string_lossy().to_string());
+    }
+
+    Ok(target)
+}
+
+/// Fail if `relative` passes through or ends at a symlink under `target`;
+/// copying through one could write outside the export directory.
+async fn refuse_symlinks(target: &Path, relative: &Path) -> Result<(), ToolError> {
+    let mut path = target.to_path_buf();
+    for component in relative.components() {
+        path.push(component);
+        let is_symlink = fs::symlink_metadata(&path)
+            .await
+            .is_ok_and(|meta| meta.file_type().is_symlink());
+        if is_symlink {
+            return Err(ToolError::PermissionDenied(format!(
+                "Refusing to export through symlink {}",
+                path.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Collect artifacts with the default limits and export them when asked,
+/// under the active write policy.
+pub async fn gather(
+    sandbox: &Sandbox,
+    export_to: Option<&str>,
+    workspace: &Path,
+) -> Result<Value, ToolError> {
+    let mut set = collect(sandbox, &ArtifactLimits::default()).await?;
+    if let Some(export_to) = export_to {
+        let policy = active_policy_or_default(workspace);
+        export(sandbox, &mut set, export_to, workspace, &policy).await?;
+    }
+    serde_json::to_value(&set)
+        .map_err(|e| ToolError::Execution(format!("Failed to encode artifacts: {}", e)))
+}
+
+fn encode(bytes: Vec<u8>) -> (&'static str, String) {
+    if bytes.contains(&0) {
+        return ("base64", BASE64.encode(bytes));
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => ("text", text),
+        Err(e) => ("base64", BASE64.encode(e.into_bytes())),
+    }
+}
+
+/// Sniff the first block of a file too large to inline.
+async fn guess_encoding(path: &Path) -> &'static str {
+    use tokio::io::AsyncReadExt;
+
+    let mut head = vec![0; 8192];
+    let read = match fs::File::open(path).await {
+        Ok(mut file) => file.read(&mut head).await.unwrap_or(0),
+        Err(_) => 0,
+    };
+    head.truncate(read);
+    // A multi-byte character cut at the boundary still counts as text
+    let valid = match std::str::from_utf8(&head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    if valid && !head.contains(&0) {
+        "text"
+    } else {
+        "base64"
+    }
+}
+
+/// Canonicalize the longest existing prefix of `path`, so symlinks inside
+/// the workspace cannot redirect an export elsewhere.
+fn resolve(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    while let Some(parent) = existing.parent() {
+        if existing.exists() {
+            break;
+        }
+        rest.push(existing.file_name().unwrap_or_default());
+        existing = parent;
+    }
+    let mut resolved = std::fs::canonicalize(existing).unwrap_or_else(|_| existing.to_path_buf());
+    resolved.extend(rest.into_iter().rev());
+    resolved
+}
+
+/// Resolve `.` and `..` components without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::runner::{CodeRunner, SHELL};
+    use tempfile::TempDir;
+
+    async fn sandbox_with_files() -> Sandbox {
+        let sandbox = CodeRunner::new(&SHELL).prepare("true").await.unwrap();
+        let dir = sandbox.path();
+        std::fs::write(dir.join("data.csv"), "a,b\n1,2\n").unwrap();
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        std::fs::write(dir.join("out/image.png"), [0x89, b'P', b'N', b'G', 0, 1]).unwrap();
+        std::fs::write(dir.join("big.txt"), "x".repeat(64)).unwrap();
+        std::fs::create_dir_all(dir.join("__pycache__")).unwrap();
+        std::fs::write(dir.join("__pycache__/mod.pyc"), "cache").unwrap();
+        sandbox
+    }
+
+    #[tokio::test]
+    async fn test_collect_encodes_and_limits() {
+        let sandbox = sandbox_with_files().await;
+        let limits = ArtifactLimits {
+            max_file_bytes: 32,
+            ..Default::default()
+        };
+
+        let set = collect(&sandbox, &limits).await.unwrap();
+        let paths: Vec<&str> = set.files.iter().map(|a| a.path.as_str()).collect();
+        assert_eq!(paths, vec!["big.txt", "data.csv", "out/image.png"]);
+        assert!(!set.truncated);
+
+        let big = &set.files[0];
+        assert_eq!(big.size, 64);
+        assert!(big.content.is_none());
+        assert!(big.omitted.is_some());
+        assert_eq!(big.encoding, "text");
+
+        assert_eq!(set.files[1].encoding, "text");
+        assert_eq!(set.files[1].content.as_deref(), Some("a,b\n1,2\n"));
+
+        let image = &set.files[2];
+        assert_eq!(image.encoding, "base64");
+        assert_eq!(image.content.as_deref(), Some("iVBORwAB"));
+    }
+
+    #[tokio::test]
+    async fn test_collect_truncates_file_count() {
+        let sandbox = sandbox_with_files().await;
+        let limits = ArtifactLimits {
+            max_files: 1,
+            ..Default::default()
+        };
+
+        let set = collect(&sandbox, &limits).await.unwrap();
+        assert_eq!(set.files.len(), 1);
+        assert!(set.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_export_respects_policy() {
+        let sandbox = sandbox_with_files().await;
+        let workspace = TempDir::new().unwrap();
+        let policy = SecurityPolicy {
+            allowed_paths: vec![workspace.path().to_path_buf()],
+            ..Default::default()
+        };
+        let mut set = collect(&sandbox, &ArtifactLimits::default()).await.unwrap();
+
+        let escaped = export(&sandbox, &mut set, "../outside", workspace.path(), &policy).await;
+        assert!(matches!(escaped, Err(ToolError::PermissionDenied(_))));
+
+        let target = export(&sandbox, &mut set, "results", workspace.path(), &policy)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(target.join("data.csv")).unwrap(),
+            "a,b\n1,2\n"
+        );
+        assert!(target.join("out/image.png").exists());
+        assert!(set.files.iter().all(|a| a.exported_to.is_some()));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_export_refuses_symlinked_destination() {
+        let sandbox = sandbox_with_files().await;
+        let workspace = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let policy = SecurityPolicy {
+            allowed_paths: vec![workspace.path().to_path_buf()],
+            ..Default::default()
+        };
+        let results = workspace.path().join("results");
+        std::fs::create_dir_all(&results).unwrap();
+        std::fs::write(outside.path().join("victim.txt"), "keep").unwrap();
+        std::os::unix::fs::symlink(outside.path().join("victim.txt"), results.join("data.csv"))
+            .unwrap();
+        std::os::unix::fs::symlink(outside.path(), results.join("out")).unwrap();
+
+        let set = collect(&sandbox, &ArtifactLimits::default()).await.unwrap();
+        for only in ["data.csv", "out/image.png"] {
+            let mut one = ArtifactSet {
+                files: set
+                    .files
+                    .iter()
+                    .filter(|a| a.path == only)
+                    .cloned()
+                    .collect(),
+                truncated: false,
+            };
+            let result = export(&sandbox, &mut one, "results", workspace.path(), &policy).await;
+            assert!(
+                matches!(result, Err(ToolError::PermissionDenied(_))),
+                "{}",
+                only
+            );
+        }
+        assert_eq!(
+            std::fs::read_to_string(outside.path().join("victim.txt")).unwrap(),
+            "keep"
+        );
+        assert!(!outside.path().join("image.png").exists());
+    }
+}
@@ -0,0 +1,397 @@
+//! Structural code search and rewrite.
+//!
+//! Patterns are matched against syntax trees rather than text, so
+//! `$X.unwrap()` finds calls however they are spaced, split across lines or
+//! commented. A rewrite template reuses the captured metavariables and goes
+//! through the same diff preview and atomic write as the other edit tools.
+
+use crate::analysis::display_path;
+use crate::analysis::structural::{self, Language, Query, Relation};
+use crate::tools::edit::{self, FileChange, TextEdit};
+use crate::tools::replace::{line_columns, workspace_files};
+use crate::tools::{Tool, ToolError};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use tracing::info;
+
+/// Matches reported when no limit is given
+const DEFAULT_LIMIT: usize = 100;
+/// Characters of matched text reported per match
+const MAX_TEXT: usize = 400;
+
+#[derive(Debug, Deserialize)]
+struct AstSearchParams {
+    /// Code pattern with `$NAME` and `$$$NAME` metavariables
+    #[serde(default)]
+    pattern: Option<String>,
+    /// Syntax node kind to match, alone or together with the pattern
+    #[serde(default)]
+    kind: Option<String>,
+    /// rust, python, javascript or typescript (default: rust)
+    #[serde(default)]
+    language: Option<String>,
+    /// Pattern some enclosing node must match
+    #[serde(default)]
+    inside: Option<String>,
+    /// Pattern no enclosing node may match
+    #[serde(default)]
+    not_inside: Option<String>,
+    /// Pattern some node below the match must match
+    #[serde(default)]
+    has: Option<String>,
+    /// Pattern no node below the match may match
+    #[serde(default)]
+    not_has: Option<String>,
+    /// Template replacing each match, with the captured metavariables
+    #[serde(default)]
+    rewrite: Option<String>,
+    /// Globs of files to search (default: all), relative to the workspace
+    #[serde(default)]
+    paths: Vec<String>,
+    /// Globs of files to leave alone
+    #[serde(default)]
+    exclude: Vec<String>,
+    /// Report rewrites and diffs without writing
+    #[serde(default)]
+    dry_run: bool,
+    /// Most matches to report; all are counted and rewritten
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct AstMatch {
+    file: String,
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+    text: String,
+    captures: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replacement: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct FileRewrite {
+    file: String,
+    matches: usize,
+    diff: String,
+}
+
+#[derive(Debug)]
+pub struct AstSearchTool {
+    workspace: PathBuf,
+}
+
+impl AstSearchTool {
+    pub fn new() -> Self {
+        Self {
+            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        }
+    }
+}
+
+impl Default for AstSearchTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn query(params: &AstSearchParams) -> Result<Query, String> {
+    let language = match &params.language {
+        Some(name) => Language::parse(name).ok_or_else(|| {
+            format!(
+                "Unknown language {} (expected one of: {})",
+                name,
+                Language::NAMES.join(", ")
+            )
+        })?,
+        None => Language::Rust,
+    };
+    let mut query = Query::new(language, params.pattern.as_deref(), params.kind.as_deref())?;
+    let relations = [
+        (Relation::Inside, &params.inside),
+        (Relation::NotInside, &params.not_inside),
+        (Relation::Has, &params.has),
+        (Relation::NotHas, &params.not_has),
+    ];
+    for (relation, pattern) in relations {
+        if let Some(pattern) = pattern {
+            query = query.with(relation, pattern)?;
+        }
+    }
+    if let Some(template) = &params.rewrite {
+        let captured = query.metavariables();
+        let unknown: Vec<String> = structural::metavariables(template)
+            .into_iter()
+            .filter(|name| !captured.contains(name))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!(
+                "Rewrite uses metavariables the pattern does not capture: {}",
+                unknown.join(", ")
+            ));
+        }
+    }
+    Ok(query)
+}
+
+fn excerpt(text: &str) -> String {
+    match text.char_indices().nth(MAX_TEXT) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text.to_string(),
+    }
+}
+
+#[async_trait]
+impl Tool for AstSearchTool {
+    fn name(&self) -> &str {
+        "ast_search"
+    }
+
+    fn description(&self) -> &str {
+        "Structural search over Rust, Python, JavaScript and TypeScript syntax trees: code patterns with $NAME/$$$NAME metavariables, node kinds, inside/has constraints, captured text per match, and optional rewrites previewed as diffs and written atomically"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: AstSearchParams =
+            serde_json::from_value(params).map_err(|e| ToolError::InvalidParams(e.to_string()))?;
+        let query = query(&params).map_err(ToolError::InvalidParams)?;
+        let language = query.language();
+        let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+
+        let mut total = 0;
+        let mut reported = Vec::new();
+        let mut files = Vec::new();
+        let mut changes = Vec::new();
+        for path in workspace_files(&self.workspace, &params.paths, &params.exclude)? {
+            let searched = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| language.extensions().contains(&e));
+            if !searched {
+                continue;
+            }
+            let Ok(original) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let mut matches = query.find(&path, &original).map_err(ToolError::Execution)?;
+            if params.rewrite.is_some() {
+                // Only the outermost of nested matches is rewritten
+                let mut end = 0;
+                matches.retain(|m| {
+                    let outermost = m.start >= end;
+                    if outermost {
+                        end = m.end;
+                    }
+                    outermost
+                });
+            }
+            if matches.is_empty() {
+                continue;
+            }
+            total += matches.len();
+
+            let file = display_path(&self.workspace, &path);
+            let mut offsets: Vec<usize> = matches.iter().flat_map(|m| [m.start, m.end]).collect();
+            offsets.sort_unstable();
+            offsets.dedup();
+            let positions: HashMap<usize, (usize, usize)> = offsets
+                .iter()
+                .copied()
+                .zip(line_columns(&original, &offsets))
+                .collect();
+
+            let mut edits = Vec::new();
+            for m in &matches {
+                let replacement = params
+                    .rewrite
+                    .as_ref()
+                    .map(|template| structural::rewrite(template, &m.captures));
+                if let Some(replacement) = &replacement {
+                    edits.push(TextEdit::new(m.start, m.end, replacement.clone()));
+                }
+                if reported.len() < limit {
+                    let (line, column) = positions[&m.start];
+                    let (end_line, end_column) = positions[&m.end];
+                    reported.push(AstMatch {
+                        file: file.clone(),
+                        line,
+                        column,
+                        end_line,
+                        end_column,
+                        text: excerpt(&original[m.start..m.end]),
+                        captures: m.captures.clone(),
+                        replacement,
+                    });
+                }
+            }
+
+            if params.rewrite.is_some() {
+                let change = FileChange {
+                    path,
+                    file: file.clone(),
+                    updated: edit::apply(&original, &edits),
+                    original,
+                };
+                if change.original != change.updated {
+                    files.push(FileRewrite {
+                        file,
+                        matches: matches.len(),
+                        diff: change.diff(),
+                    });
+                    changes.push(change);
+                }
+            }
+        }
+
+        let mut result = json!({
+            "language": language.name(),
+            "total_matches": total,
+            "truncated": total > reported.len(),
+            "matches": reported,
+        });
+        if params.rewrite.is_some() {
+            let applied = !params.dry_run && !changes.is_empty();
+            if applied {
+                edit::write_all_atomic(&changes)
+                    .map_err(|e| ToolError::Execution(format!("Nothing rewritten: {}", e)))?;
+                info!("Rewrote {} matches in {} files", total, changes.len());
+            }
+            result["files_changed"] = json!(changes.len());
+            result["applied"] = json!(applied);
+            result["files"] = json!(files);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn tool(dir: &TempDir) -> AstSearchTool {
+        let files = [
+            (".gitignore", "target/\n"),
+            (
+                "src/lib.rs",
+                "pub async fn load(path: &str) -> String {\n    read(path)\n        .await\n        .unwrap()\n}\n\nfn parse(text: &str) -> u32 {\n    text.parse().unwrap()\n}\n",
+            ),
+            ("src/ui.ts", "fetch(url).then(r => r.json());\n"),
+            ("target/gen.rs", "fn g() { x.unwrap(); }\n"),
+        ];
+        for (file, content) in files {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        AstSearchTool {
+            workspace: dir.path().to_path_buf(),
+        }
+    }
+
+    fn read(dir: &TempDir, file: &str) -> String {
+        std::fs::read_to_string(dir.path().join(file)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_search_reports_spans_and_captures() {
+        let dir = TempDir::new().unwrap();
+        let tool = tool(&dir);
+
+        let result = tool
+            .execute(json!({"pattern": "$X.unwrap()"}))
+            .await
+            .unwrap();
+        assert_eq!(result["total_matches"], 2);
+        assert_eq!(result["language"], "rust");
+        assert!(result.get("applied").is_none());
+        let first = &result["matches"][0];
+        assert_eq!(first["file"], "src/lib.rs");
+        assert_eq!(
+            (
+                &first["line"],
+                &first["column"],
+                &first["end_line"],
+                &first["end_column"]
+            ),
+            (&json!(2), &json!(5), &json!(4), &json!(18))
+        );
+        assert_eq!(
+            first["captures"],
+            json!({"X": "read(path)\n        .await"})
+        );
+
+        let result = tool
+            .execute(json!({
+                "pattern": "$X.unwrap()",
+                "inside": "async fn $F($$$) { $$$ }",
+                "limit": 0
+            }))
+            .await
+            .unwrap();
+        assert_eq!(result["total_matches"], 1);
+        assert_eq!(result["truncated"], true);
+
+        let result = tool
+            .execute(json!({"pattern": "$P.then($$$)", "language": "ts"}))
+            .await
+            .unwrap();
+        assert_eq!(result["matches"][0]["captures"]["P"], "fetch(url)");
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_previews_then_writes() {
+        let dir = TempDir::new().unwrap();
+        let tool = tool(&dir);
+        let params = json!({
+            "pattern": "$X.unwrap()",
+            "not_inside": "async fn $F($$$) { $$$ }",
+            "rewrite": "$X.expect(\"valid number\")",
+            "dry_run": true
+        });
+
+        let result = tool.execute(params.clone()).await.unwrap();
+        assert_eq!(result["applied"], false);
+        assert_eq!(
+            result["matches"][0]["replacement"],
+            "text.parse().expect(\"valid number\")"
+        );
+        assert_eq!(
+            result["files"][0]["diff"],
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -5,5 +5,5 @@\n }\n \n fn parse(text: &str) -> u32 {\n-    text.parse().unwrap()\n+    text.parse().expect(\"valid number\")\n }\n"
+        );
+        assert!(read(&dir, "src/lib.rs").contains("text.parse().unwrap()"));
+
+        let mut params = params;
+        params["dry_run"] = json!(false);
+        let result = tool.execute(params).await.unwrap();
+        assert_eq!(result["files_changed"], 1);
+        let updated = read(&dir, "src/lib.rs");
+        assert!(updated.contains("text.parse().expect(\"valid number\")"));
+        assert!(updated.contains("        .unwrap()"));
+        assert_eq!(read(&dir, "target/gen.rs"), "fn g() { x.unwrap(); }\n");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_params() {
+        let dir = TempDir::new().unwrap();
+        let tool = tool(&dir);
+        for params in [
+            json!({}),
+            json!({"pattern": "fn ("}),
+            json!({"pattern": "$X", "language": "cobol"}),
+            json!({"kind": "no_such_kind"}),
+            json!({"pattern": "$X.unwrap()", "rewrite": "$Y?"}),
+            json!({"pattern": "$X.unwrap()", "paths": ["src/[.rs"]}),
+        ] {
+            let err = tool.execute(params.clone()).await.unwrap_err();
+            assert!(matches!(err, ToolError::InvalidParams(_)), "{}", params);
+        }
+    }
+}
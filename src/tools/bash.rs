@@ -91,6 +91,10 @@ impl BashTool {
             // Prevent loading of LD_PRELOAD libraries
             cmd.env_remove("LD_PRELOAD");
             cmd.env_remove("LD_LIBRARY_PATH");
+
+            if let Some(jail) = crate::security::jail::NamespaceJail::for_active_policy() {
+                jail.with_writable(&cwd).apply(&mut cmd);
+            }
         }
 
         let timeout_duration = Duration::from_millis(params.timeout_ms);
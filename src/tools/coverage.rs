@@ -1,21 +1,111 @@
+use crate::analysis::coverage_report::{self, CoverageReport, DiffCoverage};
+use crate::tools::history::{self, Metrics};
 use crate::tools::{Tool, ToolError};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::process::Command;
 use tokio::time::{timeout, Duration};
 use tracing::{debug, info};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct CoverageParams {
     #[serde(default)]
     path: Option<String>,
+    /// "lcov" (default), "cobertura" or "text"
     #[serde(default)]
     format: Option<String>,
     #[serde(default)]
     exclude_files: Option<Vec<String>>,
+    /// "llvm-cov" or "tarpaulin"; detected when omitted
+    #[serde(default)]
+    backend: Option<String>,
+    /// Existing LCOV or Cobertura file to read instead of running tests
+    #[serde(default)]
+    report: Option<String>,
+    /// Fail when line coverage is below this percentage; with `base`
+    /// this applies to the changed lines
+    #[serde(default)]
+    min_coverage: Option<f64>,
+    /// Git ref to diff against for coverage of changed lines
+    #[serde(default)]
+    base: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    LlvmCov,
+    Tarpaulin,
+}
+
+impl Backend {
+    fn name(self) -> &'static str {
+        match self {
+            Backend::LlvmCov => "llvm-cov",
+            Backend::Tarpaulin => "tarpaulin",
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self, ToolError> {
+        match name {
+            "llvm-cov" | "cargo-llvm-cov" => Ok(Backend::LlvmCov),
+            "tarpaulin" | "cargo-tarpaulin" => Ok(Backend::Tarpaulin),
+            other => Err(ToolError::InvalidParams(format!(
+                "Unknown coverage backend: {}. Use: llvm-cov, tarpaulin",
+                other
+            ))),
+        }
+    }
+
+    async fn installed(self) -> bool {
+        Command::new("cargo")
+            .arg(self.name())
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .is_ok_and(|status| status.success())
+    }
+}
+
+/// Why coverage could not be measured.
+#[derive(Debug, thiserror::Error)]
+pub enum CoverageError {
+    /// Neither cargo-llvm-cov nor cargo-tarpaulin is installed
+    #[error("{0}")]
+    NotInstalled(String),
+    #[error(transparent)]
+    Failed(#[from] ToolError),
+}
+
+impl From<CoverageError> for ToolError {
+    fn from(error: CoverageError) -> Self {
+        match error {
+            CoverageError::NotInstalled(message) => ToolError::Execution(message),
+            CoverageError::Failed(error) => error,
+        }
+    }
+}
+
+/// Translate a file glob into a regex for `--ignore-filename-regex`.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c if "\\.+()|[]{}^$".contains(c) => {
+                regex.push('\\');
+                regex.push(c);
+            }
+            c => regex.push(c),
+        }
+    }
+    regex
 }
 
 #[derive(Debug)]
@@ -25,64 +115,119 @@ pub struct CoverageTool {
 
 impl CoverageTool {
     pub fn new() -> Self {
-        Self {
-            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        Self::with_workspace(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+    }
+
+    /// Measure and record coverage for `workspace` instead of the current directory.
+    pub fn with_workspace(workspace: PathBuf) -> Self {
+        Self { workspace }
+    }
+
+    /// Run the installed backend over the crate at `dir`, with file paths
+    /// relative to it.
+    pub(crate) async fn measure(dir: &Path) -> Result<CoverageReport, ToolError> {
+        let tool = Self {
+            workspace: dir.to_path_buf(),
+        };
+        let params = CoverageParams::default();
+        let backend = tool.select_backend(&params).await?;
+        let mut report = tool.collect_report(backend, &params).await?;
+        report.relativize(&dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf()));
+        Ok(report)
+    }
+
+    fn target_dir(&self, params: &CoverageParams) -> PathBuf {
+        match &params.path {
+            Some(path) => self.workspace.join(path),
+            None => self.workspace.clone(),
+        }
+    }
+
+    async fn select_backend(&self, params: &CoverageParams) -> Result<Backend, CoverageError> {
+        if let Some(name) = &params.backend {
+            let backend = Backend::parse(name)?;
+            if !backend.installed().await {
+                return Err(CoverageError::NotInstalled(format!(
+                    "cargo-{0} not installed. Install with: cargo install cargo-{0}",
+                    backend.name()
+                )));
+            }
+            return Ok(backend);
+        }
+
+        for backend in [Backend::LlvmCov, Backend::Tarpaulin] {
+            if backend.installed().await {
+                return Ok(backend);
+            }
+        }
+        Err(CoverageError::NotInstalled(
+            "No coverage backend installed. Install with: cargo install cargo-llvm-cov (or cargo-tarpaulin)"
+                .to_string(),
+        ))
+    }
+
+    fn llvm_cov_command(&self, params: &CoverageParams, report: &Path) -> Command {
+        let mut cmd = Command::new("cargo");
+        cmd.arg("llvm-cov");
+        match params.format.as_deref() {
+            Some("cobertura") => cmd.arg("--cobertura"),
+            _ => cmd.arg("--lcov"),
+        };
+        cmd.arg("--output-path").arg(report);
+
+        let excludes = params
+            .exclude_files
+            .as_ref()
+            .filter(|excludes| !excludes.is_empty());
+        if let Some(excludes) = excludes {
+            let patterns: Vec<String> = excludes.iter().map(|g| glob_to_regex(g)).collect();
+            cmd.arg("--ignore-filename-regex").arg(patterns.join("|"));
         }
+
+        cmd.arg("--all-features");
+        cmd.arg("--workspace");
+        cmd
     }
 
-    async fn run_tarpaulin(&self, params: &CoverageParams) -> Result<String, ToolError> {
+    fn tarpaulin_command(&self, params: &CoverageParams, output_dir: &Path) -> Command {
         let mut cmd = Command::new("cargo");
         cmd.arg("tarpaulin");
 
-        // Output format
+        cmd.arg("--out");
         match params.format.as_deref() {
-            Some("json") => {
-                cmd.arg("--out");
-                cmd.arg("Json");
-            }
-            Some("html") => {
-                cmd.arg("--out");
-                cmd.arg("Html");
-            }
-            Some("lcov") => {
-                cmd.arg("--out");
-                cmd.arg("Lcov");
-            }
-            _ => {
-                // Default to stdout for text summary
-                cmd.arg("--out");
-                cmd.arg("Stdout");
-            }
-        }
+            Some("text") => cmd.arg("Stdout"),
+            Some("cobertura") => cmd.arg("Xml"),
+            _ => cmd.arg("Lcov"),
+        };
+        cmd.arg("--output-dir").arg(output_dir);
 
         // Exclude files
-        if let Some(excludes) = &params.exclude_files {
-            cmd.arg("--exclude-files");
-            cmd.arg(excludes.join(" "));
-        } else {
+        cmd.arg("--exclude-files");
+        match &params.exclude_files {
+            Some(excludes) => cmd.args(excludes),
             // Default exclusions
-            cmd.arg("--exclude-files");
-            cmd.arg("target/*");
-        }
+            None => cmd.arg("target/*"),
+        };
 
         // Additional useful flags
         cmd.arg("--all-features");
         cmd.arg("--workspace");
         cmd.arg("--timeout");
         cmd.arg("120");
+        cmd
+    }
 
-        // Set working directory
-        if let Some(path) = &params.path {
-            let target_path = self.workspace.join(path);
-            cmd.current_dir(target_path);
-        } else {
-            cmd.current_dir(&self.workspace);
-        }
-
+    async fn run_backend(
+        &self,
+        backend: Backend,
+        mut cmd: Command,
+        params: &CoverageParams,
+    ) -> Result<String, ToolError> {
+        cmd.current_dir(self.target_dir(params));
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
 
-        info!("Running cargo tarpaulin for coverage analysis");
+        info!("Running cargo {} for coverage analysis", backend.name());
         debug!("Command: {:?}", cmd);
 
         let timeout_duration = Duration::from_secs(180); // 3 minutes
@@ -93,9 +238,13 @@ impl CoverageTool {
                     Ok(String::from_utf8_lossy(&output.stdout).to_string())
                 } else {
                     let stderr = String::from_utf8_lossy(&output.stderr);
+                    let label = match backend {
+                        Backend::LlvmCov => "cargo llvm-cov",
+                        Backend::Tarpaulin => "Tarpaulin",
+                    };
                     Err(ToolError::Execution(format!(
-                        "Tarpaulin failed: {}",
-                        stderr
+                        "{} failed: {}",
+                        label, stderr
                     )))
                 }
             }
@@ -106,6 +255,214 @@ impl CoverageTool {
         }
     }
 
+    /// Run `backend` and parse the LCOV or Cobertura report it writes.
+    async fn collect_report(
+        &self,
+        backend: Backend,
+        params: &CoverageParams,
+    ) -> Result<CoverageReport, ToolError> {
+        let output_dir = tempfile::TempDir::new()
+            .map_err(|e| ToolError::Execution(format!("Failed to create report dir: {}", e)))?;
+        let cobertura = params.format.as_deref() == Some("cobertura");
+
+        let report_path = match backend {
+            Backend::LlvmCov => {
+                let file = if cobertura {
+                    "cobertura.xml"
+                } else {
+                    "lcov.info"
+                };
+                let path = output_dir.path().join(file);
+                let cmd = self.llvm_cov_command(params, &path);
+                self.run_backend(backend, cmd, params).await?;
+                path
+            }
+            Backend::Tarpaulin => {
+                let cmd = self.tarpaulin_command(params, output_dir.path());
+                self.run_backend(backend, cmd, params).await?;
+                let file = if cobertura {
+                    "cobertura.xml"
+                } else {
+                    "lcov.info"
+                };
+                output_dir.path().join(file)
+            }
+        };
+
+        let text = tokio::fs::read_to_string(&report_path).await.map_err(|e| {
+            ToolError::Execution(format!(
+                "cargo {} did not write {}: {}",
+                backend.name(),
+                report_path.display(),
+                e
+            ))
+        })?;
+        Ok(coverage_report::parse(&text))
+    }
+
+    async fn git(&self, params: &CoverageParams, args: &[&str]) -> Result<String, ToolError> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(self.target_dir(params))
+            .output()
+            .await
+            .map_err(|e| ToolError::Execution(format!("Failed to run git: {}", e)))?;
+        if !output.status.success() {
+            return Err(ToolError::Execution(format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Lines added or modified since the merge base with `base`, including
+    /// uncommitted changes and untracked files.
+    async fn changed_lines(
+        &self,
+        params: &CoverageParams,
+        base: &str,
+    ) -> Result<(String, BTreeMap<String, Vec<usize>>), ToolError> {
+        let merge_base = self
+            .git(params, &["merge-base", base, "HEAD"])
+            .await
+            .map_err(|e| ToolError::InvalidParams(format!("Cannot diff against {}: {}", base, e)))?
+            .trim()
+            .to_string();
+
+        let diff = self
+            .git(
+                params,
+                &[
+                    "diff",
+                    "--unified=0",
+                    "--no-color",
+                    "--no-ext-diff",
+                    "--relative",
+                    &merge_base,
+                ],
+            )
+            .await?;
+        let mut changed = coverage_report::changed_lines(&diff);
+
+        let untracked = self
+            .git(params, &["ls-files", "--others", "--exclude-standard"])
+            .await?;
+        let root = self.target_dir(params);
+        for file in untracked.lines() {
+            if let Ok(text) = std::fs::read_to_string(root.join(file)) {
+                changed.insert(file.to_string(), (1..=text.lines().count()).collect());
+            }
+        }
+        Ok((merge_base, changed))
+    }
+
+    async fn report_json(
+        &self,
+        source: &str,
+        mut report: CoverageReport,
+        params: &CoverageParams,
+    ) -> Result<Value, ToolError> {
+        let root = self
+            .target_dir(params)
+            .canonicalize()
+            .unwrap_or_else(|_| self.target_dir(params));
+        report.relativize(&root);
+
+        let mut diff: Option<(String, DiffCoverage)> = None;
+        if let Some(base) = &params.base {
+            let (merge_base, changed) = self.changed_lines(params, base).await?;
+            diff = Some((
+                merge_base,
+                coverage_report::diff_coverage(&report, &changed),
+            ));
+        }
+
+        if let Some(minimum) = params.min_coverage {
+            match &diff {
+                Some((_, diff)) if diff.percent < minimum => {
+                    let listing: Vec<String> = diff
+                        .files
+                        .iter()
+                        .filter(|f| !f.uncovered.is_empty())
+                        .map(|f| {
+                            let ranges: Vec<String> = f
+                                .uncovered
+                                .iter()
+                                .map(|r| {
+                                    if r.start == r.end {
+                                        r.start.to_string()
+                                    } else {
+                                        format!("{}-{}", r.start, r.end)
+                                    }
+                                })
+                                .collect();
+                            format!("{}:{}", f.file, ranges.join(","))
+                        })
+                        .collect();
+                    return Err(ToolError::Execution(format!(
+                        "Diff coverage {:.2}% is below the minimum of {:.2}% ({}/{} changed lines). Uncovered: {}",
+                        diff.percent,
+                        minimum,
+                        diff.lines_covered,
+                        diff.lines_changed,
+                        listing.join(" ")
+                    )));
+                }
+                None if report.percent < minimum => {
+                    let mut worst: Vec<_> = report.files.iter().collect();
+                    worst.sort_by(|a, b| a.percent.total_cmp(&b.percent));
+                    let listing: Vec<String> = worst
+                        .iter()
+                        .take(5)
+                        .map(|f| format!("{} {:.2}%", f.file, f.percent))
+                        .collect();
+                    return Err(ToolError::Execution(format!(
+                        "Coverage {:.2}% is below the minimum of {:.2}% ({}/{} lines). Lowest: {}",
+                        report.percent,
+                        minimum,
+                        report.lines_hit,
+                        report.lines_total,
+                        listing.join(", ")
+                    )));
+                }
+                _ => {}
+            }
+        }
+
+        let mut result = serde_json::json!({
+            "backend": source,
+            "coverage_percent": report.percent,
+            "lines_hit": report.lines_hit,
+            "lines_total": report.lines_total,
+            "uncovered_lines": report.lines_total - report.lines_hit,
+            "functions_hit": report.functions_hit,
+            "functions_total": report.functions_total,
+            "files_analyzed": report.files.len(),
+            "min_coverage": params.min_coverage,
+            "files": report.files,
+        });
+        if let (Some((merge_base, diff)), Some(base)) = (diff, &params.base) {
+            result["diff_coverage"] = serde_json::json!({
+                "base": base,
+                "merge_base": merge_base,
+                "percent": diff.percent,
+                "lines_changed": diff.lines_changed,
+                "lines_covered": diff.lines_covered,
+                "files": diff.files,
+            });
+        }
+
+        let metrics = Metrics {
+            coverage_percent: Some(report.percent),
+            ..Metrics::default()
+        };
+        let scope = history::scope(params.path.as_deref());
+        history::record(&self.workspace, &scope, "coverage", metrics).await;
+        Ok(result)
+    }
+
     fn parse_coverage_output(&self, output: String) -> Result<Value, ToolError> {
         // If it's JSON, parse it directly
         if let Ok(json) = serde_json::from_str::<Value>(&output) {
@@ -181,32 +538,42 @@ impl Tool for CoverageTool {
     }
 
     fn description(&self) -> &str {
-        "Run cargo-tarpaulin for real code coverage analysis"
+        "Run cargo-llvm-cov or cargo-tarpaulin for per-file and per-function code coverage"
     }
 
     async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        Ok(self.run(params).await?)
+    }
+}
+
+impl CoverageTool {
+    /// [`Tool::execute`], telling a missing backend apart from a failed run.
+    pub(crate) async fn run(&self, params: Value) -> Result<Value, CoverageError> {
         let params: CoverageParams =
             serde_json::from_value(params).map_err(|e| ToolError::InvalidParams(e.to_string()))?;
 
-        // Check if tarpaulin is installed
-        let check_cmd = Command::new("cargo")
-            .arg("tarpaulin")
-            .arg("--version")
-            .output()
-            .await;
-
-        if check_cmd.is_err() || !check_cmd.unwrap().status.success() {
-            return Err(ToolError::Execution(
-                "cargo-tarpaulin not installed. Install with: cargo install cargo-tarpaulin"
-                    .to_string(),
-            ));
+        if let Some(path) = &params.report {
+            let text = tokio::fs::read_to_string(self.workspace.join(path))
+                .await
+                .map_err(|e| ToolError::NotFound(format!("Coverage report {}: {}", path, e)))?;
+            return Ok(self
+                .report_json("report", coverage_report::parse(&text), &params)
+                .await?);
         }
 
-        // Run tarpaulin
-        let output = self.run_tarpaulin(&params).await?;
+        let backend = self.select_backend(&params).await?;
+
+        // Tarpaulin's own text summary, without per-line data
+        if backend == Backend::Tarpaulin && params.format.as_deref() == Some("text") {
+            let output_dir = tempfile::TempDir::new()
+                .map_err(|e| ToolError::Execution(format!("Failed to create report dir: {}", e)))?;
+            let cmd = self.tarpaulin_command(&params, output_dir.path());
+            let output = self.run_backend(backend, cmd, &params).await?;
+            return Ok(self.parse_coverage_output(output)?);
+        }
 
-        // Parse and return results
-        self.parse_coverage_output(output)
+        let report = self.collect_report(backend, &params).await?;
+        Ok(self.report_json(backend.name(), report, &params).await?)
     }
 }
 
@@ -220,7 +587,7 @@ mod tests {
         assert_eq!(tool.name(), "coverage");
         assert_eq!(
             tool.description(),
-            "Run cargo-tarpaulin for real code coverage analysis"
+            "Run cargo-llvm-cov or cargo-tarpaulin for per-file and per-function code coverage"
         );
     }
 
@@ -250,4 +617,97 @@ Coverage Results:
         assert_eq!(result["uncovered_lines"], 264);
         assert_eq!(result["files_analyzed"], 5);
     }
+
+    #[tokio::test]
+    async fn test_existing_report_and_min_coverage() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let lcov = format!(
+            "SF:{}/src/lib.rs\nFN:1,covered\nFNDA:1,covered\nDA:1,1\nDA:2,1\nDA:3,0\nDA:4,0\nend_of_record\n",
+            dir.path().canonicalize().unwrap().display()
+        );
+        std::fs::write(dir.path().join("lcov.info"), lcov).unwrap();
+        let tool = CoverageTool {
+            workspace: dir.path().to_path_buf(),
+        };
+
+        let result = tool
+            .execute(serde_json::json!({ "report": "lcov.info" }))
+            .await
+            .unwrap();
+        assert_eq!(result["backend"], "report");
+        assert_eq!(result["coverage_percent"], 50.0);
+        assert_eq!(result["files"][0]["file"], "src/lib.rs");
+        assert_eq!(result["files"][0]["uncovered"][0]["start"], 3);
+        assert_eq!(result["files"][0]["uncovered"][0]["end"], 4);
+        assert_eq!(result["files"][0]["functions"][0]["name"], "covered");
+
+        let err = tool
+            .execute(serde_json::json!({ "report": "lcov.info", "min_coverage": 80.0 }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("below the minimum of 80.00%"));
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert_eq!(glob_to_regex("target/*"), "target/.*");
+        assert_eq!(glob_to_regex("src/*.rs"), "src/.*\\.rs");
+        assert!(Backend::parse("grcov").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_diff_coverage_against_base() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(dir.path())
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "a\nb\nc\n").unwrap();
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["commit", "-qm", "base"]);
+        std::fs::write(dir.path().join("src/lib.rs"), "a\nb\nc\nd\ne\n").unwrap();
+
+        let lcov = format!(
+            "SF:{}/src/lib.rs\nDA:1,1\nDA:2,0\nDA:4,1\nDA:5,0\nend_of_record\n",
+            dir.path().canonicalize().unwrap().display()
+        );
+        std::fs::write(dir.path().join("lcov.info"), lcov).unwrap();
+        let tool = CoverageTool {
+            workspace: dir.path().to_path_buf(),
+        };
+
+        let result = tool
+            .execute(serde_json::json!({ "report": "lcov.info", "base": "HEAD" }))
+            .await
+            .unwrap();
+        let diff = &result["diff_coverage"];
+        assert_eq!(diff["lines_changed"], 2);
+        assert_eq!(diff["percent"], 50.0);
+        assert_eq!(diff["files"][0]["file"], "src/lib.rs");
+        assert_eq!(diff["files"][0]["uncovered"][0]["start"], 5);
+
+        // The threshold applies to changed lines, not the whole file
+        let err = tool
+            .execute(serde_json::json!({
+                "report": "lcov.info",
+                "base": "HEAD",
+                "min_coverage": 60.0
+            }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("src/lib.rs:5"));
+
+        assert!(tool
+            .execute(serde_json::json!({ "report": "lcov.info", "base": "no-such-ref" }))
+            .await
+            .is_err());
+    }
 }
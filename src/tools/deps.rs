@@ -0,0 +1,224 @@
+use crate::analysis::dependencies;
+use crate::tools::{Tool, ToolError};
+use crate::watcher;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Component, PathBuf};
+use tracing::info;
+
+/// Heaviest subtrees listed unless `top` says otherwise.
+const DEFAULT_TOP: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DepsParams {
+    #[serde(default = "default_path")]
+    path: String,
+    /// Include the whole graph as "json" nodes and edges, or as "dot" source
+    #[serde(default)]
+    format: Option<String>,
+    /// File, relative to the workspace, to write the exported graph to
+    #[serde(default)]
+    output: Option<String>,
+    /// Number of heaviest direct dependencies to report
+    #[serde(default)]
+    top: Option<usize>,
+}
+
+fn default_path() -> String {
+    ".".to_string()
+}
+
+#[derive(Debug)]
+pub struct DepsTool {
+    workspace: PathBuf,
+}
+
+impl DepsTool {
+    pub fn new() -> Self {
+        Self {
+            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        }
+    }
+
+    fn resolve(&self, path: &str) -> Result<PathBuf, ToolError> {
+        let relative = PathBuf::from(path);
+        if relative.is_absolute()
+            || relative
+                .components()
+                .any(|c| matches!(c, Component::ParentDir))
+        {
+            return Err(ToolError::InvalidParams(
+                "Path must be within workspace".to_string(),
+            ));
+        }
+        Ok(self.workspace.join(relative))
+    }
+}
+
+impl Default for DepsTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for DepsTool {
+    fn name(&self) -> &str {
+        "deps"
+    }
+
+    fn description(&self) -> &str {
+        "Build the dependency graph from Cargo, npm and Python manifests and lockfiles; report duplicate versions, heaviest subtrees and likely-unused dependencies, and export JSON or DOT"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: DepsParams =
+            serde_json::from_value(params).map_err(|e| ToolError::InvalidParams(e.to_string()))?;
+        let format = params.format.as_deref();
+        if !matches!(format, None | Some("json") | Some("dot")) {
+            return Err(ToolError::InvalidParams(format!(
+                "Unknown format: {}. Use: json, dot",
+                format.unwrap_or_default()
+            )));
+        }
+        if params.output.is_some() && format.is_none() {
+            return Err(ToolError::InvalidParams(
+                "output requires format \"json\" or \"dot\"".to_string(),
+            ));
+        }
+
+        let target = self.resolve(&params.path)?;
+        if !target.exists() {
+            return Err(ToolError::NotFound(format!(
+                "Path not found: {}",
+                params.path
+            )));
+        }
+        info!("Analyzing dependencies in {}", params.path);
+
+        let workspace = self.workspace.clone();
+        let top = params.top.unwrap_or(DEFAULT_TOP);
+        let (graph, report) = tokio::task::spawn_blocking(move || {
+            let graph = dependencies::load(&workspace, &target);
+            let report = graph.report(&workspace, top);
+            (graph, report)
+        })
+        .await
+        .map_err(|e| ToolError::Execution(e.to_string()))?;
+        if graph.manifests.is_empty() && report.errors.is_empty() {
+            return Err(ToolError::NotFound(format!(
+                "No Cargo.toml, package.json, pyproject.toml or requirements file under {}",
+                params.path
+            )));
+        }
+
+        let mut result =
+            serde_json::to_value(&report).map_err(|e| ToolError::Execution(e.to_string()))?;
+        let export = match format {
+            Some("json") => Some(
+                serde_json::to_value(graph.export())
+                    .map_err(|e| ToolError::Execution(e.to_string()))?,
+            ),
+            Some(_) => Some(Value::from(graph.to_dot())),
+            None => None,
+        };
+        let Some(export) = export else {
+            return Ok(result);
+        };
+
+        match &params.output {
+            Some(output) => {
+                let text = match &export {
+                    Value::String(dot) => dot.clone(),
+                    graph => serde_json::to_string_pretty(graph)
+                        .map_err(|e| ToolError::Execution(e.to_string()))?,
+                };
+                let dest = self.resolve(output)?;
+                if let Some(parent) = dest.parent() {
+                    tokio::fs::create_dir_all(parent)
+                        .await
+                        .map_err(|e| ToolError::Execution(e.to_string()))?;
+                }
+                tokio::fs::write(&dest, &text).await.map_err(|e| {
+                    ToolError::Execution(format!("Failed to write {}: {}", output, e))
+                })?;
+                watcher::record_write(&dest, text.as_bytes());
+                result["output"] = Value::from(output.as_str());
+            }
+            None => {
+                let key = if format == Some("dot") {
+                    "dot"
+                } else {
+                    "graph"
+                };
+                result[key] = export;
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn tool(dir: &TempDir) -> DepsTool {
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"app\"\n\n[dependencies]\nanyhow = \"1\"\nlog = \"0.4\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/main.rs"),
+            "fn main() -> anyhow::Result<()> { Ok(()) }\n",
+        )
+        .unwrap();
+        DepsTool {
+            workspace: dir.path().to_path_buf(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deps_report_and_exports() {
+        let dir = TempDir::new().unwrap();
+        let tool = tool(&dir);
+
+        let result = tool.execute(json!({})).await.unwrap();
+        assert_eq!(result["summary"]["direct_dependencies"], 2);
+        assert_eq!(result["unused"][0]["name"], "log");
+        assert!(result.get("graph").is_none());
+
+        let result = tool.execute(json!({"format": "json"})).await.unwrap();
+        assert_eq!(result["graph"]["edges"].as_array().unwrap().len(), 2);
+
+        let result = tool
+            .execute(json!({"format": "dot", "output": "out/deps.dot"}))
+            .await
+            .unwrap();
+        assert_eq!(result["output"], "out/deps.dot");
+        let dot = std::fs::read_to_string(dir.path().join("out/deps.dot")).unwrap();
+        assert!(dot.contains("\"Cargo.toml\" -> \"cargo:log@0.4\";"));
+    }
+
+    #[tokio::test]
+    async fn test_deps_invalid_params() {
+        let dir = TempDir::new().unwrap();
+        let tool = tool(&dir);
+
+        for params in [
+            json!({"format": "svg"}),
+            json!({"output": "deps.json"}),
+            json!({"path": "../elsewhere"}),
+            json!({"format": "dot", "output": "/tmp/deps.dot"}),
+        ] {
+            let err = tool.execute(params.clone()).await.unwrap_err();
+            assert!(matches!(err, ToolError::InvalidParams(_)), "{}", params);
+        }
+        let err = tool.execute(json!({"path": "missing"})).await.unwrap_err();
+        assert!(matches!(err, ToolError::NotFound(_)));
+    }
+}
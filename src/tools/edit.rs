@@ -0,0 +1,345 @@
+//! Byte-range text edits, unified diffs and atomic file writes shared by the
+//! tools that rewrite source files.
+
+use crate::watcher;
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Replace `start..end` (byte offsets) with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    pub fn new(start: usize, end: usize, replacement: impl Into<String>) -> Self {
+        Self {
+            start,
+            end,
+            replacement: replacement.into(),
+        }
+    }
+
+    /// Whether applying both edits would be ambiguous. Two insertions at the
+    /// same offset conflict, since their order is undefined.
+    pub fn conflicts_with(&self, other: &TextEdit) -> bool {
+        if self == other {
+            return false;
+        }
+        let overlap = self.start < other.end && other.start < self.end;
+        let same_point = self.start == other.start && (self.is_empty() || other.is_empty());
+        overlap || same_point
+    }
+
+    fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Apply non-conflicting edits; duplicates are applied once.
+///
+/// Edits that fall outside `source` or off a char boundary are ignored.
+pub fn apply(source: &str, edits: &[TextEdit]) -> String {
+    let mut edits: Vec<&TextEdit> = edits
+        .iter()
+        .filter(|e| {
+            e.start <= e.end
+                && e.end <= source.len()
+                && source.is_char_boundary(e.start)
+                && source.is_char_boundary(e.end)
+        })
+        .collect();
+    edits.sort_by_key(|e| (e.start, e.end));
+    edits.dedup();
+
+    let mut out = String::with_capacity(source.len());
+    let mut cursor = 0;
+    for edit in edits {
+        if edit.start < cursor {
+            continue;
+        }
+        out.push_str(&source[cursor..edit.start]);
+        out.push_str(&edit.replacement);
+        cursor = edit.end;
+    }
+    out.push_str(&source[cursor..]);
+    out
+}
+
+/// Write `contents` to a temporary file beside `path` and rename it over
+/// `path`, so readers never see a partial file.
+pub fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty());
+    let mut file = tempfile::NamedTempFile::new_in(dir.unwrap_or(Path::new(".")))?;
+    file.write_all(contents.as_bytes())?;
+    if let Ok(metadata) = std::fs::metadata(path) {
+        file.as_file().set_permissions(metadata.permissions())?;
+    }
+    file.persist(path).map_err(|e| e.error)?;
+    watcher::record_write(path, contents.as_bytes());
+    Ok(())
+}
+
+/// New contents for one file of a change spanning several files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    /// Workspace-relative path shown in diffs
+    pub file: String,
+    pub original: String,
+    pub updated: String,
+}
+
+impl FileChange {
+    pub fn diff(&self) -> String {
+        unified_diff(&self.file, &self.original, &self.updated)
+    }
+}
+
+/// Write every change or none of them.
+///
+/// All new contents are staged in temporary files first, and nothing is
+/// replaced if any file no longer holds its `original` contents, so edits
+/// made since the change was planned are never overwritten. Files already
+/// replaced are restored if a later rename fails.
+pub fn write_all_atomic(changes: &[FileChange]) -> std::io::Result<()> {
+    let mut staged = Vec::with_capacity(changes.len());
+    for change in changes {
+        if std::fs::read_to_string(&change.path)? != change.original {
+            return Err(std::io::Error::other(format!(
+                "{} changed on disk",
+                change.file
+            )));
+        }
+        let dir = change.path.parent().filter(|p| !p.as_os_str().is_empty());
+        let mut file = tempfile::NamedTempFile::new_in(dir.unwrap_or(Path::new(".")))?;
+        file.write_all(change.updated.as_bytes())?;
+        file.as_file()
+            .set_permissions(std::fs::metadata(&change.path)?.permissions())?;
+        staged.push(file);
+    }
+
+    for (done, (file, change)) in staged.into_iter().zip(changes).enumerate() {
+        if let Err(e) = file.persist(&change.path) {
+            for earlier in &changes[..done] {
+                let _ = write_atomic(&earlier.path, &earlier.original);
+            }
+            return Err(e.error);
+        }
+        watcher::record_write(&change.path, change.updated.as_bytes());
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Largest middle section diffed line by line; beyond this the changed
+/// region is reported as one replacement.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+fn line_ops(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut ops = vec![Op::Equal; prefix];
+    if a.len() * b.len() > MAX_DIFF_CELLS {
+        ops.extend(std::iter::repeat_n(Op::Delete, a.len()));
+        ops.extend(std::iter::repeat_n(Op::Insert, b.len()));
+    } else {
+        // Longest common subsequence table, filled from the end
+        let width = b.len() + 1;
+        let mut lcs = vec![0u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i * width + j] = if a[i] == b[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push(Op::Equal);
+                i += 1;
+                j += 1;
+            } else if i < a.len()
+                && (j == b.len() || lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+            {
+                // Deletions go before insertions, as in `diff -u`
+                ops.push(Op::Delete);
+                i += 1;
+            } else {
+                ops.push(Op::Insert);
+                j += 1;
+            }
+        }
+    }
+    ops.extend(std::iter::repeat_n(Op::Equal, suffix));
+    ops
+}
+
+/// A unified diff of `old` and `new` with three lines of context, or an
+/// empty string when they are equal.
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    const CONTEXT: usize = 3;
+    if old == new {
+        return String::new();
+    }
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let ops = line_ops(&a, &b);
+
+    // Old and new line index reached before each op
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for op in &ops {
+        positions.push((i, j));
+        match op {
+            Op::Equal => {
+                i += 1;
+                j += 1;
+            }
+            Op::Delete => i += 1,
+            Op::Insert => j += 1,
+        }
+    }
+    positions.push((i, j));
+
+    let mut out = format!("--- a/{}\n+++ b/{}\n", path, path);
+    let changes: Vec<usize> = (0..ops.len()).filter(|&k| ops[k] != Op::Equal).collect();
+    let mut k = 0;
+    while k < changes.len() {
+        // Extend the hunk while the next change is within two contexts
+        let first = changes[k];
+        let mut last = first;
+        while k + 1 < changes.len() && changes[k + 1] - last <= 2 * CONTEXT {
+            k += 1;
+            last = changes[k];
+        }
+        k += 1;
+
+        let start = first.saturating_sub(CONTEXT);
+        let end = (last + 1 + CONTEXT).min(ops.len());
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start)
+        ));
+        for (op, &(i, j)) in ops[start..end].iter().zip(&positions[start..end]) {
+            match op {
+                Op::Equal => out.push_str(&format!(" {}\n", a[i])),
+                Op::Delete => out.push_str(&format!("-{}\n", a[i])),
+                Op::Insert => out.push_str(&format!("+{}\n", b[j])),
+            }
+        }
+    }
+    out
+}
+
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        // An empty range names the line before it
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_conflicts() {
+        let source = "let x = 1;\nreturn x;\n";
+        let edits = vec![
+            TextEdit::new(11, 18, ""),
+            TextEdit::new(4, 5, "y"),
+            TextEdit::new(4, 5, "y"),
+        ];
+        assert_eq!(apply(source, &edits), "let y = 1;\nx;\n");
+
+        let a = TextEdit::new(0, 5, "a");
+        assert!(a.conflicts_with(&TextEdit::new(4, 6, "b")));
+        assert!(!a.conflicts_with(&TextEdit::new(5, 6, "b")));
+        assert!(!a.conflicts_with(&a.clone()));
+        assert!(TextEdit::new(3, 3, "x").conflicts_with(&TextEdit::new(3, 3, "y")));
+        assert!(TextEdit::new(3, 3, "x").conflicts_with(&TextEdit::new(3, 5, "y")));
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let diff = unified_diff("src/x.rs", old, new);
+        assert_eq!(
+            diff,
+            "--- a/src/x.rs\n+++ b/src/x.rs\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -9,3 +9,4 @@\n i\n j\n k\n+l\n"
+        );
+        assert_eq!(unified_diff("x", "same\n", "same\n"), "");
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("f.txt");
+        std::fs::write(&path, "old").unwrap();
+        write_atomic(&path, "new").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_write_all_atomic() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let change = |name: &str, original: &str, updated: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, original).unwrap();
+            FileChange {
+                path,
+                file: name.to_string(),
+                original: original.to_string(),
+                updated: updated.to_string(),
+            }
+        };
+        let a = change("a.rs", "struct Old;\n", "struct New;\n");
+        let b = change("b.rs", "use Old;\n", "use New;\n");
+        assert_eq!(
+            a.diff(),
+            "--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-struct Old;\n+struct New;\n"
+        );
+
+        // b.rs was edited after the change was planned: nothing is written
+        std::fs::write(&b.path, "use Old; // edited\n").unwrap();
+        let err = write_all_atomic(&[a.clone(), b.clone()]).unwrap_err();
+        assert!(err.to_string().contains("b.rs changed on disk"));
+        assert_eq!(std::fs::read_to_string(&a.path).unwrap(), "struct Old;\n");
+
+        std::fs::write(&b.path, "use Old;\n").unwrap();
+        write_all_atomic(&[a.clone(), b.clone()]).unwrap();
+        assert_eq!(std::fs::read_to_string(&a.path).unwrap(), "struct New;\n");
+        assert_eq!(std::fs::read_to_string(&b.path).unwrap(), "use New;\n");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -1,4 +1,5 @@
 use super::{Tool, ToolError};
+use crate::watcher;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -89,6 +90,7 @@ impl Tool for FileWriteTool {
                 .await
                 .map_err(|e| ToolError::Execution(format!("Failed to write file: {}", e)))?;
         }
+        watcher::record_write(&path, params.content.as_bytes());
 
         Ok(serde_json::json!({
             "path": path.to_string_lossy(),
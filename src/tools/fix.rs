@@ -1,10 +1,16 @@
 use super::{Tool, ToolError};
+use crate::analysis::diagnostics::{parse_cargo_messages, Diagnostic, Suggestion};
+use crate::analysis::lexer::Syntax;
+use crate::analysis::{display_path, source_files};
+use crate::tools::edit::{self, TextEdit};
+use crate::tools::formatters;
 use crate::tools::pmat::PmatTool;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::path::PathBuf;
-use tracing::info;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct FixParams {
@@ -32,9 +38,10 @@ pub struct FixTool {
 
 impl FixTool {
     pub fn new() -> Self {
+        let workspace = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
         Self {
-            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
-            pmat: PmatTool::new(),
+            pmat: PmatTool::with_workspace(workspace.clone()),
+            workspace,
         }
     }
 
@@ -85,147 +92,301 @@ impl FixTool {
         Ok(results)
     }
 
-    async fn fix_format(&self, path: &str, dry_run: bool) -> Result<Vec<FixResult>, ToolError> {
-        let mut results = Vec::new();
+    async fn fix_format(&self, path: &str, dry_run: bool) -> Result<FormatOutcome, ToolError> {
+        let target = self.workspace.join(path);
+        if !target.exists() {
+            return Err(ToolError::NotFound(format!("Path not found: {}", path)));
+        }
 
-        // For Rust files, use rustfmt
-        let target_path = PathBuf::from(path);
-        if target_path.is_file() && path.ends_with(".rs") {
-            results.push(self.fix_rust_format(path, dry_run).await?);
-        } else if target_path.is_dir() {
-            // Find all Rust files
-            for entry in walkdir::WalkDir::new(&target_path)
-                .into_iter()
-                .filter_map(|e| e.ok())
-                .filter(|e| e.path().extension().is_some_and(|ext| ext == "rs"))
-                .filter(|e| !e.path().to_string_lossy().contains("target"))
-            {
-                if entry.file_type().is_file() {
-                    results.push(
-                        self.fix_rust_format(entry.path().to_str().unwrap(), dry_run)
-                            .await?,
-                    );
-                }
+        let mut outcome = FormatOutcome::default();
+        // Per language: the formatter used (if any) and the file counts
+        let mut usage: BTreeMap<&'static str, FormatterUsage> = BTreeMap::new();
+
+        for file in source_files(&target, Syntax::EXTENSIONS) {
+            let Some(syntax) = Syntax::from_path(&file) else {
+                continue;
+            };
+            let candidates = formatters::candidates(&file, &self.workspace, syntax);
+            let formatter = candidates
+                .iter()
+                .copied()
+                .find(|f| formatters::is_available(*f));
+            let language = formatters::language_name(syntax);
+            let entry = usage.entry(language).or_insert_with(|| FormatterUsage {
+                language,
+                formatter: None,
+                tried: candidates.iter().map(|f| f.name).collect(),
+                files: 0,
+                changed: 0,
+                failed: 0,
+            });
+            entry.files += 1;
+            let Some(formatter) = formatter else {
+                continue;
+            };
+            entry.formatter.get_or_insert(formatter.name);
+
+            let display = display_path(&self.workspace, &file);
+            let source = std::fs::read_to_string(&file)
+                .map_err(|e| ToolError::Execution(format!("Failed to read {}: {}", display, e)))?;
+            let formatted =
+                match formatters::format_source(formatter, &file, &self.workspace, &source).await {
+                    Ok(formatted) => formatted,
+                    Err(error) => {
+                        entry.failed += 1;
+                        outcome.results.push(FixResult {
+                            file: display,
+                            issue: "Code formatting".to_string(),
+                            fixed: false,
+                            description: error,
+                        });
+                        continue;
+                    }
+                };
+
+            if formatted == source {
+                continue;
             }
+            entry.changed += 1;
+            if !dry_run {
+                edit::write_atomic(&file, &formatted).map_err(|e| {
+                    ToolError::Execution(format!("Failed to write {}: {}", display, e))
+                })?;
+            }
+            outcome.diffs.push(FileDiff {
+                diff: edit::unified_diff(&display, &source, &formatted),
+                file: display.clone(),
+            });
+            outcome.results.push(FixResult {
+                file: display,
+                issue: "Code formatting".to_string(),
+                fixed: !dry_run,
+                description: if dry_run {
+                    format!("Would format with {}", formatter.name)
+                } else {
+                    format!("Formatted with {}", formatter.name)
+                },
+            });
         }
 
-        Ok(results)
+        let (available, unavailable): (Vec<_>, Vec<_>) =
+            usage.into_values().partition(|u| u.formatter.is_some());
+        outcome.formatters = available;
+        outcome.unavailable = unavailable;
+        Ok(outcome)
     }
 
-    async fn fix_rust_format(&self, path: &str, dry_run: bool) -> Result<FixResult, ToolError> {
+    /// Clippy diagnostics for files under `target`, run from the workspace.
+    async fn run_clippy(&self, target: &Path) -> Result<Vec<Diagnostic>, ToolError> {
         use tokio::process::Command;
 
-        let args = if dry_run {
-            vec!["--check", path]
-        } else {
-            vec![path]
-        };
-
-        let output = Command::new("rustfmt")
-            .args(&args)
+        let output = Command::new("cargo")
+            .args(["clippy", "--all-targets", "--message-format=json"])
+            .current_dir(&self.workspace)
             .output()
             .await
-            .map_err(|e| ToolError::Execution(format!("Failed to run rustfmt: {}", e)))?;
-
-        let fixed = output.status.success() && !dry_run;
-        let description = if dry_run {
-            if output.status.success() {
-                "File is already formatted correctly".to_string()
-            } else {
-                "Would format file with rustfmt".to_string()
-            }
-        } else if output.status.success() {
-            "Formatted with rustfmt".to_string()
-        } else {
+            .map_err(|e| ToolError::Execution(format!("Failed to run clippy: {}", e)))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let diagnostics = parse_cargo_messages(&stdout);
+        if !output.status.success() && diagnostics.is_empty() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            format!("rustfmt failed: {}", stderr)
-        };
+            return Err(ToolError::Execution(format!("Clippy failed: {}", stderr)));
+        }
 
-        Ok(FixResult {
-            file: path.to_string(),
-            issue: "Code formatting".to_string(),
-            fixed,
-            description,
-        })
+        Ok(diagnostics
+            .into_iter()
+            .filter(|d| self.workspace.join(&d.file).starts_with(target))
+            .collect())
     }
 
-    async fn fix_lint(&self, path: &str, dry_run: bool) -> Result<Vec<FixResult>, ToolError> {
-        let mut results = Vec::new();
-
-        // For Rust, use clippy with --fix
-        if path.ends_with(".rs") || PathBuf::from(path).is_dir() {
-            use tokio::process::Command;
+    async fn fix_lint(&self, path: &str, dry_run: bool) -> Result<LintOutcome, ToolError> {
+        let target = self.workspace.join(path);
+        let mut outcome = LintOutcome::default();
+        // Contents before the first change, keyed by workspace-relative path
+        let mut originals: BTreeMap<String, String> = BTreeMap::new();
+        let mut current: BTreeMap<String, String> = BTreeMap::new();
+        let mut deferred = Vec::new();
+
+        // Applying one batch of fixes can reveal or unblock others, so rerun
+        // clippy until nothing applicable is left
+        for pass in 1..=MAX_LINT_PASSES {
+            let diagnostics = self.run_clippy(&target).await?;
+            let plan = plan_lint_fixes(diagnostics);
+            outcome.remaining = plan.remaining;
+            deferred = plan.deferred;
+            if plan.fixes.is_empty() {
+                break;
+            }
 
-            let mut cmd = Command::new("cargo");
-            cmd.arg("clippy");
+            let mut edits: BTreeMap<String, Vec<TextEdit>> = BTreeMap::new();
+            for diagnostic in &plan.fixes {
+                for suggestion in machine_applicable(diagnostic) {
+                    edits
+                        .entry(suggestion.file.clone())
+                        .or_default()
+                        .push(TextEdit::new(
+                            suggestion.byte_start,
+                            suggestion.byte_end,
+                            suggestion.replacement.clone(),
+                        ));
+                }
+                outcome.results.push(FixResult {
+                    file: diagnostic.file.clone(),
+                    issue: diagnostic.message.clone(),
+                    fixed: !dry_run,
+                    description: format!(
+                        "{} {} at line {}",
+                        if dry_run { "Would apply" } else { "Applied" },
+                        diagnostic.code.as_deref().unwrap_or("suggestion"),
+                        diagnostic.line
+                    ),
+                });
+            }
 
-            if !dry_run {
-                cmd.arg("--fix");
-                cmd.arg("--allow-dirty");
-                cmd.arg("--allow-staged");
+            for (file, file_edits) in edits {
+                let full_path = self.workspace.join(&file);
+                let source = std::fs::read_to_string(&full_path)
+                    .map_err(|e| ToolError::Execution(format!("Failed to read {}: {}", file, e)))?;
+                let updated = edit::apply(&source, &file_edits);
+                originals.entry(file.clone()).or_insert(source);
+                if !dry_run {
+                    edit::write_atomic(&full_path, &updated).map_err(|e| {
+                        ToolError::Execution(format!("Failed to write {}: {}", file, e))
+                    })?;
+                }
+                current.insert(file, updated);
             }
 
-            if PathBuf::from(path).is_file() {
-                // Clippy works on the whole project, not individual files
-                cmd.arg("--");
-                cmd.arg("-W");
-                cmd.arg("clippy::all");
+            // Later passes need the rewritten files on disk
+            if dry_run {
+                break;
             }
+            debug!("Lint pass {} applied {} fixes", pass, plan.fixes.len());
+        }
+        outcome.remaining.extend(deferred);
+
+        outcome.diffs = originals
+            .iter()
+            .map(|(file, original)| FileDiff {
+                file: file.clone(),
+                diff: edit::unified_diff(file, original, &current[file]),
+            })
+            .filter(|d| !d.diff.is_empty())
+            .collect();
+
+        for diagnostic in &outcome.remaining {
+            outcome.results.push(FixResult {
+                file: diagnostic.file.clone(),
+                issue: diagnostic.message.clone(),
+                fixed: false,
+                description: format!(
+                    "Cannot auto-fix {} at line {}",
+                    diagnostic.code.as_deref().unwrap_or(&diagnostic.level),
+                    diagnostic.line
+                ),
+            });
+        }
+        if outcome.results.is_empty() {
+            outcome.results.push(FixResult {
+                file: path.to_string(),
+                issue: "Linting".to_string(),
+                fixed: false,
+                description: "No lint issues found".to_string(),
+            });
+        }
 
-            let output = cmd
-                .output()
-                .await
-                .map_err(|e| ToolError::Execution(format!("Failed to run clippy: {}", e)))?;
+        Ok(outcome)
+    }
+}
 
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
+#[derive(Debug, Clone, Serialize)]
+struct FormatterUsage {
+    language: &'static str,
+    /// The formatter that ran, or `None` when none is installed
+    formatter: Option<&'static str>,
+    /// Formatters considered, in order of preference
+    tried: Vec<&'static str>,
+    files: usize,
+    changed: usize,
+    failed: usize,
+}
 
-            // Parse clippy output for warnings/errors
-            let mut _fixed_count = 0;
-            for line in stderr.lines().chain(stdout.lines()) {
-                if line.contains("warning:") || line.contains("error:") {
-                    let issue = line
-                        .split("warning:")
-                        .nth(1)
-                        .or_else(|| line.split("error:").nth(1))
-                        .unwrap_or("Unknown issue")
-                        .trim();
-
-                    if !dry_run && line.contains("fixed") {
-                        _fixed_count += 1;
-                    }
+#[derive(Debug, Default)]
+struct FormatOutcome {
+    results: Vec<FixResult>,
+    diffs: Vec<FileDiff>,
+    formatters: Vec<FormatterUsage>,
+    unavailable: Vec<FormatterUsage>,
+}
 
-                    results.push(FixResult {
-                        file: path.to_string(),
-                        issue: issue.to_string(),
-                        fixed: !dry_run && line.contains("fixed"),
-                        description: if dry_run {
-                            format!("Would fix: {}", issue)
-                        } else if line.contains("fixed") {
-                            format!("Fixed: {}", issue)
-                        } else {
-                            format!("Cannot auto-fix: {}", issue)
-                        },
-                    });
-                }
-            }
+/// Upper bound on clippy runs in one lint fix.
+const MAX_LINT_PASSES: usize = 4;
 
-            if results.is_empty() {
-                results.push(FixResult {
-                    file: path.to_string(),
-                    issue: "Linting".to_string(),
-                    fixed: false,
-                    description: if output.status.success() {
-                        "No lint issues found".to_string()
-                    } else {
-                        "Clippy analysis completed".to_string()
-                    },
-                });
-            }
-        }
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDiff {
+    pub file: String,
+    pub diff: String,
+}
 
-        Ok(results)
+#[derive(Debug, Default)]
+struct LintOutcome {
+    results: Vec<FixResult>,
+    diffs: Vec<FileDiff>,
+    /// Diagnostics left for a human or the model
+    remaining: Vec<Diagnostic>,
+}
+
+struct LintPlan {
+    /// Diagnostics whose suggestions are applied this pass
+    fixes: Vec<Diagnostic>,
+    /// Fixable, but conflicting with an earlier fix in this pass
+    deferred: Vec<Diagnostic>,
+    remaining: Vec<Diagnostic>,
+}
+
+fn machine_applicable(diagnostic: &Diagnostic) -> impl Iterator<Item = &Suggestion> {
+    diagnostic
+        .suggestions
+        .iter()
+        .filter(|s| s.applicability == "MachineApplicable")
+}
+
+/// Pick the diagnostics whose machine-applicable suggestions can be applied
+/// together. A diagnostic's edits are taken all or nothing; one that
+/// conflicts with an already accepted edit is deferred to the next pass.
+fn plan_lint_fixes(diagnostics: Vec<Diagnostic>) -> LintPlan {
+    let mut plan = LintPlan {
+        fixes: Vec::new(),
+        deferred: Vec::new(),
+        remaining: Vec::new(),
+    };
+    let mut accepted: Vec<(String, TextEdit)> = Vec::new();
+
+    for diagnostic in diagnostics {
+        let edits: Vec<(String, TextEdit)> = machine_applicable(&diagnostic)
+            .map(|s| {
+                let edit = TextEdit::new(s.byte_start, s.byte_end, s.replacement.clone());
+                (s.file.clone(), edit)
+            })
+            .collect();
+        if edits.is_empty() {
+            plan.remaining.push(diagnostic);
+            continue;
+        }
+        let conflicts = edits.iter().any(|(file, edit)| {
+            accepted
+                .iter()
+                .any(|(other_file, other)| file == other_file && edit.conflicts_with(other))
+        });
+        if conflicts {
+            plan.deferred.push(diagnostic);
+        } else {
+            accepted.extend(edits);
+            plan.fixes.push(diagnostic);
+        }
     }
+    plan
 }
 
 impl Default for FixTool {
@@ -261,10 +422,31 @@ impl Tool for FixTool {
             ));
         }
 
+        let mut details = serde_json::Map::new();
         let results = match params.fix_type.as_str() {
             "complexity" => self.fix_complexity(&params.path, params.dry_run).await?,
-            "format" => self.fix_format(&params.path, params.dry_run).await?,
-            "lint" => self.fix_lint(&params.path, params.dry_run).await?,
+            "format" => {
+                let outcome = self.fix_format(&params.path, params.dry_run).await?;
+                details.insert("diffs".to_string(), serde_json::json!(outcome.diffs));
+                details.insert(
+                    "formatters".to_string(),
+                    serde_json::json!(outcome.formatters),
+                );
+                details.insert(
+                    "unavailable".to_string(),
+                    serde_json::json!(outcome.unavailable),
+                );
+                outcome.results
+            }
+            "lint" => {
+                let outcome = self.fix_lint(&params.path, params.dry_run).await?;
+                details.insert("diffs".to_string(), serde_json::json!(outcome.diffs));
+                details.insert(
+                    "diagnostics".to_string(),
+                    serde_json::json!(outcome.remaining),
+                );
+                outcome.results
+            }
             _ => {
                 return Err(ToolError::InvalidParams(format!(
                     "Unknown fix type: {}. Use: complexity, format, lint",
@@ -276,7 +458,7 @@ impl Tool for FixTool {
         let total_fixed = results.iter().filter(|r| r.fixed).count();
         let total_issues = results.len();
 
-        Ok(serde_json::json!({
+        let mut output = serde_json::json!({
             "fix_type": params.fix_type,
             "path": params.path,
             "dry_run": params.dry_run,
@@ -286,7 +468,11 @@ impl Tool for FixTool {
                 "requires_manual": total_issues - total_fixed
             },
             "results": results
-        }))
+        });
+        if let Value::Object(map) = &mut output {
+            map.extend(details);
+        }
+        Ok(output)
     }
 }
 
@@ -313,4 +499,151 @@ mod tests {
         let result = tool.execute(params).await;
         assert!(result.is_err());
     }
+
+    fn diagnostic(line: usize, edits: &[(usize, usize, &str)], applicability: &str) -> Diagnostic {
+        Diagnostic {
+            level: "warning".to_string(),
+            code: Some("clippy::test".to_string()),
+            message: format!("lint at {}", line),
+            file: "src/lib.rs".to_string(),
+            line,
+            column: 1,
+            end_line: line,
+            end_column: 1,
+            suggestions: edits
+                .iter()
+                .map(|&(start, end, replacement)| Suggestion {
+                    file: "src/lib.rs".to_string(),
+                    byte_start: start,
+                    byte_end: end,
+                    line,
+                    replacement: replacement.to_string(),
+                    applicability: applicability.to_string(),
+                })
+                .collect(),
+            rendered: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_lint_fixes_resolves_overlaps() {
+        let plan = plan_lint_fixes(vec![
+            diagnostic(1, &[(0, 10, "a")], "MachineApplicable"),
+            // Overlaps the first fix: deferred as a whole, including the
+            // edit that would not conflict on its own
+            diagnostic(2, &[(5, 12, "b"), (40, 41, "c")], "MachineApplicable"),
+            diagnostic(3, &[(10, 12, "d")], "MachineApplicable"),
+            diagnostic(4, &[(20, 22, "e")], "MaybeIncorrect"),
+            diagnostic(5, &[], "MachineApplicable"),
+        ]);
+        let lines = |d: &[Diagnostic]| d.iter().map(|d| d.line).collect::<Vec<_>>();
+        assert_eq!(lines(&plan.fixes), vec![1, 3]);
+        assert_eq!(lines(&plan.deferred), vec![2]);
+        assert_eq!(lines(&plan.remaining), vec![4, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_fix_lint_applies_machine_applicable_suggestions() {
+        let clippy = std::process::Command::new("cargo")
+            .args(["clippy", "--version"])
+            .output();
+        if !clippy.is_ok_and(|o| o.status.success()) {
+            return;
+        }
+
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"lintme\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        let source = "pub fn double(x: u32) -> u32 {\n    return x * 2;\n}\n\npub fn first(v: &[u32]) -> Option<&u32> {\n    v.iter().nth(0)\n}\n";
+        std::fs::write(dir.path().join("src/lib.rs"), source).unwrap();
+
+        let tool = FixTool {
+            workspace: dir.path().to_path_buf(),
+            pmat: PmatTool::with_workspace(dir.path().to_path_buf()),
+        };
+
+        let preview = tool
+            .execute(serde_json::json!({ "fix_type": "lint", "path": "src", "dry_run": true }))
+            .await
+            .unwrap();
+        let diff = preview["diffs"][0]["diff"].as_str().unwrap();
+        assert!(
+            diff.contains("-    return x * 2;\n+    x * 2\n"),
+            "{}",
+            diff
+        );
+        assert!(diff.contains("-    v.iter().nth(0)\n"), "{}", diff);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("src/lib.rs")).unwrap(),
+            source
+        );
+
+        let applied = tool
+            .execute(serde_json::json!({ "fix_type": "lint", "path": "src" }))
+            .await
+            .unwrap();
+        assert!(applied["summary"]["fixed"].as_u64().unwrap() >= 2);
+        assert_eq!(applied["diagnostics"], serde_json::json!([]));
+        let fixed = std::fs::read_to_string(dir.path().join("src/lib.rs")).unwrap();
+        assert!(
+            fixed.contains("    x * 2\n") && !fixed.contains("nth(0)"),
+            "{}",
+            fixed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fix_format_reports_diffs_and_formatters() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "fn f( ){let x=1;}\n").unwrap();
+        std::fs::write(dir.path().join("target/gen.rs"), "fn g( ){}\n").unwrap();
+        std::fs::write(dir.path().join("tool.py"), "x=1\n").unwrap();
+
+        let tool = FixTool {
+            workspace: dir.path().to_path_buf(),
+            pmat: PmatTool::with_workspace(dir.path().to_path_buf()),
+        };
+        let result = tool
+            .execute(serde_json::json!({ "fix_type": "format", "path": ".", "dry_run": true }))
+            .await
+            .unwrap();
+
+        // Each language is either formatted or listed as unavailable
+        let languages = |key: &str| -> Vec<String> {
+            result[key]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|u| u["language"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let mut all = [languages("formatters"), languages("unavailable")].concat();
+        all.sort();
+        assert_eq!(all, vec!["python", "rust"]);
+
+        if formatters::is_available(formatters::RUSTFMT) {
+            let rust = &result["formatters"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|u| u["language"] == "rust")
+                .unwrap();
+            assert_eq!(rust["formatter"], "rustfmt");
+            // Build output under target/ is skipped
+            assert_eq!(rust["files"], 1);
+            let diffs = result["diffs"].as_array().unwrap();
+            assert!(diffs.iter().any(
+                |d| d["file"] == "lib.rs" && d["diff"].as_str().unwrap().contains("+fn f() {")
+            ));
+            assert_eq!(
+                std::fs::read_to_string(dir.path().join("lib.rs")).unwrap(),
+                "fn f( ){let x=1;}\n"
+            );
+        }
+    }
 }
@@ -0,0 +1,271 @@
+//! External code formatters and how to pick one for a file.
+//!
+//! Every formatter is run in stdin/stdout mode, so checking and rewriting a
+//! file work the same way for all of them.
+
+use crate::analysis::lexer::Syntax;
+use crate::tools::runner::resolve_program;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Formatter {
+    pub name: &'static str,
+    pub program: &'static str,
+}
+
+pub const RUSTFMT: Formatter = Formatter {
+    name: "rustfmt",
+    program: "rustfmt",
+};
+pub const BLACK: Formatter = Formatter {
+    name: "black",
+    program: "black",
+};
+pub const RUFF: Formatter = Formatter {
+    name: "ruff",
+    program: "ruff",
+};
+pub const PRETTIER: Formatter = Formatter {
+    name: "prettier",
+    program: "prettier",
+};
+pub const DENO: Formatter = Formatter {
+    name: "deno fmt",
+    program: "deno",
+};
+pub const GOFMT: Formatter = Formatter {
+    name: "gofmt",
+    program: "gofmt",
+};
+pub const SHFMT: Formatter = Formatter {
+    name: "shfmt",
+    program: "shfmt",
+};
+
+const PRETTIER_CONFIGS: &[&str] = &[
+    ".prettierrc",
+    ".prettierrc.json",
+    ".prettierrc.yaml",
+    ".prettierrc.yml",
+    ".prettierrc.js",
+    ".prettierrc.cjs",
+    ".prettierrc.mjs",
+    ".prettierrc.toml",
+    "prettier.config.js",
+    "prettier.config.cjs",
+    "prettier.config.mjs",
+];
+
+pub fn language_name(syntax: Syntax) -> &'static str {
+    match syntax {
+        Syntax::Rust => "rust",
+        Syntax::Python => "python",
+        Syntax::JavaScript => "javascript",
+        Syntax::Go => "go",
+        Syntax::Shell => "shell",
+    }
+}
+
+/// Directories from `file`'s parent up to and including `root`.
+fn ancestors<'a>(file: &'a Path, root: &'a Path) -> impl Iterator<Item = &'a Path> {
+    let stop = file.starts_with(root);
+    let mut done = false;
+    file.ancestors().skip(1).take_while(move |dir| {
+        let keep = !done;
+        done = !stop || *dir == root;
+        keep
+    })
+}
+
+fn find_upwards(file: &Path, root: &Path, names: &[&str]) -> Option<PathBuf> {
+    ancestors(file, root)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+fn file_contains(path: &Path, needle: &str) -> bool {
+    std::fs::read_to_string(path).is_ok_and(|text| text.contains(needle))
+}
+
+/// Formatters for `file` in order of preference, judged from the project
+/// configuration found between the file and `root`.
+pub fn candidates(file: &Path, root: &Path, syntax: Syntax) -> Vec<Formatter> {
+    match syntax {
+        Syntax::Rust => vec![RUSTFMT],
+        Syntax::Go => vec![GOFMT],
+        Syntax::Shell => vec![SHFMT],
+        Syntax::Python => {
+            let ruff_config = find_upwards(file, root, &["ruff.toml", ".ruff.toml"]).is_some()
+                || find_upwards(file, root, &["pyproject.toml"])
+                    .is_some_and(|p| file_contains(&p, "[tool.ruff"));
+            if ruff_config {
+                vec![RUFF, BLACK]
+            } else {
+                vec![BLACK, RUFF]
+            }
+        }
+        Syntax::JavaScript => {
+            let deno_config = find_upwards(file, root, &["deno.json", "deno.jsonc"]).is_some();
+            let prettier_config = find_upwards(file, root, PRETTIER_CONFIGS).is_some()
+                || find_upwards(file, root, &["package.json"])
+                    .is_some_and(|p| file_contains(&p, "\"prettier\""));
+            if deno_config && !prettier_config {
+                vec![DENO, PRETTIER]
+            } else {
+                vec![PRETTIER, DENO]
+            }
+        }
+    }
+}
+
+pub fn is_available(formatter: Formatter) -> bool {
+    resolve_program(formatter.program).is_file()
+}
+
+/// The `edition` of the nearest Cargo.toml, which rustfmt needs to parse
+/// newer syntax when reading stdin.
+fn rust_edition(file: &Path, root: &Path) -> Option<String> {
+    let manifest = find_upwards(file, root, &["Cargo.toml"])?;
+    let text = std::fs::read_to_string(manifest).ok()?;
+    text.lines().find_map(|line| {
+        let value = line
+            .trim()
+            .strip_prefix("edition")?
+            .trim()
+            .strip_prefix('=')?;
+        Some(value.trim().trim_matches('"').to_string())
+    })
+}
+
+fn arguments(formatter: Formatter, file: &Path, root: &Path) -> Vec<String> {
+    let name = file.to_string_lossy().to_string();
+    let args: Vec<&str> = match formatter.name {
+        "rustfmt" => {
+            let mut args = vec!["--emit".to_string(), "stdout".to_string()];
+            if let Some(edition) = rust_edition(file, root) {
+                args.extend(["--edition".to_string(), edition]);
+            }
+            return args;
+        }
+        "black" => vec!["-q", "--stdin-filename", &name, "-"],
+        "ruff" => vec!["format", "--stdin-filename", &name, "-"],
+        "prettier" => vec!["--stdin-filepath", &name],
+        "deno fmt" => {
+            let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("js");
+            return vec!["fmt".into(), "--ext".into(), ext.into(), "-".into()];
+        }
+        "shfmt" => vec!["--filename", &name],
+        _ => Vec::new(),
+    };
+    args.into_iter().map(str::to_string).collect()
+}
+
+/// Run `formatter` over `source` and return the formatted text.
+pub async fn format_source(
+    formatter: Formatter,
+    file: &Path,
+    root: &Path,
+    source: &str,
+) -> Result<String, String> {
+    let mut child = Command::new(resolve_program(formatter.program))
+        .args(arguments(formatter, file, root))
+        .current_dir(file.parent().unwrap_or(root))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", formatter.name, e))?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = source.to_string();
+    let writer = tokio::spawn(async move {
+        let _ = stdin.write_all(input.as_bytes()).await;
+    });
+
+    let output = timeout(Duration::from_secs(30), child.wait_with_output())
+        .await
+        .map_err(|_| format!("{} timeout (30s)", formatter.name))?
+        .map_err(|e| format!("{} failed: {}", formatter.name, e))?;
+    let _ = writer.await;
+
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            formatter.name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| format!("{} produced invalid UTF-8", formatter.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_candidates_follow_project_config() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("app/web")).unwrap();
+        let py = root.join("app/main.py");
+        let js = root.join("app/web/index.ts");
+
+        assert_eq!(candidates(&py, root, Syntax::Python), vec![BLACK, RUFF]);
+        std::fs::write(
+            root.join("pyproject.toml"),
+            "[tool.ruff]\nline-length = 100\n",
+        )
+        .unwrap();
+        assert_eq!(candidates(&py, root, Syntax::Python), vec![RUFF, BLACK]);
+
+        assert_eq!(
+            candidates(&js, root, Syntax::JavaScript),
+            vec![PRETTIER, DENO]
+        );
+        std::fs::write(root.join("app/deno.json"), "{}").unwrap();
+        assert_eq!(
+            candidates(&js, root, Syntax::JavaScript),
+            vec![DENO, PRETTIER]
+        );
+        std::fs::write(root.join("app/web/.prettierrc"), "{}").unwrap();
+        assert_eq!(
+            candidates(&js, root, Syntax::JavaScript),
+            vec![PRETTIER, DENO]
+        );
+    }
+
+    #[test]
+    fn test_config_search_stops_at_root() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("ws/src")).unwrap();
+        std::fs::write(dir.path().join("ruff.toml"), "").unwrap();
+        let root = dir.path().join("ws");
+        let file = root.join("src/a.py");
+        assert_eq!(candidates(&file, &root, Syntax::Python)[0], BLACK);
+    }
+
+    #[tokio::test]
+    async fn test_rustfmt_via_stdin() {
+        if !is_available(RUSTFMT) {
+            return;
+        }
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"x\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        let file = dir.path().join("lib.rs");
+        let formatted = format_source(RUSTFMT, &file, dir.path(), "async fn f( ){let x=1;}")
+            .await
+            .unwrap();
+        assert_eq!(formatted, "async fn f() {\n    let x = 1;\n}\n");
+    }
+}
@@ -0,0 +1,744 @@
+use crate::analysis::complexity::{self, Thresholds};
+use crate::analysis::diagnostics;
+use crate::analysis::satd;
+use crate::config::QualityThresholds;
+use crate::security::active_policy_or_default;
+use crate::tools::coverage::{CoverageError, CoverageTool};
+use crate::tools::history::{self, ComplexityMetrics, Metrics};
+use crate::tools::{Tool, ToolError};
+use crate::watcher;
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+use tracing::info;
+
+/// Checks run by default, in order.
+pub const CHECKS: &[&str] = &["complexity", "satd", "coverage", "clippy", "fmt"];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GateParams {
+    #[serde(default)]
+    path: Option<String>,
+    /// Subset of [`CHECKS`] to run
+    #[serde(default)]
+    checks: Option<Vec<String>>,
+    /// Existing LCOV or Cobertura file to use instead of running tests
+    #[serde(default)]
+    coverage_report: Option<String>,
+    /// Write a SARIF 2.1.0 log to this path
+    #[serde(default)]
+    sarif: Option<String>,
+    #[serde(default)]
+    max_complexity: Option<u32>,
+    #[serde(default)]
+    max_cognitive: Option<u32>,
+    #[serde(default)]
+    min_coverage: Option<f64>,
+    #[serde(default)]
+    max_satd: Option<usize>,
+    /// Pass even when a check could not run, e.g. without a coverage backend
+    #[serde(default)]
+    allow_skipped: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Pass,
+    Fail,
+    Skipped,
+}
+
+/// One located problem, reported as a SARIF result.
+#[derive(Debug, Clone, Serialize)]
+struct Finding {
+    rule: String,
+    /// SARIF level: "error", "warning" or "note"
+    level: &'static str,
+    message: String,
+    file: String,
+    line: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CheckResult {
+    name: &'static str,
+    status: Status,
+    value: String,
+    limit: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    findings: Vec<Finding>,
+    /// Measurements kept in the metrics history
+    #[serde(skip)]
+    metrics: Metrics,
+}
+
+impl CheckResult {
+    fn new(name: &'static str, passed: bool, value: String, limit: String) -> Self {
+        Self {
+            name,
+            status: if passed { Status::Pass } else { Status::Fail },
+            value,
+            limit,
+            message: None,
+            findings: Vec::new(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn skipped(name: &'static str, reason: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Skipped,
+            value: "-".to_string(),
+            limit: "-".to_string(),
+            message: Some(reason.into()),
+            findings: Vec::new(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn failed(name: &'static str, error: impl Into<String>) -> Self {
+        Self {
+            status: Status::Fail,
+            ..Self::skipped(name, error)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GateTool {
+    workspace: PathBuf,
+    /// Program run for the clippy and fmt checks
+    cargo: PathBuf,
+}
+
+impl GateTool {
+    pub fn new() -> Self {
+        Self {
+            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            cargo: PathBuf::from("cargo"),
+        }
+    }
+
+    /// Where to write the SARIF log: a relative path inside the workspace
+    /// that the active policy allows writing to.
+    fn sarif_path(&self, path: &str) -> Result<PathBuf, ToolError> {
+        let relative = Path::new(path);
+        let contained = relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !contained || path.trim().is_empty() {
+            return Err(ToolError::InvalidParams(format!(
+                "SARIF path must be relative and inside the workspace: {}",
+                path
+            )));
+        }
+        let root = self
+            .workspace
+            .canonicalize()
+            .unwrap_or_else(|_| self.workspace.clone());
+        let resolved = root.join(relative);
+        let policy = active_policy_or_default(&self.workspace).canonicalized();
+        if !policy.allows_path(&resolved) {
+            return Err(ToolError::PermissionDenied(format!(
+                "SARIF path {} is outside the allowed paths",
+                resolved.display()
+            )));
+        }
+        Ok(resolved)
+    }
+
+    fn relative(&self, file: &str) -> String {
+        let root = self
+            .workspace
+            .canonicalize()
+            .unwrap_or_else(|_| self.workspace.clone());
+        Path::new(file)
+            .strip_prefix(&root)
+            .or_else(|_| Path::new(file).strip_prefix(&self.workspace))
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| file.to_string())
+    }
+
+    async fn check_complexity(&self, target: &Path, limits: QualityThresholds) -> CheckResult {
+        let thresholds = Thresholds {
+            cyclomatic: limits.max_complexity,
+            cognitive: limits.max_cognitive,
+        };
+        let (root, target) = (self.workspace.clone(), target.to_path_buf());
+        let report = match tokio::task::spawn_blocking(move || {
+            complexity::analyze_path(&root, &target, thresholds)
+        })
+        .await
+        {
+            Ok(report) => report,
+            Err(e) => return CheckResult::failed("complexity", e.to_string()),
+        };
+
+        let mut result = CheckResult::new(
+            "complexity",
+            report.violations.is_empty(),
+            format!(
+                "max {}/{}, {} over",
+                report.summary.max_complexity,
+                report.summary.max_cognitive,
+                report.violations.len()
+            ),
+            format!(
+                "cyclomatic ≤ {}, cognitive ≤ {}",
+                limits.max_complexity, limits.max_cognitive
+            ),
+        );
+        result.metrics.complexity = serde_json::to_value(&report)
+            .ok()
+            .and_then(|report| ComplexityMetrics::from_report(&report));
+        result.findings = report
+            .violations
+            .into_iter()
+            .map(|v| Finding {
+                rule: format!("complexity/{}", v.metric),
+                level: if v.severity == "high" {
+                    "error"
+                } else {
+                    "warning"
+                },
+                message: v.message,
+                file: v.file,
+                line: v.line,
+            })
+            .collect();
+        result
+    }
+
+    async fn check_satd(&self, target: &Path, limits: QualityThresholds) -> CheckResult {
+        let (root, target) = (self.workspace.clone(), target.to_path_buf());
+        let report =
+            match tokio::task::spawn_blocking(move || satd::scan_path(&root, &target, false)).await
+            {
+                Ok(report) => report,
+                Err(e) => return CheckResult::failed("satd", e.to_string()),
+            };
+
+        let total = report.summary.total_items;
+        let mut result = CheckResult::new(
+            "satd",
+            total <= limits.max_satd,
+            format!("{} items", total),
+            format!("≤ {}", limits.max_satd),
+        );
+        result.metrics.satd_items = Some(total);
+        for file in report.files {
+            for item in file.items {
+                result.findings.push(Finding {
+                    rule: format!("satd/{}", item.category),
+                    level: match item.severity {
+                        "critical" | "high" => "error",
+                        "medium" => "warning",
+                        _ => "note",
+                    },
+                    message: format!("{}: {}", item.marker, item.text),
+                    file: file.file.clone(),
+                    line: item.line,
+                });
+            }
+        }
+        result
+    }
+
+    async fn check_coverage(&self, params: &GateParams, limits: QualityThresholds) -> CheckResult {
+        let coverage = CoverageTool::with_workspace(self.workspace.clone());
+        let report = match coverage
+            .run(json!({
+                "path": params.path,
+                "report": params.coverage_report,
+            }))
+            .await
+        {
+            Ok(report) => report,
+            // No backend is an environment gap, not a coverage failure
+            Err(CoverageError::NotInstalled(e)) => return CheckResult::skipped("coverage", e),
+            Err(CoverageError::Failed(ToolError::Execution(e))) => {
+                return CheckResult::failed("coverage", e)
+            }
+            Err(CoverageError::Failed(e)) => return CheckResult::failed("coverage", e.to_string()),
+        };
+
+        let percent = report["coverage_percent"].as_f64().unwrap_or(0.0);
+        let mut result = CheckResult::new(
+            "coverage",
+            percent >= limits.min_coverage,
+            format!("{:.2}%", percent),
+            format!("≥ {}%", limits.min_coverage),
+        );
+        for file in report["files"].as_array().into_iter().flatten() {
+            let file_percent = file["percent"].as_f64().unwrap_or(100.0);
+            if file_percent >= limits.min_coverage {
+                continue;
+            }
+            result.findings.push(Finding {
+                rule: "coverage/file".to_string(),
+                level: "warning",
+                message: format!(
+                    "{:.2}% line coverage, below {}%",
+                    file_percent, limits.min_coverage
+                ),
+                file: file["file"].as_str().unwrap_or_default().to_string(),
+                line: file["uncovered"][0]["start"].as_u64().unwrap_or(0) as usize,
+            });
+        }
+        result
+    }
+
+    /// Run cargo for the `check` of that name. Only a missing cargo skips
+    /// the check; a run that cannot start or times out fails it.
+    async fn cargo(
+        &self,
+        check: &'static str,
+        target: &Path,
+        args: &[&str],
+    ) -> Result<(bool, String, String), CheckResult> {
+        let mut cmd = Command::new(&self.cargo);
+        cmd.args(args)
+            .current_dir(target)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        info!("Running cargo {}", args.join(" "));
+        match timeout(Duration::from_secs(300), cmd.output()).await {
+            Ok(Ok(output)) => Ok((
+                output.status.success(),
+                String::from_utf8_lossy(&output.stdout).to_string(),
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            )),
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => Err(CheckResult::skipped(
+                check,
+                format!("cargo not installed: {}", e),
+            )),
+            Ok(Err(e)) => Err(CheckResult::failed(
+                check,
+                format!("Failed to run cargo: {}", e),
+            )),
+            Err(_) => Err(CheckResult::failed(
+                check,
+                format!("cargo {} timeout (300s)", args[0]),
+            )),
+        }
+    }
+
+    async fn check_clippy(&self, target: &Path) -> CheckResult {
+        let args = [
+            "clippy",
+            "--workspace",
+            "--all-targets",
+            "--message-format=json",
+        ];
+        let (success, stdout, stderr) = match self.cargo("clippy", target, &args).await {
+            Ok(output) => output,
+            Err(result) => return result,
+        };
+        let found: Vec<_> = diagnostics::parse_cargo_messages(&stdout)
+            .into_iter()
+            .filter(|d| d.level == "warning" || d.level == "error")
+            .collect();
+        if !success && found.is_empty() {
+            let reason = stderr.lines().last().unwrap_or("cargo clippy failed");
+            return CheckResult::failed("clippy", reason);
+        }
+
+        let mut result = CheckResult::new(
+            "clippy",
+            found.is_empty(),
+            format!("{} diagnostics", found.len()),
+            "0".to_string(),
+        );
+        result.findings = found
+            .into_iter()
+            .map(|d| Finding {
+                rule: d.code.unwrap_or_else(|| "clippy".to_string()),
+                level: if d.level == "error" {
+                    "error"
+                } else {
+                    "warning"
+                },
+                message: d.message,
+                file: self.relative(&d.file),
+                line: d.line,
+            })
+            .collect();
+        result
+    }
+
+    async fn check_fmt(&self, target: &Path) -> CheckResult {
+        let (success, stdout, stderr) = match self
+            .cargo("fmt", target, &["fmt", "--all", "--check"])
+            .await
+        {
+            Ok(output) => output,
+            Err(result) => return result,
+        };
+        let diffs = parse_fmt_check(&stdout);
+        if !success && diffs.is_empty() {
+            let reason = stderr.lines().last().unwrap_or("cargo fmt failed");
+            return CheckResult::failed("fmt", reason);
+        }
+
+        let files: BTreeMap<&str, usize> = diffs.iter().map(|(f, l)| (f.as_str(), *l)).collect();
+        let mut result = CheckResult::new(
+            "fmt",
+            files.is_empty(),
+            format!("{} files", files.len()),
+            "0".to_string(),
+        );
+        result.findings = diffs
+            .iter()
+            .map(|(file, line)| Finding {
+                rule: "rustfmt".to_string(),
+                level: "warning",
+                message: "Code is not formatted; run `cargo fmt`".to_string(),
+                file: self.relative(file),
+                line: *line,
+            })
+            .collect();
+        result
+    }
+}
+
+/// `(file, line)` of each hunk in `cargo fmt --check` output.
+fn parse_fmt_check(output: &str) -> Vec<(String, usize)> {
+    // "Diff in /p/src/lib.rs at line 3:" (older) or "Diff in /p/src/lib.rs:3:"
+    let pattern = Regex::new(r"^Diff in (.+?)(?: at line |:)(\d+):").unwrap();
+    output
+        .lines()
+        .filter_map(|line| {
+            let caps = pattern.captures(line)?;
+            Some((caps[1].to_string(), caps[2].parse().ok()?))
+        })
+        .collect()
+}
+
+fn table(checks: &[CheckResult]) -> String {
+    let mut out = format!(
+        "{:<12} {:<8} {:<24} {}\n",
+        "Check", "Status", "Value", "Limit"
+    );
+    for check in checks {
+        let status = match check.status {
+            Status::Pass => "PASS",
+            Status::Fail => "FAIL",
+            Status::Skipped => "SKIP",
+        };
+        out.push_str(&format!(
+            "{:<12} {:<8} {:<24} {}\n",
+            check.name, status, check.value, check.limit
+        ));
+        match (&check.message, check.status) {
+            (Some(message), Status::Skipped) => {
+                out.push_str(&format!("{:<12} skipped: {}\n", "", message.trim()))
+            }
+            (Some(message), _) => out.push_str(&format!("{:<12} {}\n", "", message.trim())),
+            (None, _) => {}
+        }
+    }
+    out
+}
+
+/// A SARIF 2.1.0 log with one result per finding.
+fn sarif(checks: &[CheckResult]) -> Value {
+    let findings: Vec<&Finding> = checks.iter().flat_map(|c| &c.findings).collect();
+
+    let mut rules: BTreeMap<&str, &str> = BTreeMap::new();
+    for check in checks {
+        for finding in &check.findings {
+            rules.entry(finding.rule.as_str()).or_insert(check.name);
+        }
+    }
+    let rules: Vec<Value> = rules
+        .iter()
+        .map(|(id, check)| {
+            json!({
+                "id": id,
+                "shortDescription": { "text": format!("{} check: {}", check, id) },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            let mut location = json!({ "artifactLocation": { "uri": finding.file } });
+            if finding.line > 0 {
+                location["region"] = json!({ "startLine": finding.line });
+            }
+            json!({
+                "ruleId": finding.rule,
+                "level": finding.level,
+                "message": { "text": finding.message },
+                "locations": [{ "physicalLocation": location }],
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "pcode",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }]
+    })
+}
+
+impl Default for GateTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for GateTool {
+    fn name(&self) -> &str {
+        "gate"
+    }
+
+    fn description(&self) -> &str {
+        "Check complexity, SATD, coverage, clippy and formatting against quality thresholds"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: GateParams =
+            serde_json::from_value(params).map_err(|e| ToolError::InvalidParams(e.to_string()))?;
+
+        let defaults = QualityThresholds::from_env();
+        let limits = QualityThresholds {
+            max_complexity: params.max_complexity.unwrap_or(defaults.max_complexity),
+            max_cognitive: params.max_cognitive.unwrap_or(defaults.max_cognitive),
+            min_coverage: params.min_coverage.unwrap_or(defaults.min_coverage),
+            max_satd: params.max_satd.unwrap_or(defaults.max_satd),
+        };
+
+        let selected: Vec<String> = match &params.checks {
+            Some(checks) if !checks.is_empty() => checks.clone(),
+            _ => CHECKS.iter().map(|c| c.to_string()).collect(),
+        };
+        if let Some(unknown) = selected.iter().find(|c| !CHECKS.contains(&c.as_str())) {
+            return Err(ToolError::InvalidParams(format!(
+                "Unknown gate check: {}. Use: {}",
+                unknown,
+                CHECKS.join(", ")
+            )));
+        }
+
+        let sarif_path = params
+            .sarif
+            .as_deref()
+            .map(|path| self.sarif_path(path))
+            .transpose()?;
+
+        let target = self.workspace.join(params.path.as_deref().unwrap_or("."));
+        if !target.exists() {
+            return Err(ToolError::NotFound(format!(
+                "Path not found: {}",
+                target.display()
+            )));
+        }
+
+        let mut checks = Vec::new();
+        for name in CHECKS.iter().filter(|c| selected.iter().any(|s| s == *c)) {
+            let result = match *name {
+                "complexity" => self.check_complexity(&target, limits).await,
+                "satd" => self.check_satd(&target, limits).await,
+                "coverage" => self.check_coverage(&params, limits).await,
+                "clippy" => self.check_clippy(&target).await,
+                _ => self.check_fmt(&target).await,
+            };
+            checks.push(result);
+        }
+
+        // Coverage runs record their own snapshot
+        let mut metrics = Metrics::default();
+        for check in &checks {
+            metrics.merge(&check.metrics);
+        }
+        let scope = history::scope(params.path.as_deref());
+        history::record(&self.workspace, &scope, "gate", metrics).await;
+
+        // A check that could not run enforces nothing, so it only passes
+        // when explicitly allowed
+        let skipped = checks.iter().any(|c| c.status == Status::Skipped);
+        let passed =
+            checks.iter().all(|c| c.status != Status::Fail) && (params.allow_skipped || !skipped);
+        let mut table = table(&checks);
+        if skipped && !params.allow_skipped {
+            table.push_str("Skipped checks fail the gate; allow them with --allow-skipped\n");
+        }
+        let mut result = json!({
+            "passed": passed,
+            "table": table,
+            "checks": checks,
+        });
+
+        if let Some(path) = sarif_path {
+            let log = serde_json::to_string_pretty(&sarif(&checks))
+                .map_err(|e| ToolError::Execution(e.to_string()))?;
+            tokio::fs::write(&path, &log)
+                .await
+                .map_err(|e| ToolError::Execution(format!("Failed to write SARIF: {}", e)))?;
+            watcher::record_write(&path, log.as_bytes());
+            result["sarif"] = json!(path.display().to_string());
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_gate_fails_on_violations_and_writes_sarif() {
+        let dir = TempDir::new().unwrap();
+        let mut source = String::from("// FIXME: handle overflow\nfn f(x: u32) -> u32 {\n");
+        for i in 0..25 {
+            source.push_str(&format!("    if x == {} {{ return {}; }}\n", i, i));
+        }
+        source.push_str("    0\n}\n");
+        std::fs::write(dir.path().join("lib.rs"), source).unwrap();
+
+        let tool = GateTool {
+            workspace: dir.path().to_path_buf(),
+            ..GateTool::new()
+        };
+        let result = tool
+            .execute(json!({
+                "checks": ["complexity", "satd"],
+                "sarif": "gate.sarif",
+            }))
+            .await
+            .unwrap();
+
+        assert_eq!(result["passed"], false);
+        assert_eq!(result["checks"][0]["name"], "complexity");
+        assert_eq!(result["checks"][0]["status"], "fail");
+        assert_eq!(result["checks"][1]["status"], "fail");
+        assert!(result["table"].as_str().unwrap().contains("FAIL"));
+
+        let log: Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.path().join("gate.sarif")).unwrap())
+                .unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        assert!(results
+            .iter()
+            .any(|r| r["ruleId"] == "complexity/cyclomatic"
+                && r["locations"][0]["physicalLocation"]["region"]["startLine"] == 2));
+        assert!(results
+            .iter()
+            .any(|r| r["ruleId"] == "satd/defect" && r["level"] == "error"));
+
+        // Relaxed limits pass
+        let result = tool
+            .execute(json!({
+                "checks": ["complexity", "satd"],
+                "max_complexity": 30,
+                "max_cognitive": 100,
+                "max_satd": 1,
+            }))
+            .await
+            .unwrap();
+        assert_eq!(result["passed"], true);
+    }
+
+    #[tokio::test]
+    async fn test_gate_rejects_unknown_check() {
+        let tool = GateTool::new();
+        let result = tool.execute(json!({ "checks": ["lint"] })).await;
+        assert!(matches!(result, Err(ToolError::InvalidParams(_))));
+    }
+
+    #[tokio::test]
+    async fn test_gate_fails_when_cargo_cannot_run() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "fn f() {}\n").unwrap();
+        // Present but not executable
+        let cargo = dir.path().join("cargo");
+        std::fs::write(&cargo, "not a program").unwrap();
+        let tool = GateTool {
+            workspace: dir.path().to_path_buf(),
+            cargo,
+        };
+
+        let result = tool
+            .execute(json!({ "checks": ["clippy", "fmt"] }))
+            .await
+            .unwrap();
+        assert_eq!(result["passed"], false);
+        assert_eq!(result["checks"][0]["status"], "fail");
+        assert_eq!(result["checks"][1]["status"], "fail");
+
+        let tool = GateTool {
+            cargo: dir.path().join("missing/cargo"),
+            ..tool
+        };
+        let result = tool.execute(json!({ "checks": ["fmt"] })).await.unwrap();
+        assert_eq!(result["checks"][0]["status"], "skipped");
+        assert_eq!(result["passed"], false);
+        let table = result["table"].as_str().unwrap();
+        assert!(table.contains("skipped: cargo not installed"), "{}", table);
+
+        let result = tool
+            .execute(json!({ "checks": ["fmt"], "allow_skipped": true }))
+            .await
+            .unwrap();
+        assert_eq!(result["passed"], true);
+    }
+
+    #[tokio::test]
+    async fn test_gate_rejects_sarif_outside_workspace() {
+        let dir = TempDir::new().unwrap();
+        let tool = GateTool {
+            workspace: dir.path().to_path_buf(),
+            ..GateTool::new()
+        };
+        for sarif in ["../gate.sarif", "/tmp/gate.sarif", "out/../../gate.sarif"] {
+            let result = tool
+                .execute(json!({ "checks": ["satd"], "sarif": sarif }))
+                .await;
+            assert!(
+                matches!(result, Err(ToolError::InvalidParams(_))),
+                "{}",
+                sarif
+            );
+        }
+        assert!(!dir.path().parent().unwrap().join("gate.sarif").exists());
+    }
+
+    #[test]
+    fn test_parse_fmt_check() {
+        let output = "Diff in /w/src/lib.rs at line 3:\n-a\n+b\nDiff in /w/src/main.rs:10:\n";
+        assert_eq!(
+            parse_fmt_check(output),
+            vec![
+                ("/w/src/lib.rs".to_string(), 3),
+                ("/w/src/main.rs".to_string(), 10)
+            ]
+        );
+    }
+}
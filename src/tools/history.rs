@@ -0,0 +1,338 @@
+//! Local history of quality metrics under `.pcode/metrics/`.
+//!
+//! `pmat`, `coverage` and `gate` runs append a [`Snapshot`] of what they
+//! measured to `history.jsonl`, keyed by the commit checked out and the time
+//! of the run. A run usually measures only some metrics, so snapshots of the
+//! same commit and scope are merged on read, newer values winning.
+
+use crate::analysis::dependencies;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+/// Store location, relative to the workspace root.
+pub const METRICS_DIR: &str = ".pcode/metrics";
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// Upper bounds of the cyclomatic complexity bands in
+/// [`ComplexityMetrics::distribution`]; anything above falls in "21+".
+const BANDS: &[(u32, &str)] = &[(5, "1-5"), (10, "6-10"), (20, "11-20")];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ComplexityMetrics {
+    pub total_functions: usize,
+    pub max_cyclomatic: u32,
+    pub average_cyclomatic: f64,
+    pub max_cognitive: u32,
+    pub average_cognitive: f64,
+    /// Functions per cyclomatic complexity band
+    pub distribution: BTreeMap<String, usize>,
+}
+
+impl ComplexityMetrics {
+    /// Read a complexity report in the schema shared by both `pmat` backends.
+    pub fn from_report(report: &Value) -> Option<Self> {
+        let summary = report.get("summary")?;
+        let number = |key: &str| summary[key].as_f64().unwrap_or(0.0);
+
+        let mut distribution = BTreeMap::new();
+        let functions = report["files"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|file| file["functions"].as_array().into_iter().flatten());
+        for function in functions {
+            let cyclomatic = function["cyclomatic"].as_u64().unwrap_or(1) as u32;
+            let band = BANDS
+                .iter()
+                .find(|(limit, _)| cyclomatic <= *limit)
+                .map_or("21+", |(_, band)| band);
+            *distribution.entry(band.to_string()).or_insert(0) += 1;
+        }
+
+        Some(Self {
+            total_functions: number("total_functions") as usize,
+            max_cyclomatic: number("max_complexity") as u32,
+            average_cyclomatic: number("average_complexity"),
+            max_cognitive: number("max_cognitive") as u32,
+            average_cognitive: number("average_cognitive"),
+            distribution,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub complexity: Option<ComplexityMetrics>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub satd_items: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage_percent: Option<f64>,
+    /// Size of the release binary in bytes, when one is built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_size: Option<u64>,
+}
+
+impl Metrics {
+    /// Overwrite every metric `newer` has a value for.
+    pub fn merge(&mut self, newer: &Metrics) {
+        if newer.complexity.is_some() {
+            self.complexity = newer.complexity.clone();
+        }
+        self.satd_items = newer.satd_items.or(self.satd_items);
+        self.coverage_percent = newer.coverage_percent.or(self.coverage_percent);
+        self.binary_size = newer.binary_size.or(self.binary_size);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Full SHA of the commit checked out
+    pub commit: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Whether the working tree had uncommitted changes
+    pub dirty: bool,
+    /// Analyzed path relative to the workspace, "." for all of it
+    pub scope: String,
+    /// Tool that took the snapshot
+    pub source: String,
+    pub metrics: Metrics,
+}
+
+pub struct MetricsStore {
+    dir: PathBuf,
+}
+
+impl MetricsStore {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            dir: workspace.join(METRICS_DIR),
+        }
+    }
+
+    pub fn append(&self, snapshot: &Snapshot) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let line = serde_json::to_string(snapshot)?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(HISTORY_FILE))?;
+        writeln!(file, "{}", line)
+    }
+
+    /// Every snapshot in recording order. Unreadable lines are skipped.
+    pub fn load(&self) -> Vec<Snapshot> {
+        let Ok(text) = std::fs::read_to_string(self.dir.join(HISTORY_FILE)) else {
+            return Vec::new();
+        };
+        text.lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    /// Merged metrics of each commit measured at `scope`.
+    pub fn by_commit(&self, scope: &str) -> BTreeMap<String, (u64, Metrics)> {
+        let mut commits: BTreeMap<String, (u64, Metrics)> = BTreeMap::new();
+        for snapshot in self.load().into_iter().filter(|s| s.scope == scope) {
+            let entry = commits.entry(snapshot.commit).or_default();
+            entry.0 = entry.0.max(snapshot.timestamp);
+            entry.1.merge(&snapshot.metrics);
+        }
+        commits
+    }
+}
+
+/// Normalize a tool's `path` argument into a snapshot scope.
+pub fn scope(path: Option<&str>) -> String {
+    let path = path.unwrap_or("").trim();
+    let path = path
+        .strip_prefix("./")
+        .unwrap_or(path)
+        .trim_end_matches('/');
+    if path.is_empty() {
+        ".".to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Trimmed stdout of a successful `git` command run in `workspace`.
+pub(crate) async fn git(workspace: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(workspace)
+        .output()
+        .await
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Size of `target/release/<package>`, if the workspace has built one since
+/// `committed`, the commit time of HEAD in seconds since the Unix epoch.
+///
+/// An older binary was built from some earlier commit, so its size says
+/// nothing about the one checked out.
+fn binary_size(workspace: &Path, committed: u64) -> Option<u64> {
+    let name = dependencies::cargo_package_name(workspace)?;
+    let binary =
+        workspace
+            .join("target/release")
+            .join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+    let metadata = std::fs::metadata(binary).ok()?;
+    let built = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    (built.as_secs() >= committed).then_some(metadata.len())
+}
+
+/// Record `metrics` against the commit checked out in `workspace`.
+///
+/// Best effort: outside a git repository nothing is recorded, and failures
+/// are logged rather than failing the run that measured the metrics.
+pub async fn record(workspace: &Path, scope: &str, source: &str, mut metrics: Metrics) {
+    let Some(commit) = git(workspace, &["rev-parse", "HEAD"]).await else {
+        debug!(
+            "Not recording metrics: {} is not a git checkout",
+            workspace.display()
+        );
+        return;
+    };
+    // The store itself must not mark the tree dirty
+    let status = git(workspace, &["status", "--porcelain", "--", ".", ":!.pcode"]).await;
+    let committed = git(workspace, &["log", "-1", "--format=%ct"])
+        .await
+        .and_then(|time| time.parse().ok());
+    metrics.binary_size = metrics
+        .binary_size
+        .or_else(|| committed.and_then(|time| binary_size(workspace, time)));
+    if metrics.is_empty() {
+        return;
+    }
+
+    let snapshot = Snapshot {
+        commit,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        dirty: status.is_some_and(|s| !s.is_empty()),
+        scope: scope.to_string(),
+        source: source.to_string(),
+        metrics,
+    };
+    if let Err(e) = MetricsStore::new(workspace).append(&snapshot) {
+        warn!("Failed to record metrics: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn snapshot(commit: &str, timestamp: u64, scope: &str, metrics: Metrics) -> Snapshot {
+        Snapshot {
+            commit: commit.to_string(),
+            timestamp,
+            dirty: false,
+            scope: scope.to_string(),
+            source: "test".to_string(),
+            metrics,
+        }
+    }
+
+    #[test]
+    fn test_complexity_from_report() {
+        let report = json!({
+            "summary": {"total_functions": 3, "max_complexity": 24, "average_complexity": 10.0,
+                        "max_cognitive": 30, "average_cognitive": 11.5},
+            "files": [{"file": "a.rs", "functions": [
+                {"cyclomatic": 1}, {"cyclomatic": 6}, {"cyclomatic": 24}
+            ]}]
+        });
+        let metrics = ComplexityMetrics::from_report(&report).unwrap();
+        assert_eq!(metrics.total_functions, 3);
+        assert_eq!(metrics.max_cyclomatic, 24);
+        assert_eq!(metrics.average_cognitive, 11.5);
+        assert_eq!(metrics.distribution["1-5"], 1);
+        assert_eq!(metrics.distribution["6-10"], 1);
+        assert_eq!(metrics.distribution["21+"], 1);
+        assert!(ComplexityMetrics::from_report(&json!({"error": "x"})).is_none());
+    }
+
+    #[test]
+    fn test_store_merges_snapshots_per_commit() {
+        let dir = TempDir::new().unwrap();
+        let store = MetricsStore::new(dir.path());
+        assert!(store.load().is_empty());
+
+        let satd = Metrics {
+            satd_items: Some(4),
+            coverage_percent: Some(70.0),
+            ..Metrics::default()
+        };
+        let coverage = Metrics {
+            coverage_percent: Some(82.5),
+            ..Metrics::default()
+        };
+        store.append(&snapshot("aaa", 10, ".", satd)).unwrap();
+        store.append(&snapshot("aaa", 20, ".", coverage)).unwrap();
+        store
+            .append(&snapshot("aaa", 30, "src", Metrics::default()))
+            .unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(METRICS_DIR).join(HISTORY_FILE))
+            .unwrap()
+            .write_all(b"not json\n")
+            .unwrap();
+
+        assert_eq!(store.load().len(), 3);
+        let commits = store.by_commit(".");
+        let (timestamp, metrics) = &commits["aaa"];
+        assert_eq!(*timestamp, 20);
+        assert_eq!(metrics.satd_items, Some(4));
+        assert_eq!(metrics.coverage_percent, Some(82.5));
+    }
+
+    #[test]
+    fn test_scope_and_binary_size() {
+        assert_eq!(scope(None), ".");
+        assert_eq!(scope(Some("./")), ".");
+        assert_eq!(scope(Some("./src/")), "src");
+
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n\n[dependencies]\nname = \"x\"\n",
+        )
+        .unwrap();
+        assert_eq!(binary_size(dir.path(), 0), None);
+
+        let release = dir.path().join("target/release");
+        std::fs::create_dir_all(&release).unwrap();
+        let binary = format!("demo{}", std::env::consts::EXE_SUFFIX);
+        std::fs::write(release.join(binary), vec![0u8; 128]).unwrap();
+        assert_eq!(binary_size(dir.path(), 0), Some(128));
+
+        // A binary older than the commit was built from an earlier one
+        let later = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600;
+        assert_eq!(binary_size(dir.path(), later), None);
+    }
+}
@@ -1,14 +1,12 @@
+use crate::security::{active_policy_or_default, SecurityPolicy};
+use crate::tools::artifacts;
+use crate::tools::runner::{CodeRunner, RunRequest, DENO, DENO_TYPESCRIPT, NODE};
 use crate::tools::{Tool, ToolError};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::path::PathBuf;
-use std::process::Stdio;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use tempfile::TempDir;
-use tokio::fs;
-use tokio::process::Command;
-use tokio::time::timeout;
 use tracing::{debug, info};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,11 +20,23 @@ struct JavaScriptParams {
     args: Option<Vec<String>>,
     #[serde(default)]
     use_deno: bool, // Use Deno instead of Node.js for better security
+    #[serde(default)]
+    typescript: bool, // Run as TypeScript with type checking (requires Deno)
+    #[serde(default)]
+    export_to: Option<String>, // Workspace directory to copy generated files into
+}
+
+/// A TypeScript diagnostic reported by `deno run --check`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeCheckError {
+    pub code: String,
+    pub message: String,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
 }
 
 #[derive(Debug)]
 pub struct JavaScriptTool {
-    #[allow(dead_code)]
     workspace: PathBuf,
 }
 
@@ -37,150 +47,155 @@ impl JavaScriptTool {
         }
     }
 
-    async fn create_sandbox_dir(&self) -> Result<TempDir, ToolError> {
-        TempDir::new()
-            .map_err(|e| ToolError::Execution(format!("Failed to create temp dir: {}", e)))
-    }
-
-    async fn write_js_script(&self, dir: &TempDir, code: &str) -> Result<PathBuf, ToolError> {
-        let script_path = dir.path().join("script.js");
-        fs::write(&script_path, code)
-            .await
-            .map_err(|e| ToolError::Execution(format!("Failed to write script: {}", e)))?;
-        Ok(script_path)
-    }
-
-    fn build_node_command(&self, script_path: &PathBuf) -> Command {
-        let mut cmd = Command::new("node");
-
-        // Security flags for Node.js
-        cmd.arg("--no-deprecation");
-        cmd.arg("--no-warnings");
-        cmd.arg("--disallow-code-generation-from-strings");
-
-        // Add the script
-        cmd.arg(script_path);
-
-        // Set working directory to sandbox
-        if let Some(parent) = script_path.parent() {
-            cmd.current_dir(parent);
-        }
-
-        // Configure process
-        cmd.stdin(Stdio::piped());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
-
-        // Environment restrictions
-        cmd.env_clear();
-        cmd.env("HOME", "/tmp");
-        cmd.env("TMPDIR", "/tmp");
-        cmd.env("PATH", "/usr/bin:/bin");
-        cmd.env("NODE_ENV", "production");
-        cmd.env("NODE_OPTIONS", "--max-old-space-size=256");
-
-        cmd
-    }
-
-    fn build_deno_command(&self, script_path: &PathBuf) -> Command {
-        let mut cmd = Command::new("deno");
-
-        // Deno run with no permissions (secure by default)
-        cmd.arg("run");
-        cmd.arg("--no-prompt");
-
-        // Add the script
-        cmd.arg(script_path);
-
-        // Set working directory to sandbox
-        if let Some(parent) = script_path.parent() {
-            cmd.current_dir(parent);
-        }
-
-        // Configure process
-        cmd.stdin(Stdio::piped());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
-
-        cmd
-    }
-
-    async fn check_runtime(&self, use_deno: bool) -> Result<bool, ToolError> {
-        let runtime = if use_deno { "deno" } else { "node" };
-
-        let check = Command::new(runtime).arg("--version").output().await;
-
-        match check {
-            Ok(output) if output.status.success() => Ok(true),
-            _ => Ok(false),
-        }
-    }
-
     async fn execute_javascript(&self, params: &JavaScriptParams) -> Result<Value, ToolError> {
-        // Create sandbox directory
-        let sandbox_dir = self.create_sandbox_dir().await?;
-        let script_path = self.write_js_script(&sandbox_dir, &params.code).await?;
-
-        // Check which runtime to use
-        let use_deno = params.use_deno || !self.check_runtime(false).await?;
+        // Check which runtime to use; TypeScript always runs on Deno
+        let use_deno =
+            params.use_deno || params.typescript || !CodeRunner::new(&NODE).is_available().await;
+
+        let runner = CodeRunner::new(match (use_deno, params.typescript) {
+            (_, true) => &DENO_TYPESCRIPT,
+            (true, false) => &DENO,
+            (false, false) => &NODE,
+        });
 
-        if use_deno && !self.check_runtime(true).await? {
-            return Err(ToolError::Execution(
-                "Neither Node.js nor Deno found. Please install Node.js or Deno.".to_string(),
-            ));
+        if use_deno && !runner.is_available().await {
+            return Err(ToolError::Execution(if params.typescript {
+                "TypeScript requires Deno. Please install Deno.".to_string()
+            } else {
+                "Neither Node.js nor Deno found. Please install Node.js or Deno.".to_string()
+            }));
         }
 
-        // Build command
-        let mut cmd = if use_deno {
-            self.build_deno_command(&script_path)
+        let sandbox = runner.prepare(&params.code).await?;
+
+        // Deno grants nothing by default; mirror pcode's own policy
+        let flags = if use_deno {
+            deno_permission_flags(&active_policy_or_default(&self.workspace), sandbox.path())
         } else {
-            self.build_node_command(&script_path)
+            Vec::new()
         };
 
-        // Add any user args
-        if let Some(args) = &params.args {
-            for arg in args {
-                cmd.arg(arg);
-            }
-        }
+        let args = params.args.clone().unwrap_or_default();
+        let request = RunRequest {
+            code: &params.code,
+            args: &args,
+            stdin: params.stdin.as_deref(),
+            timeout: Duration::from_millis(params.timeout_ms.unwrap_or(30000)),
+            flags,
+        };
 
         debug!(
             "Executing JavaScript in sandbox with {}",
             if use_deno { "Deno" } else { "Node.js" }
         );
 
-        // Set timeout
-        let timeout_duration = Duration::from_millis(params.timeout_ms.unwrap_or(30000));
-
-        // Execute
-        let output = match timeout(timeout_duration, cmd.output()).await {
-            Ok(Ok(output)) => output,
-            Ok(Err(e)) => {
-                return Err(ToolError::Execution(format!("Process error: {}", e)));
-            }
-            Err(_) => {
-                return Err(ToolError::Execution(format!(
-                    "Execution timeout ({}ms)",
-                    timeout_duration.as_millis()
-                )));
-            }
+        let output = runner.run_in(&sandbox, &request).await?;
+        let type_errors = if params.typescript {
+            parse_type_errors(&output.stderr)
+        } else {
+            Vec::new()
         };
 
-        // Parse output
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        let artifacts =
+            artifacts::gather(&sandbox, params.export_to.as_deref(), &self.workspace).await?;
 
         Ok(serde_json::json!({
-            "success": output.status.success(),
-            "exit_code": output.status.code().unwrap_or(-1),
-            "stdout": stdout,
-            "stderr": stderr,
+            "success": output.success && type_errors.is_empty(),
+            "exit_code": output.exit_code,
+            "stdout": output.stdout,
+            "stderr": output.stderr,
             "runtime": if use_deno { "deno" } else { "node" },
-            "duration_ms": timeout_duration.as_millis() as u64,
+            "language": if params.typescript { "typescript" } else { "javascript" },
+            "type_errors": type_errors,
+            "artifacts": artifacts,
+            "duration_ms": output.duration_ms,
         }))
     }
 }
 
+/// Map a security policy onto Deno permission flags.
+///
+/// The sandbox directory holding the script is always readable and
+/// writable; everything else comes from the policy.
+pub fn deno_permission_flags(policy: &SecurityPolicy, sandbox_dir: &Path) -> Vec<String> {
+    let mut flags = Vec::new();
+
+    let paths: Vec<String> = std::iter::once(sandbox_dir)
+        .chain(policy.allowed_paths.iter().map(PathBuf::as_path))
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    flags.push(format!("--allow-read={}", paths.join(",")));
+    flags.push(format!("--allow-write={}", paths.join(",")));
+
+    if policy.allow_network {
+        match &policy.network_policy {
+            Some(network) if !network.allowed_domains.is_empty() => {
+                let hosts: Vec<String> = if network.allowed_ports.is_empty() {
+                    network.allowed_domains.clone()
+                } else {
+                    network
+                        .allowed_domains
+                        .iter()
+                        .flat_map(|domain| {
+                            network
+                                .allowed_ports
+                                .iter()
+                                .map(move |port| format!("{}:{}", domain, port))
+                        })
+                        .collect()
+                };
+                flags.push(format!("--allow-net={}", hosts.join(",")));
+            }
+            // A policy with an explicit, empty domain list grants nothing
+            Some(_) => {}
+            None => flags.push("--allow-net".to_string()),
+        }
+    }
+
+    if policy.allow_process_spawn {
+        flags.push("--allow-run".to_string());
+    }
+
+    flags
+}
+
+/// Extract `TSxxxx [ERROR]` diagnostics and their locations from Deno output.
+pub fn parse_type_errors(stderr: &str) -> Vec<TypeCheckError> {
+    let mut errors: Vec<TypeCheckError> = Vec::new();
+
+    for line in stderr.lines() {
+        let line = line.trim();
+        if let Some(pos) = line.find(" [ERROR]: ") {
+            let code = line[..pos].rsplit(' ').next().unwrap_or("");
+            if code.starts_with("TS") {
+                errors.push(TypeCheckError {
+                    code: code.to_string(),
+                    message: line[pos + " [ERROR]: ".len()..].to_string(),
+                    line: None,
+                    column: None,
+                });
+            }
+        } else if let Some(location) = line.strip_prefix("at ") {
+            // Location lines look like: at file:///tmp/x/script.ts:3:7
+            let Some(last) = errors.last_mut() else {
+                continue;
+            };
+            if last.line.is_some() {
+                continue;
+            }
+            let mut parts = location.rsplitn(3, ':');
+            let column = parts.next().and_then(|c| c.parse().ok());
+            let line_no = parts.next().and_then(|l| l.parse().ok());
+            if line_no.is_some() {
+                last.line = line_no;
+                last.column = column;
+            }
+        }
+    }
+
+    errors
+}
+
 impl Default for JavaScriptTool {
     fn default() -> Self {
         Self::new()
@@ -287,4 +302,71 @@ mod tests {
             assert!(result["stdout"].as_str().unwrap().contains("3.14159"));
         }
     }
+
+    #[test]
+    fn test_deno_flags_follow_policy() {
+        use crate::security::{NetworkPolicy, Protocol};
+
+        let sandbox = Path::new("/tmp/sandbox");
+        let mut policy = SecurityPolicy {
+            allowed_paths: vec![PathBuf::from("/work")],
+            ..Default::default()
+        };
+
+        let flags = deno_permission_flags(&policy, sandbox);
+        assert_eq!(
+            flags,
+            vec![
+                "--allow-read=/tmp/sandbox,/work",
+                "--allow-write=/tmp/sandbox,/work"
+            ]
+        );
+
+        policy.allow_network = true;
+        policy.allow_process_spawn = true;
+        policy.network_policy = Some(NetworkPolicy {
+            allowed_domains: vec!["api.github.com".to_string(), "crates.io".to_string()],
+            allowed_ports: vec![443],
+            allowed_protocols: vec![Protocol::Https],
+        });
+
+        let flags = deno_permission_flags(&policy, sandbox);
+        assert!(flags.contains(&"--allow-net=api.github.com:443,crates.io:443".to_string()));
+        assert!(flags.contains(&"--allow-run".to_string()));
+
+        policy.network_policy = None;
+        let flags = deno_permission_flags(&policy, sandbox);
+        assert!(flags.contains(&"--allow-net".to_string()));
+    }
+
+    #[test]
+    fn test_parse_type_errors() {
+        let stderr = "Check file:///tmp/.tmpAbc/script.ts\n\
+            error: TS2322 [ERROR]: Type 'string' is not assignable to type 'number'.\n\
+            const x: number = \"a\";\n\
+                  ^\n\
+                at file:///tmp/.tmpAbc/script.ts:1:7\n";
+
+        let errors = parse_type_errors(stderr);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "TS2322");
+        assert!(errors[0].message.starts_with("Type 'string'"));
+        assert_eq!(errors[0].line, Some(1));
+        assert_eq!(errors[0].column, Some(7));
+    }
+
+    #[tokio::test]
+    async fn test_typescript_type_error() {
+        let tool = JavaScriptTool::new();
+        let params = serde_json::json!({
+            "code": "const x: number = 'a';\nconsole.log(x);",
+            "typescript": true
+        });
+
+        // This test might fail if Deno is not installed
+        if let Ok(result) = tool.execute(params).await {
+            assert!(!result["success"].as_bool().unwrap());
+            assert_eq!(result["type_errors"][0]["code"], "TS2322");
+        }
+    }
 }
@@ -0,0 +1,598 @@
+//! Code navigation through language servers: `definition`, `references`,
+//! `hover`, `workspace_symbols` and `diagnostics`.
+//!
+//! Positions are 1-based lines and character columns, as an editor shows
+//! them; they are converted to and from the protocol's 0-based UTF-16
+//! offsets here.
+
+use crate::lsp::{
+    char_column, path_to_uri, uri_to_path, utf16_offset, LspError, LspManager, Position,
+};
+use crate::tools::{Tool, ToolError};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Symbols returned by `workspace_symbols` unless `limit` says otherwise.
+const DEFAULT_SYMBOL_LIMIT: usize = 50;
+/// How long `diagnostics` waits for a report on a file it just sent.
+const DEFAULT_DIAGNOSTICS_WAIT_MS: u64 = 2000;
+
+/// Names of the protocol's `SymbolKind` values, from 1.
+const SYMBOL_KINDS: &[&str] = &[
+    "file",
+    "module",
+    "namespace",
+    "package",
+    "class",
+    "method",
+    "property",
+    "field",
+    "constructor",
+    "enum",
+    "interface",
+    "function",
+    "variable",
+    "constant",
+    "string",
+    "number",
+    "boolean",
+    "array",
+    "object",
+    "key",
+    "null",
+    "enum_member",
+    "struct",
+    "event",
+    "operator",
+    "type_parameter",
+];
+const SEVERITIES: &[&str] = &["error", "warning", "information", "hint"];
+
+impl From<LspError> for ToolError {
+    fn from(e: LspError) -> Self {
+        match e {
+            LspError::Unsupported(_) => ToolError::InvalidParams(e.to_string()),
+            _ => ToolError::Execution(e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PositionParams {
+    path: String,
+    /// 1-based
+    line: usize,
+    /// 1-based, in characters
+    column: usize,
+    /// For `references`: list the declaration too
+    #[serde(default = "default_true")]
+    include_declaration: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+struct SymbolParams {
+    query: String,
+    /// Server to ask; by default every running server and those whose
+    /// project files are at the workspace root
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiagnosticsParams {
+    /// File to check; without it, everything reported so far
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    wait_ms: Option<u64>,
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, ToolError> {
+    serde_json::from_value(params).map_err(|e| ToolError::InvalidParams(e.to_string()))
+}
+
+/// `path` inside the workspace, which must be an existing file.
+pub(crate) fn resolve(workspace: &Path, path: &str) -> Result<PathBuf, ToolError> {
+    let relative = Path::new(path.trim());
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(ToolError::InvalidParams(
+            "Path must be within workspace".to_string(),
+        ));
+    }
+    let path = workspace.join(relative);
+    if !path.is_file() {
+        return Err(ToolError::NotFound(format!(
+            "No such file: {}",
+            relative.display()
+        )));
+    }
+    Ok(path)
+}
+
+/// Reads files once per call to turn protocol ranges into editor
+/// positions with the source line attached.
+struct Locations<'a> {
+    workspace: &'a Path,
+    files: HashMap<PathBuf, Option<Vec<String>>>,
+}
+
+impl<'a> Locations<'a> {
+    fn new(workspace: &'a Path) -> Self {
+        Self {
+            workspace,
+            files: HashMap::new(),
+        }
+    }
+
+    fn line(&mut self, path: &Path, line: usize) -> Option<&str> {
+        self.files
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                std::fs::read_to_string(path)
+                    .ok()
+                    .map(|text| text.lines().map(str::to_string).collect())
+            })
+            .as_ref()?
+            .get(line)
+            .map(String::as_str)
+    }
+
+    fn point(&mut self, path: &Path, position: &Value) -> (usize, usize) {
+        let line = position["line"].as_u64().unwrap_or(0) as usize;
+        let offset = position["character"].as_u64().unwrap_or(0) as u32;
+        let column = self
+            .line(path, line)
+            .map_or(offset as usize, |text| char_column(text, offset));
+        (line + 1, column + 1)
+    }
+
+    /// `{file, line, column, end_line, end_column, text}` for a URI and an
+    /// optional range.
+    fn describe(&mut self, uri: &str, range: Option<&Value>) -> Value {
+        let Some(path) = uri_to_path(uri) else {
+            return json!({ "file": uri });
+        };
+        let file = path
+            .strip_prefix(self.workspace)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+        let Some(range) = range.filter(|r| r.is_object()) else {
+            return json!({ "file": file });
+        };
+        let (line, column) = self.point(&path, &range["start"]);
+        let (end_line, end_column) = self.point(&path, &range["end"]);
+        let text = self.line(&path, line - 1).map(str::trim);
+        json!({
+            "file": file,
+            "line": line,
+            "column": column,
+            "end_line": end_line,
+            "end_column": end_column,
+            "text": text
+        })
+    }
+
+    /// A `Location`, `Location[]` or `LocationLink[]` result as a list.
+    fn list(&mut self, result: &Value) -> Vec<Value> {
+        let items = match result {
+            Value::Array(items) => items.iter().collect(),
+            Value::Null => Vec::new(),
+            single => vec![single],
+        };
+        items
+            .into_iter()
+            .map(|item| match item["targetUri"].as_str() {
+                Some(uri) => self.describe(uri, Some(&item["targetSelectionRange"])),
+                None => self.describe(item["uri"].as_str().unwrap_or(""), Some(&item["range"])),
+            })
+            .collect()
+    }
+}
+
+/// The protocol position for a 1-based line and column of `path`.
+pub(crate) fn position(path: &Path, line: usize, column: usize) -> Result<Position, ToolError> {
+    if line == 0 || column == 0 {
+        return Err(ToolError::InvalidParams(
+            "line and column start at 1".to_string(),
+        ));
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| ToolError::Execution(e.to_string()))?;
+    let source_line = text.lines().nth(line - 1).ok_or_else(|| {
+        ToolError::InvalidParams(format!("{} has no line {}", path.display(), line))
+    })?;
+    Ok(Position {
+        line: (line - 1) as u32,
+        character: utf16_offset(source_line, column - 1),
+    })
+}
+
+/// Hover contents (`MarkupContent`, `MarkedString` or a list of them) as
+/// one markdown string.
+fn hover_text(contents: &Value) -> String {
+    match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .map(hover_text)
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        Value::Object(object) => {
+            let value = object.get("value").and_then(|v| v.as_str()).unwrap_or("");
+            match object.get("language").and_then(|l| l.as_str()) {
+                Some(language) => format!("```{}\n{}\n```", language, value),
+                None => value.to_string(),
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+fn kind_name(kinds: &[&str], value: &Value) -> Value {
+    value
+        .as_u64()
+        .and_then(|k| kinds.get((k as usize).checked_sub(1)?))
+        .map_or(Value::Null, |name| json!(name))
+}
+
+/// Where the symbol at a position is defined.
+pub struct DefinitionTool {
+    lsp: Arc<LspManager>,
+}
+
+impl DefinitionTool {
+    pub fn new(lsp: Arc<LspManager>) -> Self {
+        Self { lsp }
+    }
+}
+
+/// Every use of the symbol at a position.
+pub struct ReferencesTool {
+    lsp: Arc<LspManager>,
+}
+
+impl ReferencesTool {
+    pub fn new(lsp: Arc<LspManager>) -> Self {
+        Self { lsp }
+    }
+}
+
+/// Type and documentation of the symbol at a position.
+pub struct HoverTool {
+    lsp: Arc<LspManager>,
+}
+
+impl HoverTool {
+    pub fn new(lsp: Arc<LspManager>) -> Self {
+        Self { lsp }
+    }
+}
+
+/// Symbols across the workspace matching a query.
+pub struct WorkspaceSymbolsTool {
+    lsp: Arc<LspManager>,
+}
+
+impl WorkspaceSymbolsTool {
+    pub fn new(lsp: Arc<LspManager>) -> Self {
+        Self { lsp }
+    }
+}
+
+/// Errors and warnings reported by the language servers.
+pub struct DiagnosticsTool {
+    lsp: Arc<LspManager>,
+}
+
+impl DiagnosticsTool {
+    pub fn new(lsp: Arc<LspManager>) -> Self {
+        Self { lsp }
+    }
+}
+
+#[async_trait]
+impl Tool for DefinitionTool {
+    fn name(&self) -> &str {
+        "definition"
+    }
+
+    fn description(&self) -> &str {
+        "Go to the definition of the symbol at a 1-based line and column, using the file's language server (rust-analyzer, pyright, typescript-language-server)"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: PositionParams = parse(params)?;
+        let path = resolve(self.lsp.workspace(), &params.path)?;
+        let position = position(&path, params.line, params.column)?;
+        let client = self.lsp.client_for(&path).await?;
+        let result = client.definition(&path, position).await?;
+        Ok(json!({ "definitions": Locations::new(self.lsp.workspace()).list(&result) }))
+    }
+}
+
+#[async_trait]
+impl Tool for ReferencesTool {
+    fn name(&self) -> &str {
+        "references"
+    }
+
+    fn description(&self) -> &str {
+        "Find all references to the symbol at a 1-based line and column, using the file's language server"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: PositionParams = parse(params)?;
+        let path = resolve(self.lsp.workspace(), &params.path)?;
+        let position = position(&path, params.line, params.column)?;
+        let client = self.lsp.client_for(&path).await?;
+        let result = client
+            .references(&path, position, params.include_declaration)
+            .await?;
+        let references = Locations::new(self.lsp.workspace()).list(&result);
+        Ok(json!({ "count": references.len(), "references": references }))
+    }
+}
+
+#[async_trait]
+impl Tool for HoverTool {
+    fn name(&self) -> &str {
+        "hover"
+    }
+
+    fn description(&self) -> &str {
+        "Show the type signature and documentation of the symbol at a 1-based line and column, using the file's language server"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: PositionParams = parse(params)?;
+        let path = resolve(self.lsp.workspace(), &params.path)?;
+        let position = position(&path, params.line, params.column)?;
+        let client = self.lsp.client_for(&path).await?;
+        let result = client.hover(&path, position).await?;
+        if result.is_null() {
+            return Ok(json!({ "contents": null }));
+        }
+        let mut hover = json!({ "contents": hover_text(&result["contents"]) });
+        if result["range"].is_object() {
+            let uri = path_to_uri(&path);
+            hover["range"] =
+                Locations::new(self.lsp.workspace()).describe(&uri, Some(&result["range"]));
+        }
+        Ok(hover)
+    }
+}
+
+#[async_trait]
+impl Tool for WorkspaceSymbolsTool {
+    fn name(&self) -> &str {
+        "workspace_symbols"
+    }
+
+    fn description(&self) -> &str {
+        "Search symbols across the workspace by name through the language servers, with their kind, container and location"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: SymbolParams = parse(params)?;
+        let mut languages: Vec<String> = match &params.language {
+            Some(language) => vec![language.clone()],
+            None => {
+                let running = self.lsp.running().await;
+                let mut languages: Vec<String> =
+                    running.iter().map(|c| c.language().to_string()).collect();
+                for language in self.lsp.detected_languages() {
+                    languages.push(language.to_string());
+                }
+                languages
+            }
+        };
+        languages.sort();
+        languages.dedup();
+        if languages.is_empty() {
+            return Err(ToolError::InvalidParams(format!(
+                "No language server running or detected; pass language ({})",
+                self.lsp.languages().join(", ")
+            )));
+        }
+
+        let limit = params.limit.unwrap_or(DEFAULT_SYMBOL_LIMIT);
+        let mut locations = Locations::new(self.lsp.workspace());
+        let mut symbols = Vec::new();
+        for language in &languages {
+            let client = self.lsp.client(language).await?;
+            let result = client.workspace_symbols(&params.query).await?;
+            for symbol in result.as_array().into_iter().flatten() {
+                let location = &symbol["location"];
+                let mut entry = locations.describe(
+                    location["uri"].as_str().unwrap_or(""),
+                    location.get("range"),
+                );
+                entry["name"] = symbol["name"].clone();
+                entry["kind"] = kind_name(SYMBOL_KINDS, &symbol["kind"]);
+                if let Some(container) = symbol["containerName"].as_str() {
+                    entry["container"] = json!(container);
+                }
+                symbols.push(entry);
+            }
+        }
+        let total = symbols.len();
+        symbols.truncate(limit);
+        Ok(json!({ "languages": languages, "total": total, "symbols": symbols }))
+    }
+}
+
+#[async_trait]
+impl Tool for DiagnosticsTool {
+    fn name(&self) -> &str {
+        "diagnostics"
+    }
+
+    fn description(&self) -> &str {
+        "Compiler and linter diagnostics from the language servers for a file, or everything reported so far"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: DiagnosticsParams = parse(params)?;
+        let reports = match &params.path {
+            Some(path) => {
+                let path = resolve(self.lsp.workspace(), path)?;
+                let wait =
+                    Duration::from_millis(params.wait_ms.unwrap_or(DEFAULT_DIAGNOSTICS_WAIT_MS));
+                let client = self.lsp.client_for(&path).await?;
+                let diagnostics = client.diagnostics(&path, wait).await?;
+                vec![(path_to_uri(&path), diagnostics)]
+            }
+            None => {
+                let mut reports = Vec::new();
+                for client in self.lsp.running().await {
+                    reports.extend(client.published_diagnostics());
+                }
+                reports
+            }
+        };
+
+        let mut locations = Locations::new(self.lsp.workspace());
+        let mut diagnostics = Vec::new();
+        for (uri, items) in &reports {
+            for item in items {
+                let mut entry = locations.describe(uri, Some(&item["range"]));
+                entry["severity"] = kind_name(SEVERITIES, &item["severity"]);
+                entry["message"] = item["message"].clone();
+                for key in ["source", "code"] {
+                    if !item[key].is_null() {
+                        entry[key] = item[key].clone();
+                    }
+                }
+                diagnostics.push(entry);
+            }
+        }
+        Ok(json!({ "count": diagnostics.len(), "diagnostics": diagnostics }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::fake_server;
+    use tempfile::TempDir;
+
+    fn lsp(dir: &TempDir) -> Arc<LspManager> {
+        std::fs::write(
+            dir.path().join("app.py"),
+            "class Greeter:\n    def greet(self, name):\n        return name  # TODO: format\n\nGreeter().greet('x')\n",
+        )
+        .unwrap();
+        let servers = vec![fake_server::config(dir)];
+        Arc::new(LspManager::with_servers(dir.path().to_path_buf(), servers))
+    }
+
+    #[tokio::test]
+    async fn test_navigation_tools() {
+        let dir = TempDir::new().unwrap();
+        let lsp = lsp(&dir);
+        let at_call = json!({"path": "app.py", "line": 5, "column": 12});
+
+        let result = DefinitionTool::new(lsp.clone())
+            .execute(at_call.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            result["definitions"][0],
+            json!({"file": "app.py", "line": 2, "column": 9, "end_line": 2, "end_column": 14, "text": "def greet(self, name):"})
+        );
+
+        let result = ReferencesTool::new(lsp.clone())
+            .execute(at_call.clone())
+            .await
+            .unwrap();
+        assert_eq!(result["count"], 2);
+        assert_eq!(result["references"][1]["line"], 5);
+
+        let result = HoverTool::new(lsp.clone()).execute(at_call).await.unwrap();
+        assert_eq!(result["contents"], "def greet(self, name):");
+
+        let result = WorkspaceSymbolsTool::new(lsp.clone())
+            .execute(json!({"query": "greet"}))
+            .await
+            .unwrap();
+        assert_eq!(result["languages"], json!(["python"]));
+        let kinds: Vec<&str> = result["symbols"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(kinds, vec!["class", "function"]);
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_tool() {
+        let dir = TempDir::new().unwrap();
+        let tool = DiagnosticsTool::new(lsp(&dir));
+
+        let result = tool.execute(json!({})).await.unwrap();
+        assert_eq!(result["count"], 0);
+
+        let result = tool.execute(json!({"path": "app.py"})).await.unwrap();
+        assert_eq!(result["count"], 1);
+        let diagnostic = &result["diagnostics"][0];
+        assert_eq!(diagnostic["file"], "app.py");
+        assert_eq!(diagnostic["line"], 3);
+        assert_eq!(diagnostic["column"], 24);
+        assert_eq!(diagnostic["severity"], "warning");
+        assert_eq!(diagnostic["code"], "todo");
+
+        let result = tool.execute(json!({})).await.unwrap();
+        assert_eq!(result["count"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_params() {
+        let dir = TempDir::new().unwrap();
+        let lsp = lsp(&dir);
+        std::fs::write(dir.path().join("notes.txt"), "text\n").unwrap();
+        let tool = DefinitionTool::new(lsp.clone());
+
+        for params in [
+            json!({"path": "app.py", "line": 0, "column": 1}),
+            json!({"path": "app.py", "line": 99, "column": 1}),
+            json!({"path": "../app.py", "line": 1, "column": 1}),
+            json!({"path": "notes.txt", "line": 1, "column": 1}),
+            json!({"path": "app.py"}),
+        ] {
+            let err = tool.execute(params.clone()).await.unwrap_err();
+            assert!(
+                matches!(err, ToolError::InvalidParams(_)),
+                "{}: {}",
+                params,
+                err
+            );
+        }
+        let err = tool
+            .execute(json!({"path": "missing.py", "line": 1, "column": 1}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::NotFound(_)));
+
+        let err = WorkspaceSymbolsTool::new(lsp)
+            .execute(json!({"query": "greet"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParams(_)));
+    }
+}
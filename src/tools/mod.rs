@@ -1,15 +1,34 @@
+pub mod artifacts;
+pub mod ast_search;
 pub mod bash;
 pub mod coverage;
+pub mod deps;
 pub mod dev_cli;
+pub mod edit;
 pub mod file;
 pub mod fix;
+pub mod formatters;
+pub mod gate;
+pub mod history;
 pub mod javascript;
 pub mod llm;
+pub mod lsp;
+pub mod params;
 pub mod pmat;
+pub mod pmat_schema;
 pub mod process;
 pub mod python;
+pub mod python_session;
 pub mod refactor;
+pub mod rename;
+pub mod replace;
+pub mod repo_map;
+pub mod runner;
 pub mod stream_exec;
+pub mod symbol_index;
+pub mod symbols;
+pub mod testgen;
+pub mod trends;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
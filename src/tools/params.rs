@@ -0,0 +1,280 @@
+//! Parameters for `/tool <text>` commands, shared by the interactive chat
+//! and `pcode -c`.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+/// Build the JSON parameters for `tool_name` from the free text after the
+/// command name, failing with the command's usage on malformed input.
+pub fn parse_tool_params(tool_name: &str, params_str: &str) -> Result<Value> {
+    match tool_name {
+        "file_read" => Ok(json!({ "path": params_str })),
+        "file_write" => {
+            let parts: Vec<&str> = params_str.splitn(2, ' ').collect();
+            if parts.len() == 2 {
+                Ok(json!({ "path": parts[0], "content": parts[1] }))
+            } else {
+                anyhow::bail!("Usage: /file_write <path> <content>");
+            }
+        }
+        "process" => {
+            let parts: Vec<&str> = params_str.split_whitespace().collect();
+            if parts.is_empty() {
+                anyhow::bail!("Usage: /process <command> [args...]");
+            }
+            Ok(json!({
+                "command": parts[0],
+                "args": if parts.len() > 1 { Some(parts[1..].to_vec()) } else { None }
+            }))
+        }
+        "llm" => Ok(json!({ "prompt": params_str })),
+        "token_estimate" => Ok(json!({ "text": params_str })),
+        "pmat" => {
+            let parts: Vec<&str> = params_str.split_whitespace().collect();
+            if parts.len() < 2 {
+                anyhow::bail!(
+                    "Usage: /pmat <command> <path>\n   Commands: complexity, satd, coverage, tdg, big-o"
+                );
+            }
+            Ok(json!({ "command": parts[0], "path": parts[1], "args": parts[2..].to_vec() }))
+        }
+        "bash" => Ok(json!({ "command": params_str })),
+        "gate" | "testgen" | "deps" => match params_str.trim() {
+            "" | "{}" => Ok(json!({})),
+            path => Ok(json!({ "path": path })),
+        },
+        "trends" => match params_str.trim() {
+            "" | "{}" => Ok(json!({})),
+            limit => match limit.parse::<usize>() {
+                Ok(limit) => Ok(json!({ "limit": limit })),
+                Err(_) => anyhow::bail!("Usage: /trends [commits]"),
+            },
+        },
+        "symbols" => {
+            let text = params_str.trim();
+            let (first, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+            Ok(match first {
+                "outline" => json!({ "action": "outline", "path": rest.trim() }),
+                "lookup" | "search" => json!({ "action": first, "query": rest.trim() }),
+                "update" | "" | "{}" => json!({ "action": "update" }),
+                _ => json!({ "action": "search", "query": text }),
+            })
+        }
+        "definition" | "references" | "hover" => {
+            // <path>:<line>:<column>, as compilers print locations
+            let mut parts = params_str.trim().rsplitn(3, ':');
+            match (
+                parts.next().map(str::parse::<usize>),
+                parts.next().map(str::parse::<usize>),
+                parts.next(),
+            ) {
+                (Some(Ok(column)), Some(Ok(line)), Some(path)) => {
+                    Ok(json!({ "path": path, "line": line, "column": column }))
+                }
+                _ => anyhow::bail!("Usage: /{} <path>:<line>:<column>", tool_name),
+            }
+        }
+        "rename" => {
+            let mut parts: Vec<&str> = params_str.split_whitespace().collect();
+            let dry_run = parts.last() == Some(&"--dry-run");
+            if dry_run {
+                parts.pop();
+            }
+            let [target, new_name] = parts[..] else {
+                anyhow::bail!("Usage: /rename <old_name|path:line:column> <new_name> [--dry-run]");
+            };
+            let mut params = match parse_tool_params("definition", target) {
+                Ok(position) => position,
+                Err(_) => json!({ "old_name": target }),
+            };
+            params["new_name"] = json!(new_name);
+            params["dry_run"] = json!(dry_run);
+            Ok(params)
+        }
+        "replace" => {
+            let mut parts: Vec<&str> = params_str.split_whitespace().collect();
+            let dry_run = parts.last() == Some(&"--dry-run");
+            if dry_run {
+                parts.pop();
+            }
+            if parts.len() < 2 {
+                anyhow::bail!(
+                    "Usage: /replace <pattern> <replacement> [glob...] [--dry-run]\n   Use JSON params for patterns with spaces, exclude globs or confirm ids"
+                );
+            }
+            Ok(json!({
+                "pattern": parts[0],
+                "replacement": parts[1],
+                "paths": parts[2..].to_vec(),
+                "dry_run": dry_run
+            }))
+        }
+        "ast_search" => {
+            let usage = "Usage: /ast_search <pattern> [--lang <language>] [--kind <kind>] [--inside|--not-inside|--has|--not-has <pattern>] [--rewrite <template>] [--path <glob>] [--dry-run]";
+            // Patterns contain spaces, so options are split on " --"
+            let padded = format!(" {}", params_str);
+            let mut segments = padded.split(" --");
+            let pattern = segments.next().unwrap_or_default().trim();
+            let mut params = json!({});
+            if !pattern.is_empty() {
+                params["pattern"] = json!(pattern);
+            }
+            let mut paths = Vec::new();
+            for segment in segments {
+                let (flag, value) = segment.split_once(' ').unwrap_or((segment, ""));
+                let value = value.trim();
+                match (flag, value.is_empty()) {
+                    ("dry-run", true) => params["dry_run"] = json!(true),
+                    ("path", false) => paths.push(value),
+                    ("lang", false) => params["language"] = json!(value),
+                    ("kind" | "inside" | "not-inside" | "has" | "not-has" | "rewrite", false) => {
+                        params[flag.replace('-', "_")] = json!(value)
+                    }
+                    _ => anyhow::bail!(usage),
+                }
+            }
+            if params.get("pattern").is_none() && params.get("kind").is_none() {
+                anyhow::bail!(usage);
+            }
+            if !paths.is_empty() {
+                params["paths"] = json!(paths);
+            }
+            Ok(params)
+        }
+        "workspace_symbols" => Ok(json!({ "query": params_str.trim() })),
+        "diagnostics" => match params_str.trim() {
+            "" | "{}" => Ok(json!({})),
+            path => Ok(json!({ "path": path })),
+        },
+        "dev_cli" => {
+            let parts: Vec<&str> = params_str.split_whitespace().collect();
+            if parts.is_empty() {
+                anyhow::bail!(
+                    "Usage: /dev_cli <tool> [args...]\n   Tools: rg, fd, cargo, git, make, etc."
+                );
+            }
+            Ok(json!({ "tool": parts[0], "args": parts[1..].to_vec() }))
+        }
+        "fix" => {
+            let parts: Vec<&str> = params_str.split_whitespace().collect();
+            if parts.len() < 2 {
+                anyhow::bail!(
+                    "Usage: /fix <type> <path> [--dry-run]\n   Types: complexity, format, lint"
+                );
+            }
+            let dry_run = parts.get(2).is_some_and(|&s| s == "--dry-run");
+            Ok(json!({
+                "fix_type": parts[0],
+                "path": parts[1],
+                "dry_run": dry_run
+            }))
+        }
+        "python" | "rust" | "go" | "ruby" | "shell" => Ok(json!({ "code": params_str })),
+        "javascript" => {
+            // Check if params contain use_deno or typescript flags
+            if let Some(code) = params_str.strip_suffix(" --ts") {
+                Ok(json!({ "code": code, "typescript": true }))
+            } else if params_str.ends_with(" --deno") {
+                let code = params_str.trim_end_matches(" --deno");
+                Ok(json!({ "code": code, "use_deno": true }))
+            } else {
+                Ok(json!({ "code": params_str }))
+            }
+        }
+        _ => anyhow::bail!("Unknown tool: {}", tool_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trends_params_parsing() {
+        assert_eq!(
+            parse_tool_params("trends", "20").unwrap(),
+            json!({ "limit": 20 })
+        );
+        assert!(parse_tool_params("trends", "many").is_err());
+    }
+
+    #[test]
+    fn test_symbols_params_parsing() {
+        assert_eq!(
+            parse_tool_params("symbols", "ToolRegistry").unwrap(),
+            json!({ "action": "search", "query": "ToolRegistry" })
+        );
+        assert_eq!(
+            parse_tool_params("symbols", "lookup ToolRegistry::execute").unwrap(),
+            json!({ "action": "lookup", "query": "ToolRegistry::execute" })
+        );
+        assert_eq!(
+            parse_tool_params("symbols", "outline src/main.rs").unwrap(),
+            json!({ "action": "outline", "path": "src/main.rs" })
+        );
+    }
+
+    #[test]
+    fn test_lsp_params_parsing() {
+        assert_eq!(
+            parse_tool_params("definition", "src/main.rs:12:5").unwrap(),
+            json!({ "path": "src/main.rs", "line": 12, "column": 5 })
+        );
+        assert!(parse_tool_params("hover", "src/main.rs:12").is_err());
+        assert_eq!(parse_tool_params("diagnostics", "").unwrap(), json!({}));
+        assert_eq!(
+            parse_tool_params("rename", "src/lib.rs:3:12 ToolRegistry --dry-run").unwrap(),
+            json!({
+                "path": "src/lib.rs", "line": 3, "column": 12,
+                "new_name": "ToolRegistry", "dry_run": true
+            })
+        );
+        assert_eq!(
+            parse_tool_params("rename", "Registry ToolRegistry").unwrap(),
+            json!({ "old_name": "Registry", "new_name": "ToolRegistry", "dry_run": false })
+        );
+        assert_eq!(
+            parse_tool_params("rename", "tools::Registry ToolRegistry").unwrap(),
+            json!({ "old_name": "tools::Registry", "new_name": "ToolRegistry", "dry_run": false })
+        );
+        assert!(parse_tool_params("rename", "Registry").is_err());
+    }
+
+    #[test]
+    fn test_replace_params_parsing() {
+        assert_eq!(
+            parse_tool_params("replace", r"foo\((\d+)\) bar($1) src/**/*.rs --dry-run").unwrap(),
+            json!({
+                "pattern": r"foo\((\d+)\)",
+                "replacement": "bar($1)",
+                "paths": ["src/**/*.rs"],
+                "dry_run": true
+            })
+        );
+        assert!(parse_tool_params("replace", "foo").is_err());
+    }
+
+    #[test]
+    fn test_ast_search_params_parsing() {
+        assert_eq!(
+            parse_tool_params(
+                "ast_search",
+                "$X.unwrap() --inside async fn $F($$$) { $$$ } --rewrite $X? --path src/**/*.rs --dry-run"
+            )
+            .unwrap(),
+            json!({
+                "pattern": "$X.unwrap()",
+                "inside": "async fn $F($$$) { $$$ }",
+                "rewrite": "$X?",
+                "paths": ["src/**/*.rs"],
+                "dry_run": true
+            })
+        );
+        assert_eq!(
+            parse_tool_params("ast_search", "--kind match_expression --not-has _ => $$$").unwrap(),
+            json!({ "kind": "match_expression", "not_has": "_ => $$$" })
+        );
+        assert!(parse_tool_params("ast_search", "").is_err());
+        assert!(parse_tool_params("ast_search", "$X --lang").is_err());
+    }
+}
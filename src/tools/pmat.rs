@@ -1,16 +1,24 @@
-use super::{Tool, ToolError};
+use super::history::{self, ComplexityMetrics, Metrics};
+use super::runner::resolve_program;
+use super::{pmat_schema, Tool, ToolError};
+use crate::analysis::complexity::{self, Thresholds};
+use crate::analysis::{big_o, dead_code, satd, tdg};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::time::Duration;
 use tokio::process::Command;
+use tokio::sync::OnceCell;
 use tokio::time::timeout;
-use tracing::info;
+use tracing::{info, warn};
+
+/// Commands supported by both backends.
+const COMMANDS: &[&str] = &["complexity", "satd", "tdg", "dead-code", "big-o"];
 
 #[derive(Debug, Serialize, Deserialize)]
 struct PmatParams {
-    /// Command to run: complexity, satd, tdg, big-o, etc.
+    /// Command to run: complexity, satd, tdg, dead-code, big-o
     command: String,
     /// Path to analyze (file or directory)
     path: String,
@@ -21,181 +29,157 @@ struct PmatParams {
 
 pub struct PmatTool {
     workspace: PathBuf,
+    /// Installed pmat binary, probed on first use
+    binary: OnceCell<Option<PathBuf>>,
 }
 
 impl PmatTool {
     pub fn new() -> Self {
+        Self::with_workspace(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+    }
+
+    /// Analyze and record metrics for `workspace` instead of the current directory.
+    pub fn with_workspace(workspace: PathBuf) -> Self {
         Self {
-            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            workspace,
+            binary: OnceCell::new(),
         }
     }
 
+    async fn pmat_binary(&self) -> Option<&PathBuf> {
+        self.binary
+            .get_or_init(|| async {
+                let program = resolve_program("pmat");
+                let status = Command::new(&program)
+                    .arg("--version")
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .await;
+                matches!(status, Ok(s) if s.success()).then_some(program)
+            })
+            .await
+            .as_ref()
+    }
+
+    /// Run `pmat analyze <command> --format json` and normalize its output.
     async fn execute_pmat(
         &self,
+        binary: &Path,
         command: &str,
         path: &str,
         args: &[String],
-    ) -> Result<String, ToolError> {
-        // Create a temporary Python script with mock PMAT functionality
-        let script = match command {
-            "complexity" => {
-                r#"
-import json
-import sys
-import os
-
-def analyze_complexity(path):
-    # Mock complexity analysis
-    result = {
-        "summary": {
-            "max_complexity": 15,
-            "average_complexity": 8.5,
-            "total_functions": 10,
-            "violations": 0
-        },
-        "files": [
-            {
-                "file": "src/main.rs",
-                "functions": [
-                    {"name": "main", "complexity": 5},
-                    {"name": "process", "complexity": 15}
-                ]
-            }
-        ],
-        "details": []
-    }
-    return result
+    ) -> Result<Value, ToolError> {
+        let mut cmd = Command::new(binary);
+        cmd.args(["analyze", command, "--path", path, "--format", "json"]);
+        // Flags only the native analyzers understand
+        cmd.args(
+            args.iter()
+                .filter(|arg| !NATIVE_FLAGS.contains(&arg.as_str())),
+        );
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+        cmd.current_dir(&self.workspace);
+        cmd.kill_on_drop(true);
 
-if __name__ == "__main__":
-    path = sys.argv[1] if len(sys.argv) > 1 else "."
-    print(json.dumps(analyze_complexity(path)))
-"#
-            }
-            "satd" => {
-                r#"
-import json
-import sys
-
-def analyze_satd(path):
-    # Mock SATD analysis
-    result = {
-        "summary": {
-            "total_items": 3,
-            "categories": {
-                "TODO": 2,
-                "FIXME": 1
-            }
-        },
-        "items": []
-    }
-    return result
+        let output = match timeout(Duration::from_secs(120), cmd.output()).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => return Err(ToolError::Execution(format!("Process error: {}", e))),
+            Err(_) => return Err(ToolError::Execution("PMAT timeout (120s)".to_string())),
+        };
+        if !output.status.success() {
+            return Err(ToolError::Execution(format!(
+                "pmat exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
 
-if __name__ == "__main__":
-    path = sys.argv[1] if len(sys.argv) > 1 else "."
-    print(json.dumps(analyze_satd(path)))
-"#
-            }
-            "tdg" => {
-                r#"
-import json
-import sys
-
-def analyze_tdg(path):
-    # Mock TDG analysis
-    result = {
-        "summary": {
-            "tdg_score": 0.85,
-            "total_tests": 50,
-            "independent_tests": 45,
-            "dependent_tests": 5
+        // Progress lines may precede the JSON document
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let json = stdout.find(['{', '[']).map_or("", |start| &stdout[start..]);
+        let raw: Value = serde_json::from_str(json)
+            .map_err(|e| ToolError::Execution(format!("Failed to parse PMAT output: {}", e)))?;
+
+        match command {
+            "complexity" => pmat_schema::complexity(&raw, parse_thresholds(args)?),
+            "satd" => pmat_schema::satd(&raw),
+            "tdg" => pmat_schema::tdg(&raw),
+            "dead-code" => pmat_schema::dead_code(&raw),
+            _ => pmat_schema::big_o(&raw),
         }
+        .map_err(ToolError::Execution)
     }
-    return result
 
-if __name__ == "__main__":
-    path = sys.argv[1] if len(sys.argv) > 1 else "."
-    print(json.dumps(analyze_tdg(path)))
-"#
-            }
-            "dead-code" => {
-                r#"
-import json
-import sys
-
-def analyze_dead_code(path):
-    # Mock dead code analysis
-    result = {
-        "summary": {
-            "total_functions": 100,
-            "dead_functions": 5,
-            "coverage_estimate": 95.0
-        },
-        "dead_code": []
+    /// Run the built-in analyzer for `command`.
+    async fn execute_native(
+        &self,
+        command: &str,
+        target: PathBuf,
+        args: &[String],
+    ) -> Result<Value, ToolError> {
+        let thresholds = parse_thresholds(args)?;
+        let blame = args.iter().any(|arg| arg == "--blame");
+        let workspace = self.workspace.clone();
+        let command = command.to_string();
+
+        let report = tokio::task::spawn_blocking(move || {
+            let report = match command.as_str() {
+                "complexity" => {
+                    serde_json::to_value(complexity::analyze_path(&workspace, &target, thresholds))
+                }
+                "satd" => serde_json::to_value(satd::scan_path(&workspace, &target, blame)),
+                "tdg" => serde_json::to_value(tdg::analyze_path(&workspace, &target, thresholds)),
+                "dead-code" => serde_json::to_value(dead_code::analyze_path(&workspace, &target)),
+                _ => serde_json::to_value(big_o::analyze_path(&workspace, &target)),
+            };
+            report.map_err(|e| ToolError::Execution(format!("Failed to encode report: {}", e)))
+        })
+        .await
+        .map_err(|e| ToolError::Execution(format!("Analysis failed: {}", e)))??;
+
+        Ok(report)
     }
-    return result
 
-if __name__ == "__main__":
-    path = sys.argv[1] if len(sys.argv) > 1 else "."
-    print(json.dumps(analyze_dead_code(path)))
-"#
-            }
-            _ => {
-                return Err(ToolError::InvalidParams(format!(
-                    "Unknown PMAT command error: {}",
-                    command
-                )));
-            }
+    /// Add complexity and SATD results to the metrics history.
+    async fn record(&self, params: &PmatParams, result: &Value) {
+        let metrics = match params.command.as_str() {
+            "complexity" => Metrics {
+                complexity: ComplexityMetrics::from_report(result),
+                ..Metrics::default()
+            },
+            "satd" => Metrics {
+                satd_items: result["summary"]["total_items"]
+                    .as_u64()
+                    .map(|n| n as usize),
+                ..Metrics::default()
+            },
+            _ => return,
         };
-
-        // Write script to a temporary file
-        let temp_dir = std::env::temp_dir();
-        let script_path = temp_dir.join(format!("pmat_{}.py", command));
-        std::fs::write(&script_path, script)
-            .map_err(|e| ToolError::Execution(format!("Failed to write script: {}", e)))?;
-
-        let mut cmd = Command::new("python3");
-        cmd.arg(&script_path);
-        cmd.arg(path);
-
-        // Add any additional arguments
-        for arg in args {
-            cmd.arg(arg);
-        }
-
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
-        cmd.current_dir(&self.workspace);
-
-        let timeout_duration = Duration::from_secs(60);
-
-        match timeout(timeout_duration, cmd.output()).await {
-            Ok(Ok(output)) => {
-                // Clean up temp script
-                let _ = std::fs::remove_file(&script_path);
-
-                if output.status.success() {
-                    Ok(String::from_utf8_lossy(&output.stdout).to_string())
-                } else {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    Err(ToolError::Execution(format!("Python error: {}", stderr)))
-                }
-            }
-            Ok(Err(e)) => {
-                let _ = std::fs::remove_file(&script_path);
-                Err(ToolError::Execution(format!("Process error: {}", e)))
-            }
-            Err(_) => {
-                let _ = std::fs::remove_file(&script_path);
-                Err(ToolError::Execution("PMAT timeout (60s)".to_string()))
-            }
-        }
+        let scope = history::scope(Some(&params.path));
+        history::record(&self.workspace, &scope, "pmat", metrics).await;
     }
+}
 
-    async fn parse_json_output(&self, output: String) -> Result<Value, ToolError> {
-        // PMAT outputs JSON by default for most commands
-        serde_json::from_str(&output)
-            .map_err(|e| ToolError::Execution(format!("Failed to parse PMAT output: {}", e)))
+/// Arguments consumed by the native backend; `--native` forces it.
+const NATIVE_FLAGS: &[&str] = &["--native", "--blame"];
+
+/// Read `--max-cyclomatic N` and `--max-cognitive N` from the extra args.
+fn parse_thresholds(args: &[String]) -> Result<Thresholds, ToolError> {
+    let mut thresholds = Thresholds::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let slot = match arg.as_str() {
+            "--max-cyclomatic" => &mut thresholds.cyclomatic,
+            "--max-cognitive" => &mut thresholds.cognitive,
+            _ => continue,
+        };
+        *slot = iter
+            .next()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| ToolError::InvalidParams(format!("{} expects a number", arg)))?;
     }
+    Ok(thresholds)
 }
 
 impl Default for PmatTool {
@@ -211,7 +195,7 @@ impl Tool for PmatTool {
     }
 
     fn description(&self) -> &str {
-        "Run PMAT (Pragmatic Metrics for Agile Teams) analysis"
+        "Run PMAT (Pragmatic Metrics for Agile Teams) analysis, with native fallback"
     }
 
     async fn execute(&self, params: Value) -> Result<Value, ToolError> {
@@ -231,13 +215,42 @@ impl Tool for PmatTool {
             ));
         }
 
-        // Execute PMAT command
-        let output = self
-            .execute_pmat(&params.command, &params.path, &params.args)
-            .await?;
+        if !COMMANDS.contains(&params.command.as_str()) {
+            return Err(ToolError::InvalidParams(format!(
+                "Unknown PMAT command error: {}. Use: {}",
+                params.command,
+                COMMANDS.join(", ")
+            )));
+        }
 
-        // PMAT outputs JSON when we use --format json
-        self.parse_json_output(output).await
+        let force_native = params.args.iter().any(|arg| arg == "--native");
+        let mut fallback_reason = None;
+        if let (false, Some(binary)) = (force_native, self.pmat_binary().await) {
+            match self
+                .execute_pmat(binary, &params.command, &params.path, &params.args)
+                .await
+            {
+                Ok(mut result) => {
+                    result["backend"] = Value::from("pmat");
+                    self.record(&params, &result).await;
+                    return Ok(result);
+                }
+                Err(e) => {
+                    warn!("pmat failed, using native analyzer: {}", e);
+                    fallback_reason = Some(e.to_string());
+                }
+            }
+        }
+
+        let mut result = self
+            .execute_native(&params.command, target_path, &params.args)
+            .await?;
+        result["backend"] = Value::from("native");
+        if let Some(reason) = fallback_reason {
+            result["fallback_reason"] = Value::from(reason);
+        }
+        self.record(&params, &result).await;
+        Ok(result)
     }
 }
 
@@ -253,7 +266,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_pmat_invalid_command() {
-        let tool = PmatTool::new();
+        let dir = tempfile::TempDir::new().unwrap();
+        let tool = PmatTool::with_workspace(dir.path().to_path_buf());
 
         // Test with a command that doesn't exist
         let params = serde_json::json!({
@@ -264,4 +278,51 @@ mod tests {
         let result = tool.execute(params).await;
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_parse_thresholds() {
+        let args = vec![
+            "--max-cyclomatic".to_string(),
+            "12".to_string(),
+            "--max-cognitive".to_string(),
+            "8".to_string(),
+        ];
+        let thresholds = parse_thresholds(&args).unwrap();
+        assert_eq!((thresholds.cyclomatic, thresholds.cognitive), (12, 8));
+
+        let missing = vec!["--max-cyclomatic".to_string()];
+        assert!(matches!(
+            parse_thresholds(&missing),
+            Err(ToolError::InvalidParams(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unrecognized_pmat_json_falls_back_to_native() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let fake = dir.path().join("pmat");
+        std::fs::write(&fake, "#!/bin/sh\necho '{\"report\": {\"entries\": []}}'\n").unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(
+            dir.path().join("lib.rs"),
+            "fn branchy(x: u8) -> u8 { if x > 1 { if x > 2 { 3 } else { 2 } } else { 1 } }\n",
+        )
+        .unwrap();
+        let tool = PmatTool {
+            workspace: dir.path().to_path_buf(),
+            binary: OnceCell::new_with(Some(Some(fake))),
+        };
+
+        let params = serde_json::json!({ "command": "complexity", "path": "." });
+        let result = tool.execute(params).await.unwrap();
+        assert_eq!(result["backend"], "native");
+        assert!(result["fallback_reason"]
+            .as_str()
+            .unwrap()
+            .contains("Unrecognized pmat JSON"));
+        assert_eq!(result["summary"]["total_functions"], 1);
+    }
 }
@@ -0,0 +1,393 @@
+//! Normalizes `pmat ... --format json` output into the schema produced by
+//! the native analyzers in [`crate::analysis`].
+//!
+//! pmat's JSON has changed shape between releases (field names such as
+//! `file` / `file_path` / `path`, metrics nested under `metrics`), so every
+//! lookup accepts a list of candidate keys. Dotted keys descend into nested
+//! objects.
+
+use crate::analysis::complexity::{self, FileComplexity, FunctionComplexity, Thresholds};
+use crate::analysis::tdg;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+fn lookup<'a>(value: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    keys.iter().find_map(|key| {
+        key.split('.')
+            .try_fold(value, |current, part| current.get(part))
+            .filter(|found| !found.is_null())
+    })
+}
+
+fn text(value: &Value, keys: &[&str]) -> String {
+    match lookup(value, keys) {
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+        None => String::new(),
+    }
+}
+
+fn number(value: &Value, keys: &[&str]) -> Option<f64> {
+    lookup(value, keys).and_then(Value::as_f64)
+}
+
+fn array<'a>(value: &'a Value, keys: &[&str]) -> &'a [Value] {
+    lookup(value, keys)
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+}
+
+/// Fail unless `raw` has one of the `lists` as an array or a `summary`
+/// object; anything else is a schema this module does not know, and
+/// normalizing it would report a clean codebase.
+fn recognize(raw: &Value, lists: &[&str]) -> Result<(), String> {
+    let known = lists
+        .iter()
+        .any(|key| lookup(raw, &[key]).is_some_and(Value::is_array))
+        || lookup(raw, &["summary"]).is_some_and(Value::is_object);
+    if known {
+        Ok(())
+    } else {
+        Err(format!(
+            "Unrecognized pmat JSON: expected a summary or one of {}",
+            lists.join(", ")
+        ))
+    }
+}
+
+const FILE_KEYS: &[&str] = &["file", "file_path", "path", "filename"];
+const LINE_KEYS: &[&str] = &["line", "start_line", "line_start", "location.line"];
+
+/// Map pmat's severity vocabulary onto low/medium/high/critical.
+fn severity(value: &Value) -> String {
+    let raw = text(value, &["severity", "priority"]).to_lowercase();
+    match raw.as_str() {
+        "critical" | "blocker" => "critical",
+        "high" | "error" | "major" => "high",
+        "low" | "info" | "minor" => "low",
+        _ => "medium",
+    }
+    .to_string()
+}
+
+pub fn complexity(raw: &Value, thresholds: Thresholds) -> Result<Value, String> {
+    recognize(raw, &["files", "file_metrics", "results", "violations"])?;
+    let files: Vec<FileComplexity> = array(raw, &["files", "file_metrics", "results"])
+        .iter()
+        .map(|file| FileComplexity {
+            file: text(file, FILE_KEYS),
+            functions: array(file, &["functions", "function_metrics"])
+                .iter()
+                .map(|function| {
+                    let metric = |keys: &[&str]| number(function, keys).unwrap_or(0.0) as u32;
+                    let line = metric(LINE_KEYS) as usize;
+                    FunctionComplexity {
+                        name: text(function, &["name", "function", "function_name"]),
+                        line,
+                        end_line: number(function, &["end_line", "line_end"])
+                            .map_or(line, |l| l as usize),
+                        cyclomatic: metric(&["cyclomatic", "metrics.cyclomatic", "complexity"])
+                            .max(1),
+                        cognitive: metric(&["cognitive", "metrics.cognitive"]),
+                        nesting: metric(&["nesting", "nesting_depth", "metrics.nesting_max"]),
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+
+    if files.iter().any(|f| !f.functions.is_empty()) {
+        return serde_json::to_value(complexity::build_report(files, Vec::new(), thresholds))
+            .map_err(|e| e.to_string());
+    }
+
+    // Summary-only output: keep pmat's own violations
+    let violations: Vec<Value> = array(raw, &["violations"])
+        .iter()
+        .map(|violation| {
+            let rule = text(violation, &["metric", "rule"]).to_lowercase();
+            json!({
+                "file": text(violation, FILE_KEYS),
+                "function": text(violation, &["function", "function_name", "name"]),
+                "line": number(violation, LINE_KEYS).unwrap_or(0.0) as u64,
+                "metric": if rule.contains("cognitive") { "cognitive" } else { "cyclomatic" },
+                "value": number(violation, &["value", "complexity"]).unwrap_or(0.0) as u64,
+                "threshold": number(violation, &["threshold"]).unwrap_or(0.0) as u64,
+                "severity": severity(violation),
+                "message": text(violation, &["message"]),
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "summary": {
+            "total_files": number(raw, &["summary.total_files"]).unwrap_or(0.0) as u64,
+            "total_functions": number(raw, &["summary.total_functions"]).unwrap_or(0.0) as u64,
+            "max_complexity": number(raw, &["summary.max_complexity", "summary.max_cyclomatic"]).unwrap_or(0.0) as u64,
+            "average_complexity": number(raw, &["summary.average_complexity", "summary.avg_cyclomatic"]).unwrap_or(0.0),
+            "max_cognitive": number(raw, &["summary.max_cognitive"]).unwrap_or(0.0) as u64,
+            "average_cognitive": number(raw, &["summary.average_cognitive", "summary.avg_cognitive"]).unwrap_or(0.0),
+            "violations": violations.len(),
+            "thresholds": thresholds,
+        },
+        "files": [],
+        "violations": violations,
+        "errors": [],
+    }))
+}
+
+pub fn satd(raw: &Value) -> Result<Value, String> {
+    const ITEMS: &[&str] = &["items", "satd_items", "results", "debts"];
+    recognize(raw, ITEMS)?;
+    let mut files: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    let mut categories: BTreeMap<String, usize> = BTreeMap::new();
+    let mut by_severity: BTreeMap<String, usize> = BTreeMap::new();
+
+    for item in array(raw, ITEMS) {
+        let marker = text(item, &["marker", "tag", "debt_type", "category"]);
+        let severity = severity(item);
+        *categories.entry(marker.clone()).or_insert(0) += 1;
+        *by_severity.entry(severity.clone()).or_insert(0) += 1;
+
+        let mut normalized = json!({
+            "line": number(item, LINE_KEYS).unwrap_or(0.0) as u64,
+            "marker": marker,
+            "category": text(item, &["category", "debt_type"]).to_lowercase(),
+            "severity": severity,
+            "text": text(item, &["text", "content", "comment", "message"]),
+        });
+        if let Some(author) = lookup(item, &["author"]) {
+            normalized["author"] = author.clone();
+        }
+        files
+            .entry(text(item, FILE_KEYS))
+            .or_default()
+            .push(normalized);
+    }
+
+    let total: usize = files.values().map(Vec::len).sum();
+    Ok(json!({
+        "summary": {
+            "total_items": total,
+            "files_scanned": number(raw, &["summary.files_scanned", "summary.total_files"]).unwrap_or(0.0) as u64,
+            "files_with_debt": files.len(),
+            "categories": categories,
+            "by_severity": by_severity,
+        },
+        "files": files
+            .into_iter()
+            .map(|(file, items)| json!({ "file": file, "items": items }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+pub fn tdg(raw: &Value) -> Result<Value, String> {
+    const FILES: &[&str] = &["files", "results", "file_scores"];
+    recognize(raw, FILES)?;
+    let mut files: Vec<Value> = array(raw, FILES)
+        .iter()
+        .map(|file| {
+            let score = number(file, &["score", "tdg_score", "value", "total"]).unwrap_or(0.0);
+            json!({
+                "file": text(file, FILE_KEYS),
+                "score": score,
+                "grade": match lookup(file, &["grade"]) {
+                    Some(Value::String(grade)) => grade.clone(),
+                    _ => tdg::grade(score).to_string(),
+                },
+                "components": lookup(file, &["components", "breakdown"]).cloned().unwrap_or(json!({})),
+            })
+        })
+        .collect();
+    files.sort_by(|a, b| {
+        b["score"]
+            .as_f64()
+            .unwrap_or(0.0)
+            .total_cmp(&a["score"].as_f64().unwrap_or(0.0))
+    });
+
+    let scores: Vec<f64> = files.iter().filter_map(|f| f["score"].as_f64()).collect();
+    let average = number(
+        raw,
+        &["summary.average_score", "summary.tdg_score", "average"],
+    )
+    .unwrap_or_else(|| {
+        if scores.is_empty() {
+            0.0
+        } else {
+            scores.iter().sum::<f64>() / scores.len() as f64
+        }
+    });
+
+    Ok(json!({
+        "summary": {
+            "total_files": files.len(),
+            "average_score": (average * 100.0).round() / 100.0,
+            "max_score": scores.iter().copied().fold(0.0, f64::max),
+            "grade": tdg::grade(average),
+        },
+        "files": files,
+    }))
+}
+
+pub fn dead_code(raw: &Value) -> Result<Value, String> {
+    const ITEMS: &[&str] = &["dead_code", "items", "results", "dead_functions"];
+    recognize(raw, ITEMS)?;
+    let dead: Vec<Value> = array(raw, ITEMS)
+        .iter()
+        .map(|item| {
+            json!({
+                "file": text(item, FILE_KEYS),
+                "name": text(item, &["name", "function", "symbol"]),
+                "line": number(item, LINE_KEYS).unwrap_or(0.0) as u64,
+                "kind": text(item, &["kind", "type"]).to_lowercase(),
+            })
+        })
+        .collect();
+    let total = number(raw, &["summary.total_functions", "summary.total"]).unwrap_or(0.0);
+
+    Ok(json!({
+        "summary": {
+            "total_functions": total as u64,
+            "dead_functions": dead.len(),
+            "dead_percentage": if total > 0.0 {
+                (dead.len() as f64 / total * 1000.0).round() / 10.0
+            } else {
+                0.0
+            },
+        },
+        "dead_code": dead,
+    }))
+}
+
+pub fn big_o(raw: &Value) -> Result<Value, String> {
+    const FUNCTIONS: &[&str] = &["functions", "results", "analyses"];
+    recognize(raw, FUNCTIONS)?;
+    let mut by_class: BTreeMap<String, usize> = BTreeMap::new();
+    let functions: Vec<Value> = array(raw, FUNCTIONS)
+        .iter()
+        .map(|function| {
+            let class = text(
+                function,
+                &["class", "time_complexity.notation", "time_complexity", "complexity"],
+            );
+            *by_class.entry(class.clone()).or_insert(0) += 1;
+            json!({
+                "file": text(function, FILE_KEYS),
+                "function": text(function, &["function", "name", "function_name"]),
+                "line": number(function, LINE_KEYS).unwrap_or(0.0) as u64,
+                "class": class,
+                "evidence": lookup(function, &["evidence", "reasons"]).cloned().unwrap_or(json!([])),
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "summary": {
+            "total_functions": functions.len(),
+            "by_class": by_class,
+        },
+        "functions": functions,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complexity_with_function_metrics() {
+        let raw = json!({
+            "files": [{
+                "path": "src/lib.rs",
+                "functions": [
+                    {"name": "tangled", "line_start": 3, "metrics": {"cyclomatic": 25, "cognitive": 9}},
+                    {"name": "tidy", "line_start": 40, "metrics": {"cyclomatic": 2, "cognitive": 1}}
+                ]
+            }]
+        });
+
+        let normalized = complexity(&raw, Thresholds::default()).unwrap();
+        assert_eq!(normalized["summary"]["total_functions"], 2);
+        assert_eq!(normalized["files"][0]["file"], "src/lib.rs");
+        assert_eq!(normalized["violations"][0]["function"], "tangled");
+        assert_eq!(normalized["violations"][0]["line"], 3);
+        assert_eq!(normalized["violations"][0]["value"], 25);
+    }
+
+    #[test]
+    fn test_complexity_summary_only() {
+        let raw = json!({
+            "summary": {"total_files": 4, "total_functions": 30, "max_cyclomatic": 22},
+            "violations": [{"file_path": "a.rs", "rule": "cognitive-complexity", "value": 31, "threshold": 15, "line": 8, "severity": "error"}]
+        });
+
+        let normalized = complexity(&raw, Thresholds::default()).unwrap();
+        assert_eq!(normalized["summary"]["max_complexity"], 22);
+        let violation = &normalized["violations"][0];
+        assert_eq!(violation["metric"], "cognitive");
+        assert_eq!(violation["severity"], "high");
+        assert_eq!(violation["file"], "a.rs");
+    }
+
+    #[test]
+    fn test_satd_groups_by_file() {
+        let raw = json!({
+            "items": [
+                {"file": "a.rs", "line": 3, "debt_type": "Defect", "tag": "FIXME", "severity": "High", "text": "FIXME: x"},
+                {"file": "a.rs", "line": 9, "debt_type": "Requirement", "tag": "TODO", "severity": "Low", "text": "TODO: y"},
+                {"file": "b.py", "line": 1, "debt_type": "Design", "tag": "HACK", "severity": "Medium", "text": "HACK"}
+            ]
+        });
+
+        let normalized = satd(&raw).unwrap();
+        assert_eq!(normalized["summary"]["total_items"], 3);
+        assert_eq!(normalized["summary"]["files_with_debt"], 2);
+        assert_eq!(normalized["summary"]["categories"]["FIXME"], 1);
+        assert_eq!(
+            normalized["files"][0]["items"][1]["category"],
+            "requirement"
+        );
+    }
+
+    #[test]
+    fn test_tdg_dead_code_and_big_o() {
+        let tdg = tdg(
+            &json!({"results": [{"file_path": "x.rs", "tdg_score": 3.2}, {"file_path": "y.rs", "tdg_score": 0.4}]}),
+        )
+        .unwrap();
+        assert_eq!(tdg["files"][0]["file"], "x.rs");
+        assert_eq!(tdg["files"][0]["grade"], "D");
+        assert_eq!(tdg["summary"]["average_score"], 1.8);
+
+        let dead = dead_code(
+            &json!({"summary": {"total_functions": 10}, "dead_code": [{"file": "x.rs", "name": "old", "line": 4, "kind": "Function"}]}),
+        )
+        .unwrap();
+        assert_eq!(dead["summary"]["dead_percentage"], 10.0);
+        assert_eq!(dead["dead_code"][0]["kind"], "function");
+
+        let big_o = big_o(
+            &json!({"functions": [{"file": "x.rs", "name": "sort", "time_complexity": {"notation": "O(n^2)"}}]}),
+        )
+        .unwrap();
+        assert_eq!(big_o["functions"][0]["class"], "O(n^2)");
+        assert_eq!(big_o["summary"]["by_class"]["O(n^2)"], 1);
+    }
+
+    #[test]
+    fn test_unknown_schema_is_an_error() {
+        let raw = json!({"version": "9.0", "report": {"entries": [{"loc": "a.rs"}]}});
+        assert!(complexity(&raw, Thresholds::default()).is_err());
+        assert!(satd(&raw).is_err());
+        assert!(tdg(&raw).is_err());
+        assert!(dead_code(&raw).is_err());
+        assert!(big_o(&raw).is_err());
+        assert!(satd(&json!([])).is_err());
+
+        // Known keys with nothing in them are a clean result
+        let clean = satd(&json!({"items": []})).unwrap();
+        assert_eq!(clean["summary"]["total_items"], 0);
+    }
+}
@@ -47,6 +47,11 @@ impl Tool for ProcessTool {
             .stderr(Stdio::piped())
             .stdin(Stdio::null());
 
+        #[cfg(target_os = "linux")]
+        if let Some(jail) = crate::security::jail::NamespaceJail::for_active_policy() {
+            jail.apply(&mut cmd);
+        }
+
         let timeout_duration = Duration::from_millis(params.timeout_ms.unwrap_or(30000));
 
         let result = match timeout(timeout_duration, cmd.output()).await {
@@ -1,18 +1,21 @@
+use crate::tools::artifacts;
+use crate::tools::python_session::{PythonSession, WORKER_SCRIPT};
+use crate::tools::runner::{CodeRunner, RunRequest, PYTHON};
 use crate::tools::{Tool, ToolError};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::process::Stdio;
+use std::sync::Arc;
 use std::time::Duration;
-use tempfile::TempDir;
-use tokio::fs;
 use tokio::process::Command;
-use tokio::time::timeout;
+use tokio::sync::Mutex;
 use tracing::{debug, info};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct PythonParams {
+    #[serde(default)]
     code: String,
     #[serde(default)]
     timeout_ms: Option<u64>,
@@ -20,174 +23,165 @@ struct PythonParams {
     stdin: Option<String>,
     #[serde(default)]
     args: Option<Vec<String>>,
+    /// Name of a persistent session; state survives between calls
+    #[serde(default)]
+    session: Option<String>,
+    /// Session action: execute (default), reset, shutdown
+    #[serde(default)]
+    action: Option<String>,
+    /// Workspace directory to copy generated files into
+    #[serde(default)]
+    export_to: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct PythonTool {
-    #[allow(dead_code)]
     workspace: PathBuf,
+    runner: CodeRunner,
+    sessions: Mutex<HashMap<String, Arc<Mutex<PythonSession>>>>,
 }
 
 impl PythonTool {
     pub fn new() -> Self {
         Self {
             workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            runner: CodeRunner::new(&PYTHON),
+            sessions: Mutex::new(HashMap::new()),
         }
     }
 
-    async fn create_sandbox_dir(&self) -> Result<TempDir, ToolError> {
-        TempDir::new()
-            .map_err(|e| ToolError::Execution(format!("Failed to create temp dir: {}", e)))
-    }
-
-    async fn write_python_script(&self, dir: &TempDir, code: &str) -> Result<PathBuf, ToolError> {
-        let script_path = dir.path().join("script.py");
-        fs::write(&script_path, code)
-            .await
-            .map_err(|e| ToolError::Execution(format!("Failed to write script: {}", e)))?;
-        Ok(script_path)
-    }
-
-    fn build_sandbox_command(&self, script_path: &PathBuf) -> Command {
-        let mut cmd = Command::new("python3");
-
-        // Add security flags
-        cmd.arg("-B"); // Don't write bytecode
-        cmd.arg("-E"); // Ignore environment variables
-        cmd.arg("-I"); // Isolated mode (implies -E and -s)
-        cmd.arg("-S"); // Don't import site module
-        cmd.arg("-u"); // Unbuffered output
+    async fn execute_python(&self, params: &PythonParams) -> Result<Value, ToolError> {
+        let args = params.args.clone().unwrap_or_default();
+        let request = RunRequest {
+            code: &params.code,
+            args: &args,
+            stdin: params.stdin.as_deref(),
+            timeout: Duration::from_millis(params.timeout_ms.unwrap_or(30000)),
+            flags: Vec::new(),
+        };
 
-        // Add the script
-        cmd.arg(script_path);
+        debug!("Executing Python in sandbox");
+        let sandbox = self.runner.prepare(&params.code).await?;
+        let output = self.runner.run_in(&sandbox, &request).await?;
+        let artifacts =
+            artifacts::gather(&sandbox, params.export_to.as_deref(), &self.workspace).await?;
 
-        // Set working directory to sandbox
-        if let Some(parent) = script_path.parent() {
-            cmd.current_dir(parent);
-        }
-
-        // Configure process
-        cmd.stdin(Stdio::piped());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
-
-        // Environment restrictions
-        cmd.env_clear();
-        cmd.env("HOME", "/tmp");
-        cmd.env("TMPDIR", "/tmp");
-        cmd.env("PATH", "/usr/bin:/bin");
-        cmd.env("PYTHONDONTWRITEBYTECODE", "1");
-        cmd.env("PYTHONUNBUFFERED", "1");
-
-        // Additional restrictions without requiring privileges
-        cmd.env("PYTHONPATH", ""); // No additional module paths
-        cmd.env("PYTHONHOME", ""); // No custom Python home
-        cmd.env("PYTHONSTARTUP", ""); // No startup script
-
-        cmd
+        Ok(serde_json::json!({
+            "success": output.success,
+            "exit_code": output.exit_code,
+            "stdout": output.stdout,
+            "stderr": output.stderr,
+            "artifacts": artifacts,
+            "duration_ms": output.duration_ms,
+        }))
     }
 
-    async fn apply_platform_sandbox(&self, cmd: &mut Command) -> Result<(), ToolError> {
-        // Skip platform-specific sandboxing in tests or when it would require privileges
-        if cfg!(test) || std::env::var("PCODE_NO_SANDBOX").is_ok() {
-            return Ok(());
-        }
+    async fn start_session(&self) -> Result<PythonSession, ToolError> {
+        let sandbox = self
+            .runner
+            .prepare_named("worker.py", WORKER_SCRIPT)
+            .await?;
 
-        #[cfg(target_os = "linux")]
-        {
-            // Only use systemd-run if we're running as root (which we shouldn't be)
-            // For non-root users, rely on Python's built-in isolation flags
-            let is_root = unsafe { libc::geteuid() } == 0;
-            if self.is_systemd_available().await && is_root {
-                let mut systemd_cmd = Command::new("systemd-run");
-                systemd_cmd.arg("--scope");
-                systemd_cmd.arg("--quiet");
-                systemd_cmd.arg("--property=MemoryMax=256M");
-                systemd_cmd.arg("--property=CPUQuota=50%");
-                systemd_cmd.arg("--");
-
-                // Move the python command to systemd-run
-                let python_args: Vec<_> = cmd.as_std().get_args().map(|s| s.to_owned()).collect();
-                systemd_cmd.arg("python3");
-                for arg in python_args {
-                    systemd_cmd.arg(arg);
-                }
+        let mut cmd: Command = self.runner.command(PYTHON.run, &sandbox, &[]);
+        self.runner.apply_sandbox(&mut cmd, &sandbox).await;
 
-                *cmd = systemd_cmd;
-            }
-        }
+        debug!("Starting Python session worker: {:?}", cmd);
+        PythonSession::spawn(cmd, sandbox)
+    }
 
-        #[cfg(target_os = "macos")]
-        {
-            // macOS sandbox-exec also requires privileges in some cases
-            // Skip it for now to avoid permission issues
-            debug!("Skipping macOS sandbox-exec to avoid permission requirements");
+    /// The live session called `name`, started if there is none.
+    async fn session(&self, name: &str) -> Result<Arc<Mutex<PythonSession>>, ToolError> {
+        if let Some(session) = self.live_session(name).await {
+            return Ok(session);
         }
-
-        Ok(())
+        // Started without holding the map so other sessions keep running
+        let started = Arc::new(Mutex::new(self.start_session().await?));
+        let mut sessions = self.sessions.lock().await;
+        Ok(sessions.entry(name.to_string()).or_insert(started).clone())
     }
 
-    #[cfg(target_os = "linux")]
-    async fn is_systemd_available(&self) -> bool {
-        // Check if systemd-run exists and supports our options
-        match Command::new("systemd-run").arg("--version").output().await {
-            Ok(output) if output.status.success() => {
-                // Check version to ensure it supports our properties
-                let version_str = String::from_utf8_lossy(&output.stdout);
-                debug!("systemd-run version: {}", version_str);
-                true
-            }
-            _ => false,
+    /// The session called `name`, dropping it if its worker died (crash,
+    /// timeout, exit()).
+    async fn live_session(&self, name: &str) -> Option<Arc<Mutex<PythonSession>>> {
+        let mut sessions = self.sessions.lock().await;
+        let session = sessions.get(name)?.clone();
+        // A session busy running a cell is alive
+        let dead = session
+            .try_lock()
+            .is_ok_and(|mut session| !session.is_alive());
+        if dead {
+            sessions.remove(name);
+            return None;
         }
+        Some(session)
     }
 
-    async fn execute_python(&self, params: &PythonParams) -> Result<Value, ToolError> {
-        // Create sandbox directory
-        let sandbox_dir = self.create_sandbox_dir().await?;
-        let script_path = self.write_python_script(&sandbox_dir, &params.code).await?;
-
-        // Build command
-        let mut cmd = self.build_sandbox_command(&script_path);
-
-        // Add any user args
-        if let Some(args) = &params.args {
-            for arg in args {
-                cmd.arg(arg);
-            }
+    /// Forget `session` if it is still the one registered as `name`.
+    async fn remove_session(&self, name: &str, session: &Arc<Mutex<PythonSession>>) {
+        let mut sessions = self.sessions.lock().await;
+        if sessions.get(name).is_some_and(|s| Arc::ptr_eq(s, session)) {
+            sessions.remove(name);
         }
+    }
 
-        // Apply platform-specific sandboxing
-        self.apply_platform_sandbox(&mut cmd).await?;
-
-        debug!("Executing Python in sandbox: {:?}", cmd);
-
-        // Set timeout
+    async fn execute_session(&self, name: &str, params: &PythonParams) -> Result<Value, ToolError> {
         let timeout_duration = Duration::from_millis(params.timeout_ms.unwrap_or(30000));
+        let action = params.action.as_deref().unwrap_or("execute");
 
-        // Execute
-        let output = match timeout(timeout_duration, cmd.output()).await {
-            Ok(Ok(output)) => output,
-            Ok(Err(e)) => {
-                return Err(ToolError::Execution(format!("Process error: {}", e)));
+        let mut artifacts = None;
+        let reply = match action {
+            "execute" => {
+                if params.code.trim().is_empty() {
+                    return Err(ToolError::InvalidParams("Code cannot be empty".to_string()));
+                }
+                // Only this session's handle is held while the cell runs
+                let handle = self.session(name).await?;
+                let mut session = handle.lock().await;
+                let reply = session.execute(&params.code, timeout_duration).await;
+                if reply.is_err() || !session.is_alive() {
+                    self.remove_session(name, &handle).await;
+                }
+                let reply = reply?;
+                if session.is_alive() {
+                    artifacts = Some(
+                        artifacts::gather(
+                            session.sandbox(),
+                            params.export_to.as_deref(),
+                            &self.workspace,
+                        )
+                        .await?,
+                    );
+                }
+                reply
+            }
+            "reset" => match self.live_session(name).await {
+                Some(handle) => handle.lock().await.reset(timeout_duration).await?,
+                None => serde_json::json!({ "ok": true, "op": "reset" }),
+            },
+            "shutdown" => {
+                let removed = self.sessions.lock().await.remove(name);
+                match removed {
+                    Some(handle) => handle.lock().await.shutdown(timeout_duration).await?,
+                    None => serde_json::json!({ "ok": true, "op": "shutdown" }),
+                }
             }
-            Err(_) => {
-                return Err(ToolError::Execution(format!(
-                    "Execution timeout ({}ms)",
-                    timeout_duration.as_millis()
+            _ => {
+                return Err(ToolError::InvalidParams(format!(
+                    "Unknown session action: {}. Use: execute, reset, shutdown",
+                    action
                 )));
             }
         };
 
-        // Parse output
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-
         Ok(serde_json::json!({
-            "success": output.status.success(),
-            "exit_code": output.status.code().unwrap_or(-1),
-            "stdout": stdout,
-            "stderr": stderr,
-            "duration_ms": timeout_duration.as_millis() as u64,
+            "success": reply["ok"].as_bool().unwrap_or(false),
+            "session": name,
+            "action": action,
+            "stdout": reply.get("stdout").cloned().unwrap_or(Value::Null),
+            "stderr": reply.get("stderr").cloned().unwrap_or(Value::Null),
+            "result": reply.get("result").cloned().unwrap_or(Value::Null),
+            "error": reply.get("error").cloned().unwrap_or(Value::Null),
+            "artifacts": artifacts,
         }))
     }
 }
@@ -215,21 +209,22 @@ impl Tool for PythonTool {
         info!("Executing Python code in sandbox");
 
         // Basic validation
-        if params.code.trim().is_empty() {
+        if params.session.is_none() && params.code.trim().is_empty() {
             return Err(ToolError::InvalidParams("Code cannot be empty".to_string()));
         }
 
         // Check Python availability
-        let check = Command::new("python3").arg("--version").output().await;
-
-        if check.is_err() || !check.unwrap().status.success() {
+        if !self.runner.is_available().await {
             return Err(ToolError::Execution(
                 "Python 3 not found. Please install Python 3.".to_string(),
             ));
         }
 
         // Execute in sandbox
-        self.execute_python(&params).await
+        match &params.session {
+            Some(name) => self.execute_session(name, &params).await,
+            None => self.execute_python(&params).await,
+        }
     }
 }
 
@@ -296,4 +291,145 @@ mod tests {
         let result = tool.execute(params).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_python_session_keeps_state() {
+        let tool = PythonTool::new();
+        let define = serde_json::json!({
+            "code": "import math\nvalue = 21",
+            "session": "state"
+        });
+
+        // This test might fail if Python is not installed
+        if tool.execute(define).await.is_ok() {
+            let params = serde_json::json!({
+                "code": "print('doubling')\nmath.floor(value * 2.5)",
+                "session": "state"
+            });
+            let result = tool.execute(params).await.unwrap();
+            assert!(result["success"].as_bool().unwrap());
+            assert_eq!(result["stdout"], "doubling\n");
+            assert_eq!(result["result"], "52");
+
+            let reset = serde_json::json!({ "session": "state", "action": "reset" });
+            assert!(tool.execute(reset).await.unwrap()["success"]
+                .as_bool()
+                .unwrap());
+
+            let params = serde_json::json!({ "code": "value", "session": "state" });
+            let result = tool.execute(params).await.unwrap();
+            assert!(!result["success"].as_bool().unwrap());
+            assert_eq!(result["error"]["type"], "NameError");
+
+            let shutdown = serde_json::json!({ "session": "state", "action": "shutdown" });
+            assert!(tool.execute(shutdown).await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_python_session_raw_stdout_is_captured() {
+        let tool = PythonTool::new();
+        let params = serde_json::json!({
+            "code": "import os, sys\nsys.__stdout__.write('raw\\n')\nos.write(1, b'fd\\n')\nprint('done')",
+            "session": "raw"
+        });
+
+        if let Ok(result) = tool.execute(params).await {
+            assert!(result["success"].as_bool().unwrap());
+            assert_eq!(result["stdout"], "done\nraw\nfd\n");
+
+            // The reply stream is intact, so the session keeps working
+            let params = serde_json::json!({ "code": "6 * 7", "session": "raw" });
+            let result = tool.execute(params).await.unwrap();
+            assert_eq!(result["result"], "42");
+            assert_eq!(result["stdout"], "");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_python_sessions_run_concurrently() {
+        let tool = PythonTool::new();
+        let slow = serde_json::json!({
+            "code": "import time\ntime.sleep(2)",
+            "session": "slow"
+        });
+        let quick = serde_json::json!({ "code": "1 + 1", "session": "quick" });
+
+        // Warm both workers up first so only the cells are timed
+        let warm = serde_json::json!({ "code": "0", "session": "slow" });
+        if tool.execute(warm).await.is_err() {
+            return;
+        }
+        tool.execute(serde_json::json!({ "code": "0", "session": "quick" }))
+            .await
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        let (slow, quick) = tokio::join!(tool.execute(slow), async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let result = tool.execute(quick).await;
+            (result, started.elapsed())
+        });
+        assert!(slow.unwrap()["success"].as_bool().unwrap());
+        let (quick, elapsed) = quick;
+        assert_eq!(quick.unwrap()["result"], "2");
+        assert!(elapsed < Duration::from_millis(1500), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn test_python_session_exception_traceback() {
+        let tool = PythonTool::new();
+        let params = serde_json::json!({
+            "code": "def explode():\n    raise ValueError('boom')\n\nexplode()",
+            "session": "errors"
+        });
+
+        if let Ok(result) = tool.execute(params).await {
+            let error = &result["error"];
+            assert_eq!(error["type"], "ValueError");
+            assert_eq!(error["message"], "boom");
+            let frames = error["traceback"].as_array().unwrap();
+            assert_eq!(frames.last().unwrap()["function"], "explode");
+            assert_eq!(frames.last().unwrap()["line"], 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_python_session_unknown_action() {
+        let tool = PythonTool::new();
+        let params = serde_json::json!({ "session": "any", "action": "restart" });
+
+        let result = tool.execute(params).await;
+        assert!(matches!(result, Err(ToolError::InvalidParams(_))));
+    }
+
+    #[tokio::test]
+    async fn test_python_returns_artifacts() {
+        let tool = PythonTool::new();
+        let params = serde_json::json!({
+            "code": "open('out.csv', 'w').write('a,b\\n1,2\\n')\nopen('blob.bin', 'wb').write(bytes([0, 255]))"
+        });
+
+        if let Ok(result) = tool.execute(params).await {
+            let files = result["artifacts"]["files"].as_array().unwrap();
+            assert_eq!(files.len(), 2);
+            assert_eq!(files[0]["path"], "blob.bin");
+            assert_eq!(files[0]["encoding"], "base64");
+            assert_eq!(files[0]["content"], "AP8=");
+            assert_eq!(files[1]["path"], "out.csv");
+            assert_eq!(files[1]["content"], "a,b\n1,2\n");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_python_export_outside_workspace_denied() {
+        let tool = PythonTool::new();
+        let params = serde_json::json!({
+            "code": "open('out.txt', 'w').write('x')",
+            "export_to": "/etc/pcode-artifacts"
+        });
+
+        let result = tool.execute(params).await;
+        assert!(matches!(result, Err(ToolError::PermissionDenied(_))));
+    }
 }
@@ -0,0 +1,220 @@
+use crate::tools::runner::Sandbox;
+use crate::tools::ToolError;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::time::timeout;
+use tracing::debug;
+
+/// Worker loop run inside the long-lived interpreter.
+///
+/// Requests and replies are single-line JSON objects. Replies go over a
+/// private copy of the original stdout; fd 1, and with it `sys.__stdout__`,
+/// is pointed at a scratch file whose contents are returned with the
+/// cell's output, so nothing user code writes can reach the reply stream.
+pub const WORKER_SCRIPT: &str = r#"
+import ast
+import io
+import json
+import os
+import sys
+import tempfile
+import traceback
+
+_reply_stream = os.fdopen(os.dup(1), "w")
+_raw_stdout = tempfile.TemporaryFile(buffering=0)
+os.dup2(_raw_stdout.fileno(), 1)
+_namespace = {"__name__": "__main__"}
+
+
+def _drain_raw_stdout():
+    sys.__stdout__.flush()
+    _raw_stdout.seek(0)
+    data = _raw_stdout.read()
+    _raw_stdout.seek(0)
+    _raw_stdout.truncate()
+    return data.decode("utf-8", "replace")
+
+
+def _frames(tb):
+    frames = []
+    for frame in traceback.extract_tb(tb):
+        if frame.filename == __file__:
+            continue
+        frames.append({
+            "file": frame.filename,
+            "line": frame.lineno,
+            "function": frame.name,
+            "source": frame.line,
+        })
+    return frames
+
+
+def _execute(code):
+    stdout, stderr = io.StringIO(), io.StringIO()
+    result = {"result": None, "error": None}
+    sys.stdout, sys.stderr = stdout, stderr
+    try:
+        tree = ast.parse(code, "<cell>", "exec")
+        last = None
+        if tree.body and isinstance(tree.body[-1], ast.Expr):
+            last = ast.Expression(tree.body.pop().value)
+        exec(compile(tree, "<cell>", "exec"), _namespace)
+        if last is not None:
+            value = eval(compile(last, "<cell>", "eval"), _namespace)
+            if value is not None:
+                result["result"] = repr(value)
+    except BaseException as exc:
+        if isinstance(exc, SystemExit):
+            raise
+        result["error"] = {
+            "type": type(exc).__name__,
+            "message": str(exc),
+            "traceback": _frames(exc.__traceback__),
+        }
+    finally:
+        sys.stdout, sys.stderr = sys.__stdout__, sys.__stderr__
+    result["stdout"] = stdout.getvalue() + _drain_raw_stdout()
+    result["stderr"] = stderr.getvalue()
+    return result
+
+
+def _reply(payload):
+    _reply_stream.write(json.dumps(payload) + "\n")
+    _reply_stream.flush()
+
+
+for line in sys.stdin:
+    try:
+        request = json.loads(line)
+    except ValueError as exc:
+        _reply({"ok": False, "error": {"type": "ProtocolError", "message": str(exc), "traceback": []}})
+        continue
+    op = request.get("op")
+    if op == "execute":
+        try:
+            payload = _execute(request.get("code", ""))
+        except SystemExit:
+            _reply({"ok": True, "op": "shutdown"})
+            break
+        payload["ok"] = payload["error"] is None
+        _reply(payload)
+    elif op == "reset":
+        _namespace.clear()
+        _namespace["__name__"] = "__main__"
+        _reply({"ok": True, "op": "reset"})
+    elif op == "shutdown":
+        _reply({"ok": True, "op": "shutdown"})
+        break
+    else:
+        _reply({"ok": False, "error": {"type": "ProtocolError", "message": "unknown op: %s" % op, "traceback": []}})
+"#;
+
+/// A long-lived Python interpreter that keeps its globals between requests.
+#[derive(Debug)]
+pub struct PythonSession {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    // Keeps the worker script and the session's scratch directory alive
+    sandbox: Sandbox,
+}
+
+impl PythonSession {
+    /// Spawn a worker from a fully configured command.
+    ///
+    /// The command must run the worker script found in `sandbox` with piped
+    /// stdin and stdout.
+    pub fn spawn(mut cmd: Command, sandbox: Sandbox) -> Result<Self, ToolError> {
+        cmd.kill_on_drop(true);
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| ToolError::Execution(format!("Failed to start Python session: {}", e)))?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| ToolError::Execution("Python session has no stdin".to_string()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| ToolError::Execution("Python session has no stdout".to_string()))?;
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+            sandbox,
+        })
+    }
+
+    pub async fn execute(&mut self, code: &str, limit: Duration) -> Result<Value, ToolError> {
+        self.request(serde_json::json!({ "op": "execute", "code": code }), limit)
+            .await
+    }
+
+    pub async fn reset(&mut self, limit: Duration) -> Result<Value, ToolError> {
+        self.request(serde_json::json!({ "op": "reset" }), limit)
+            .await
+    }
+
+    pub async fn shutdown(&mut self, limit: Duration) -> Result<Value, ToolError> {
+        let reply = self
+            .request(serde_json::json!({ "op": "shutdown" }), limit)
+            .await;
+        let _ = timeout(limit, self.child.wait()).await;
+        let _ = self.child.kill().await;
+        reply
+    }
+
+    async fn request(&mut self, request: Value, limit: Duration) -> Result<Value, ToolError> {
+        let mut line = request.to_string();
+        line.push('\n');
+        debug!("Python session request: {}", request["op"]);
+
+        let exchange = async {
+            self.stdin.write_all(line.as_bytes()).await?;
+            self.stdin.flush().await?;
+            let mut reply = String::new();
+            self.stdout.read_line(&mut reply).await?;
+            Ok::<_, std::io::Error>(reply)
+        };
+
+        let reply = match timeout(limit, exchange).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(e)) => {
+                return Err(ToolError::Execution(format!(
+                    "Python session I/O error: {}",
+                    e
+                )))
+            }
+            Err(_) => {
+                let _ = self.child.kill().await;
+                return Err(ToolError::Execution(format!(
+                    "Execution timeout ({}ms), session terminated",
+                    limit.as_millis()
+                )));
+            }
+        };
+
+        if reply.is_empty() {
+            return Err(ToolError::Execution(
+                "Python session exited unexpectedly".to_string(),
+            ));
+        }
+
+        serde_json::from_str(&reply)
+            .map_err(|e| ToolError::Execution(format!("Invalid session reply: {}", e)))
+    }
+
+    /// The session's working directory.
+    pub fn sandbox(&self) -> &Sandbox {
+        &self.sandbox
+    }
+
+    /// Whether the worker process is still running.
+    pub fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
@@ -1,10 +1,21 @@
+use crate::analysis::complexity::{self, FunctionComplexity};
+use crate::analysis::diagnostics::parse_cargo_messages;
+use crate::tools::edit;
 use crate::tools::{Tool, ToolError};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
 use tracing::{debug, info, warn};
 
+/// Attempts per function before the original is restored.
+const DEFAULT_MAX_ATTEMPTS: usize = 3;
+
 #[derive(Debug, Serialize, Deserialize)]
 struct RefactorParams {
     path: String,
@@ -14,11 +25,82 @@ struct RefactorParams {
     max_complexity: Option<u32>,
     #[serde(default)]
     focus: Option<String>, // "complexity", "coverage", "debt", "all"
+    /// Proposals tried per function when auto-applying
+    #[serde(default)]
+    max_attempts: Option<usize>,
+}
+
+/// Source of replacement code; the LLM outside of tests.
+#[async_trait]
+pub(crate) trait Proposer: Send + Sync {
+    async fn propose(&self, prompt: &str) -> Result<String, ToolError>;
+}
+
+pub(crate) struct LlmProposer;
+
+#[async_trait]
+impl Proposer for LlmProposer {
+    async fn propose(&self, prompt: &str) -> Result<String, ToolError> {
+        let response = crate::tools::llm::LlmTool::new()
+            .execute(json!({
+                "prompt": prompt,
+                "max_tokens": 2000,
+                "temperature": 0.2
+            }))
+            .await?;
+        Ok(response["response"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+struct Measured {
+    cyclomatic: u32,
+    cognitive: u32,
+}
+
+impl From<&FunctionComplexity> for Measured {
+    fn from(function: &FunctionComplexity) -> Self {
+        Self {
+            cyclomatic: function.cyclomatic,
+            cognitive: function.cognitive,
+        }
+    }
+}
+
+/// Outcome of auto-applying one refactoring.
+#[derive(Debug, Serialize)]
+struct AppliedRefactoring {
+    file: String,
+    function: String,
+    /// "applied", "reverted", "failed" (no proposal was ever written) or
+    /// "skipped"
+    status: &'static str,
+    attempts: usize,
+    before: Option<Measured>,
+    after: Option<Measured>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl AppliedRefactoring {
+    fn skipped(suggestion: &RefactoringSuggestion, reason: impl Into<String>) -> Self {
+        Self {
+            file: suggestion.file.clone(),
+            function: suggestion.function.clone(),
+            status: "skipped",
+            attempts: 0,
+            before: None,
+            after: None,
+            error: Some(reason.into()),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct RefactorTool {
-    #[allow(dead_code)]
     workspace: PathBuf,
 }
 
@@ -29,13 +111,21 @@ impl RefactorTool {
         }
     }
 
-    async fn analyze_with_pmat(&self, path: &str) -> Result<Value, ToolError> {
+    async fn analyze_with_pmat(
+        &self,
+        path: &str,
+        max_complexity: Option<u32>,
+    ) -> Result<Value, ToolError> {
         // Use the PMAT tool to analyze code
-        let pmat_tool = crate::tools::pmat::PmatTool::new();
+        let pmat_tool = crate::tools::pmat::PmatTool::with_workspace(self.workspace.clone());
+        let args: Vec<String> = match max_complexity {
+            Some(max) => vec!["--max-cyclomatic".to_string(), max.to_string()],
+            None => Vec::new(),
+        };
         let params = json!({
             "command": "complexity",
             "path": path,
-            "args": []
+            "args": args
         });
 
         pmat_tool.execute(params).await
@@ -171,6 +261,315 @@ impl RefactorTool {
             }
         }
     }
+
+    /// Run `cargo check` and the tests matching `filter`; on failure return
+    /// the output to show the model.
+    async fn verify(&self, filter: Option<&str>) -> Result<(), String> {
+        let mut check = Command::new("cargo");
+        check
+            .args(["check", "--all-targets", "--message-format=json"])
+            .current_dir(&self.workspace)
+            .kill_on_drop(true);
+        let check = timeout(Duration::from_secs(600), check.output())
+            .await
+            .map_err(|_| "cargo check timeout (600s)".to_string())?
+            .map_err(|e| format!("Failed to run cargo check: {}", e))?;
+        if !check.status.success() {
+            let stdout = String::from_utf8_lossy(&check.stdout);
+            let errors: Vec<String> = parse_cargo_messages(&stdout)
+                .into_iter()
+                .filter(|d| d.level == "error")
+                .take(5)
+                .map(|d| d.rendered.unwrap_or(d.message))
+                .collect();
+            return Err(if errors.is_empty() {
+                tail(&String::from_utf8_lossy(&check.stderr), 40)
+            } else {
+                errors.join("\n")
+            });
+        }
+
+        let mut test = Command::new("cargo");
+        test.arg("test")
+            .current_dir(&self.workspace)
+            .kill_on_drop(true);
+        if let Some(filter) = filter {
+            test.arg(filter);
+        }
+        let output = timeout(Duration::from_secs(600), test.output())
+            .await
+            .map_err(|_| "cargo test timeout (600s)".to_string())?
+            .map_err(|e| format!("Failed to run cargo test: {}", e))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let combined = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Err(tail(&combined, 60))
+    }
+
+    async fn auto_apply(
+        &self,
+        suggestions: &[RefactoringSuggestion],
+        proposer: &dyn Proposer,
+        max_attempts: usize,
+    ) -> Vec<AppliedRefactoring> {
+        // Whether each test filter passed before any change
+        let mut baselines: HashMap<Option<String>, Result<(), String>> = HashMap::new();
+        let mut applied = Vec::new();
+
+        for suggestion in suggestions {
+            if !suggestion.file.ends_with(".rs") {
+                applied.push(AppliedRefactoring::skipped(
+                    suggestion,
+                    "Only Rust functions can be auto-applied",
+                ));
+                continue;
+            }
+            let filter = test_filter(&suggestion.file);
+            if !baselines.contains_key(&filter) {
+                let baseline = self.verify(filter.as_deref()).await;
+                baselines.insert(filter.clone(), baseline);
+            }
+            if let Err(error) = &baselines[&filter] {
+                applied.push(AppliedRefactoring::skipped(
+                    suggestion,
+                    format!("Build or tests fail before refactoring:\n{}", error),
+                ));
+                continue;
+            }
+            applied.push(
+                self.refactor_function(suggestion, proposer, max_attempts, filter.as_deref())
+                    .await,
+            );
+        }
+        applied
+    }
+
+    /// Replace one function with proposals until the build and its tests
+    /// pass, restoring the original file if a proposal was written but none
+    /// passed.
+    async fn refactor_function(
+        &self,
+        suggestion: &RefactoringSuggestion,
+        proposer: &dyn Proposer,
+        max_attempts: usize,
+        filter: Option<&str>,
+    ) -> AppliedRefactoring {
+        let path = self.workspace.join(&suggestion.file);
+        let Ok(original) = std::fs::read_to_string(&path) else {
+            return AppliedRefactoring::skipped(suggestion, "Cannot read file");
+        };
+        let Some(function) = locate(&original, &suggestion.function, suggestion.line as usize)
+        else {
+            return AppliedRefactoring::skipped(suggestion, "Function not found");
+        };
+        // Replace doc comments and attributes too, so a reply repeating
+        // them does not duplicate them
+        let start = item_start(&original, function.line).unwrap_or(function.line);
+        let body = lines(&original, start, function.end_line);
+
+        let mut result = AppliedRefactoring {
+            before: Some(Measured::from(&function)),
+            ..AppliedRefactoring::skipped(suggestion, "")
+        };
+        let mut prompt = refactor_prompt(&suggestion.file, &function, &body);
+        let mut written = false;
+
+        for attempt in 1..=max_attempts.max(1) {
+            result.attempts = attempt;
+            let proposal = match proposer.propose(&prompt).await {
+                Ok(response) => extract_code(&response),
+                Err(e) => {
+                    result.error = Some(e.to_string());
+                    break;
+                }
+            };
+            let Some(code) = proposal else {
+                prompt = retry_prompt(&prompt, "", "No ```rust code block in the reply.");
+                continue;
+            };
+
+            let updated = replace_lines(&original, start, function.end_line, &code);
+            let syntax_error = syn::parse_file(&updated).err().map(|e| e.to_string());
+            let outcome = match syntax_error {
+                Some(e) => Err(format!("Syntax error: {}", e)),
+                None => match edit::write_atomic(&path, &updated) {
+                    Err(e) => {
+                        result.error = Some(format!("Failed to write file: {}", e));
+                        break;
+                    }
+                    Ok(()) => {
+                        written = true;
+                        self.verify(filter).await
+                    }
+                },
+            };
+
+            match outcome {
+                Ok(()) => {
+                    result.status = "applied";
+                    result.error = None;
+                    result.after = locate(&updated, &suggestion.function, function.line)
+                        .map(|f| Measured::from(&f));
+                    return result;
+                }
+                Err(feedback) => {
+                    debug!(
+                        "Attempt {} for {} failed verification",
+                        attempt, suggestion.function
+                    );
+                    prompt = retry_prompt(&prompt, &code, &feedback);
+                    result.error = Some(feedback);
+                }
+            }
+        }
+
+        if !written {
+            result.status = "failed";
+            return result;
+        }
+        if let Err(e) = edit::write_atomic(&path, &original) {
+            warn!("Failed to restore {}: {}", suggestion.file, e);
+        }
+        result.status = "reverted";
+        result
+    }
+}
+
+/// Test name filter for the module defined in `file`, e.g.
+/// `src/tools/refactor.rs` gives `tools::refactor`. Crate roots and files
+/// outside `src/` run every test.
+pub(crate) fn test_filter(file: &str) -> Option<String> {
+    let module = Path::new(file).strip_prefix("src").ok()?.with_extension("");
+    let parts: Vec<String> = module
+        .iter()
+        .map(|part| part.to_string_lossy().to_string())
+        .filter(|part| part != "mod")
+        .collect();
+    match parts.as_slice() {
+        [] => None,
+        [root] if root == "lib" || root == "main" => None,
+        _ => Some(parts.join("::")),
+    }
+}
+
+/// The function called `name` closest to `near_line`.
+fn locate(source: &str, name: &str, near_line: usize) -> Option<FunctionComplexity> {
+    complexity::analyze_source(source)
+        .ok()?
+        .into_iter()
+        .filter(|f| f.name == name || f.name.ends_with(&format!("::{}", name)))
+        .min_by_key(|f| f.line.abs_diff(near_line))
+}
+
+/// First line of the function whose name is on `ident_line`, counting its
+/// doc comments and other outer attributes.
+fn item_start(source: &str, ident_line: usize) -> Option<usize> {
+    struct Finder {
+        ident_line: usize,
+        start: Option<usize>,
+    }
+
+    impl Finder {
+        fn check(&mut self, ident: &syn::Ident, item: &dyn Spanned) {
+            if ident.span().start().line == self.ident_line {
+                self.start = Some(item.span().start().line);
+            }
+        }
+    }
+
+    impl<'ast> Visit<'ast> for Finder {
+        fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+            self.check(&item.sig.ident, item);
+            visit::visit_item_fn(self, item);
+        }
+
+        fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+            self.check(&item.sig.ident, item);
+            visit::visit_impl_item_fn(self, item);
+        }
+
+        fn visit_trait_item_fn(&mut self, item: &'ast syn::TraitItemFn) {
+            self.check(&item.sig.ident, item);
+            visit::visit_trait_item_fn(self, item);
+        }
+    }
+
+    let file = syn::parse_file(source).ok()?;
+    let mut finder = Finder {
+        ident_line,
+        start: None,
+    };
+    finder.visit_file(&file);
+    finder.start
+}
+
+/// Lines `start..=end` (1-based) of `source`.
+fn lines(source: &str, start: usize, end: usize) -> String {
+    source
+        .lines()
+        .skip(start.saturating_sub(1))
+        .take(end + 1 - start)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Replace lines `start..=end` (1-based) of `source` with `replacement`.
+fn replace_lines(source: &str, start: usize, end: usize, replacement: &str) -> String {
+    let all: Vec<&str> = source.lines().collect();
+    let mut out: Vec<&str> = all[..start - 1].to_vec();
+    out.extend(replacement.trim_end_matches('\n').lines());
+    out.extend(&all[end.min(all.len())..]);
+    let mut text = out.join("\n");
+    if source.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+/// The first fenced code block of a reply.
+pub(crate) fn extract_code(response: &str) -> Option<String> {
+    let start = response.find("```")?;
+    let after_fence = &response[start + 3..];
+    let body = &after_fence[after_fence.find('\n')? + 1..];
+    let end = body.find("```")?;
+    let code = body[..end].trim_end();
+    (!code.trim().is_empty()).then(|| code.to_string())
+}
+
+pub(crate) fn tail(text: &str, count: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    lines[lines.len().saturating_sub(count)..].join("\n")
+}
+
+fn refactor_prompt(file: &str, function: &FunctionComplexity, body: &str) -> String {
+    format!(
+        "Refactor this Rust function from {} to reduce its complexity \
+         (cyclomatic {}, cognitive {}).\n\n```rust\n{}\n```\n\n\
+         Keep the signature and behavior unchanged; the project must still \
+         compile and pass its tests. You may add private helper functions \
+         next to it. Reply with only the complete replacement code in a \
+         single ```rust code block.",
+        file, function.cyclomatic, function.cognitive, body
+    )
+}
+
+fn retry_prompt(previous: &str, attempt: &str, feedback: &str) -> String {
+    // Keep only the original request so prompts do not grow per retry
+    let request = previous
+        .split("\n\nYour previous attempt")
+        .next()
+        .unwrap_or(previous);
+    format!(
+        "{}\n\nYour previous attempt\n```rust\n{}\n```\nfailed verification:\n{}\n\n\
+         Fix the problem and reply with the complete replacement code in a \
+         single ```rust code block.",
+        request, attempt, feedback
+    )
 }
 
 #[derive(Debug, Serialize)]
@@ -207,7 +606,9 @@ impl Tool for RefactorTool {
         info!("Running AI-powered refactoring analysis on {}", params.path);
 
         // Step 1: Analyze with PMAT
-        let analysis = self.analyze_with_pmat(&params.path).await?;
+        let analysis = self
+            .analyze_with_pmat(&params.path, params.max_complexity)
+            .await?;
         debug!("PMAT analysis complete");
 
         // Step 2: Generate refactoring suggestions
@@ -238,23 +639,38 @@ impl Tool for RefactorTool {
             }));
         }
 
-        // Step 4: Optionally auto-apply (not implemented yet)
+        // Step 4: Optionally apply, keeping only changes that build and pass tests
+        let mut applied = Vec::new();
         if params.auto_apply {
-            warn!("Auto-apply not yet implemented. Showing suggestions only.");
+            let max_attempts = params.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS);
+            if crate::config::Config::from_env().has_api_key() {
+                applied = self
+                    .auto_apply(&suggestions, &LlmProposer, max_attempts)
+                    .await;
+            } else {
+                applied = suggestions
+                    .iter()
+                    .map(|s| {
+                        AppliedRefactoring::skipped(s, "Auto-apply requires AI_STUDIO_API_KEY")
+                    })
+                    .collect();
+            }
         }
+        let applied_count = applied.iter().filter(|a| a.status == "applied").count();
 
         Ok(json!({
             "status": "success",
             "suggestions_count": enhanced_suggestions.len(),
             "suggestions": enhanced_suggestions,
+            "applied": applied,
             "original_analysis": analysis,
             "message": format!(
                 "Found {} refactoring opportunities. {}",
                 enhanced_suggestions.len(),
                 if params.auto_apply {
-                    "Auto-apply not yet implemented."
+                    format!("Applied {} verified refactorings.", applied_count)
                 } else {
-                    "Review suggestions above."
+                    "Review suggestions above.".to_string()
                 }
             )
         }))
@@ -304,4 +720,192 @@ mod tests {
         assert_eq!(suggestion.line, 42);
         assert_eq!(suggestion.severity, "high");
     }
+
+    /// Replies with canned responses and records the prompts it was sent.
+    struct Scripted {
+        replies: std::sync::Mutex<Vec<&'static str>>,
+        prompts: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl Scripted {
+        fn new(replies: &[&'static str]) -> Self {
+            Self {
+                replies: std::sync::Mutex::new(replies.iter().rev().copied().collect()),
+                prompts: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Proposer for Scripted {
+        async fn propose(&self, prompt: &str) -> Result<String, ToolError> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            let reply = self.replies.lock().unwrap().pop().unwrap_or("no code");
+            Ok(reply.to_string())
+        }
+    }
+
+    const CLASSIFY: &str = "pub fn classify(n: i32) -> &'static str {
+    if n < 0 {
+        if n < -100 {
+            \"very negative\"
+        } else {
+            \"negative\"
+        }
+    } else if n == 0 {
+        \"zero\"
+    } else {
+        \"positive\"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn classify_values() {
+        assert_eq!(super::classify(-500), \"very negative\");
+        assert_eq!(super::classify(-5), \"negative\");
+        assert_eq!(super::classify(0), \"zero\");
+        assert_eq!(super::classify(7), \"positive\");
+    }
+}
+";
+
+    #[test]
+    fn test_helpers() {
+        assert_eq!(
+            test_filter("src/tools/refactor.rs").as_deref(),
+            Some("tools::refactor")
+        );
+        assert_eq!(
+            test_filter("src/analysis/mod.rs").as_deref(),
+            Some("analysis")
+        );
+        assert_eq!(test_filter("src/lib.rs"), None);
+        assert_eq!(test_filter("tests/config_test.rs"), None);
+
+        let reply = "Here you go:\n```rust\nfn f() {}\n```\nDone.";
+        assert_eq!(extract_code(reply).as_deref(), Some("fn f() {}"));
+        assert_eq!(extract_code("no code"), None);
+
+        assert_eq!(
+            replace_lines("a\nb\nc\nd\n", 2, 3, "x\ny\nz"),
+            "a\nx\ny\nz\nd\n"
+        );
+
+        let source = "struct S;\n\n/// Docs\n#[inline]\npub fn\nrun() {}\nimpl S {\n    fn go(&self) {}\n}\n";
+        assert_eq!(item_start(source, 6), Some(3));
+        assert_eq!(item_start(source, 8), Some(8));
+        assert_eq!(item_start(source, 1), None);
+    }
+
+    /// Fails every request, as when no API key is configured.
+    struct Unavailable;
+
+    #[async_trait]
+    impl Proposer for Unavailable {
+        async fn propose(&self, _prompt: &str) -> Result<String, ToolError> {
+            Err(ToolError::Execution("LLM unavailable".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_proposal_leaves_file_untouched() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let lib = dir.path().join("lib.rs");
+        let source = format!("/// Sorts numbers into words\n{}", CLASSIFY);
+        std::fs::write(&lib, &source).unwrap();
+        let modified = std::fs::metadata(&lib).unwrap().modified().unwrap();
+
+        let tool = RefactorTool {
+            workspace: dir.path().to_path_buf(),
+        };
+        let suggestion = RefactoringSuggestion {
+            file: "lib.rs".to_string(),
+            function: "classify".to_string(),
+            line: 2,
+            issue_type: "high_complexity".to_string(),
+            severity: "medium".to_string(),
+            description: String::new(),
+            suggested_fix: String::new(),
+        };
+        let result = tool
+            .refactor_function(&suggestion, &Unavailable, 3, None)
+            .await;
+        assert_eq!(result.status, "failed");
+        assert_eq!(result.attempts, 1);
+        assert_eq!(
+            result.error.as_deref(),
+            Some("Tool execution error: LLM unavailable")
+        );
+        assert_eq!(std::fs::read_to_string(&lib).unwrap(), source);
+        assert_eq!(
+            std::fs::metadata(&lib).unwrap().modified().unwrap(),
+            modified
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auto_apply_verifies_and_reverts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        let lib = dir.path().join("src/lib.rs");
+        std::fs::write(&lib, CLASSIFY).unwrap();
+
+        let tool = RefactorTool {
+            workspace: dir.path().to_path_buf(),
+        };
+        let suggestion = RefactoringSuggestion {
+            file: "src/lib.rs".to_string(),
+            function: "classify".to_string(),
+            line: 1,
+            issue_type: "high_complexity".to_string(),
+            severity: "medium".to_string(),
+            description: String::new(),
+            suggested_fix: String::new(),
+        };
+
+        // Compiles but changes behavior, so the tests reject it every time
+        let wrong = "```rust\npub fn classify(_n: i32) -> &'static str {\n    \"zero\"\n}\n```";
+        let proposer = Scripted::new(&[wrong, wrong]);
+        let applied = tool
+            .auto_apply(std::slice::from_ref(&suggestion), &proposer, 2)
+            .await;
+        assert_eq!(applied[0].status, "reverted");
+        assert_eq!(applied[0].attempts, 2);
+        assert!(applied[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("classify_values"));
+        assert_eq!(std::fs::read_to_string(&lib).unwrap(), CLASSIFY);
+
+        // A type error is fed back, then a correct rewrite is kept
+        let broken = "```rust\npub fn classify(n: i32) -> &'static str {\n    n\n}\n```";
+        let fixed = "```rust\npub fn classify(n: i32) -> &'static str {
+    match n {
+        ..=-101 => \"very negative\",
+        -100..=-1 => \"negative\",
+        0 => \"zero\",
+        _ => \"positive\",
+    }
+}\n```";
+        let proposer = Scripted::new(&[broken, fixed]);
+        let applied = tool
+            .auto_apply(std::slice::from_ref(&suggestion), &proposer, 3)
+            .await;
+        let result = &applied[0];
+        assert_eq!(result.status, "applied", "{:?}", result.error);
+        assert_eq!(result.attempts, 2);
+        assert!(proposer.prompts.lock().unwrap()[1].contains("mismatched types"));
+        assert!(result.before.unwrap().cognitive > result.after.unwrap().cognitive);
+        let source = std::fs::read_to_string(&lib).unwrap();
+        assert!(source.contains("-100..=-1 => \"negative\""));
+        assert!(source.contains("fn classify_values()"));
+    }
 }
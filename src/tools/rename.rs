@@ -0,0 +1,619 @@
+//! Cross-file rename.
+//!
+//! The language server's rename is used when one handles the file, since
+//! it knows which identifiers are the same symbol. Without one, every
+//! whole-identifier occurrence in code is renamed, leaving comments and
+//! string literals alone.
+
+use crate::analysis::lexer::{segments, SegmentKind, Syntax};
+use crate::analysis::{display_path, source_files};
+use crate::lsp::{byte_offset, uri_to_path, LspError, LspManager, Position};
+use crate::tools::edit::{self, FileChange, TextEdit};
+use crate::tools::lsp::{position, resolve};
+use crate::tools::{Tool, ToolError};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+
+const METHODS: &[&str] = &["auto", "lsp", "syntax"];
+
+#[derive(Debug, Deserialize)]
+struct RenameParams {
+    new_name: String,
+    /// Current name; found at `line` and `column` when omitted
+    #[serde(default)]
+    old_name: Option<String>,
+    /// File declaring or using the symbol
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    line: Option<usize>,
+    #[serde(default)]
+    column: Option<usize>,
+    /// Directory the syntax-aware rename searches (default: workspace)
+    #[serde(default)]
+    scope: Option<String>,
+    /// "auto" (language server when available), "lsp" or "syntax"
+    #[serde(default = "default_method")]
+    method: String,
+    /// Show the diff without changing files
+    #[serde(default)]
+    dry_run: bool,
+}
+
+fn default_method() -> String {
+    "auto".to_string()
+}
+
+#[derive(Debug, Serialize)]
+struct RenamedFile {
+    file: String,
+    edits: usize,
+    diff: String,
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| !c.is_ascii_digit()) && name.chars().all(is_identifier_char)
+}
+
+/// Byte ranges where `name` appears as a whole identifier in the code of
+/// `source`, outside comments and string literals.
+fn identifier_matches(source: &str, syntax: Syntax, name: &str) -> Vec<(usize, usize)> {
+    let mut matches = Vec::new();
+    for segment in segments(source, syntax)
+        .into_iter()
+        .filter(|s| s.kind == SegmentKind::Code)
+    {
+        let code = &source[segment.start..segment.end];
+        for (i, _) in code.match_indices(name) {
+            let start = segment.start + i;
+            let end = start + name.len();
+            let before = source[..start].chars().next_back();
+            let after = source[end..].chars().next();
+            if !before.is_some_and(is_identifier_char) && !after.is_some_and(is_identifier_char) {
+                matches.push((start, end));
+            }
+        }
+    }
+    matches
+}
+
+/// The identifier covering the 1-based `line` and `column` of `source`.
+fn identifier_at(source: &str, line: usize, column: usize) -> Option<String> {
+    let chars: Vec<char> = source.lines().nth(line.checked_sub(1)?)?.chars().collect();
+    let at = column.checked_sub(1)?.min(chars.len());
+    let start = (0..at)
+        .rev()
+        .take_while(|&i| is_identifier_char(chars[i]))
+        .last()
+        .unwrap_or(at);
+    let end = (at..chars.len())
+        .take_while(|&i| is_identifier_char(chars[i]))
+        .last()
+        .map_or(at, |i| i + 1);
+    let name: String = chars[start..end].iter().collect();
+    is_identifier(&name).then_some(name)
+}
+
+/// 1-based line and column of a byte offset.
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Text edits per file of a `WorkspaceEdit`, in either of its forms.
+fn workspace_edits(edit: &Value) -> Result<BTreeMap<String, Vec<Value>>, ToolError> {
+    let mut edits: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    if let Some(changes) = edit["changes"].as_object() {
+        for (uri, file_edits) in changes {
+            edits
+                .entry(uri.clone())
+                .or_default()
+                .extend(file_edits.as_array().into_iter().flatten().cloned());
+        }
+    }
+    for change in edit["documentChanges"].as_array().into_iter().flatten() {
+        if let Some(kind) = change["kind"].as_str() {
+            return Err(ToolError::Execution(format!(
+                "Language server rename would {} a file, which is not supported",
+                kind
+            )));
+        }
+        let uri = change["textDocument"]["uri"].as_str().unwrap_or_default();
+        edits
+            .entry(uri.to_string())
+            .or_default()
+            .extend(change["edits"].as_array().into_iter().flatten().cloned());
+    }
+    Ok(edits)
+}
+
+/// Whether `path` resolves to somewhere inside `workspace`, following
+/// symlinks and `..` components.
+fn within(workspace: &Path, path: &Path) -> bool {
+    match (
+        std::fs::canonicalize(workspace),
+        std::fs::canonicalize(path),
+    ) {
+        (Ok(workspace), Ok(path)) => path.starts_with(workspace),
+        _ => false,
+    }
+}
+
+/// Byte-offset edits for the LSP `TextEdit`s of one file, or why one of
+/// them does not fit `original`.
+fn text_edits(original: &str, edits: &[Value]) -> Result<Vec<TextEdit>, String> {
+    edits
+        .iter()
+        .map(|e| {
+            let point = |p: &Value| {
+                serde_json::from_value::<Position>(p.clone())
+                    .ok()
+                    .and_then(|position| byte_offset(original, position))
+                    .ok_or_else(|| format!("position {} is not in the file", p))
+            };
+            let replacement = e["newText"]
+                .as_str()
+                .ok_or_else(|| format!("edit {} has no newText", e))?;
+            let (start, end) = (point(&e["range"]["start"])?, point(&e["range"]["end"])?);
+            if start > end {
+                return Err(format!("range {} ends before it starts", e["range"]));
+            }
+            Ok(TextEdit::new(start, end, replacement))
+        })
+        .collect()
+}
+
+/// Whether the language server could not be used at all, as opposed to
+/// having answered with an error.
+fn unavailable(error: &LspError) -> bool {
+    !matches!(error, LspError::Server { .. })
+}
+
+/// Rename symbols across files, through the language server or by
+/// syntax-aware identifier matching.
+pub struct RenameTool {
+    lsp: Arc<LspManager>,
+}
+
+impl RenameTool {
+    pub fn new(lsp: Arc<LspManager>) -> Self {
+        Self { lsp }
+    }
+
+    fn workspace(&self) -> &Path {
+        self.lsp.workspace()
+    }
+
+    /// Changes from the language server handling `path`, or `None` when
+    /// there is none and `fallback` allows renaming without it.
+    async fn lsp_changes(
+        &self,
+        path: &Path,
+        at: Position,
+        new_name: &str,
+        fallback: bool,
+    ) -> Result<Option<Vec<(FileChange, usize)>>, ToolError> {
+        let result = match self.lsp.client_for(path).await {
+            Ok(client) => client.rename(path, at, new_name).await,
+            Err(e) => Err(e),
+        };
+        let edit = match result {
+            Ok(edit) => edit,
+            Err(e) if fallback && unavailable(&e) => {
+                info!("Renaming without a language server: {}", e);
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if edit.is_null() {
+            return Err(ToolError::NotFound(
+                "Language server found no symbol to rename".to_string(),
+            ));
+        }
+
+        let mut changes = Vec::new();
+        for (uri, edits) in workspace_edits(&edit)? {
+            let file = uri_to_path(&uri)
+                .filter(|p| within(self.workspace(), p))
+                .ok_or_else(|| {
+                    ToolError::PermissionDenied(format!(
+                        "Rename would edit {}, outside the workspace",
+                        uri
+                    ))
+                })?;
+            let original = std::fs::read_to_string(&file)
+                .map_err(|e| ToolError::Execution(format!("Failed to read {}: {}", uri, e)))?;
+            // One unusable edit would leave the rename half done
+            let text_edits = text_edits(&original, &edits)
+                .map_err(|e| ToolError::Execution(format!("Invalid edit for {}: {}", uri, e)))?;
+            let updated = edit::apply(&original, &text_edits);
+            let change = FileChange {
+                file: display_path(self.workspace(), &file),
+                path: file,
+                original,
+                updated,
+            };
+            changes.push((change, text_edits.len()));
+        }
+        Ok(Some(changes))
+    }
+
+    /// Changes renaming every code occurrence of `old_name` in the files
+    /// under `scope`, limited to one language when `syntax` is given.
+    fn syntax_changes(
+        &self,
+        scope: &Path,
+        syntax: Option<Syntax>,
+        old_name: &str,
+        new_name: &str,
+    ) -> Vec<(FileChange, usize)> {
+        let mut changes = Vec::new();
+        for path in source_files(scope, Syntax::EXTENSIONS) {
+            let Some(file_syntax) = Syntax::from_path(&path) else {
+                continue;
+            };
+            if syntax.is_some_and(|s| s != file_syntax) {
+                continue;
+            }
+            let Ok(original) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let edits: Vec<TextEdit> = identifier_matches(&original, file_syntax, old_name)
+                .into_iter()
+                .map(|(start, end)| TextEdit::new(start, end, new_name))
+                .collect();
+            if edits.is_empty() {
+                continue;
+            }
+            let updated = edit::apply(&original, &edits);
+            let change = FileChange {
+                file: display_path(self.workspace(), &path),
+                path,
+                original,
+                updated,
+            };
+            changes.push((change, edits.len()));
+        }
+        changes
+    }
+}
+
+#[async_trait]
+impl Tool for RenameTool {
+    fn name(&self) -> &str {
+        "rename"
+    }
+
+    fn description(&self) -> &str {
+        "Rename a symbol across files with a multi-file diff preview, applied atomically: uses the language server's rename when available, otherwise renames whole identifiers outside strings and comments"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: RenameParams =
+            serde_json::from_value(params).map_err(|e| ToolError::InvalidParams(e.to_string()))?;
+        if !METHODS.contains(&params.method.as_str()) {
+            return Err(ToolError::InvalidParams(format!(
+                "Unknown method: {}. Use: {}",
+                params.method,
+                METHODS.join(", ")
+            )));
+        }
+        let new_name = params.new_name.trim();
+        if !is_identifier(new_name) {
+            return Err(ToolError::InvalidParams(format!(
+                "Not an identifier: {:?}",
+                new_name
+            )));
+        }
+
+        // The file named by `path`, with its contents
+        let target = match &params.path {
+            Some(path) => {
+                let path = resolve(self.workspace(), path)?;
+                let source = std::fs::read_to_string(&path)
+                    .map_err(|e| ToolError::Execution(e.to_string()))?;
+                Some((path, source))
+            }
+            None => None,
+        };
+        // The symbol's line and column in it: given, or where `old_name`
+        // first occurs in its code
+        let at = match (&target, params.line, params.column) {
+            (Some(_), Some(line), Some(column)) => Some((line, column)),
+            (Some((path, source)), _, _) => Syntax::from_path(path)
+                .zip(params.old_name.as_deref())
+                .and_then(|(syntax, name)| {
+                    identifier_matches(source, syntax, name.trim())
+                        .first()
+                        .copied()
+                })
+                .map(|(start, _)| line_column(source, start)),
+            _ => None,
+        };
+        let old_name = match (&params.old_name, &target, at) {
+            (Some(name), _, _) => name.trim().to_string(),
+            (None, Some((_, source)), Some((line, column))) => identifier_at(source, line, column)
+                .ok_or_else(|| {
+                    ToolError::InvalidParams(format!("No identifier at {}:{}", line, column))
+                })?,
+            _ => {
+                return Err(ToolError::InvalidParams(
+                    "rename requires old_name, or path with line and column".to_string(),
+                ))
+            }
+        };
+        if old_name == new_name {
+            return Err(ToolError::InvalidParams(format!(
+                "{} is already named {}",
+                old_name, new_name
+            )));
+        }
+
+        let mut method = "lsp";
+        let mut changes = match (params.method.as_str(), &target, at) {
+            ("syntax", _, _) => None,
+            (_, Some((path, _)), Some((line, column))) => {
+                let position = position(path, line, column)?;
+                let fallback = params.method == "auto";
+                self.lsp_changes(path, position, new_name, fallback).await?
+            }
+            ("lsp", _, _) => {
+                return Err(ToolError::InvalidParams(
+                    "lsp rename requires path with line and column, or with old_name".to_string(),
+                ))
+            }
+            _ => None,
+        };
+        if changes.is_none() {
+            method = "syntax";
+            let scope = match &params.scope {
+                Some(scope) if !scope.trim().is_empty() && scope.trim() != "." => {
+                    let scope = Path::new(scope.trim());
+                    if scope.is_absolute() || scope.components().any(|c| c.as_os_str() == "..") {
+                        return Err(ToolError::InvalidParams(
+                            "Scope must be within workspace".to_string(),
+                        ));
+                    }
+                    self.workspace().join(scope)
+                }
+                _ => self.workspace().to_path_buf(),
+            };
+            let syntax = target
+                .as_ref()
+                .and_then(|(path, _)| Syntax::from_path(path));
+            changes = Some(self.syntax_changes(&scope, syntax, &old_name, new_name));
+        }
+
+        let mut planned: Vec<(FileChange, usize)> = changes
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(c, _)| c.original != c.updated)
+            .collect();
+        planned.sort_by(|a, b| a.0.file.cmp(&b.0.file));
+        if planned.is_empty() {
+            return Err(ToolError::NotFound(format!(
+                "No occurrences of {} to rename",
+                old_name
+            )));
+        }
+
+        let files: Vec<RenamedFile> = planned
+            .iter()
+            .map(|(change, edits)| RenamedFile {
+                file: change.file.clone(),
+                edits: *edits,
+                diff: change.diff(),
+            })
+            .collect();
+        if !params.dry_run {
+            let changes: Vec<FileChange> = planned.into_iter().map(|(c, _)| c).collect();
+            edit::write_all_atomic(&changes)
+                .map_err(|e| ToolError::Execution(format!("Rename not applied: {}", e)))?;
+            // The server still holds the pre-rename text of open documents
+            if let (true, Some((path, _))) = (method == "lsp", &target) {
+                if let Ok(client) = self.lsp.client_for(path).await {
+                    let _ = client.resync().await;
+                }
+            }
+            info!(
+                "Renamed {} to {} in {} files",
+                old_name,
+                new_name,
+                files.len()
+            );
+        }
+
+        Ok(json!({
+            "method": method,
+            "old_name": old_name,
+            "new_name": new_name,
+            "applied": !params.dry_run,
+            "total_edits": files.iter().map(|f| f.edits).sum::<usize>(),
+            "files": files,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::fake_server;
+    use tempfile::TempDir;
+
+    fn write(dir: &TempDir, file: &str, content: &str) {
+        let path = dir.path().join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn read(dir: &TempDir, file: &str) -> String {
+        std::fs::read_to_string(dir.path().join(file)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rename_with_language_server() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "app.py", "class Greeter:\n    pass\n");
+        write(&dir, "main.py", "from app import Greeter\n\nGreeter()\n");
+        let servers = vec![fake_server::config(&dir)];
+        let lsp = Arc::new(LspManager::with_servers(dir.path().to_path_buf(), servers));
+        let tool = RenameTool::new(lsp);
+
+        let params =
+            json!({"path": "app.py", "line": 1, "column": 8, "new_name": "Host", "dry_run": true});
+        let result = tool.execute(params).await.unwrap();
+        assert_eq!(result["method"], "lsp");
+        assert_eq!(result["old_name"], "Greeter");
+        assert_eq!(result["total_edits"], 3);
+        assert_eq!(result["files"][1]["file"], "main.py");
+        assert_eq!(
+            result["files"][1]["diff"],
+            "--- a/main.py\n+++ b/main.py\n@@ -1,3 +1,3 @@\n-from app import Greeter\n+from app import Host\n \n-Greeter()\n+Host()\n"
+        );
+        assert_eq!(
+            read(&dir, "main.py"),
+            "from app import Greeter\n\nGreeter()\n"
+        );
+
+        // Without a position the first occurrence in `path` is used
+        let params = json!({"path": "main.py", "old_name": "Greeter", "new_name": "Host"});
+        let result = tool.execute(params).await.unwrap();
+        assert_eq!(result["applied"], true);
+        assert_eq!(read(&dir, "app.py"), "class Host:\n    pass\n");
+        assert_eq!(read(&dir, "main.py"), "from app import Host\n\nHost()\n");
+    }
+
+    #[test]
+    fn test_lsp_edits_are_all_or_nothing() {
+        let original = "class Greeter:\n    pass\n";
+        let edit = |start: Value, end: Value| json!({"range": {"start": start, "end": end}, "newText": "Host"});
+        let valid = edit(
+            json!({"line": 0, "character": 6}),
+            json!({"line": 0, "character": 13}),
+        );
+        assert_eq!(
+            text_edits(original, std::slice::from_ref(&valid))
+                .unwrap()
+                .len(),
+            1
+        );
+        for invalid in [
+            edit(
+                json!({"line": 9, "character": 0}),
+                json!({"line": 9, "character": 4}),
+            ),
+            edit(json!({"line": "x"}), json!({"line": 0, "character": 4})),
+            edit(
+                json!({"line": 0, "character": 4}),
+                json!({"line": 0, "character": 1}),
+            ),
+            json!({"range": valid["range"]}),
+        ] {
+            assert!(
+                text_edits(original, &[valid.clone(), invalid.clone()]).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_within_resolves_parent_components() {
+        let dir = TempDir::new().unwrap();
+        let workspace = dir.path().join("ws");
+        write(&dir, "ws/app.py", "");
+        write(&dir, "secret.py", "");
+        assert!(within(&workspace, &workspace.join("app.py")));
+        assert!(!within(&workspace, &workspace.join("../secret.py")));
+    }
+
+    #[tokio::test]
+    async fn test_syntax_rename_skips_strings_and_comments() {
+        let dir = TempDir::new().unwrap();
+        write(
+            &dir,
+            "src/registry.rs",
+            "/// A Registry of tools\npub struct Registry;\nimpl Registry {}\n",
+        );
+        write(
+            &dir,
+            "src/main.rs",
+            "use crate::registry::Registry;\nfn main() {\n    let r = Registry; // Registry\n    println!(\"Registry\");\n    let registry = RegistryExt;\n}\n",
+        );
+        write(&dir, "tools/registry.py", "Registry = 1\n");
+        // No language servers configured, so `auto` falls back
+        let lsp = Arc::new(LspManager::with_servers(
+            dir.path().to_path_buf(),
+            Vec::new(),
+        ));
+        let tool = RenameTool::new(lsp);
+
+        let params =
+            json!({"path": "src/registry.rs", "line": 2, "column": 12, "new_name": "ToolRegistry"});
+        let result = tool.execute(params).await.unwrap();
+        assert_eq!(result["method"], "syntax");
+        assert_eq!(result["total_edits"], 4);
+        assert_eq!(
+            read(&dir, "src/main.rs"),
+            "use crate::registry::ToolRegistry;\nfn main() {\n    let r = ToolRegistry; // Registry\n    println!(\"Registry\");\n    let registry = RegistryExt;\n}\n"
+        );
+        assert_eq!(
+            read(&dir, "src/registry.rs"),
+            "/// A Registry of tools\npub struct ToolRegistry;\nimpl ToolRegistry {}\n"
+        );
+        assert_eq!(read(&dir, "tools/registry.py"), "Registry = 1\n");
+
+        let err = tool
+            .execute(json!({"path": "src/registry.rs", "line": 2, "column": 12, "new_name": "X", "method": "lsp"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParams(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_rename_invalid_params() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "src/lib.rs", "pub fn run() {}\n");
+        let lsp = Arc::new(LspManager::with_servers(
+            dir.path().to_path_buf(),
+            Vec::new(),
+        ));
+        let tool = RenameTool::new(lsp);
+
+        for params in [
+            json!({"old_name": "run", "new_name": "2run"}),
+            json!({"old_name": "run", "new_name": "run-fast"}),
+            json!({"old_name": "run", "new_name": "run"}),
+            json!({"new_name": "start"}),
+            json!({"old_name": "run", "new_name": "start", "method": "rewrite"}),
+            json!({"old_name": "run", "new_name": "start", "scope": "../"}),
+            json!({"path": "/etc/passwd", "line": 1, "column": 1, "new_name": "start"}),
+        ] {
+            let err = tool.execute(params.clone()).await.unwrap_err();
+            assert!(
+                matches!(err, ToolError::InvalidParams(_)),
+                "{}: {}",
+                params,
+                err
+            );
+        }
+        let err = tool
+            .execute(json!({"old_name": "walk", "new_name": "start"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::NotFound(_)));
+    }
+}
@@ -0,0 +1,368 @@
+//! Regex search and replace across the workspace.
+//!
+//! Files are walked the way git sees them: `.gitignore`, `.ignore` and
+//! hidden files are skipped. A dry run lists every match with an id, and a
+//! later call can pass some of those ids as `confirm` to write only them.
+
+use crate::analysis::display_path;
+use crate::tools::edit::{self, FileChange, TextEdit};
+use crate::tools::{Tool, ToolError};
+use async_trait::async_trait;
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+#[derive(Debug, Deserialize)]
+struct ReplaceParams {
+    /// Regular expression; `(?m)`, `(?i)` and other inline flags apply
+    pattern: String,
+    /// Replacement text, with `$1` or `${name}` for capture groups
+    replacement: String,
+    /// Globs of files to search (default: all), relative to the workspace
+    #[serde(default)]
+    paths: Vec<String>,
+    /// Globs of files to leave alone
+    #[serde(default)]
+    exclude: Vec<String>,
+    /// Report matches and diffs without writing
+    #[serde(default)]
+    dry_run: bool,
+    /// Ids of the matches to replace, from an earlier dry run; all when
+    /// omitted
+    #[serde(default)]
+    confirm: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Match {
+    /// `file:line:column` of the match
+    id: String,
+    line: usize,
+    column: usize,
+    text: String,
+    replacement: String,
+}
+
+#[derive(Debug, Serialize)]
+struct FileReplacements {
+    file: String,
+    matches: usize,
+    diff: String,
+    changes: Vec<Match>,
+}
+
+#[derive(Debug)]
+pub struct ReplaceTool {
+    workspace: PathBuf,
+}
+
+impl ReplaceTool {
+    pub fn new() -> Self {
+        Self {
+            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        }
+    }
+}
+
+impl Default for ReplaceTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Files in `workspace` selected by the globs, in path order, skipping
+/// what git ignores.
+pub(crate) fn workspace_files(
+    workspace: &Path,
+    paths: &[String],
+    exclude: &[String],
+) -> Result<Vec<PathBuf>, ToolError> {
+    let mut overrides = OverrideBuilder::new(workspace);
+    let globs = paths
+        .iter()
+        .map(|g| g.trim().to_string())
+        .chain(exclude.iter().map(|g| format!("!{}", g.trim())));
+    for glob in globs {
+        overrides
+            .add(&glob)
+            .map_err(|e| ToolError::InvalidParams(format!("Invalid glob {}: {}", glob, e)))?;
+    }
+    let overrides = overrides
+        .build()
+        .map_err(|e| ToolError::InvalidParams(e.to_string()))?;
+
+    let mut files: Vec<PathBuf> = WalkBuilder::new(workspace)
+        .overrides(overrides)
+        // Honor .gitignore outside git repositories too
+        .require_git(false)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// 1-based line and column of each byte offset in `offsets`, which must be
+/// ascending.
+pub(crate) fn line_columns(source: &str, offsets: &[usize]) -> Vec<(usize, usize)> {
+    let mut positions = Vec::with_capacity(offsets.len());
+    let (mut line, mut line_start, mut cursor) = (1, 0, 0);
+    for &offset in offsets {
+        for (i, c) in source[cursor..offset].char_indices() {
+            if c == '\n' {
+                line += 1;
+                line_start = cursor + i + 1;
+            }
+        }
+        cursor = offset;
+        positions.push((line, source[line_start..offset].chars().count() + 1));
+    }
+    positions
+}
+
+/// Every match of `regex` in `source` with its expanded replacement.
+fn find_matches(
+    source: &str,
+    regex: &Regex,
+    replacement: &str,
+    file: &str,
+) -> Vec<(TextEdit, Match)> {
+    let captures: Vec<_> = regex.captures_iter(source).collect();
+    let starts: Vec<usize> = captures.iter().map(|c| c.get(0).unwrap().start()).collect();
+    captures
+        .iter()
+        .zip(line_columns(source, &starts))
+        .map(|(caps, (line, column))| {
+            let whole = caps.get(0).unwrap();
+            let mut expanded = String::new();
+            caps.expand(replacement, &mut expanded);
+            let edit = TextEdit::new(whole.start(), whole.end(), expanded.clone());
+            let found = Match {
+                id: format!("{}:{}:{}", file, line, column),
+                line,
+                column,
+                text: whole.as_str().to_string(),
+                replacement: expanded,
+            };
+            (edit, found)
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Tool for ReplaceTool {
+    fn name(&self) -> &str {
+        "replace"
+    }
+
+    fn description(&self) -> &str {
+        "Regex search and replace across files selected by globs, skipping gitignored and hidden files: per-file match counts and diffs, optional confirmation of individual matches, all files written atomically"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: ReplaceParams =
+            serde_json::from_value(params).map_err(|e| ToolError::InvalidParams(e.to_string()))?;
+        if params.pattern.is_empty() {
+            return Err(ToolError::InvalidParams("pattern is empty".to_string()));
+        }
+        let regex = Regex::new(&params.pattern)
+            .map_err(|e| ToolError::InvalidParams(format!("Invalid pattern: {}", e)))?;
+        let confirmed: Option<HashSet<&str>> = params
+            .confirm
+            .as_ref()
+            .map(|ids| ids.iter().map(|id| id.trim()).collect());
+
+        let mut seen = HashSet::new();
+        let mut changes = Vec::new();
+        let mut files = Vec::new();
+        for path in workspace_files(&self.workspace, &params.paths, &params.exclude)? {
+            // Binary and non-UTF-8 files are not searched
+            let Ok(original) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let file = display_path(&self.workspace, &path);
+            let (edits, matches): (Vec<TextEdit>, Vec<Match>) =
+                find_matches(&original, &regex, &params.replacement, &file)
+                    .into_iter()
+                    .filter(|(_, m)| {
+                        confirmed
+                            .as_ref()
+                            .is_none_or(|ids| ids.contains(m.id.as_str()))
+                    })
+                    .unzip();
+            if matches.is_empty() {
+                continue;
+            }
+            seen.extend(matches.iter().map(|m| m.id.clone()));
+
+            let change = FileChange {
+                path,
+                file: file.clone(),
+                updated: edit::apply(&original, &edits),
+                original,
+            };
+            files.push(FileReplacements {
+                file,
+                matches: matches.len(),
+                diff: change.diff(),
+                changes: matches,
+            });
+            changes.push(change);
+        }
+
+        if let Some(ids) = &confirmed {
+            let mut stale: Vec<&str> = ids
+                .iter()
+                .filter(|id| !seen.contains(**id))
+                .copied()
+                .collect();
+            if !stale.is_empty() {
+                stale.sort();
+                return Err(ToolError::InvalidParams(format!(
+                    "No longer matching, run a dry run again: {}",
+                    stale.join(", ")
+                )));
+            }
+        }
+
+        let total: usize = files.iter().map(|f| f.matches).sum();
+        let changed: Vec<FileChange> = changes
+            .into_iter()
+            .filter(|c| c.original != c.updated)
+            .collect();
+        let applied = !params.dry_run && !changed.is_empty();
+        if applied {
+            edit::write_all_atomic(&changed)
+                .map_err(|e| ToolError::Execution(format!("Nothing replaced: {}", e)))?;
+            info!("Replaced {} matches in {} files", total, changed.len());
+        }
+
+        Ok(json!({
+            "total_matches": total,
+            "files_changed": changed.len(),
+            "applied": applied,
+            "files": files,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn tool(dir: &TempDir) -> ReplaceTool {
+        let files = [
+            (".gitignore", "generated/\n"),
+            ("src/lib.rs", "let a = foo(1);\nlet b = foo(22);\n"),
+            ("src/util.rs", "fn x() { foo(3) }\n"),
+            ("src/util_test.rs", "foo(4);\n"),
+            ("generated/out.rs", "foo(5);\n"),
+            ("README.md", "Call foo(6).\n"),
+        ];
+        for (file, content) in files {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        ReplaceTool {
+            workspace: dir.path().to_path_buf(),
+        }
+    }
+
+    fn read(dir: &TempDir, file: &str) -> String {
+        std::fs::read_to_string(dir.path().join(file)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replace_with_globs_and_gitignore() {
+        let dir = TempDir::new().unwrap();
+        let tool = tool(&dir);
+        let params = json!({
+            "pattern": r"foo\((\d+)\)",
+            "replacement": "bar(${1}, None)",
+            "paths": ["src/**/*.rs"],
+            "exclude": ["*_test.rs"],
+            "dry_run": true
+        });
+
+        let result = tool.execute(params.clone()).await.unwrap();
+        assert_eq!(result["total_matches"], 3);
+        assert_eq!(result["applied"], false);
+        assert_eq!(result["files"][0]["file"], "src/lib.rs");
+        assert_eq!(result["files"][0]["matches"], 2);
+        assert_eq!(
+            result["files"][0]["changes"][1],
+            json!({"id": "src/lib.rs:2:9", "line": 2, "column": 9, "text": "foo(22)", "replacement": "bar(22, None)"})
+        );
+        assert_eq!(
+            result["files"][1]["diff"],
+            "--- a/src/util.rs\n+++ b/src/util.rs\n@@ -1 +1 @@\n-fn x() { foo(3) }\n+fn x() { bar(3, None) }\n"
+        );
+        assert_eq!(
+            read(&dir, "src/lib.rs"),
+            "let a = foo(1);\nlet b = foo(22);\n"
+        );
+
+        let mut params = params;
+        params["dry_run"] = json!(false);
+        let result = tool.execute(params).await.unwrap();
+        assert_eq!(result["files_changed"], 2);
+        assert_eq!(
+            read(&dir, "src/lib.rs"),
+            "let a = bar(1, None);\nlet b = bar(22, None);\n"
+        );
+        assert_eq!(read(&dir, "src/util_test.rs"), "foo(4);\n");
+        assert_eq!(read(&dir, "generated/out.rs"), "foo(5);\n");
+        assert_eq!(read(&dir, "README.md"), "Call foo(6).\n");
+    }
+
+    #[tokio::test]
+    async fn test_replace_confirmed_matches_only() {
+        let dir = TempDir::new().unwrap();
+        let tool = tool(&dir);
+        let params = json!({
+            "pattern": "foo",
+            "replacement": "baz",
+            "confirm": ["src/lib.rs:2:9", "README.md:1:6"]
+        });
+
+        let result = tool.execute(params.clone()).await.unwrap();
+        assert_eq!(result["total_matches"], 2);
+        assert_eq!(
+            read(&dir, "src/lib.rs"),
+            "let a = foo(1);\nlet b = baz(22);\n"
+        );
+        assert_eq!(read(&dir, "README.md"), "Call baz(6).\n");
+
+        // The confirmed matches are gone now
+        let err = tool.execute(params).await.unwrap_err();
+        assert!(
+            err.to_string().contains("README.md:1:6, src/lib.rs:2:9"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replace_invalid_params() {
+        let dir = TempDir::new().unwrap();
+        let tool = tool(&dir);
+        for params in [
+            json!({"pattern": "", "replacement": "x"}),
+            json!({"pattern": "foo(", "replacement": "x"}),
+            json!({"pattern": "foo", "replacement": "x", "paths": ["src/[.rs"]}),
+            json!({"pattern": "foo"}),
+        ] {
+            let err = tool.execute(params.clone()).await.unwrap_err();
+            assert!(matches!(err, ToolError::InvalidParams(_)), "{}", params);
+        }
+    }
+}
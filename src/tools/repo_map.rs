@@ -0,0 +1,301 @@
+//! Repository map for LLM context.
+//!
+//! The map lists the workspace's source directories and, file by file, the
+//! signatures of its most central symbols, fitted to a token budget. A
+//! symbol is central when many other files mention its name; a name
+//! declared in several places shares its mentions between them. Files are
+//! shown in order of the summed centrality of their symbols, so a small
+//! budget keeps the core of the codebase and drops the periphery.
+
+use crate::analysis::symbols::{Symbol, SymbolKind};
+use crate::token_estimation::CompactTokenCounter;
+use crate::tools::symbol_index::SymbolIndex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// Most signatures listed under one file.
+const MAX_SIGNATURES: usize = 8;
+/// Deepest directory level shown in the tree.
+const MAX_TREE_DEPTH: usize = 3;
+/// Share of the budget the directory tree may take.
+const TREE_SHARE: usize = 4;
+
+/// A file and its symbols with their centrality scores.
+#[derive(Debug, Clone)]
+pub struct RankedFile {
+    pub path: String,
+    pub score: f64,
+    pub symbols: Vec<(Symbol, f64)>,
+}
+
+/// Whether a symbol earns a line of its own in the map. Impls and modules
+/// add nothing over their items, and test helpers are not an interface.
+fn listed(symbol: &Symbol) -> bool {
+    let in_tests = symbol
+        .container
+        .as_deref()
+        .is_some_and(|c| c == "tests" || c.ends_with("::tests"));
+    !in_tests && !matches!(symbol.kind, SymbolKind::Impl | SymbolKind::Module)
+}
+
+/// Score every indexed file's symbols, most central file first.
+pub fn rank(index: &SymbolIndex) -> Vec<RankedFile> {
+    let mut declarations: HashMap<&str, usize> = HashMap::new();
+    for symbol in index
+        .files
+        .values()
+        .flat_map(|e| &e.symbols)
+        .filter(|s| listed(s))
+    {
+        *declarations.entry(symbol.name.as_str()).or_default() += 1;
+    }
+
+    // Files mentioning each declared name
+    let mut mentions: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for (file, entry) in &index.files {
+        for word in &entry.identifiers {
+            if let Some((name, _)) = declarations.get_key_value(word.as_str()) {
+                mentions.entry(name).or_default().insert(file);
+            }
+        }
+    }
+
+    let mut ranked: Vec<RankedFile> = index
+        .files
+        .iter()
+        .map(|(file, entry)| {
+            let rust = file.ends_with(".rs");
+            let traits: HashSet<&str> = entry
+                .symbols
+                .iter()
+                .filter(|s| s.kind == SymbolKind::Trait)
+                .map(|s| s.name.as_str())
+                .collect();
+            let symbols: Vec<(Symbol, f64)> = entry
+                .symbols
+                .iter()
+                .filter(|s| listed(s))
+                .map(|symbol| {
+                    let name = symbol.name.as_str();
+                    // Private Rust items, trait methods aside, are only used
+                    // where they are declared, whatever other files say
+                    let private = rust
+                        && !symbol.signature.starts_with("pub")
+                        && !symbol
+                            .container
+                            .as_deref()
+                            .is_some_and(|c| traits.contains(c));
+                    let others = match mentions.get(name) {
+                        Some(files) if !private => {
+                            files.len() - usize::from(files.contains(file.as_str()))
+                        }
+                        _ => 0,
+                    };
+                    let score = others as f64 / declarations[name] as f64;
+                    (symbol.clone(), score)
+                })
+                .collect();
+            RankedFile {
+                path: file.clone(),
+                score: symbols.iter().map(|(_, score)| score).sum(),
+                symbols,
+            }
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.path.cmp(&b.path)));
+    ranked
+}
+
+/// Directories holding source files, with how many each holds in total.
+fn tree_lines(ranked: &[RankedFile]) -> Vec<String> {
+    let mut counts: BTreeMap<Vec<&str>, usize> = BTreeMap::new();
+    for file in ranked {
+        let parts: Vec<&str> = file.path.split('/').collect();
+        let dirs = &parts[..parts.len() - 1];
+        for depth in 1..=dirs.len().min(MAX_TREE_DEPTH) {
+            *counts.entry(dirs[..depth].to_vec()).or_default() += 1;
+        }
+    }
+    let root_files = ranked.iter().filter(|f| !f.path.contains('/')).count();
+    let mut lines = Vec::new();
+    if root_files > 0 {
+        lines.push(format!("  ./ ({} files at the root)", root_files));
+    }
+    for (dir, count) in counts {
+        lines.push(format!(
+            "{}{}/ ({})",
+            "  ".repeat(dir.len()),
+            dir[dir.len() - 1],
+            count
+        ));
+    }
+    lines
+}
+
+/// Lines describing `file`: its path, then up to `limit` of its best
+/// symbols mentioned elsewhere, in declaration order.
+fn file_block(file: &RankedFile, limit: usize) -> Vec<String> {
+    let mut best: Vec<&(Symbol, f64)> = file.symbols.iter().filter(|(_, s)| *s > 0.0).collect();
+    best.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.line.cmp(&b.0.line)));
+    best.truncate(limit);
+    best.sort_by_key(|(symbol, _)| symbol.line);
+
+    let mut lines = vec![format!("{}:", file.path)];
+    for (symbol, _) in best {
+        lines.push(match (&symbol.container, symbol.kind) {
+            (Some(container), SymbolKind::Method) => {
+                format!("  {}: {}", container, symbol.signature)
+            }
+            _ => format!("  {}", symbol.signature),
+        });
+    }
+    lines
+}
+
+/// Render `ranked` within `budget` tokens: the directory tree, then each
+/// file with as many of its best signatures as still fit.
+pub fn fit(ranked: &[RankedFile], budget: usize) -> String {
+    let counter = CompactTokenCounter::instance();
+    let cost = |lines: &[String]| -> usize { lines.iter().map(|l| counter.count_tokens(l)).sum() };
+
+    let header = format!(
+        "Repository map ({} source files, most central first):",
+        ranked.len()
+    );
+    let mut used = counter.count_tokens(&header);
+    if ranked.is_empty() || used > budget {
+        return String::new();
+    }
+    let mut out = vec![header];
+
+    let tree = tree_lines(ranked);
+    let tree_budget = budget / TREE_SHARE;
+    let mut tree_used = counter.count_tokens("Directories:");
+    let shown: Vec<String> = tree
+        .into_iter()
+        .take_while(|line| {
+            tree_used += counter.count_tokens(line);
+            tree_used <= tree_budget
+        })
+        .collect();
+    if !shown.is_empty() {
+        used += cost(&shown) + counter.count_tokens("Directories:");
+        out.push("Directories:".to_string());
+        out.extend(shown);
+    }
+    out.push(String::new());
+
+    for file in ranked {
+        let fitting = (0..=MAX_SIGNATURES).rev().find_map(|limit| {
+            let block = file_block(file, limit);
+            let block_cost = cost(&block);
+            (used + block_cost <= budget).then_some((block, block_cost))
+        });
+        let Some((block, block_cost)) = fitting else {
+            break;
+        };
+        used += block_cost;
+        out.extend(block);
+    }
+    out.join("\n")
+}
+
+/// Map of the workspace within `budget` tokens, built from the symbol
+/// index (which this brings up to date). Empty when `budget` is 0 or there
+/// are no source files.
+pub fn render(workspace: &Path, budget: usize) -> String {
+    if budget == 0 {
+        return String::new();
+    }
+    let (index, _) = SymbolIndex::open(workspace);
+    fit(&rank(&index), budget)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, file: &str, content: &str) {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn workspace() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "src/registry.rs",
+            "pub struct Registry;\nimpl Registry {\n    pub fn execute(&self) {}\n}\npub fn unused_helper() {}\nfn count() {}\n",
+        );
+        write(
+            root,
+            "src/a.rs",
+            "use crate::registry::Registry;\nfn a(r: &Registry) { let count = 1; r.execute(); }\n",
+        );
+        write(root, "src/b.rs", "fn b(r: &crate::registry::Registry) {}\n");
+        write(
+            root,
+            "src/nested/c.rs",
+            "// Registry is only mentioned here\nfn c() {}\n",
+        );
+        write(
+            root,
+            "tools/run.py",
+            "class Runner:\n    def execute(self):\n        pass\n",
+        );
+        write(root, "tools/main.py", "from run import Runner\n");
+        dir
+    }
+
+    #[test]
+    fn test_rank_by_mentions() {
+        let dir = workspace();
+        let (index, _) = SymbolIndex::open(dir.path());
+        let ranked = rank(&index);
+
+        assert_eq!(ranked[0].path, "src/registry.rs");
+        let scores: HashMap<&str, f64> = ranked[0]
+            .symbols
+            .iter()
+            .map(|(s, score)| (s.name.as_str(), *score))
+            .collect();
+        assert_eq!(scores["Registry"], 2.0);
+        // `execute` is declared twice, so the two files mentioning it count
+        // half for each declaration
+        assert_eq!(scores["execute"], 1.0);
+        assert_eq!(scores["unused_helper"], 0.0);
+        // A private function cannot be what another file's `count` refers to
+        assert_eq!(scores["count"], 0.0);
+        assert!(ranked
+            .iter()
+            .all(|f| f.symbols.iter().all(|(s, _)| listed(s))));
+    }
+
+    #[test]
+    fn test_fit_within_budget() {
+        let dir = workspace();
+        let full = render(dir.path(), 10_000);
+        assert!(full.starts_with("Repository map (6 source files"));
+        assert!(full.contains("  src/ (4)\n    nested/ (1)\n  tools/ (2)"));
+        assert!(!full.contains("unused_helper"));
+        assert!(full.contains(
+            "src/registry.rs:\n  pub struct Registry;\n  Registry: pub fn execute(&self) {}"
+        ));
+        assert!(full.contains("tools/run.py:\n  class Runner\n  Runner: def execute(self)"));
+
+        let counter = CompactTokenCounter::instance();
+        let total = counter.count_tokens(&full);
+        for budget in [total / 2, total / 4, 20] {
+            let map = render(dir.path(), budget);
+            assert!(counter.count_tokens(&map) <= budget, "{}", map);
+        }
+        let small = render(dir.path(), total / 2);
+        assert!(small.contains("src/registry.rs:\n  pub struct Registry;"));
+
+        assert_eq!(render(dir.path(), 0), "");
+        assert_eq!(render(TempDir::new().unwrap().path(), 1000), "");
+    }
+}
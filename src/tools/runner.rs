@@ -0,0 +1,619 @@
+//! Shared scaffolding for sandboxed code execution.
+//!
+//! Every language is described by a [`LanguageProfile`]: the file extension
+//! of the snippet, an optional compile step, the run command and the
+//! environment. [`CodeRunner`] turns a profile into a temp-dir sandbox,
+//! writes the snippet, builds the restricted command and enforces the
+//! timeout. [`RunnerTool`] exposes a profile directly as a tool.
+
+use crate::tools::{Tool, ToolError};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::timeout;
+use tracing::{debug, info};
+
+/// Declarative description of how to run snippets of one language.
+///
+/// Command templates may use `{src}` (the snippet), `{bin}` (compiled
+/// output), `{dir}` (the sandbox directory) and `{flags}` (caller supplied
+/// runtime flags, spliced in as separate arguments). Environment values may
+/// use `{dir}` as well.
+#[derive(Debug)]
+pub struct LanguageProfile {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub extension: &'static str,
+    pub compile: Option<&'static [&'static str]>,
+    pub run: &'static [&'static str],
+    pub env: &'static [(&'static str, &'static str)],
+    /// Variables copied from pcode's own environment when set
+    pub inherit_env: &'static [&'static str],
+    pub version: &'static [&'static str],
+}
+
+pub const PYTHON: LanguageProfile = LanguageProfile {
+    name: "python",
+    description: "Execute Python code in a secure sandbox",
+    extension: "py",
+    compile: None,
+    // -B no bytecode, -E/-I isolated mode, -S no site module, -u unbuffered
+    run: &["python3", "-B", "-E", "-I", "-S", "-u", "{src}"],
+    env: &[
+        ("PYTHONDONTWRITEBYTECODE", "1"),
+        ("PYTHONUNBUFFERED", "1"),
+        ("PYTHONPATH", ""),
+        ("PYTHONHOME", ""),
+        ("PYTHONSTARTUP", ""),
+    ],
+    inherit_env: &[],
+    version: &["python3", "--version"],
+};
+
+pub const NODE: LanguageProfile = LanguageProfile {
+    name: "node",
+    description: "Execute JavaScript code with Node.js",
+    extension: "js",
+    compile: None,
+    run: &[
+        "node",
+        "--no-deprecation",
+        "--no-warnings",
+        "--disallow-code-generation-from-strings",
+        "{src}",
+    ],
+    env: &[
+        ("NODE_ENV", "production"),
+        ("NODE_OPTIONS", "--max-old-space-size=256"),
+    ],
+    inherit_env: &[],
+    version: &["node", "--version"],
+};
+
+pub const DENO: LanguageProfile = LanguageProfile {
+    name: "deno",
+    description: "Execute JavaScript code with Deno",
+    extension: "js",
+    compile: None,
+    run: &["deno", "run", "--no-prompt", "{flags}", "{src}"],
+    env: &[("DENO_DIR", "{dir}/.deno")],
+    inherit_env: &[],
+    version: &["deno", "--version"],
+};
+
+pub const DENO_TYPESCRIPT: LanguageProfile = LanguageProfile {
+    name: "typescript",
+    description: "Execute TypeScript code with Deno",
+    extension: "ts",
+    compile: None,
+    run: &["deno", "run", "--no-prompt", "--check", "{flags}", "{src}"],
+    env: &[("DENO_DIR", "{dir}/.deno")],
+    inherit_env: &[],
+    version: &["deno", "--version"],
+};
+
+pub const RUST: LanguageProfile = LanguageProfile {
+    name: "rust",
+    description: "Compile and run a Rust snippet in a secure sandbox",
+    extension: "rs",
+    compile: Some(&["rustc", "--edition", "2021", "-o", "{bin}", "{src}"]),
+    run: &["{bin}"],
+    env: &[],
+    // rustup proxies locate their toolchains through these
+    inherit_env: &["HOME", "RUSTUP_HOME", "CARGO_HOME", "RUSTUP_TOOLCHAIN"],
+    version: &["rustc", "--version"],
+};
+
+pub const GO: LanguageProfile = LanguageProfile {
+    name: "go",
+    description: "Compile and run a Go snippet in a secure sandbox",
+    extension: "go",
+    compile: Some(&["go", "build", "-o", "{bin}", "{src}"]),
+    run: &["{bin}"],
+    env: &[
+        ("GOCACHE", "{dir}/.gocache"),
+        ("GOPATH", "{dir}/.gopath"),
+        ("GO111MODULE", "off"),
+        ("CGO_ENABLED", "0"),
+    ],
+    inherit_env: &["GOROOT"],
+    version: &["go", "version"],
+};
+
+pub const RUBY: LanguageProfile = LanguageProfile {
+    name: "ruby",
+    description: "Execute Ruby code in a secure sandbox",
+    extension: "rb",
+    compile: None,
+    run: &["ruby", "--disable-gems", "{src}"],
+    env: &[],
+    inherit_env: &[],
+    version: &["ruby", "--version"],
+};
+
+pub const SHELL: LanguageProfile = LanguageProfile {
+    name: "shell",
+    description: "Execute a POSIX shell script in a secure sandbox",
+    extension: "sh",
+    compile: None,
+    run: &["sh", "{src}"],
+    env: &[],
+    inherit_env: &[],
+    version: &["sh", "-c", "exit 0"],
+};
+
+/// Profiles exposed as standalone [`RunnerTool`]s.
+pub const EXTENDED_LANGUAGES: &[&LanguageProfile] = &[&RUST, &GO, &RUBY, &SHELL];
+
+/// A snippet written into its own temporary directory.
+#[derive(Debug)]
+pub struct Sandbox {
+    dir: TempDir,
+    script: PathBuf,
+}
+
+impl Sandbox {
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn script(&self) -> &Path {
+        &self.script
+    }
+
+    fn binary(&self) -> PathBuf {
+        self.dir.path().join("snippet.bin")
+    }
+
+    /// Whether `path` was created by the runner rather than the snippet.
+    pub fn is_runner_file(&self, path: &Path) -> bool {
+        path == self.script || path == self.binary()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RunRequest<'a> {
+    pub code: &'a str,
+    pub args: &'a [String],
+    pub stdin: Option<&'a str>,
+    pub timeout: Duration,
+    /// Substituted for `{flags}` in the run template
+    pub flags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunOutput {
+    pub success: bool,
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+    /// "compile" when the compile step failed, otherwise "run"
+    pub stage: &'static str,
+    pub duration_ms: u64,
+}
+
+impl RunOutput {
+    fn from_output(output: &Output, stage: &'static str, started: Instant) -> Self {
+        Self {
+            success: output.status.success(),
+            exit_code: output.status.code().unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            stage,
+            duration_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CodeRunner {
+    profile: &'static LanguageProfile,
+}
+
+impl CodeRunner {
+    pub fn new(profile: &'static LanguageProfile) -> Self {
+        Self { profile }
+    }
+
+    pub fn profile(&self) -> &'static LanguageProfile {
+        self.profile
+    }
+
+    /// Whether the profile's toolchain is installed.
+    pub async fn is_available(&self) -> bool {
+        let Some((program, args)) = self.profile.version.split_first() else {
+            return false;
+        };
+        let mut cmd = Command::new(program);
+        cmd.args(args).stdout(Stdio::null()).stderr(Stdio::null());
+        matches!(cmd.status().await, Ok(status) if status.success())
+    }
+
+    /// Create a sandbox directory and write the snippet into it.
+    pub async fn prepare(&self, code: &str) -> Result<Sandbox, ToolError> {
+        self.prepare_named(&format!("script.{}", self.profile.extension), code)
+            .await
+    }
+
+    pub async fn prepare_named(&self, file_name: &str, code: &str) -> Result<Sandbox, ToolError> {
+        let dir = TempDir::new()
+            .map_err(|e| ToolError::Execution(format!("Failed to create temp dir: {}", e)))?;
+        let script = dir.path().join(file_name);
+        fs::write(&script, code)
+            .await
+            .map_err(|e| ToolError::Execution(format!("Failed to write script: {}", e)))?;
+        Ok(Sandbox { dir, script })
+    }
+
+    /// Build the restricted command for a template.
+    pub fn command(&self, template: &[&str], sandbox: &Sandbox, flags: &[String]) -> Command {
+        let mut argv = Vec::new();
+        for part in template {
+            if *part == "{flags}" {
+                argv.extend(flags.iter().cloned());
+            } else {
+                argv.push(expand(part, sandbox));
+            }
+        }
+
+        // Resolve the program before PATH is narrowed below
+        let program = resolve_program(&argv[0]);
+        debug!("Sandboxed {} command: {:?}", self.profile.name, argv);
+
+        let mut cmd = Command::new(program);
+        cmd.args(&argv[1..]);
+        cmd.current_dir(sandbox.path());
+        cmd.stdin(Stdio::piped());
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+        cmd.kill_on_drop(true);
+
+        // Environment restrictions
+        cmd.env_clear();
+        cmd.env("HOME", "/tmp");
+        cmd.env("TMPDIR", "/tmp");
+        cmd.env("PATH", "/usr/bin:/bin");
+        for name in self.profile.inherit_env {
+            if let Ok(value) = std::env::var(name) {
+                cmd.env(name, value);
+            }
+        }
+        for (name, value) in self.profile.env {
+            cmd.env(name, expand(value, sandbox));
+        }
+
+        cmd
+    }
+
+    /// Confine a command built by [`Self::command`].
+    ///
+    /// Uses the namespace jail when a sandbox policy is active, otherwise a
+    /// systemd scope when running as root. Skipped in tests or when
+    /// `PCODE_NO_SANDBOX` is set.
+    pub async fn apply_sandbox(&self, cmd: &mut Command, sandbox: &Sandbox) {
+        // Skip platform-specific sandboxing in tests or when it would require privileges
+        if cfg!(test) || std::env::var("PCODE_NO_SANDBOX").is_ok() {
+            return;
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            // Prefer the namespace jail; it needs no privileges
+            if let Some(jail) = crate::security::jail::NamespaceJail::for_active_policy() {
+                if jail.with_writable(sandbox.path()).apply(cmd) {
+                    return;
+                }
+            }
+
+            // Only use systemd-run if we're running as root (which we shouldn't be)
+            let is_root = unsafe { libc::geteuid() } == 0;
+            if is_root && is_systemd_available().await {
+                *cmd = wrap_in_systemd_scope(cmd);
+            }
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = (cmd, sandbox);
+    }
+
+    /// Compile (when the profile has a compile step) and run the snippet.
+    pub async fn run(&self, request: &RunRequest<'_>) -> Result<RunOutput, ToolError> {
+        let sandbox = self.prepare(request.code).await?;
+        self.run_in(&sandbox, request).await
+    }
+
+    pub async fn run_in(
+        &self,
+        sandbox: &Sandbox,
+        request: &RunRequest<'_>,
+    ) -> Result<RunOutput, ToolError> {
+        let started = Instant::now();
+
+        if let Some(compile) = self.profile.compile {
+            let mut cmd = self.command(compile, sandbox, &[]);
+            self.apply_sandbox(&mut cmd, sandbox).await;
+            let output = run_with_timeout(cmd, None, request.timeout).await?;
+            if !output.status.success() {
+                return Ok(RunOutput::from_output(&output, "compile", started));
+            }
+        }
+
+        let remaining = request.timeout.saturating_sub(started.elapsed());
+        let mut cmd = self.command(self.profile.run, sandbox, &request.flags);
+        cmd.args(request.args);
+        self.apply_sandbox(&mut cmd, sandbox).await;
+
+        let output = run_with_timeout(cmd, request.stdin, remaining).await?;
+        Ok(RunOutput::from_output(&output, "run", started))
+    }
+}
+
+/// Run a command to completion, feeding `stdin` and enforcing `limit`.
+pub async fn run_with_timeout(
+    mut cmd: Command,
+    stdin: Option<&str>,
+    limit: Duration,
+) -> Result<Output, ToolError> {
+    let execution = async {
+        let mut child = cmd.spawn()?;
+        if let Some(mut pipe) = child.stdin.take() {
+            if let Some(input) = stdin {
+                pipe.write_all(input.as_bytes()).await?;
+            }
+            // Dropping the pipe signals EOF
+        }
+        child.wait_with_output().await
+    };
+
+    match timeout(limit, execution).await {
+        Ok(Ok(output)) => Ok(output),
+        Ok(Err(e)) => Err(ToolError::Execution(format!("Process error: {}", e))),
+        Err(_) => Err(ToolError::Execution(format!(
+            "Execution timeout ({}ms)",
+            limit.as_millis()
+        ))),
+    }
+}
+
+#[cfg(target_os = "linux")]
+async fn is_systemd_available() -> bool {
+    match Command::new("systemd-run").arg("--version").output().await {
+        Ok(output) if output.status.success() => {
+            let version_str = String::from_utf8_lossy(&output.stdout);
+            debug!("systemd-run version: {}", version_str);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Re-create a command inside a resource-limited systemd scope, keeping its
+/// arguments, environment, working directory and pipes.
+#[cfg(target_os = "linux")]
+fn wrap_in_systemd_scope(cmd: &Command) -> Command {
+    let inner = cmd.as_std();
+    let mut scoped = Command::new("systemd-run");
+    scoped.args([
+        "--scope",
+        "--quiet",
+        "--property=MemoryMax=256M",
+        "--property=CPUQuota=50%",
+        "--",
+    ]);
+    scoped.arg(inner.get_program());
+    scoped.args(inner.get_args());
+
+    scoped.env_clear();
+    for (name, value) in inner.get_envs() {
+        if let Some(value) = value {
+            scoped.env(name, value);
+        }
+    }
+    if let Some(dir) = inner.get_current_dir() {
+        scoped.current_dir(dir);
+    }
+    scoped.stdin(Stdio::piped());
+    scoped.stdout(Stdio::piped());
+    scoped.stderr(Stdio::piped());
+    scoped.kill_on_drop(true);
+    scoped
+}
+
+fn expand(template: &str, sandbox: &Sandbox) -> String {
+    template
+        .replace("{src}", &sandbox.script().to_string_lossy())
+        .replace("{bin}", &sandbox.binary().to_string_lossy())
+        .replace("{dir}", &sandbox.path().to_string_lossy())
+}
+
+/// Locate a program on pcode's own PATH, falling back to the bare name.
+pub fn resolve_program(program: &str) -> PathBuf {
+    if program.contains('/') {
+        return PathBuf::from(program);
+    }
+    std::env::var_os("PATH")
+        .and_then(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(program))
+                .find(|candidate| candidate.is_file())
+        })
+        .unwrap_or_else(|| PathBuf::from(program))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RunnerParams {
+    code: String,
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+    #[serde(default)]
+    stdin: Option<String>,
+    #[serde(default)]
+    args: Option<Vec<String>>,
+}
+
+/// Tool running snippets for a single [`LanguageProfile`].
+#[derive(Debug)]
+pub struct RunnerTool {
+    runner: CodeRunner,
+}
+
+impl RunnerTool {
+    pub fn new(profile: &'static LanguageProfile) -> Self {
+        Self {
+            runner: CodeRunner::new(profile),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for RunnerTool {
+    fn name(&self) -> &str {
+        self.runner.profile().name
+    }
+
+    fn description(&self) -> &str {
+        self.runner.profile().description
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: RunnerParams =
+            serde_json::from_value(params).map_err(|e| ToolError::InvalidParams(e.to_string()))?;
+        let profile = self.runner.profile();
+
+        info!("Executing {} code in sandbox", profile.name);
+
+        if params.code.trim().is_empty() {
+            return Err(ToolError::InvalidParams("Code cannot be empty".to_string()));
+        }
+
+        if !self.runner.is_available().await {
+            return Err(ToolError::Execution(format!(
+                "{} toolchain not found ({} unavailable)",
+                profile.name, profile.version[0]
+            )));
+        }
+
+        let args = params.args.unwrap_or_default();
+        let request = RunRequest {
+            code: &params.code,
+            args: &args,
+            stdin: params.stdin.as_deref(),
+            timeout: Duration::from_millis(params.timeout_ms.unwrap_or(30000)),
+            flags: Vec::new(),
+        };
+        let output = self.runner.run(&request).await?;
+
+        let mut result = serde_json::to_value(&output)
+            .map_err(|e| ToolError::Execution(format!("Failed to encode result: {}", e)))?;
+        result["language"] = Value::from(profile.name);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(code: &str) -> RunRequest<'_> {
+        RunRequest {
+            code,
+            timeout: Duration::from_secs(60),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_template_expansion() {
+        let runner = CodeRunner::new(&DENO);
+        let sandbox = runner.prepare("console.log(1)").await.unwrap();
+        let flags = vec!["--allow-read=/a".to_string(), "--allow-net".to_string()];
+
+        let cmd = runner.command(DENO.run, &sandbox, &flags);
+        let args: Vec<String> = cmd
+            .as_std()
+            .get_args()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            args,
+            vec![
+                "run".to_string(),
+                "--no-prompt".to_string(),
+                "--allow-read=/a".to_string(),
+                "--allow-net".to_string(),
+                sandbox.script().to_string_lossy().to_string(),
+            ]
+        );
+        assert!(sandbox.script().ends_with("script.js"));
+    }
+
+    #[tokio::test]
+    async fn test_shell_runner_stdin_and_args() {
+        let runner = CodeRunner::new(&SHELL);
+        let args = vec!["first".to_string()];
+        let request = RunRequest {
+            args: &args,
+            stdin: Some("from stdin\n"),
+            ..request("read line; echo \"$1 $line\"")
+        };
+
+        let output = runner.run(&request).await.unwrap();
+        assert!(output.success);
+        assert_eq!(output.stdout.trim(), "first from stdin");
+        assert_eq!(output.stage, "run");
+    }
+
+    #[tokio::test]
+    async fn test_rust_runner_compile_error() {
+        let runner = CodeRunner::new(&RUST);
+        if !runner.is_available().await {
+            return;
+        }
+
+        let output = runner
+            .run(&request("fn main() { let x: u8 = \"a\"; }"))
+            .await
+            .unwrap();
+        assert!(!output.success);
+        assert_eq!(output.stage, "compile");
+        assert!(output.stderr.contains("mismatched types"));
+
+        let output = runner
+            .run(&request("fn main() { println!(\"{}\", 6 * 7); }"))
+            .await
+            .unwrap();
+        assert!(output.success, "{}", output.stderr);
+        assert_eq!(output.stdout.trim(), "42");
+    }
+
+    #[tokio::test]
+    async fn test_runner_timeout() {
+        let runner = CodeRunner::new(&SHELL);
+        let request = RunRequest {
+            timeout: Duration::from_millis(100),
+            ..request("sleep 5")
+        };
+
+        let result = runner.run(&request).await;
+        assert!(matches!(result, Err(ToolError::Execution(msg)) if msg.contains("timeout")));
+    }
+
+    #[tokio::test]
+    async fn test_runner_tool_metadata() {
+        for profile in EXTENDED_LANGUAGES {
+            let tool = RunnerTool::new(profile);
+            assert_eq!(tool.name(), profile.name);
+            assert!(tool
+                .execute(serde_json::json!({ "code": " " }))
+                .await
+                .is_err());
+        }
+    }
+}
@@ -0,0 +1,522 @@
+//! Workspace symbol index under `.pcode/index/`.
+//!
+//! Each indexed file keeps its modification time, size and SHA-256 next to
+//! the symbols extracted from it and the identifiers it uses. An update
+//! re-reads only files whose time or size changed, and re-extracts only those
+//! whose hash changed too, so refreshing an unchanged workspace costs one
+//! `stat` per file.
+
+use crate::analysis::lexer::{segments, SegmentKind, Syntax};
+use crate::analysis::symbols::{self, Symbol, SymbolKind};
+use crate::analysis::{display_path, source_files};
+use crate::tools::edit;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::{debug, warn};
+
+/// Index location, relative to the workspace root.
+pub const INDEX_DIR: &str = ".pcode/index";
+const INDEX_FILE: &str = "symbols.json";
+/// Bumped when extraction changes, discarding indexes built before.
+const FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    /// Modification time in nanoseconds since the Unix epoch
+    pub mtime: u64,
+    pub size: u64,
+    /// SHA-256 of the contents, hex encoded
+    pub hash: String,
+    pub symbols: Vec<Symbol>,
+    /// Distinct identifiers used outside comments and strings, sorted
+    pub identifiers: Vec<String>,
+    /// Why extraction failed; the file is retried once it changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UpdateStats {
+    pub files: usize,
+    pub symbols: usize,
+    /// Files whose symbols were (re-)extracted
+    pub indexed: usize,
+    pub removed: usize,
+    /// Files with a new mtime but the same contents
+    #[serde(skip)]
+    touched: usize,
+}
+
+impl UpdateStats {
+    /// Whether the index differs from what was loaded.
+    pub fn changed(&self) -> bool {
+        self.indexed > 0 || self.removed > 0 || self.touched > 0
+    }
+}
+
+/// A symbol and where it is declared.
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolMatch {
+    pub file: String,
+    pub qualified_name: String,
+    #[serde(flatten)]
+    pub symbol: Symbol,
+    /// Fuzzy match score, higher is better
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<u32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SymbolIndex {
+    version: u32,
+    /// Entries by workspace-relative path
+    pub files: BTreeMap<String, FileEntry>,
+}
+
+impl SymbolIndex {
+    /// The stored index, or an empty one if there is none or it was built
+    /// by an incompatible version.
+    pub fn load(workspace: &Path) -> Self {
+        let path = workspace.join(INDEX_DIR).join(INDEX_FILE);
+        let stored = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Self>(&text).ok());
+        match stored {
+            Some(index) if index.version == FORMAT_VERSION => index,
+            _ => Self {
+                version: FORMAT_VERSION,
+                files: BTreeMap::new(),
+            },
+        }
+    }
+
+    pub fn save(&self, workspace: &Path) -> std::io::Result<()> {
+        let dir = workspace.join(INDEX_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let text = serde_json::to_string(self)?;
+        edit::write_atomic(&dir.join(INDEX_FILE), &text)
+    }
+
+    /// Load the index, bring it up to date with the workspace and store it
+    /// again if anything changed. Failing to store it is logged, not fatal.
+    pub fn open(workspace: &Path) -> (Self, UpdateStats) {
+        let mut index = Self::load(workspace);
+        let stats = index.update(workspace);
+        if stats.changed() {
+            if let Err(e) = index.save(workspace) {
+                warn!("Failed to save symbol index: {}", e);
+            }
+        }
+        (index, stats)
+    }
+
+    /// Re-index `paths` in the stored index even if their time and size look
+    /// unchanged, as when a watcher saw them change. Does nothing, and
+    /// returns `None`, when no index is stored yet.
+    pub fn refresh(workspace: &Path, paths: &[PathBuf]) -> Option<UpdateStats> {
+        if !workspace.join(INDEX_DIR).join(INDEX_FILE).is_file() {
+            return None;
+        }
+        let mut index = Self::load(workspace);
+        for path in paths {
+            let file = display_path(workspace, &workspace.join(path));
+            if let Some(entry) = index.files.get_mut(&file) {
+                entry.mtime = 0;
+            }
+        }
+        let stats = index.update_paths(workspace, paths);
+        if stats.changed() {
+            if let Err(e) = index.save(workspace) {
+                warn!("Failed to save symbol index: {}", e);
+            }
+        }
+        Some(stats)
+    }
+
+    /// Re-index every changed source file and drop deleted ones.
+    pub fn update(&mut self, workspace: &Path) -> UpdateStats {
+        let paths = source_files(workspace, symbols::EXTENSIONS);
+        let mut stats = self.update_paths(workspace, &paths);
+        let present: std::collections::HashSet<String> =
+            paths.iter().map(|p| display_path(workspace, p)).collect();
+        let before = self.files.len();
+        self.files.retain(|file, _| present.contains(file));
+        stats.removed += before - self.files.len();
+        stats.files = self.files.len();
+        stats.symbols = self.symbol_count();
+        stats
+    }
+
+    /// Re-index just `paths` (absolute or workspace-relative), dropping
+    /// those that no longer exist.
+    pub fn update_paths(&mut self, workspace: &Path, paths: &[PathBuf]) -> UpdateStats {
+        let mut stats = UpdateStats::default();
+        for path in paths {
+            let path = workspace.join(path);
+            let file = display_path(workspace, &path);
+            let indexable = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| symbols::EXTENSIONS.contains(&e));
+            let Some(metadata) = std::fs::metadata(&path).ok().filter(|m| m.is_file()) else {
+                stats.removed += usize::from(self.files.remove(&file).is_some());
+                continue;
+            };
+            if !indexable {
+                continue;
+            }
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos() as u64);
+            let size = metadata.len();
+            let entry = self.files.get(&file);
+            if entry.is_some_and(|e| e.mtime == mtime && e.size == size) {
+                continue;
+            }
+
+            let Ok(source) = std::fs::read_to_string(&path) else {
+                stats.removed += usize::from(self.files.remove(&file).is_some());
+                continue;
+            };
+            let hash = hex::encode(Sha256::digest(source.as_bytes()));
+            if let Some(entry) = self.files.get_mut(&file).filter(|e| e.hash == hash) {
+                // Touched but not modified
+                entry.mtime = mtime;
+                entry.size = size;
+                stats.touched += 1;
+                continue;
+            }
+
+            debug!("Indexing symbols in {}", file);
+            let (symbols, error) = match symbols::extract(&path, &source) {
+                Ok(symbols) => (symbols, None),
+                Err(error) => (Vec::new(), Some(error)),
+            };
+            let identifiers = Syntax::from_path(&path)
+                .map(|syntax| identifiers(&source, syntax))
+                .unwrap_or_default();
+            self.files.insert(
+                file,
+                FileEntry {
+                    mtime,
+                    size,
+                    hash,
+                    symbols,
+                    identifiers,
+                    error,
+                },
+            );
+            stats.indexed += 1;
+        }
+        stats.files = self.files.len();
+        stats.symbols = self.symbol_count();
+        stats
+    }
+
+    pub fn symbol_count(&self) -> usize {
+        self.files.values().map(|e| e.symbols.len()).sum()
+    }
+
+    fn matches(&self) -> impl Iterator<Item = SymbolMatch> + '_ {
+        self.files.iter().flat_map(|(file, entry)| {
+            let syntax = Syntax::from_path(Path::new(file)).unwrap_or(Syntax::Rust);
+            entry.symbols.iter().map(move |symbol| SymbolMatch {
+                file: file.clone(),
+                qualified_name: symbol.qualified_name(syntax),
+                symbol: symbol.clone(),
+                score: None,
+            })
+        })
+    }
+
+    /// Symbols named exactly `name`, or whose qualified name is or ends
+    /// with it when it contains `::` or `.`. Falls back to a
+    /// case-insensitive comparison when nothing matches exactly.
+    pub fn lookup(&self, name: &str, kind: Option<SymbolKind>) -> Vec<SymbolMatch> {
+        let qualified = name.contains("::") || name.contains('.');
+        let matches = |m: &SymbolMatch, eq: &dyn Fn(&str, &str) -> bool| {
+            kind.is_none_or(|k| m.symbol.kind == k)
+                && if qualified {
+                    // The split may fall inside a multi-byte character
+                    let split = m.qualified_name.len().checked_sub(name.len());
+                    eq(&m.qualified_name, name)
+                        || split
+                            .and_then(|split| m.qualified_name.split_at_checked(split))
+                            .is_some_and(|(owner, tail)| {
+                                owner.ends_with([':', '.']) && eq(tail, name)
+                            })
+                } else {
+                    eq(&m.symbol.name, name)
+                }
+        };
+        let exact: Vec<SymbolMatch> = self
+            .matches()
+            .filter(|m| matches(m, &|a, b| a == b))
+            .collect();
+        if !exact.is_empty() {
+            return exact;
+        }
+        self.matches()
+            .filter(|m| matches(m, &|a, b| a.eq_ignore_ascii_case(b)))
+            .collect()
+    }
+
+    /// The `limit` best fuzzy matches for `query`, best first.
+    pub fn search(&self, query: &str, kind: Option<SymbolKind>, limit: usize) -> Vec<SymbolMatch> {
+        let mut found: Vec<SymbolMatch> = self
+            .matches()
+            .filter(|m| kind.is_none_or(|k| m.symbol.kind == k))
+            .filter_map(|mut m| {
+                let score = fuzzy_score(query, &m.symbol.name)
+                    .max(fuzzy_score(query, &m.qualified_name).map(|s| s.saturating_sub(1)))?;
+                m.score = Some(score);
+                Some(m)
+            })
+            .collect();
+        found.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.qualified_name.len().cmp(&b.qualified_name.len()))
+                .then(a.file.cmp(&b.file))
+                .then(a.symbol.line.cmp(&b.symbol.line))
+        });
+        found.truncate(limit);
+        found
+    }
+
+    /// Symbols of one file in declaration order.
+    pub fn outline(&self, file: &str) -> Option<&FileEntry> {
+        self.files.get(file)
+    }
+}
+
+/// Distinct identifiers used in `source`, outside comments and string
+/// literals, in sorted order.
+fn identifiers(source: &str, syntax: Syntax) -> Vec<String> {
+    let words: BTreeSet<&str> = segments(source, syntax)
+        .into_iter()
+        .filter(|s| s.kind == SegmentKind::Code)
+        .flat_map(|s| {
+            source[s.start..s.end].split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        })
+        .filter(|word| !word.is_empty())
+        .collect();
+    words.into_iter().map(str::to_string).collect()
+}
+
+/// How well `query` matches `candidate`, ignoring case: whole name, then
+/// prefix, then substring, then the query's characters in order with a
+/// bonus for each landing at the start of a word.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<u32> {
+    let query = query.to_lowercase();
+    let lower = candidate.to_lowercase();
+    if query.is_empty() {
+        return None;
+    }
+    if lower == query {
+        return Some(1000);
+    }
+    if lower.starts_with(&query) {
+        return Some(
+            800u32
+                .saturating_sub((lower.len() - query.len()) as u32)
+                .max(601),
+        );
+    }
+    if let Some(position) = lower.find(&query) {
+        return Some(600u32.saturating_sub(position as u32).max(401));
+    }
+
+    // Lowercasing can expand a character ('İ' becomes two), so pair each
+    // lowercase character with the one it came from
+    let chars: Vec<(char, char)> = candidate
+        .chars()
+        .flat_map(|c| c.to_lowercase().map(move |lower| (c, lower)))
+        .collect();
+    let mut score = 300u32;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for q in query.chars() {
+        let offset = chars[next..].iter().position(|&(_, c)| c == q)?;
+        let at = next + offset;
+        let word_start = at == 0
+            || !chars[at - 1].0.is_alphanumeric()
+            || (chars[at].0.is_uppercase() && chars[at - 1].0.is_lowercase());
+        if word_start {
+            score += 10;
+        }
+        if let Some(p) = previous.filter(|&p| at > p + 1) {
+            score = score.saturating_sub(2 * (at - p - 1) as u32);
+        }
+        previous = Some(at);
+        next = at + 1;
+    }
+    Some(score.clamp(1, 400))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, file: &str, content: &str) {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(root, "src/lib.rs", "pub struct SecurityContext;\n");
+        write(
+            root,
+            "app.py",
+            "def main():\n    # SecurityContext\n    print(\"done\")\n",
+        );
+        write(root, "broken.rs", "fn (");
+
+        let (index, stats) = SymbolIndex::open(root);
+        assert_eq!((stats.files, stats.indexed, stats.symbols), (3, 3, 2));
+        assert!(index.files["broken.rs"].error.is_some());
+        assert_eq!(index.files["broken.rs"].identifiers, ["fn"]);
+        assert_eq!(index.files["app.py"].identifiers, ["def", "main", "print"]);
+        assert!(root.join(INDEX_DIR).join(INDEX_FILE).is_file());
+
+        let (_, stats) = SymbolIndex::open(root);
+        assert!(!stats.changed());
+
+        // Same contents with a new mtime are not re-extracted
+        let file = std::fs::File::options()
+            .write(true)
+            .open(root.join("app.py"))
+            .unwrap();
+        file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(5))
+            .unwrap();
+        let (_, stats) = SymbolIndex::open(root);
+        assert_eq!((stats.indexed, stats.touched), (0, 1));
+        let (_, stats) = SymbolIndex::open(root);
+        assert!(!stats.changed());
+
+        write(
+            root,
+            "src/lib.rs",
+            "pub struct SecurityContext;\npub fn check() {}\n",
+        );
+        std::fs::remove_file(root.join("app.py")).unwrap();
+        let (index, stats) = SymbolIndex::open(root);
+        assert_eq!((stats.indexed, stats.removed, stats.symbols), (1, 1, 2));
+        assert_eq!(
+            index.outline("src/lib.rs").unwrap().symbols[1].name,
+            "check"
+        );
+
+        let mut index = SymbolIndex::load(root);
+        write(root, "new.rs", "fn added() {}\n");
+        let stats = index.update_paths(root, &[PathBuf::from("new.rs")]);
+        assert_eq!((stats.indexed, stats.files), (1, 3));
+    }
+
+    #[test]
+    fn test_refresh_catches_same_size_edit() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let paths = [PathBuf::from("lib.rs")];
+        write(root, "lib.rs", "fn alpha() {}\n");
+        assert!(SymbolIndex::refresh(root, &paths).is_none());
+
+        SymbolIndex::open(root);
+        let modified = std::fs::metadata(root.join("lib.rs"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        // An editor writing the same number of bytes within the mtime
+        // granularity leaves time and size as they were
+        write(root, "lib.rs", "fn gamma() {}\n");
+        let file = std::fs::File::options()
+            .write(true)
+            .open(root.join("lib.rs"))
+            .unwrap();
+        file.set_modified(modified).unwrap();
+        let (_, stats) = SymbolIndex::open(root);
+        assert!(!stats.changed());
+
+        let stats = SymbolIndex::refresh(root, &paths).unwrap();
+        assert_eq!(stats.indexed, 1);
+        assert_eq!(
+            SymbolIndex::load(root).outline("lib.rs").unwrap().symbols[0].name,
+            "gamma"
+        );
+    }
+
+    #[test]
+    fn test_lookup_and_search() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "src/registry.rs",
+            "pub struct ToolRegistry;\nimpl ToolRegistry {\n    pub async fn execute(&self) {}\n    fn execute_all(&self) {}\n}\nfn execute() {}\n",
+        );
+        write(
+            root,
+            "web/app.js",
+            "class ToolRegistry {\n  execute() {}\n}\n",
+        );
+        let (index, _) = SymbolIndex::open(root);
+
+        let found = index.lookup("ToolRegistry::execute", None);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            (found[0].file.as_str(), found[0].symbol.line),
+            ("src/registry.rs", 3)
+        );
+        assert_eq!(
+            index.lookup("ToolRegistry.execute", None)[0].file,
+            "web/app.js"
+        );
+        assert_eq!(index.lookup("execute", None).len(), 3);
+        assert_eq!(index.lookup("execute", Some(SymbolKind::Function)).len(), 1);
+        assert_eq!(
+            index.lookup("toolregistry", Some(SymbolKind::Struct)).len(),
+            1
+        );
+        assert!(index.lookup("Registry::execute", None).is_empty());
+
+        write(
+            root,
+            "cafe.py",
+            "class Café:\n    def x(self):\n        pass\n",
+        );
+        let (index, _) = SymbolIndex::open(root);
+        assert_eq!(index.lookup("Café.x", None).len(), 1);
+        // "a.x" ends inside the "é" of "Café.x"
+        assert!(index.lookup("a.x", None).is_empty());
+
+        let found = index.search("toolreg", None, 10);
+        assert_eq!(found[0].symbol.name, "ToolRegistry");
+        let found = index.search("exal", None, 10);
+        assert_eq!(found[0].symbol.name, "execute_all");
+        assert!(index.search("zzz", None, 10).is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_score_order() {
+        let exact = fuzzy_score("execute", "execute").unwrap();
+        let prefix = fuzzy_score("exec", "execute").unwrap();
+        let substring = fuzzy_score("cute", "execute").unwrap();
+        let boundaries = fuzzy_score("trg", "ToolRegistry").unwrap();
+        let scattered = fuzzy_score("trg", "toolregistry").unwrap();
+        assert!(exact > prefix && prefix > substring && substring > boundaries);
+        assert!(boundaries > scattered);
+        assert_eq!(fuzzy_score("xyz", "execute"), None);
+        // Lowercase "İ" is two characters
+        assert_eq!(fuzzy_score("ab", "İİa_b"), Some(308));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::analysis::symbols::SymbolKind;
+use crate::tools::symbol_index::SymbolIndex;
+use crate::tools::{Tool, ToolError};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Component, Path, PathBuf};
+use tracing::info;
+
+/// Search results returned unless `limit` says otherwise.
+const DEFAULT_LIMIT: usize = 20;
+const ACTIONS: &[&str] = &["lookup", "search", "outline", "update"];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SymbolsParams {
+    /// "lookup", "search", "outline" or "update"
+    #[serde(default = "default_action")]
+    action: String,
+    /// Symbol name for lookup (optionally `Type::name` or `Class.name`),
+    /// or the text to fuzzy match for search
+    #[serde(default)]
+    query: Option<String>,
+    /// File to outline
+    #[serde(default)]
+    path: Option<String>,
+    /// Only symbols of this kind, e.g. "struct" or "method"
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+fn default_action() -> String {
+    "search".to_string()
+}
+
+#[derive(Debug)]
+pub struct SymbolsTool {
+    workspace: PathBuf,
+}
+
+impl SymbolsTool {
+    pub fn new() -> Self {
+        Self {
+            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        }
+    }
+}
+
+impl Default for SymbolsTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn required<'a>(value: &'a Option<String>, name: &str, action: &str) -> Result<&'a str, ToolError> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| ToolError::InvalidParams(format!("{} requires {}", action, name)))
+}
+
+/// `path` as the index keys it: relative to the workspace, `/`-separated.
+fn index_key(path: &str) -> Result<String, ToolError> {
+    let path = Path::new(path.trim());
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::CurDir => {}
+            _ => {
+                return Err(ToolError::InvalidParams(
+                    "Path must be within workspace".to_string(),
+                ))
+            }
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+#[async_trait]
+impl Tool for SymbolsTool {
+    fn name(&self) -> &str {
+        "symbols"
+    }
+
+    fn description(&self) -> &str {
+        "Find functions, types, traits, impls, classes and methods in Rust, Python and JS/TS: lookup by name, fuzzy search or per-file outline, from an index kept up to date incrementally"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: SymbolsParams =
+            serde_json::from_value(params).map_err(|e| ToolError::InvalidParams(e.to_string()))?;
+        if !ACTIONS.contains(&params.action.as_str()) {
+            return Err(ToolError::InvalidParams(format!(
+                "Unknown action: {}. Use: {}",
+                params.action,
+                ACTIONS.join(", ")
+            )));
+        }
+        let kind = match params.kind.as_deref() {
+            Some(kind) => Some(SymbolKind::parse(kind).ok_or_else(|| {
+                ToolError::InvalidParams(format!("Unknown symbol kind: {}", kind))
+            })?),
+            None => None,
+        };
+        let query = match params.action.as_str() {
+            "lookup" | "search" => Some(required(&params.query, "query", &params.action)?),
+            _ => None,
+        };
+        let file = match params.action.as_str() {
+            "outline" => Some(index_key(required(&params.path, "path", "outline")?)?),
+            _ => None,
+        };
+
+        let workspace = self.workspace.clone();
+        let (index, stats) = tokio::task::spawn_blocking(move || SymbolIndex::open(&workspace))
+            .await
+            .map_err(|e| ToolError::Execution(e.to_string()))?;
+        info!(
+            "Symbol index: {} files, {} symbols, {} re-indexed",
+            stats.files, stats.symbols, stats.indexed
+        );
+
+        let mut result = json!({ "index": stats });
+        match (params.action.as_str(), query, file) {
+            ("lookup", Some(query), _) => {
+                result["symbols"] = json!(index.lookup(query, kind));
+            }
+            ("search", Some(query), _) => {
+                let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+                result["symbols"] = json!(index.search(query, kind, limit));
+            }
+            ("outline", _, Some(file)) => {
+                let entry = index.outline(&file).ok_or_else(|| {
+                    ToolError::NotFound(format!("No indexed source file: {}", file))
+                })?;
+                let symbols: Vec<_> = entry
+                    .symbols
+                    .iter()
+                    .filter(|s| kind.is_none_or(|k| s.kind == k))
+                    .collect();
+                result["file"] = json!(file);
+                result["symbols"] = json!(symbols);
+                if let Some(error) = &entry.error {
+                    result["error"] = json!(error);
+                }
+            }
+            _ => {}
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn tool(dir: &TempDir) -> SymbolsTool {
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(
+            src.join("security.rs"),
+            "pub struct SecurityContext;\nimpl SecurityContext {\n    pub fn apply(&self) {}\n}\n",
+        )
+        .unwrap();
+        SymbolsTool {
+            workspace: dir.path().to_path_buf(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_symbols_actions() {
+        let dir = TempDir::new().unwrap();
+        let tool = tool(&dir);
+
+        let result = tool
+            .execute(json!({"action": "lookup", "query": "SecurityContext::apply"}))
+            .await
+            .unwrap();
+        assert_eq!(result["index"]["indexed"], 1);
+        assert_eq!(result["symbols"][0]["file"], "src/security.rs");
+        assert_eq!(result["symbols"][0]["line"], 3);
+        assert_eq!(result["symbols"][0]["kind"], "method");
+
+        let result = tool
+            .execute(json!({"query": "secctx", "kind": "struct"}))
+            .await
+            .unwrap();
+        assert_eq!(result["index"]["indexed"], 0);
+        assert_eq!(result["symbols"].as_array().unwrap().len(), 1);
+        assert_eq!(result["symbols"][0]["name"], "SecurityContext");
+
+        let result = tool
+            .execute(json!({"action": "outline", "path": "./src/security.rs"}))
+            .await
+            .unwrap();
+        let names: Vec<&str> = result["symbols"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["struct", "impl", "method"]);
+    }
+
+    #[tokio::test]
+    async fn test_symbols_invalid_params() {
+        let dir = TempDir::new().unwrap();
+        let tool = tool(&dir);
+
+        for params in [
+            json!({"action": "rename"}),
+            json!({"action": "lookup"}),
+            json!({"action": "outline"}),
+            json!({"action": "outline", "path": "../x.rs"}),
+            json!({"query": "x", "kind": "macro"}),
+        ] {
+            let err = tool.execute(params.clone()).await.unwrap_err();
+            assert!(matches!(err, ToolError::InvalidParams(_)), "{}", params);
+        }
+        let err = tool
+            .execute(json!({"action": "outline", "path": "src/missing.rs"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::NotFound(_)));
+    }
+}
@@ -0,0 +1,843 @@
+use crate::analysis::complexity;
+use crate::analysis::coverage_report::{self, CoverageReport};
+use crate::analysis::dependencies;
+use crate::tools::coverage::CoverageTool;
+use crate::tools::edit;
+use crate::tools::refactor::{extract_code, tail, test_filter, LlmProposer, Proposer};
+use crate::tools::{Tool, ToolError};
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use syn::spanned::Spanned;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+use tracing::{debug, info, warn};
+
+/// Functions given tests per run unless `max_functions` says otherwise.
+const DEFAULT_MAX_FUNCTIONS: usize = 5;
+/// Source shown to the model around the function under test.
+const MAX_CONTEXT_CHARS: usize = 12_000;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TestgenParams {
+    /// File or directory whose functions get tests; the whole crate by default
+    #[serde(default)]
+    path: Option<String>,
+    /// Existing LCOV or Cobertura report to start from instead of measuring
+    #[serde(default)]
+    report: Option<String>,
+    #[serde(default)]
+    max_functions: Option<usize>,
+}
+
+/// Measures line coverage of the crate in a directory.
+#[async_trait]
+trait CoverageSource: Send + Sync {
+    async fn measure(&self, dir: &Path) -> Result<CoverageReport, ToolError>;
+}
+
+struct BackendCoverage;
+
+#[async_trait]
+impl CoverageSource for BackendCoverage {
+    async fn measure(&self, dir: &Path) -> Result<CoverageReport, ToolError> {
+        CoverageTool::measure(dir).await
+    }
+}
+
+/// A function with instrumented lines, none of them executed.
+#[derive(Debug, Clone)]
+struct Target {
+    file: String,
+    function: String,
+    line: usize,
+    end_line: usize,
+    uncovered: usize,
+}
+
+/// Where the generated tests for a source file go.
+#[derive(Debug, Clone, PartialEq)]
+enum Placement {
+    /// Before `close`, the 1-based line of the closing brace of the test
+    /// module `name`
+    Module { close: usize, name: String },
+    /// A new `#[cfg(test)]` module at the end of the file
+    NewModule { name: &'static str },
+    /// The end of `tests/<target>.rs`
+    Integration { target: String },
+}
+
+/// Test functions from a reply, plus the `use` lines and helpers they share.
+#[derive(Debug, Default)]
+struct GeneratedTests {
+    support: Vec<String>,
+    tests: Vec<(String, String)>,
+}
+
+#[derive(Debug, Serialize)]
+struct Rejected {
+    name: String,
+    reason: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct FunctionResult {
+    file: String,
+    function: String,
+    line: usize,
+    /// "added", "no_gain", "none_passed" or "skipped"
+    status: &'static str,
+    /// File the tests were written to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    test_file: Option<String>,
+    kept: Vec<String>,
+    rejected: Vec<Rejected>,
+    lines_covered_before: usize,
+    lines_covered_after: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl FunctionResult {
+    fn new(target: &Target) -> Self {
+        Self {
+            file: target.file.clone(),
+            function: target.function.clone(),
+            line: target.line,
+            status: "skipped",
+            test_file: None,
+            kept: Vec::new(),
+            rejected: Vec::new(),
+            lines_covered_before: 0,
+            lines_covered_after: 0,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TestgenTool {
+    workspace: PathBuf,
+}
+
+impl TestgenTool {
+    pub fn new() -> Self {
+        Self {
+            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        }
+    }
+
+    async fn baseline(
+        &self,
+        params: &TestgenParams,
+        coverage: &dyn CoverageSource,
+    ) -> Result<CoverageReport, ToolError> {
+        let Some(path) = &params.report else {
+            return coverage.measure(&self.workspace).await;
+        };
+        let text = tokio::fs::read_to_string(self.workspace.join(path))
+            .await
+            .map_err(|e| ToolError::NotFound(format!("Coverage report {}: {}", path, e)))?;
+        let mut report = coverage_report::parse(&text);
+        report.relativize(
+            &self
+                .workspace
+                .canonicalize()
+                .unwrap_or_else(|_| self.workspace.clone()),
+        );
+        Ok(report)
+    }
+
+    async fn generate(
+        &self,
+        params: TestgenParams,
+        proposer: &dyn Proposer,
+        coverage: &dyn CoverageSource,
+    ) -> Result<Value, ToolError> {
+        let before = self.baseline(&params, coverage).await?;
+        let scope = params
+            .path
+            .as_deref()
+            .map(|p| p.trim_start_matches("./"))
+            .filter(|p| !p.is_empty() && *p != ".");
+        let targets = uncovered_functions(
+            &before,
+            &self.workspace,
+            scope,
+            params.max_functions.unwrap_or(DEFAULT_MAX_FUNCTIONS),
+        );
+        info!("Generating tests for {} uncovered functions", targets.len());
+
+        let mut current = before.clone();
+        let mut results = Vec::new();
+        for target in &targets {
+            let (result, measured) = self
+                .cover_function(target, &current, proposer, coverage)
+                .await;
+            if let Some(report) = measured {
+                current = report;
+            }
+            results.push(result);
+        }
+
+        let tests_added: usize = results.iter().map(|r| r.kept.len()).sum();
+        let delta = ((current.percent - before.percent) * 100.0).round() / 100.0;
+        Ok(json!({
+            "coverage_before": before.percent,
+            "coverage_after": current.percent,
+            "coverage_delta": delta,
+            "lines_hit_before": before.lines_hit,
+            "lines_hit_after": current.lines_hit,
+            "lines_total": current.lines_total,
+            "tests_added": tests_added,
+            "functions": results,
+            "message": if targets.is_empty() {
+                "No uncovered functions found".to_string()
+            } else {
+                format!(
+                    "Added {} tests; coverage {:.2}% -> {:.2}% ({:+.2})",
+                    tests_added, before.percent, current.percent, delta
+                )
+            }
+        }))
+    }
+
+    /// Ask for tests of one function and keep those that pass, provided
+    /// together they cover more of it. Returns the new coverage if kept.
+    async fn cover_function(
+        &self,
+        target: &Target,
+        baseline: &CoverageReport,
+        proposer: &dyn Proposer,
+        coverage: &dyn CoverageSource,
+    ) -> (FunctionResult, Option<CoverageReport>) {
+        let mut result = FunctionResult::new(target);
+        result.lines_covered_before = covered_lines(baseline, target);
+        result.lines_covered_after = result.lines_covered_before;
+
+        let Ok(source) = std::fs::read_to_string(self.workspace.join(&target.file)) else {
+            result.error = Some("Cannot read file".to_string());
+            return (result, None);
+        };
+        let placement = placement(&self.workspace, &target.file, &source);
+        let dest = match &placement {
+            Placement::Integration { target } => format!("tests/{}.rs", target),
+            _ => target.file.clone(),
+        };
+        let dest_path = self.workspace.join(&dest);
+        let original = if dest == target.file {
+            source.clone()
+        } else {
+            match std::fs::read_to_string(&dest_path) {
+                Ok(text) => text,
+                Err(e) => {
+                    result.error = Some(format!("Cannot read {}: {}", dest, e));
+                    return (result, None);
+                }
+            }
+        };
+        result.test_file = Some(dest.clone());
+
+        let prompt = self.prompt(target, &source, &placement);
+        let reply = match proposer.propose(&prompt).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                result.error = Some(e.to_string());
+                return (result, None);
+            }
+        };
+        let Some(code) = extract_code(&reply) else {
+            result.error = Some("No ```rust code block in the reply".to_string());
+            return (result, None);
+        };
+        let generated = match split_tests(&code) {
+            Ok(generated) => generated,
+            Err(e) => {
+                result.error = Some(format!("Syntax error in generated tests: {}", e));
+                return (result, None);
+            }
+        };
+
+        let mut candidates = Vec::new();
+        for (name, _) in &generated.tests {
+            if original.contains(&format!("fn {}(", name)) || candidates.contains(name) {
+                result.rejected.push(Rejected {
+                    name: name.clone(),
+                    reason: "name already used",
+                });
+            } else {
+                candidates.push(name.clone());
+            }
+        }
+
+        let passed = self
+            .passing_tests(
+                &dest_path,
+                &original,
+                &placement,
+                &generated,
+                &candidates,
+                &mut result,
+            )
+            .await;
+        result.status = "none_passed";
+        if passed.is_empty() {
+            restore(&dest_path, &original);
+            return (result, None);
+        }
+
+        let updated = render(&original, &placement, &generated, &passed);
+        if let Err(e) = edit::write_atomic(&dest_path, &updated) {
+            result.error = Some(format!("Failed to write {}: {}", dest, e));
+            restore(&dest_path, &original);
+            return (result, None);
+        }
+        let measured = match coverage.measure(&self.workspace).await {
+            Ok(report) => report,
+            Err(e) => {
+                result.status = "skipped";
+                result.error = Some(e.to_string());
+                restore(&dest_path, &original);
+                return (result, None);
+            }
+        };
+
+        let covered = covered_lines(&measured, target);
+        if covered <= result.lines_covered_before {
+            debug!("Tests for {} add no coverage", target.function);
+            result.status = "no_gain";
+            restore(&dest_path, &original);
+            return (result, None);
+        }
+        result.status = "added";
+        result.lines_covered_after = covered;
+        result.kept = passed;
+        (result, Some(measured))
+    }
+
+    /// Candidates that compile and pass: all together first, then one at a
+    /// time for any that did not report because the build failed.
+    async fn passing_tests(
+        &self,
+        dest_path: &Path,
+        original: &str,
+        placement: &Placement,
+        generated: &GeneratedTests,
+        candidates: &[String],
+        result: &mut FunctionResult,
+    ) -> Vec<String> {
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+        let module_filter = match placement {
+            Placement::Integration { .. } => None,
+            _ => test_filter(&result.file),
+        };
+
+        let combined = render(original, placement, generated, candidates);
+        let outcomes = match edit::write_atomic(dest_path, &combined) {
+            Ok(()) => self.run_tests(placement, module_filter.as_deref()).await.0,
+            Err(e) => {
+                warn!("Failed to write {}: {}", dest_path.display(), e);
+                HashMap::new()
+            }
+        };
+
+        let mut passed = Vec::new();
+        for name in candidates {
+            let path = test_path(&result.file, placement, name);
+            let outcome = match outcomes.get(&path) {
+                Some(&outcome) => Some(outcome),
+                None => {
+                    let single = render(original, placement, generated, std::slice::from_ref(name));
+                    if edit::write_atomic(dest_path, &single).is_err() {
+                        continue;
+                    }
+                    let (outcomes, output) = self.run_tests(placement, Some(name)).await;
+                    if !outcomes.contains_key(&path) {
+                        debug!("{} does not build:\n{}", name, tail(&output, 20));
+                    }
+                    outcomes.get(&path).copied()
+                }
+            };
+            match outcome {
+                Some(true) => passed.push(name.clone()),
+                Some(false) => result.rejected.push(Rejected {
+                    name: name.clone(),
+                    reason: "failed",
+                }),
+                None => result.rejected.push(Rejected {
+                    name: name.clone(),
+                    reason: "does not compile",
+                }),
+            }
+        }
+        passed
+    }
+
+    /// Run `cargo test`, returning whether each reported test passed (keyed
+    /// by its full path) and the combined output.
+    async fn run_tests(
+        &self,
+        placement: &Placement,
+        filter: Option<&str>,
+    ) -> (HashMap<String, bool>, String) {
+        let mut cmd = Command::new("cargo");
+        cmd.arg("test").current_dir(&self.workspace);
+        if let Placement::Integration { target } = placement {
+            cmd.args(["--test", target]);
+        }
+        if let Some(filter) = filter {
+            cmd.arg(filter);
+        }
+        match timeout(Duration::from_secs(600), cmd.output()).await {
+            Ok(Ok(output)) => {
+                let text = format!(
+                    "{}\n{}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                (parse_test_results(&text), text)
+            }
+            Ok(Err(e)) => (HashMap::new(), format!("Failed to run cargo test: {}", e)),
+            Err(_) => (HashMap::new(), "cargo test timeout (600s)".to_string()),
+        }
+    }
+
+    fn prompt(&self, target: &Target, source: &str, placement: &Placement) -> String {
+        let context: String = source.chars().take(MAX_CONTEXT_CHARS).collect();
+        let function = source
+            .lines()
+            .skip(target.line - 1)
+            .take(target.end_line + 1 - target.line)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let location = match placement {
+            Placement::Integration { target } => format!(
+                "The tests go in the integration test file tests/{}.rs, so only the \
+                 public API of the `{}` crate is reachable.",
+                target,
+                package_name(&self.workspace).unwrap_or_else(|| "crate".to_string())
+            ),
+            _ => "The tests go in the file's `#[cfg(test)]` module, which can reach \
+                  private items through `use super::*;`."
+                .to_string(),
+        };
+        format!(
+            "No test executes the Rust function `{}` in {}. Write unit tests for it.\n\n\
+             File contents:\n```rust\n{}\n```\n\n\
+             Function under test:\n```rust\n{}\n```\n\n\
+             {} Reply with only `#[test]` functions, and any `use` lines they need, \
+             in a single ```rust code block. Each test must pass against the current \
+             implementation and exercise a different path through the function.",
+            target.function, target.file, context, function, location
+        )
+    }
+}
+
+/// Functions whose instrumented lines were all missed, most missed lines
+/// first, skipping test modules and files outside `root`.
+fn uncovered_functions(
+    report: &CoverageReport,
+    root: &Path,
+    scope: Option<&str>,
+    limit: usize,
+) -> Vec<Target> {
+    let mut targets = Vec::new();
+    for file in &report.files {
+        if !file.file.ends_with(".rs") || Path::new(&file.file).is_absolute() {
+            continue;
+        }
+        if scope.is_some_and(|scope| !Path::new(&file.file).starts_with(scope)) {
+            continue;
+        }
+        let Ok(source) = std::fs::read_to_string(root.join(&file.file)) else {
+            continue;
+        };
+        let Ok(functions) = complexity::analyze_source(&source) else {
+            continue;
+        };
+        let test_modules = syn::parse_file(&source)
+            .map(|ast| test_module(&ast).map(|(start, end, _)| (start, end)))
+            .unwrap_or_default();
+
+        for function in functions {
+            if test_modules
+                .is_some_and(|(start, end)| function.line >= start && function.line <= end)
+            {
+                continue;
+            }
+            let lines: Vec<u64> = file
+                .line_hits
+                .range(function.line..=function.end_line)
+                .map(|(_, &hits)| hits)
+                .collect();
+            if lines.is_empty() || lines.iter().any(|&hits| hits > 0) {
+                continue;
+            }
+            targets.push(Target {
+                file: file.file.clone(),
+                function: function.name,
+                line: function.line,
+                end_line: function.end_line,
+                uncovered: lines.len(),
+            });
+        }
+    }
+    targets.sort_by_key(|t| std::cmp::Reverse(t.uncovered));
+    targets.truncate(limit);
+    targets
+}
+
+fn covered_lines(report: &CoverageReport, target: &Target) -> usize {
+    report.file(&target.file).map_or(0, |file| {
+        file.line_hits
+            .range(target.line..=target.end_line)
+            .filter(|(_, &hits)| hits > 0)
+            .count()
+    })
+}
+
+/// The top-level `#[cfg(test)] mod { ... }`: its first line, the line of its
+/// closing brace and its name.
+fn test_module(ast: &syn::File) -> Option<(usize, usize, String)> {
+    ast.items.iter().find_map(|item| {
+        let syn::Item::Mod(module) = item else {
+            return None;
+        };
+        let cfg_test = module.attrs.iter().any(|attr| {
+            attr.path().is_ident("cfg")
+                && attr
+                    .parse_args::<syn::Ident>()
+                    .is_ok_and(|ident| ident == "test")
+        });
+        let (brace, _) = module.content.as_ref()?;
+        cfg_test.then(|| {
+            (
+                module.span().start().line,
+                brace.span.close().start().line,
+                module.ident.to_string(),
+            )
+        })
+    })
+}
+
+fn placement(root: &Path, file: &str, source: &str) -> Placement {
+    if let Some((_, close, name)) = syn::parse_file(source)
+        .ok()
+        .and_then(|ast| test_module(&ast))
+    {
+        return Placement::Module { close, name };
+    }
+    let stem = Path::new(file)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    for target in [stem.clone(), format!("{}_test", stem)] {
+        if !matches!(stem.as_str(), "lib" | "main" | "mod")
+            && root.join("tests").join(format!("{}.rs", target)).is_file()
+        {
+            return Placement::Integration { target };
+        }
+    }
+    let name = if source.contains("mod tests") {
+        "generated_tests"
+    } else {
+        "tests"
+    };
+    Placement::NewModule { name }
+}
+
+/// Split a reply into its test functions and everything else.
+fn split_tests(code: &str) -> Result<GeneratedTests, syn::Error> {
+    let ast = syn::parse_file(code)?;
+    let lines: Vec<&str> = code.lines().collect();
+    let text = |item: &syn::Item| {
+        let span = item.span();
+        lines[span.start().line - 1..span.end().line].join("\n")
+    };
+
+    let mut generated = GeneratedTests::default();
+    for item in &ast.items {
+        match item {
+            syn::Item::Fn(function)
+                if function.attrs.iter().any(|attr| {
+                    attr.path()
+                        .segments
+                        .last()
+                        .is_some_and(|segment| segment.ident == "test")
+                }) =>
+            {
+                generated
+                    .tests
+                    .push((function.sig.ident.to_string(), text(item)));
+            }
+            _ => generated.support.push(text(item)),
+        }
+    }
+    Ok(generated)
+}
+
+/// `original` with the tests named in `names` added at `placement`.
+fn render(
+    original: &str,
+    placement: &Placement,
+    generated: &GeneratedTests,
+    names: &[String],
+) -> String {
+    let mut block: Vec<&str> = generated
+        .support
+        .iter()
+        .filter(|item| !original.contains(item.as_str()))
+        .map(String::as_str)
+        .collect();
+    block.extend(
+        generated
+            .tests
+            .iter()
+            .filter(|(name, _)| names.contains(name))
+            .map(|(_, code)| code.as_str()),
+    );
+    let block = block.join("\n\n");
+    let indented = || {
+        block
+            .lines()
+            .map(|line| {
+                if line.is_empty() {
+                    String::new()
+                } else {
+                    format!("    {}", line)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let mut out = original.trim_end_matches('\n').to_string();
+    match placement {
+        Placement::Module { close, .. } => {
+            let mut lines: Vec<String> = original.lines().map(str::to_string).collect();
+            let at = (*close - 1).min(lines.len());
+            lines.insert(at, format!("\n{}", indented()));
+            out = lines.join("\n");
+        }
+        Placement::NewModule { name } => {
+            out.push_str(&format!(
+                "\n\n#[cfg(test)]\nmod {} {{\n    use super::*;\n\n{}\n}}",
+                name,
+                indented()
+            ));
+        }
+        Placement::Integration { .. } => {
+            out.push_str("\n\n");
+            out.push_str(&block);
+        }
+    }
+    out.push('\n');
+    out
+}
+
+/// Map of full test path, e.g. `tools::x::tests::a`, to whether it passed.
+fn parse_test_results(output: &str) -> HashMap<String, bool> {
+    static LINE: OnceLock<Regex> = OnceLock::new();
+    let line = LINE.get_or_init(|| {
+        Regex::new(r"(?m)^test (\S+) \.\.\. (ok|FAILED|ignored)").expect("valid regex")
+    });
+    line.captures_iter(output)
+        .map(|caps| (caps[1].to_string(), &caps[2] == "ok"))
+        .collect()
+}
+
+/// Path cargo reports for the generated test `name` of `file` once placed.
+fn test_path(file: &str, placement: &Placement, name: &str) -> String {
+    let module = match placement {
+        // Integration tests sit at the root of their own crate
+        Placement::Integration { .. } => return name.to_string(),
+        Placement::Module { name: module, .. } => module.as_str(),
+        Placement::NewModule { name: module } => module,
+    };
+    match test_filter(file) {
+        Some(parent) => format!("{}::{}::{}", parent, module, name),
+        None => format!("{}::{}", module, name),
+    }
+}
+
+/// `[package] name` from the crate's Cargo.toml, as a Rust identifier.
+fn package_name(root: &Path) -> Option<String> {
+    dependencies::cargo_package_name(root).map(|name| name.replace('-', "_"))
+}
+
+fn restore(path: &Path, original: &str) {
+    if let Err(e) = edit::write_atomic(path, original) {
+        warn!("Failed to restore {}: {}", path.display(), e);
+    }
+}
+
+impl Default for TestgenTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for TestgenTool {
+    fn name(&self) -> &str {
+        "testgen"
+    }
+
+    fn description(&self) -> &str {
+        "Generate unit tests for uncovered functions, keeping those that pass and add coverage"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: TestgenParams =
+            serde_json::from_value(params).map_err(|e| ToolError::InvalidParams(e.to_string()))?;
+        if !crate::config::Config::from_env().has_api_key() {
+            return Err(ToolError::Execution(
+                "Test generation requires AI_STUDIO_API_KEY".to_string(),
+            ));
+        }
+        self.generate(params, &LlmProposer, &BackendCoverage).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Scripted(&'static str);
+
+    #[async_trait]
+    impl Proposer for Scripted {
+        async fn propose(&self, _prompt: &str) -> Result<String, ToolError> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    /// `double` is uncovered in the first report and covered afterwards.
+    struct Fake(AtomicUsize);
+
+    #[async_trait]
+    impl CoverageSource for Fake {
+        async fn measure(&self, _dir: &Path) -> Result<CoverageReport, ToolError> {
+            let hits = if self.0.fetch_add(1, Ordering::SeqCst) == 0 {
+                0
+            } else {
+                1
+            };
+            Ok(coverage_report::parse_lcov(&format!(
+                "SF:src/lib.rs\nDA:1,{0}\nDA:2,{0}\nDA:3,{0}\nend_of_record\n",
+                hits
+            )))
+        }
+    }
+
+    #[test]
+    fn test_split_and_render() {
+        let code = "use std::fmt::Write;\n\n#[test]\nfn a() {\n    assert!(true);\n}\n\n#[tokio::test]\nasync fn b() {}\n";
+        let generated = split_tests(code).unwrap();
+        assert_eq!(generated.support, vec!["use std::fmt::Write;"]);
+        let names: Vec<&str> = generated.tests.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+
+        let source = "fn f() {}\n\n#[cfg(test)]\nmod tests {\n    #[test]\n    fn old() {}\n}\n";
+        let placement = placement(Path::new("."), "src/x.rs", source);
+        assert_eq!(
+            placement,
+            Placement::Module {
+                close: 7,
+                name: "tests".to_string()
+            }
+        );
+        assert_eq!(test_path("src/x.rs", &placement, "a"), "x::tests::a");
+        let rendered = render(source, &placement, &generated, &["a".to_string()]);
+        assert!(rendered.ends_with(
+            "    fn old() {}\n\n    use std::fmt::Write;\n\n    #[test]\n    fn a() {\n        assert!(true);\n    }\n}\n"
+        ));
+        assert!(syn::parse_file(&rendered).is_ok());
+
+        // A test of the same name elsewhere under the filter is told apart
+        let output = "test tools::x::tests::a ... ok\ntest tools::x::inner::tests::a ... FAILED\ntest b ... FAILED\n";
+        let results = parse_test_results(output);
+        assert_eq!(results.get("tools::x::tests::a"), Some(&true));
+        assert_eq!(results.get("tools::x::inner::tests::a"), Some(&false));
+        assert_eq!(results.get("b"), Some(&false));
+        assert_eq!(results.get("a"), None);
+
+        let new_module = Placement::NewModule {
+            name: "generated_tests",
+        };
+        assert_eq!(
+            test_path("src/lib.rs", &new_module, "a"),
+            "generated_tests::a"
+        );
+        let integration = Placement::Integration {
+            target: "x_test".to_string(),
+        };
+        assert_eq!(test_path("src/x.rs", &integration, "a"), "a");
+    }
+
+    #[tokio::test]
+    async fn test_generate_keeps_passing_tests() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        let lib = dir.path().join("src/lib.rs");
+        std::fs::write(&lib, "pub fn double(x: i32) -> i32 {\n    x * 2\n}\n").unwrap();
+
+        let reply = "```rust
+#[test]
+fn double_doubles() {
+    assert_eq!(double(2), 4);
+}
+
+#[test]
+fn double_is_wrong() {
+    assert_eq!(double(2), 5);
+}
+
+#[test]
+fn double_does_not_build() {
+    let s: String = double(2);
+    assert!(s.is_empty());
+}
+```";
+        let tool = TestgenTool {
+            workspace: dir.path().to_path_buf(),
+        };
+        let params: TestgenParams = serde_json::from_value(json!({})).unwrap();
+        let result = tool
+            .generate(params, &Scripted(reply), &Fake(AtomicUsize::new(0)))
+            .await
+            .unwrap();
+
+        assert_eq!(result["coverage_before"], 0.0);
+        assert_eq!(result["coverage_after"], 100.0);
+        assert_eq!(result["tests_added"], 1);
+        let function = &result["functions"][0];
+        assert_eq!(function["status"], "added");
+        assert_eq!(function["kept"], json!(["double_doubles"]));
+        let reasons: Vec<&str> = function["rejected"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["reason"].as_str().unwrap())
+            .collect();
+        assert_eq!(reasons, vec!["failed", "does not compile"]);
+
+        let source = std::fs::read_to_string(&lib).unwrap();
+        assert!(source.contains("#[cfg(test)]\nmod tests {\n    use super::*;"));
+        assert!(source.contains("fn double_doubles()"));
+        assert!(!source.contains("double_is_wrong"));
+        assert!(!source.contains("double_does_not_build"));
+    }
+}
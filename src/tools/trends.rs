@@ -0,0 +1,500 @@
+use crate::tools::history::{self, Metrics, MetricsStore};
+use crate::tools::{Tool, ToolError};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Commits of history shown unless `limit` says otherwise.
+const DEFAULT_LIMIT: usize = 10;
+/// How far back from the merge base to look for a measured commit.
+const BASE_SEARCH_DEPTH: &str = "200";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TrendsParams {
+    /// Number of most recent commits to show
+    #[serde(default)]
+    limit: Option<usize>,
+    /// Scope the runs were recorded at; that of the latest run by default
+    #[serde(default)]
+    path: Option<String>,
+    /// Branch whose merge base with HEAD is the comparison point; origin's
+    /// default branch, `main` or `master` when omitted
+    #[serde(default)]
+    base: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Better {
+    Lower,
+    Higher,
+}
+
+/// A metric compared against the base.
+struct Tracked {
+    name: &'static str,
+    better: Better,
+    /// Change in the wrong direction tolerated before it is a regression,
+    /// as a fraction of the base value when `relative`
+    tolerance: f64,
+    relative: bool,
+    read: fn(&Metrics) -> Option<f64>,
+}
+
+const TRACKED: &[Tracked] = &[
+    Tracked {
+        name: "max_cyclomatic",
+        better: Better::Lower,
+        tolerance: 0.0,
+        relative: false,
+        read: |m| m.complexity.as_ref().map(|c| c.max_cyclomatic as f64),
+    },
+    Tracked {
+        name: "average_cyclomatic",
+        better: Better::Lower,
+        tolerance: 0.05,
+        relative: false,
+        read: |m| m.complexity.as_ref().map(|c| c.average_cyclomatic),
+    },
+    Tracked {
+        name: "max_cognitive",
+        better: Better::Lower,
+        tolerance: 0.0,
+        relative: false,
+        read: |m| m.complexity.as_ref().map(|c| c.max_cognitive as f64),
+    },
+    Tracked {
+        name: "average_cognitive",
+        better: Better::Lower,
+        tolerance: 0.05,
+        relative: false,
+        read: |m| m.complexity.as_ref().map(|c| c.average_cognitive),
+    },
+    Tracked {
+        name: "satd_items",
+        better: Better::Lower,
+        tolerance: 0.0,
+        relative: false,
+        read: |m| m.satd_items.map(|n| n as f64),
+    },
+    Tracked {
+        name: "coverage_percent",
+        better: Better::Higher,
+        tolerance: 0.1,
+        relative: false,
+        read: |m| m.coverage_percent,
+    },
+    Tracked {
+        name: "binary_size",
+        better: Better::Lower,
+        tolerance: 0.01,
+        relative: true,
+        read: |m| m.binary_size.map(|n| n as f64),
+    },
+];
+
+#[derive(Debug, Clone, Serialize)]
+struct Regression {
+    metric: &'static str,
+    base: f64,
+    current: f64,
+    change: f64,
+    message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CommitMetrics {
+    commit: String,
+    timestamp: u64,
+    metrics: Metrics,
+    /// Difference from the previous measured commit, per metric that moved
+    changes: BTreeMap<&'static str, f64>,
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn changes(previous: &Metrics, current: &Metrics) -> BTreeMap<&'static str, f64> {
+    TRACKED
+        .iter()
+        .filter_map(|t| {
+            let change = round((t.read)(current)? - (t.read)(previous)?);
+            (change != 0.0).then_some((t.name, change))
+        })
+        .collect()
+}
+
+fn regressions(base: &Metrics, current: &Metrics) -> Vec<Regression> {
+    TRACKED
+        .iter()
+        .filter_map(|t| {
+            let (before, after) = ((t.read)(base)?, (t.read)(current)?);
+            let worse = match t.better {
+                Better::Lower => after - before,
+                Better::Higher => before - after,
+            };
+            let allowed = if t.relative {
+                before.abs() * t.tolerance
+            } else {
+                t.tolerance
+            };
+            if worse <= allowed + f64::EPSILON {
+                return None;
+            }
+            let direction = if after > before { "rose" } else { "fell" };
+            Some(Regression {
+                metric: t.name,
+                base: before,
+                current: after,
+                change: round(after - before),
+                message: format!(
+                    "{} {} from {} to {}",
+                    t.name,
+                    direction,
+                    round(before),
+                    round(after)
+                ),
+            })
+        })
+        .collect()
+}
+
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(12)]
+}
+
+fn table(
+    commits: &[CommitMetrics],
+    base: Option<&(String, String)>,
+    found: &[Regression],
+) -> String {
+    let mut out = format!(
+        "{:<14} {:<12} {:<6} {:<10} {}\n",
+        "Commit", "Complexity", "SATD", "Coverage", "Binary"
+    );
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    for entry in commits {
+        let m = &entry.metrics;
+        out.push_str(&format!(
+            "{:<14} {:<12} {:<6} {:<10} {}\n",
+            short(&entry.commit),
+            or_dash(
+                m.complexity
+                    .as_ref()
+                    .map(|c| format!("{}/{:.2}", c.max_cyclomatic, c.average_cyclomatic))
+            ),
+            or_dash(m.satd_items.map(|n| n.to_string())),
+            or_dash(m.coverage_percent.map(|p| format!("{:.2}%", p))),
+            or_dash(m.binary_size.map(|b| format!("{} B", b))),
+        ));
+    }
+    match base {
+        Some((reference, commit)) => {
+            out.push_str(&format!("\nBase: {} ({})\n", short(commit), reference));
+            if found.is_empty() {
+                out.push_str("No regressions\n");
+            }
+            for regression in found {
+                out.push_str(&format!("REGRESSION {}\n", regression.message));
+            }
+        }
+        None => out.push_str("\nNo measured base commit to compare against\n"),
+    }
+    out
+}
+
+#[derive(Debug)]
+pub struct TrendsTool {
+    workspace: PathBuf,
+}
+
+impl TrendsTool {
+    pub fn new() -> Self {
+        Self {
+            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        }
+    }
+
+    async fn git(&self, args: &[&str]) -> Result<String, ToolError> {
+        history::git(&self.workspace, args).await.ok_or_else(|| {
+            ToolError::Execution(format!(
+                "git {} failed in {}",
+                args.join(" "),
+                self.workspace.display()
+            ))
+        })
+    }
+
+    /// The requested base branch, or the repository's default one.
+    async fn base_ref(&self, requested: Option<&str>) -> Option<String> {
+        if let Some(reference) = requested {
+            return Some(reference.to_string());
+        }
+        if let Ok(origin) = self
+            .git(&["rev-parse", "--abbrev-ref", "origin/HEAD"])
+            .await
+        {
+            return Some(origin);
+        }
+        for candidate in ["main", "master"] {
+            if self
+                .git(&["rev-parse", "--verify", "--quiet", candidate])
+                .await
+                .is_ok()
+            {
+                return Some(candidate.to_string());
+            }
+        }
+        None
+    }
+}
+
+impl Default for TrendsTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for TrendsTool {
+    fn name(&self) -> &str {
+        "trends"
+    }
+
+    fn description(&self) -> &str {
+        "Show recorded quality metrics over recent commits and flag regressions against the branch base"
+    }
+
+    async fn execute(&self, params: Value) -> Result<Value, ToolError> {
+        let params: TrendsParams =
+            serde_json::from_value(params).map_err(|e| ToolError::InvalidParams(e.to_string()))?;
+        let limit = params.limit.unwrap_or(DEFAULT_LIMIT).max(1);
+
+        let store = MetricsStore::new(&self.workspace);
+        let scope = match &params.path {
+            Some(path) => history::scope(Some(path)),
+            None => store
+                .load()
+                .pop()
+                .map_or_else(|| ".".to_string(), |s| s.scope),
+        };
+        let measured = store.by_commit(&scope);
+        info!(
+            "Metric trends for {} over {} commits ({} measured)",
+            scope,
+            limit,
+            measured.len()
+        );
+
+        let log = self
+            .git(&[
+                "rev-list",
+                "--first-parent",
+                "-n",
+                &limit.to_string(),
+                "HEAD",
+            ])
+            .await?;
+        let mut commits: Vec<CommitMetrics> = Vec::new();
+        for sha in log.lines().rev() {
+            let Some((timestamp, metrics)) = measured.get(sha) else {
+                continue;
+            };
+            let changes = commits
+                .last()
+                .map(|previous| changes(&previous.metrics, metrics))
+                .unwrap_or_default();
+            commits.push(CommitMetrics {
+                commit: sha.to_string(),
+                timestamp: *timestamp,
+                metrics: metrics.clone(),
+                changes,
+            });
+        }
+
+        // Compare the newest measured commit with the newest measured
+        // commit at or before the merge base, excluding itself
+        let current = commits.last();
+        let mut base = None;
+        if let (Some(current), Some(reference)) =
+            (current, self.base_ref(params.base.as_deref()).await)
+        {
+            let merge_base = self
+                .git(&["merge-base", "HEAD", &reference])
+                .await
+                .map_err(|e| {
+                    ToolError::InvalidParams(format!("Cannot compare with {}: {}", reference, e))
+                })?;
+            let history = self
+                .git(&[
+                    "rev-list",
+                    "--first-parent",
+                    "-n",
+                    BASE_SEARCH_DEPTH,
+                    &merge_base,
+                ])
+                .await?;
+            base = history
+                .lines()
+                .filter(|sha| *sha != current.commit)
+                .find_map(|sha| {
+                    measured
+                        .get(sha)
+                        .map(|(_, m)| (reference.clone(), sha.to_string(), m))
+                });
+        }
+
+        let found = match (&base, current) {
+            (Some((_, _, base)), Some(current)) => regressions(base, &current.metrics),
+            _ => Vec::new(),
+        };
+        let base_label = base.as_ref().map(|(r, c, _)| (r.clone(), c.clone()));
+
+        Ok(json!({
+            "scope": scope,
+            "commits": commits,
+            "base": base.as_ref().map(|(reference, commit, metrics)| json!({
+                "ref": reference,
+                "commit": commit,
+                "metrics": metrics,
+            })),
+            "regressed": !found.is_empty(),
+            "table": table(&commits, base_label.as_ref(), &found),
+            "regressions": found,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::history::{ComplexityMetrics, Snapshot};
+    use std::path::Path;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    fn commit(dir: &Path, message: &str) -> String {
+        std::fs::write(dir.join("lib.rs"), message).unwrap();
+        git(dir, &["add", "-A"]);
+        git(dir, &["commit", "-qm", message]);
+        git(dir, &["rev-parse", "HEAD"])
+    }
+
+    fn metrics(max_cyclomatic: u32, satd: usize, coverage: f64) -> Metrics {
+        Metrics {
+            complexity: Some(ComplexityMetrics {
+                max_cyclomatic,
+                average_cyclomatic: 2.0,
+                ..ComplexityMetrics::default()
+            }),
+            satd_items: Some(satd),
+            coverage_percent: Some(coverage),
+            binary_size: None,
+        }
+    }
+
+    #[test]
+    fn test_regressions_respect_direction_and_tolerance() {
+        let base = Metrics {
+            binary_size: Some(1000),
+            ..metrics(10, 2, 80.0)
+        };
+        let current = Metrics {
+            binary_size: Some(1005),
+            ..metrics(12, 1, 79.5)
+        };
+        let found = regressions(&base, &current);
+        let names: Vec<&str> = found.iter().map(|r| r.metric).collect();
+        // Fewer SATD items and a 0.5% larger binary are not regressions
+        assert_eq!(names, vec!["max_cyclomatic", "coverage_percent"]);
+        assert_eq!(found[1].message, "coverage_percent fell from 80 to 79.5");
+        assert_eq!(found[0].change, 2.0);
+
+        let changed = changes(&base, &current);
+        assert_eq!(changed["satd_items"], -1.0);
+        assert!(!changed.contains_key("average_cyclomatic"));
+    }
+
+    #[tokio::test]
+    async fn test_trends_against_branch_base() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        git(root, &["init", "-q", "-b", "main"]);
+        git(root, &["config", "user.email", "dev@example.com"]);
+        git(root, &["config", "user.name", "Dev"]);
+
+        let first = commit(root, "one");
+        let second = commit(root, "two");
+        git(root, &["checkout", "-q", "-b", "feature"]);
+        let unmeasured = commit(root, "three");
+        let head = commit(root, "four");
+
+        let store = MetricsStore::new(root);
+        for (sha, timestamp, metrics) in [
+            (&first, 1, metrics(8, 3, 75.0)),
+            (&second, 2, metrics(8, 2, 81.0)),
+            (&head, 4, metrics(14, 2, 78.0)),
+        ] {
+            store
+                .append(&Snapshot {
+                    commit: sha.clone(),
+                    timestamp,
+                    dirty: false,
+                    scope: ".".to_string(),
+                    source: "test".to_string(),
+                    metrics,
+                })
+                .unwrap();
+        }
+
+        let tool = TrendsTool {
+            workspace: root.to_path_buf(),
+        };
+        let result = tool.execute(json!({ "limit": 3 })).await.unwrap();
+        assert_eq!(result["scope"], ".");
+        let shown: Vec<&str> = result["commits"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["commit"].as_str().unwrap())
+            .collect();
+        // The last three commits, of which `three` was never measured
+        assert_eq!(shown, vec![second.as_str(), head.as_str()]);
+        assert!(!shown.contains(&unmeasured.as_str()));
+        assert_eq!(result["commits"][1]["changes"]["max_cyclomatic"], 6.0);
+
+        assert_eq!(result["base"]["ref"], "main");
+        assert_eq!(result["base"]["commit"], second);
+        assert_eq!(result["regressed"], true);
+        let regressed: Vec<&str> = result["regressions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["metric"].as_str().unwrap())
+            .collect();
+        assert_eq!(regressed, vec!["max_cyclomatic", "coverage_percent"]);
+        assert!(result["table"]
+            .as_str()
+            .unwrap()
+            .contains("REGRESSION coverage_percent fell from 81 to 78"));
+
+        // Unknown scopes have no history to compare
+        let empty = tool.execute(json!({ "path": "src/" })).await.unwrap();
+        assert_eq!(empty["scope"], "src");
+        assert_eq!(empty["commits"], json!([]));
+        assert_eq!(empty["regressed"], false);
+    }
+}
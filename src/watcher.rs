@@ -0,0 +1,367 @@
+//! Workspace file watcher.
+//!
+//! Changes are picked up with inotify (or the platform's equivalent),
+//! debounced, and filtered the way git sees the workspace: hidden paths,
+//! build directories and `.gitignore`d files are left out, and ignored
+//! directories are not watched at all. Each batch refreshes the symbol
+//! index and is announced to running language servers. Changes pcode did
+//! not write itself, whether made in an editor or by a command pcode ran,
+//! are kept until the chat takes them at its next turn.
+
+use crate::analysis::{display_path, is_skipped_dir};
+use crate::lsp::LspManager;
+use crate::tools::symbol_index::SymbolIndex;
+use ignore::gitignore::Gitignore;
+use notify_debouncer_full::notify::event::{EventKind, ModifyKind};
+use notify_debouncer_full::notify::{self, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer_opt, DebounceEventResult, Debouncer, NoCache};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use walkdir::WalkDir;
+
+/// Quiet time after the last event for a path before it is reported.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// Files whose patterns exclude paths, in increasing precedence.
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+/// Hash of the contents pcode last wrote to each file, by absolute path.
+static OWN_WRITES: OnceLock<Mutex<HashMap<PathBuf, String>>> = OnceLock::new();
+
+fn own_writes() -> &'static Mutex<HashMap<PathBuf, String>> {
+    OWN_WRITES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn hash(contents: &[u8]) -> String {
+    hex::encode(Sha256::digest(contents))
+}
+
+/// Note that pcode itself wrote `contents` to `path`, so the watcher does
+/// not report the change as made outside pcode.
+pub fn record_write(path: &Path, contents: &[u8]) {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    own_writes().lock().unwrap().insert(path, hash(contents));
+}
+
+/// Whether `path` still holds what pcode last wrote to it.
+fn is_own_write(path: &Path) -> bool {
+    let Some(expected) = own_writes().lock().unwrap().get(path).cloned() else {
+        return false;
+    };
+    std::fs::read(path).is_ok_and(|contents| hash(&contents) == expected)
+}
+
+/// The `.gitignore` and `.ignore` files of a workspace.
+struct IgnoreRules {
+    root: PathBuf,
+    /// Outermost first, so later matchers take precedence
+    matchers: Vec<Gitignore>,
+}
+
+impl IgnoreRules {
+    fn load(root: &Path) -> Self {
+        let mut files: Vec<PathBuf> = WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|e| {
+                e.depth() == 0
+                    || !e.file_type().is_dir()
+                    || !is_skipped_dir(&e.file_name().to_string_lossy())
+            })
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                e.file_type().is_file()
+                    && IGNORE_FILES.contains(&e.file_name().to_string_lossy().as_ref())
+            })
+            .map(|e| e.into_path())
+            .collect();
+        files.sort_by_key(|f| (f.components().count(), f.file_name().map(|n| n.to_owned())));
+
+        let matchers = files
+            .iter()
+            .map(|file| {
+                let (matcher, error) = Gitignore::new(file);
+                if let Some(e) = error {
+                    warn!("Ignoring invalid patterns in {}: {}", file.display(), e);
+                }
+                matcher
+            })
+            .collect();
+        Self {
+            root: root.to_path_buf(),
+            matchers,
+        }
+    }
+
+    /// Whether changes to `path` go unreported: it is outside the
+    /// workspace, hidden, in a build directory or matched by an ignore file.
+    fn ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return true;
+        };
+        if relative
+            .components()
+            .any(|c| is_skipped_dir(&c.as_os_str().to_string_lossy()))
+        {
+            return true;
+        }
+        let mut ignored = false;
+        for matcher in self.matchers.iter().filter(|m| path.starts_with(m.path())) {
+            let matched = matcher.matched_path_or_any_parents(path, is_dir);
+            if matched.is_ignore() {
+                ignored = true;
+            } else if matched.is_whitelist() {
+                ignored = false;
+            }
+        }
+        ignored
+    }
+}
+
+/// State shared with the debouncer's thread.
+struct Shared {
+    workspace: PathBuf,
+    rules: Mutex<IgnoreRules>,
+    debouncer: Mutex<Option<Debouncer<RecommendedWatcher, NoCache>>>,
+    /// Workspace-relative files changed outside pcode and not yet taken
+    external: Mutex<BTreeSet<String>>,
+    lsp: Option<Arc<LspManager>>,
+    runtime: Option<tokio::runtime::Handle>,
+}
+
+impl Shared {
+    /// Watch `dir` and the directories below it that are not ignored, and
+    /// return the files found in them.
+    fn watch_tree(&self, dir: &Path) -> Vec<PathBuf> {
+        let rules = self.rules.lock().unwrap();
+        let mut debouncer = self.debouncer.lock().unwrap();
+        let mut files = Vec::new();
+        let entries = WalkDir::new(dir)
+            .into_iter()
+            .filter_entry(|e| !rules.ignored(e.path(), e.file_type().is_dir()))
+            .filter_map(|e| e.ok());
+        for entry in entries {
+            if !entry.file_type().is_dir() {
+                files.push(entry.into_path());
+                continue;
+            }
+            let Some(debouncer) = debouncer.as_mut() else {
+                continue;
+            };
+            if let Err(e) = debouncer.watch(entry.path(), RecursiveMode::NonRecursive) {
+                warn!("Cannot watch {}: {}", entry.path().display(), e);
+            }
+        }
+        files
+    }
+
+    /// Handle one debounced batch of changed paths.
+    fn process(&self, paths: Vec<PathBuf>) {
+        let reload = paths.iter().any(|p| {
+            p.file_name()
+                .is_some_and(|n| IGNORE_FILES.contains(&n.to_string_lossy().as_ref()))
+        });
+        if reload {
+            *self.rules.lock().unwrap() = IgnoreRules::load(&self.workspace);
+        }
+
+        let mut changed = BTreeSet::new();
+        for path in paths {
+            let is_dir = path.is_dir();
+            if self.rules.lock().unwrap().ignored(&path, is_dir) {
+                continue;
+            }
+            if is_dir {
+                // A directory created or moved in brings its files along
+                // without an event for each
+                changed.extend(self.watch_tree(&path));
+            } else {
+                changed.insert(path);
+            }
+        }
+        if changed.is_empty() {
+            return;
+        }
+        let changed: Vec<PathBuf> = changed.into_iter().collect();
+        debug!("{} files changed in the workspace", changed.len());
+
+        if let Some(stats) = SymbolIndex::refresh(&self.workspace, &changed) {
+            debug!(
+                "Symbol index: {} re-indexed, {} removed",
+                stats.indexed, stats.removed
+            );
+        }
+        self.notify_servers(&changed);
+
+        let external: Vec<String> = changed
+            .iter()
+            .filter(|p| !is_own_write(p))
+            .map(|p| display_path(&self.workspace, p))
+            .collect();
+        self.external.lock().unwrap().extend(external);
+    }
+
+    fn notify_servers(&self, paths: &[PathBuf]) {
+        let (Some(lsp), Some(runtime)) = (&self.lsp, &self.runtime) else {
+            return;
+        };
+        let lsp = lsp.clone();
+        let paths = paths.to_vec();
+        runtime.spawn(async move {
+            for client in lsp.running().await {
+                if let Err(e) = client.files_changed(&paths).await {
+                    warn!(
+                        "Failed to notify {} language server of changes: {}",
+                        client.language(),
+                        e
+                    );
+                }
+            }
+        });
+    }
+}
+
+/// Watches a workspace for as long as it is alive.
+pub struct WorkspaceWatcher {
+    shared: Arc<Shared>,
+}
+
+impl WorkspaceWatcher {
+    /// Start watching `workspace`. Language servers started by `lsp` are
+    /// told about changes when this is called within a Tokio runtime.
+    pub fn start(workspace: PathBuf, lsp: Option<Arc<LspManager>>) -> notify::Result<Self> {
+        let shared = Arc::new(Shared {
+            rules: Mutex::new(IgnoreRules::load(&workspace)),
+            workspace,
+            debouncer: Mutex::new(None),
+            external: Mutex::new(BTreeSet::new()),
+            lsp,
+            runtime: tokio::runtime::Handle::try_current().ok(),
+        });
+
+        // The debouncer's thread must not keep the watcher alive
+        let weak: Weak<Shared> = Arc::downgrade(&shared);
+        let handler = move |result: DebounceEventResult| {
+            let Some(shared) = weak.upgrade() else {
+                return;
+            };
+            match result {
+                Ok(events) => shared.process(
+                    events
+                        .into_iter()
+                        // Reads and permission changes leave contents as they were
+                        .filter(|e| {
+                            !matches!(
+                                e.kind,
+                                EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_))
+                            )
+                        })
+                        .flat_map(|e| e.event.paths)
+                        .collect(),
+                ),
+                Err(errors) => {
+                    for e in errors {
+                        warn!("File watcher error: {}", e);
+                    }
+                }
+            }
+        };
+        let debouncer = new_debouncer_opt::<_, RecommendedWatcher, NoCache>(
+            DEBOUNCE,
+            None,
+            handler,
+            NoCache,
+            notify::Config::default(),
+        )?;
+        *shared.debouncer.lock().unwrap() = Some(debouncer);
+        shared.watch_tree(&shared.workspace);
+        info!("Watching {} for changes", shared.workspace.display());
+        Ok(Self { shared })
+    }
+
+    /// Files changed outside pcode since the last call, workspace-relative
+    /// and sorted.
+    pub fn take_changes(&self) -> Vec<String> {
+        std::mem::take(&mut *self.shared.external.lock().unwrap())
+            .into_iter()
+            .collect()
+    }
+
+    /// Report `files` again on the next [`Self::take_changes`], as when they
+    /// could not be passed on.
+    pub fn requeue(&self, files: Vec<String>) {
+        self.shared.external.lock().unwrap().extend(files);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, file: &str, content: &str) {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_ignore_rules() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(root, ".gitignore", "*.log\ngenerated/\n");
+        write(root, "web/.gitignore", "!keep.log\nbuild/\n");
+        let rules = IgnoreRules::load(root);
+        let ignored = |path: &str, is_dir| rules.ignored(&root.join(path), is_dir);
+
+        assert!(!ignored("src/lib.rs", false));
+        assert!(ignored("debug.log", false));
+        assert!(ignored("generated/out.rs", false));
+        assert!(ignored("generated", true));
+        assert!(ignored("web/build/app.js", false));
+        assert!(!ignored("web/keep.log", false));
+        assert!(!ignored("build/app.js", false));
+        assert!(ignored(".git/index", false));
+        assert!(ignored("src/.lib.rs.swp", false));
+        assert!(ignored("target/debug/pcode", false));
+        assert!(rules.ignored(Path::new("/elsewhere/lib.rs"), false));
+    }
+
+    #[test]
+    fn test_reports_changes_made_outside_pcode() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(root, ".gitignore", "generated/\n");
+        write(root, "src/lib.rs", "fn a() {}\n");
+        write(root, "src/own.rs", "fn b() {}\n");
+        let watcher = WorkspaceWatcher::start(root.to_path_buf(), None).unwrap();
+
+        write(root, "src/lib.rs", "fn a() { edited() }\n");
+        write(root, "src/nested/new.rs", "fn c() {}\n");
+        write(root, "generated/out.rs", "fn d() {}\n");
+        std::fs::read_to_string(root.join("src/own.rs")).unwrap();
+        let file = std::fs::File::options()
+            .write(true)
+            .open(root.join("src/own.rs"))
+            .unwrap();
+        file.set_modified(std::time::SystemTime::now()).unwrap();
+        record_write(&root.join("src/own.rs"), b"fn b() { pcode() }\n");
+        write(root, "src/own.rs", "fn b() { pcode() }\n");
+
+        let expected = ["src/lib.rs", "src/nested/new.rs"];
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut changes = BTreeSet::new();
+        while !expected.iter().all(|f| changes.contains(*f)) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+            changes.extend(watcher.take_changes());
+        }
+        // Let any late batch arrive before checking what was left out
+        std::thread::sleep(DEBOUNCE * 3);
+        changes.extend(watcher.take_changes());
+        assert_eq!(changes.into_iter().collect::<Vec<_>>(), expected);
+        assert!(watcher.take_changes().is_empty());
+    }
+}
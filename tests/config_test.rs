@@ -15,6 +15,7 @@ fn test_config_without_api_key() {
     // Use a custom isolated test that doesn't read from actual environment
     let config = Config {
         ai_studio_api_key: None,
+        repo_map_tokens: 0,
     };
     assert!(!config.has_api_key());
     assert_eq!(config.ai_studio_api_key, None);
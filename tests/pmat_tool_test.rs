@@ -1,10 +1,26 @@
 use pcode::tools::ToolRegistry;
 use serde_json::json;
+use tempfile::TempDir;
+
+/// Registry with a `pmat` tool over a scratch copy of some of this crate's
+/// sources, so runs never append to the repository's own metrics history.
+fn registry() -> (TempDir, ToolRegistry) {
+    let dir = TempDir::new().unwrap();
+    for file in ["src/chat.rs", "src/main.rs", "tests/pmat_tool_test.rs"] {
+        let target = dir.path().join(file);
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+        std::fs::copy(file, target).unwrap();
+    }
+    let mut registry = ToolRegistry::new();
+    registry.register(Box::new(pcode::tools::pmat::PmatTool::with_workspace(
+        dir.path().to_path_buf(),
+    )));
+    (dir, registry)
+}
 
 #[tokio::test]
 async fn test_pmat_complexity_analysis() {
-    let mut registry = ToolRegistry::new();
-    registry.register(Box::new(pcode::tools::pmat::PmatTool::new()));
+    let (_dir, registry) = registry();
 
     // Test complexity on a Rust file
     let request = pcode::tools::ToolRequest {
@@ -25,8 +41,7 @@ async fn test_pmat_complexity_analysis() {
 
 #[tokio::test]
 async fn test_pmat_satd_detection() {
-    let mut registry = ToolRegistry::new();
-    registry.register(Box::new(pcode::tools::pmat::PmatTool::new()));
+    let (_dir, registry) = registry();
 
     // Test SATD on source directory
     let request = pcode::tools::ToolRequest {
@@ -46,8 +61,7 @@ async fn test_pmat_satd_detection() {
 
 #[tokio::test]
 async fn test_pmat_dead_code_analysis() {
-    let mut registry = ToolRegistry::new();
-    registry.register(Box::new(pcode::tools::pmat::PmatTool::new()));
+    let (_dir, registry) = registry();
 
     // Test coverage on tests directory
     let request = pcode::tools::ToolRequest {
@@ -69,8 +83,7 @@ async fn test_pmat_dead_code_analysis() {
 
 #[tokio::test]
 async fn test_pmat_tdg_analysis() {
-    let mut registry = ToolRegistry::new();
-    registry.register(Box::new(pcode::tools::pmat::PmatTool::new()));
+    let (_dir, registry) = registry();
 
     // Test TDG on tests directory
     let request = pcode::tools::ToolRequest {
@@ -92,8 +105,7 @@ async fn test_pmat_tdg_analysis() {
 
 #[tokio::test]
 async fn test_pmat_invalid_command() {
-    let mut registry = ToolRegistry::new();
-    registry.register(Box::new(pcode::tools::pmat::PmatTool::new()));
+    let (_dir, registry) = registry();
 
     let request = pcode::tools::ToolRequest {
         tool: "pmat".to_string(),
@@ -110,8 +122,7 @@ async fn test_pmat_invalid_command() {
 
 #[tokio::test]
 async fn test_pmat_complexity_violations() {
-    let mut registry = ToolRegistry::new();
-    registry.register(Box::new(pcode::tools::pmat::PmatTool::new()));
+    let (_dir, registry) = registry();
 
     // Test on a file with known complexity violations
     let request = pcode::tools::ToolRequest {
@@ -137,8 +148,7 @@ async fn test_pmat_complexity_violations() {
 
 #[tokio::test]
 async fn test_pmat_path_validation() {
-    let mut registry = ToolRegistry::new();
-    registry.register(Box::new(pcode::tools::pmat::PmatTool::new()));
+    let (_dir, registry) = registry();
 
     // Test with absolute path outside workspace
     let request = pcode::tools::ToolRequest {